@@ -12,6 +12,7 @@ use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     net::{IpAddr, SocketAddr},
+    path::PathBuf,
     sync::Arc,
     time::Duration,
 };
@@ -30,6 +31,10 @@ struct Args {
     /// Maximum age of nodes in seconds before considering them stale
     #[arg(short, long, default_value = "3600")]
     max_age: u64,
+
+    /// Signed checkpoint file served at /checkpoints (read on every request)
+    #[arg(long)]
+    checkpoints: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,6 +52,7 @@ pub struct NodeInfo {
 pub struct AppState {
     nodes: Arc<RwLock<HashMap<String, NodeInfo>>>,
     max_age: u64,
+    checkpoints: Option<PathBuf>,
 }
 
 #[derive(Deserialize)]
@@ -84,10 +90,11 @@ struct GetNodesQuery {
 }
 
 impl AppState {
-    fn new(max_age: u64) -> Self {
+    fn new(max_age: u64, checkpoints: Option<PathBuf>) -> Self {
         Self {
             nodes: Arc::new(RwLock::new(HashMap::new())),
             max_age,
+            checkpoints,
         }
     }
 
@@ -393,6 +400,22 @@ async fn health_check(State(state): State<AppState>) -> impl IntoResponse {
     }))
 }
 
+// Serve the signed checkpoint file as published. Nodes verify the release
+// key signatures themselves, so this server is not trusted with it.
+async fn get_checkpoints(State(state): State<AppState>) -> impl IntoResponse {
+    let path = match &state.checkpoints {
+        Some(path) => path,
+        None => return (StatusCode::NOT_FOUND, "no checkpoint file".to_string()),
+    };
+    match tokio::fs::read_to_string(path).await {
+        Ok(text) => (StatusCode::OK, text),
+        Err(e) => {
+            warn!("Cannot read checkpoint file {:?}: {}", path, e);
+            (StatusCode::NOT_FOUND, "no checkpoint file".to_string())
+        }
+    }
+}
+
 // Get statistics
 async fn get_stats(State(state): State<AppState>) -> impl IntoResponse {
     // Snapshot under read lock, then compute outside
@@ -439,8 +462,11 @@ async fn main() -> anyhow::Result<()> {
 
     info!("Starting Astram DNS Server...");
     info!("Max node age: {} seconds", args.max_age);
+    if let Some(path) = &args.checkpoints {
+        info!("Serving checkpoint file {:?}", path);
+    }
 
-    let state = AppState::new(args.max_age);
+    let state = AppState::new(args.max_age, args.checkpoints);
 
     // Spawn periodic cleanup task (removes stale nodes based on last_seen)
     let cleanup_state = state.clone();
@@ -471,6 +497,7 @@ async fn main() -> anyhow::Result<()> {
         .route("/register", post(register_node))
         .route("/nodes", get(get_nodes))
         .route("/stats", get(get_stats))
+        .route("/checkpoints", get(get_checkpoints))
         .layer(CorsLayer::permissive())
         .with_state(state);
 
@@ -9,8 +9,9 @@ use clap::Parser;
 use base64::{Engine as _, engine::general_purpose};
 use futures::{SinkExt, StreamExt};
 use astram_config::config::Config;
-use Astram_core::block::{Block, BlockHeader, compute_merkle_root};
-use Astram_core::config::calculate_block_reward;
+use Astram_core::ChainParams;
+use Astram_core::block::{Block, BlockHeader, compute_merkle_root, select_transactions};
+use Astram_core::config::{BLOCK_TEMPLATE_RESERVED_SIZE, MAX_BLOCK_SIZE};
 use Astram_core::crypto::WalletKeypair;
 use Astram_core::transaction::{BINCODE_CONFIG, Transaction};
 use payout::{PayoutDb, PendingPayouts, run_balance_sync, run_payout_loop};
@@ -22,7 +23,7 @@ use share_validator::{ShareResult, initial_pool_difficulty, pool_diff_to_target,
 use shares::{FoundBlock, Share, ShareTracker};
 use vardiff::{VarDiffConfig, check_vardiff};
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::{broadcast, watch};
@@ -46,6 +47,28 @@ struct PoolConfig {
     payout_threshold_ram: U256,
     payout_interval_secs: u64,
     payout_db_path: String,
+    /// Network the payout signatures are bound to
+    chain_id: u64,
+    /// Consensus parameters of the node's network (block reward schedule)
+    chain_params: ChainParams,
+}
+
+/// Set once at startup from `PoolConfig::chain_params`
+static CHAIN_PARAMS: OnceLock<ChainParams> = OnceLock::new();
+
+fn chain_params() -> &'static ChainParams {
+    CHAIN_PARAMS.get_or_init(ChainParams::mainnet)
+}
+
+/// `NETWORK` setting: a preset name (mainnet, testnet, regtest) or the path
+/// of a custom chain params file
+fn load_chain_params(network: &str) -> Result<ChainParams> {
+    let path = std::path::Path::new(network);
+    if path.is_file() {
+        ChainParams::from_file(path)
+    } else {
+        ChainParams::for_network(network)
+    }
 }
 
 /// Load a key=value conf file into a HashMap.
@@ -109,6 +132,14 @@ impl PoolConfig {
 
         let node_rpc_url = get_setting("NODE_RPC_URL", &file, &cfg.node_rpc_url);
 
+        // Payouts default to the network's chain id once NETWORK is set
+        let network = std::env::var("NETWORK").ok().or_else(|| file.get("NETWORK").cloned());
+        let chain_params = match &network {
+            Some(network) => load_chain_params(network)?,
+            None => ChainParams::mainnet(),
+        };
+        let default_chain_id = if network.is_some() { chain_params.chain_id } else { cfg.chain_id };
+
         let pool_address = std::env::var("POOL_ADDRESS")
             .ok()
             .or_else(|| file.get("POOL_ADDRESS").cloned())
@@ -142,6 +173,9 @@ impl PoolConfig {
             payout_interval_secs: get_setting("PAYOUT_INTERVAL_SECS", &file, "600")
                 .parse().unwrap_or(600u64),
             payout_db_path: get_setting("POOL_DB_PATH", &file, "pool_data"),
+            chain_id: get_setting("CHAIN_ID", &file, &default_chain_id.to_string())
+                .parse().unwrap_or(default_chain_id),
+            chain_params,
         })
     }
 }
@@ -157,12 +191,13 @@ struct NodeClient {
 #[derive(Debug, Clone)]
 struct MempoolSnapshot {
     txs: Vec<Transaction>,
+    fees: Vec<U256>, // per tx, same order as `txs`
     total_fees: U256,
 }
 
 impl Default for MempoolSnapshot {
     fn default() -> Self {
-        Self { txs: vec![], total_fees: U256::zero() }
+        Self { txs: vec![], fees: vec![], total_fees: U256::zero() }
     }
 }
 
@@ -178,6 +213,8 @@ struct ChainStatus {
 #[derive(Deserialize)]
 struct MempoolResponse {
     transactions_b64: String,
+    #[serde(default)]
+    fees: Vec<String>,
     total_fees: String,
 }
 
@@ -250,8 +287,16 @@ impl NodeClient {
         let (txs, _) = bincode::decode_from_slice::<Vec<Transaction>, _>(&bytes, *BINCODE_CONFIG)
             .map_err(|e| anyhow!("invalid mempool bincode: {}", e))?;
 
+        // Older nodes do not report per-tx fees; treat them as zero-fee
+        let mut fees: Vec<U256> = resp
+            .fees
+            .iter()
+            .map(|f| parse_u256(f).unwrap_or_else(U256::zero))
+            .collect();
+        fees.resize(txs.len(), U256::zero());
+
         let total_fees = parse_u256(&resp.total_fees).unwrap_or_else(U256::zero);
-        Ok(MempoolSnapshot { txs, total_fees })
+        Ok(MempoolSnapshot { txs, fees, total_fees })
     }
 
     async fn submit_block(&self, block: &Block) -> Result<()> {
@@ -324,7 +369,7 @@ async fn build_template(
         status.tip_hash.clone()
     };
 
-    let base_reward = calculate_block_reward(height);
+    let base_reward = chain_params().block_reward(height);
 
     // Include pool-managed payout TXs directly (no node mempool dependency).
     // These TXs are built with fresh UTXOs each payout cycle and cleared after
@@ -337,20 +382,36 @@ async fn build_template(
         .collect();
 
     // Include pending transactions from the node mempool.
-    let mempool_txs = match client.fetch_mempool().await {
-        Ok(snapshot) => snapshot.txs,
+    let mempool = match client.fetch_mempool().await {
+        Ok(snapshot) => snapshot,
         Err(e) => {
             log::warn!("[POOL] Failed to fetch mempool: {}", e);
-            Vec::new()
+            MempoolSnapshot::default()
         }
     };
 
     let coinbase_value = base_reward;
     let coinbase = Transaction::coinbase(pool_address, coinbase_value).with_hashes();
 
+    // A transaction may not be newer than the block that contains it
+    let timestamp = chrono::Utc::now().timestamp();
+
+    // Payouts go in first; mempool transactions fill the rest of the block by fee rate
+    let payout_bytes: usize = payout_txs
+        .iter()
+        .map(|t| bincode::encode_to_vec(t, *BINCODE_CONFIG).map(|b| b.len()).unwrap_or(0))
+        .sum();
+    let budget = MAX_BLOCK_SIZE.saturating_sub(BLOCK_TEMPLATE_RESERVED_SIZE + payout_bytes);
+    let candidates: Vec<(Transaction, U256)> = mempool
+        .txs
+        .into_iter()
+        .zip(mempool.fees)
+        .filter(|(t, _)| t.timestamp <= timestamp)
+        .collect();
+
     let mut all_txs = vec![coinbase];
     all_txs.extend(payout_txs);
-    all_txs.extend(mempool_txs);
+    all_txs.extend(select_transactions(candidates, budget).into_iter().map(|(tx, _)| tx));
 
     let txids: Vec<String> = all_txs.iter().map(|t| t.txid.clone()).collect();
     let merkle_root = compute_merkle_root(&txids);
@@ -366,7 +427,7 @@ async fn build_template(
         prev_hash,
         difficulty: block_difficulty,
         pool_diff,
-        timestamp: chrono::Utc::now().timestamp(),
+        timestamp,
         merkle_root,
         transactions: all_txs,
         coinbase_value,
@@ -1164,6 +1225,7 @@ async fn main() -> Result<()> {
 
     let cfg = Config::load();
     let pool_cfg = Arc::new(PoolConfig::load(&cfg, cli.config)?);
+    let _ = CHAIN_PARAMS.set(pool_cfg.chain_params.clone());
     let client = NodeClient::new(pool_cfg.node_rpc_url.clone());
 
     // ── Payout system setup ──────────────────────────────────────────────────
@@ -1194,6 +1256,7 @@ async fn main() -> Result<()> {
     log::info!("  GBT       : {}", pool_cfg.gbt_bind);
     log::info!("  Stats API : {}", pool_cfg.stats_bind);
     log::info!("  Node RPC  : {}", pool_cfg.node_rpc_url);
+    log::info!("  Network   : {} (chain id {})", pool_cfg.chain_params.network, pool_cfg.chain_id);
     log::info!("  Pool addr : {}", pool_cfg.pool_address);
     log::info!("  Pool fee  : {}%", pool_cfg.pool_fee_percent);
     log::info!("  PPLNS win : {} shares", pool_cfg.pplns_window);
@@ -1256,10 +1319,11 @@ async fn main() -> Result<()> {
         let pay_threshold = pool_cfg.payout_threshold_ram;
         let pay_interval = pool_cfg.payout_interval_secs;
         let pay_pp = pending_payouts.clone();
+        let pay_chain_id = pool_cfg.chain_id;
         tokio::spawn(async move {
             run_payout_loop(
                 pay_http, pay_url, pay_tracker, pay_db,
-                pay_kp, pay_addr, pay_threshold, pay_interval, pay_pp,
+                pay_kp, pay_addr, pay_threshold, pay_interval, pay_pp, pay_chain_id,
             ).await;
         });
     }
@@ -16,7 +16,8 @@
 use anyhow::{Result, anyhow};
 use Astram_core::config::calculate_default_fee;
 use Astram_core::crypto::WalletKeypair;
-use Astram_core::transaction::{BINCODE_CONFIG, Transaction, TransactionInput, TransactionOutput};
+use Astram_core::transaction::{BINCODE_CONFIG, Transaction, TransactionBuilder};
+use Astram_core::utxo::Utxo;
 use primitive_types::U256;
 use rocksdb::{DB, Options, WriteBatch};
 use serde_json::Value;
@@ -150,17 +151,21 @@ fn parse_utxo_amount(u: &Value) -> U256 {
     U256::zero()
 }
 
-/// Fetch spendable UTXOs for `address` from the node.
+/// Fetch spendable UTXOs for `address` from the node (immature coinbase outputs excluded).
 async fn fetch_utxos(
     client: &reqwest::Client,
     base_url: &str,
     address: &str,
-) -> Result<Vec<(TransactionInput, U256)>> {
+) -> Result<Vec<Utxo>> {
     let url = format!("{}/address/{}/utxos", base_url, address);
     let utxos: Vec<Value> = client.get(&url).send().await?.json().await?;
 
     let mut result = Vec::new();
     for u in &utxos {
+        // Skip coinbase outputs that have not reached maturity yet
+        if u["spendable"].as_bool() == Some(false) {
+            continue;
+        }
         let txid = match u["txid"].as_str() {
             Some(s) => s.to_string(),
             None => continue,
@@ -168,10 +173,7 @@ async fn fetch_utxos(
         let vout = u["vout"].as_u64().unwrap_or(0) as u32;
         let amt = parse_utxo_amount(u);
         if amt > U256::zero() {
-            result.push((
-                TransactionInput { txid, vout, pubkey: address.to_string(), signature: None },
-                amt,
-            ));
+            result.push(Utxo::new(txid, vout, address.to_string(), amt));
         }
     }
     Ok(result)
@@ -188,20 +190,21 @@ pub async fn build_payout_tx(
     pool_address: &str,
     to: &str,
     amount: U256,
+    chain_id: u64,
 ) -> Result<Transaction> {
     let input_pool = fetch_utxos(http, base_url, pool_address).await?;
     if input_pool.is_empty() {
         return Err(anyhow!("pool wallet has no UTXOs"));
     }
 
-    let mut selected: Vec<TransactionInput> = Vec::new();
+    let mut selected: Vec<Utxo> = Vec::new();
     let mut input_sum = U256::zero();
     let mut cursor = 0usize;
 
     while cursor < input_pool.len() && input_sum < amount {
-        let (inp, amt) = input_pool[cursor].clone();
-        selected.push(inp);
-        input_sum += amt;
+        let utxo = input_pool[cursor].clone();
+        input_sum += utxo.amount();
+        selected.push(utxo);
         cursor += 1;
     }
 
@@ -219,27 +222,23 @@ pub async fn build_payout_tx(
             if cursor >= input_pool.len() {
                 return Err(anyhow!("pool balance insufficient to cover amount + fee"));
             }
-            let (inp, amt) = input_pool[cursor].clone();
-            selected.push(inp);
-            input_sum += amt;
+            let utxo = input_pool[cursor].clone();
+            input_sum += utxo.amount();
+            selected.push(utxo);
             cursor += 1;
         }
 
         let change = input_sum - amount - fee;
-        let mut outputs = vec![TransactionOutput::new(to.to_string(), amount)];
+        let mut builder = TransactionBuilder::new().chain_id(chain_id);
+        for utxo in &selected {
+            builder = builder.add_input(utxo.clone(), keypair);
+        }
+        builder = builder.add_output(to, amount);
         if change > U256::zero() {
-            outputs.push(TransactionOutput::new(pool_address.to_string(), change));
+            builder = builder.add_output(pool_address, change);
         }
 
-        let mut tx = Transaction {
-            txid: String::new(),
-            inputs: selected.clone(),
-            outputs,
-            timestamp: chrono::Utc::now().timestamp(),
-        };
-
-        tx.sign(keypair).map_err(|e| anyhow!("sign error: {}", e))?;
-        tx = tx.with_hashes();
+        let tx = builder.build().map_err(|e| anyhow!("sign error: {}", e))?;
 
         let body = bincode::encode_to_vec(&tx, *BINCODE_CONFIG)
             .map_err(|e| anyhow!("serialize error: {}", e))?;
@@ -284,6 +283,7 @@ pub async fn run_payout_loop(
     threshold: U256,
     interval_secs: u64,
     pending_payouts: PendingPayouts,
+    chain_id: u64,
 ) {
     // Stagger the first run so the pool has time to sync with the node
     sleep(Duration::from_secs(60)).await;
@@ -309,7 +309,11 @@ pub async fn run_payout_loop(
             // Rebuild the queue fresh each interval (UTXOs may have changed).
             let mut new_pending: Vec<PendingPayout> = Vec::new();
             for (miner_addr, amount) in candidates {
-                match build_payout_tx(&http, &base_url, &keypair, &pool_address, &miner_addr, amount).await {
+                match build_payout_tx(
+                    &http, &base_url, &keypair, &pool_address, &miner_addr, amount, chain_id,
+                )
+                .await
+                {
                     Ok(tx) => {
                         log::info!("💸 Queued payout {} → {} ram", miner_addr, amount);
                         new_pending.push(PendingPayout { miner_addr, amount, tx });
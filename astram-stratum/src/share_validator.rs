@@ -1,4 +1,5 @@
 use Astram_core::block::{BlockHeader, compute_header_hash};
+use Astram_core::consensus::compute_pow_hash;
 use primitive_types::U256;
 use anyhow::{Result, anyhow};
 
@@ -50,13 +51,14 @@ fn hash_hex_to_u256(hash_hex: &str) -> Result<U256> {
 /// * `block_compact` – network difficulty in compact-bits format (e.g. 0x1f05_6013).
 ///
 /// Block detection uses the same numeric comparison as the node
-/// (`hash < compact_target`), not a leading-zero string check.
+/// (`pow_hash < compact_target`), not a leading-zero string check.
+/// The returned hash is the PoW hash (DAG hash once activated), not the block hash.
 pub fn validate_share(
     header: &BlockHeader,
     pool_diff: PoolDifficulty,
     block_compact: u32,
 ) -> Result<ShareResult> {
-    let hash = compute_header_hash(header)?;
+    let hash = hex::encode(compute_pow_hash(crate::chain_params(), header)?);
 
     // Pool difficulty check (leading zeros as hex characters)
     let pool_prefix = "0".repeat(pool_diff as usize);
@@ -135,8 +137,9 @@ pub fn initial_pool_difficulty(block_compact: u32) -> PoolDifficulty {
 
 /// Validate that a claimed full-block hash satisfies block difficulty.
 /// Used by the GBT `submitblock` path where the miner already did full PoW.
+/// Returns the block (header) hash on success.
 pub fn validate_full_block(header: &BlockHeader, block_compact: u32) -> Result<String> {
-    let hash = compute_header_hash(header)?;
+    let hash = hex::encode(compute_pow_hash(crate::chain_params(), header)?);
     let block_target = compact_bits_to_u256(block_compact);
     if block_target.is_zero() {
         return Err(anyhow!("degenerate block target (compact bits = 0x{:08x})", block_compact));
@@ -144,10 +147,10 @@ pub fn validate_full_block(header: &BlockHeader, block_compact: u32) -> Result<S
     let hash_value = hash_hex_to_u256(&hash)?;
     if hash_value >= block_target {
         return Err(anyhow!(
-            "block hash {} does not meet difficulty (compact bits 0x{:08x})",
+            "PoW hash {} does not meet difficulty (compact bits 0x{:08x})",
             &hash[..8],
             block_compact
         ));
     }
-    Ok(hash)
+    compute_header_hash(header)
 }
@@ -333,6 +333,9 @@ HTTP_PORT=19533
 # DNS discovery server
 DNS_SERVER_URL=https://seed.astramchain.com
 
+# Pruning: keep only the last N block bodies (0 = keep all, minimum 101)
+# PRUNE_DEPTH=0
+
 # Network selection (default: mainnet)
 # Uncomment to use testnet:
 # ASTRAM_NETWORK=testnet
@@ -448,6 +451,11 @@ PAYOUT_INTERVAL_SECS=600
 
 # RocksDB path for persisting miner balances across pool restarts.
 POOL_DB_PATH=pool_data
+
+# ------ Network ----------------------------------------------------------------
+# Network of the node: mainnet (default), testnet, regtest, or the path of a
+# custom chain params file. Sets the block reward schedule and the payout chain id.
+# NETWORK=mainnet
 '@
 
 Set-Content -Path "$ReleaseDir/config/poolSettings.conf" -Value $PoolSettingsContent
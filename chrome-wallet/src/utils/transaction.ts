@@ -4,8 +4,8 @@
  * Replicates the Rust wallet-cli logic:
  *  1. Fetch UTXOs  →  select coins
  *  2. Build transaction (bincode)
- *  3. SHA-256 the serialized-for-hash payload
- *  4. Sign with Ed25519 (tweetnacl)
+ *  3. Compute each input's sighash (index, spent UTXO, all outputs)
+ *  4. Sign every input with Ed25519 (tweetnacl)
  *  5. POST binary to /tx
  *
  * Bincode "standard" wire format (all LE):
@@ -144,6 +144,63 @@ function serializeForHash(
   return w.bytes()
 }
 
+const SIGHASH_DOMAIN = 'ASTRAM-SIGHASH-V1'
+const MAINNET_CHAIN_ID = 1
+
+interface SpentUtxo {
+  txid: string
+  vout: number
+  to: string
+  amount: bigint
+}
+
+/**
+ * Mirrors Transaction::sighash():
+ *   SHA256(SHA256(bincode( (&str, u64, Vec<(String, u32)>, u32,
+ *     (String, u32, [u64; 4], String), Vec<TransactionOutput>, i64) )))
+ */
+function computeSighash(
+  chainId: number,
+  inputs: TxInput[],
+  inputIndex: number,
+  spent: SpentUtxo,
+  outputs: TxOutput[],
+  timestamp: number,
+): number[] {
+  const w = new BincodeWriter()
+
+  w.str(SIGHASH_DOMAIN)
+  w.u64(BigInt(chainId))
+
+  // Vec<(String, u32)>
+  w.u64(BigInt(inputs.length))
+  for (const inp of inputs) {
+    w.str(inp.txid)
+    w.u32(inp.vout)
+  }
+
+  w.u32(inputIndex)
+
+  // (txid, vout, amount, owner) of the spent UTXO
+  w.str(spent.txid)
+  w.u32(spent.vout)
+  for (const word of amountToWords(spent.amount)) w.u64(word)
+  w.str(spent.to.toLowerCase())
+
+  // Vec<TransactionOutput>
+  w.u64(BigInt(outputs.length))
+  for (const out of outputs) {
+    w.str(out.to)
+    const words = amountToWords(out.amount)
+    for (const word of words) w.u64(word)
+  }
+
+  w.i64(timestamp)
+
+  const h1 = sha256(Array.from(w.bytes()), { asBytes: true }) as number[]
+  return sha256(h1, { asBytes: true }) as number[]
+}
+
 /**
  * Serializes the complete Transaction struct for broadcast.
  */
@@ -217,11 +274,21 @@ export async function sendTransaction(
   const keypair = nacl.sign.keyPair.fromSeed(seed)
   const pubkeyHex = Buffer.from(keypair.publicKey).toString('hex')
 
+  // Signatures are bound to the node's network (replay protection)
+  let chainId = MAINNET_CHAIN_ID
+  try {
+    const status = await axios.get(`${rpcUrl}/status`)
+    chainId = Number(status.data?.network?.chain_id ?? MAINNET_CHAIN_ID)
+  } catch {
+    // Fall back to mainnet; a mismatched node rejects the signature
+  }
+
   // ── 2. Fetch UTXOs ───────────────────────────────────────────────────────
-  let utxos: Array<{ txid: string; vout: number; amount: number[] }>
+  let utxos: Array<{ txid: string; vout: number; amount: number[]; spendable?: boolean }>
   try {
     const res = await axios.get(`${rpcUrl}/address/${fromAddress}/utxos`)
-    utxos = res.data
+    // Immature coinbase outputs are listed with spendable=false
+    utxos = (res.data as typeof utxos).filter(u => u.spendable !== false)
   } catch (e: any) {
     return { success: false, error: `Failed to fetch UTXOs: ${e.message}` }
   }
@@ -239,6 +306,7 @@ export async function sendTransaction(
   // ── 3. Iterate fee convergence (mirrors CLI) ─────────────────────────────
   let fee = 0n
   let selectedInputs: TxInput[] = []
+  let selectedUtxos: SpentUtxo[] = []
   let inputSum = 0n
   let cursor = 0
 
@@ -255,6 +323,7 @@ export async function sendTransaction(
       }
       const { input, amount } = inputPool[cursor++]
       selectedInputs.push({ ...input })
+      selectedUtxos.push({ txid: input.txid, vout: input.vout, to: fromAddress, amount })
       inputSum += amount
     }
 
@@ -266,19 +335,17 @@ export async function sendTransaction(
 
     const timestamp = Math.floor(Date.now() / 1000)
 
-    // Sign
-    const hashPayload = serializeForHash(selectedInputs, outputs, timestamp)
-    const msgHash = Uint8Array.from(
-      sha256(Array.from(hashPayload), { asBytes: true }) as number[],
-    )
-    const sigBytes = nacl.sign.detached(msgHash, keypair.secretKey)
-    const sigHex = Buffer.from(sigBytes).toString('hex')
-
-    const signedInputs = selectedInputs.map(inp => ({
-      ...inp,
-      pubkey: pubkeyHex,
-      signature: sigHex,
-    }))
+    // Sign each input over its own sighash (WalletKeypair::sign hashes once more)
+    const signedInputs = selectedInputs.map((inp, i) => {
+      const sighash = computeSighash(chainId, selectedInputs, i, selectedUtxos[i], outputs, timestamp)
+      const msgHash = Uint8Array.from(sha256(sighash, { asBytes: true }) as number[])
+      const sigBytes = nacl.sign.detached(msgHash, keypair.secretKey)
+      return {
+        ...inp,
+        pubkey: pubkeyHex,
+        signature: Buffer.from(sigBytes).toString('hex'),
+      }
+    })
 
     const txid = computeTxid(selectedInputs, outputs, timestamp)
     const txBytes = serializeTransaction(txid, signedInputs, outputs, timestamp)
@@ -288,6 +355,7 @@ export async function sendTransaction(
       fee = newFee
       // reset selections and retry
       selectedInputs = []
+      selectedUtxos = []
       inputSum = 0n
       cursor = 0
       continue
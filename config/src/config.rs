@@ -5,6 +5,9 @@ use std::{fs, path::PathBuf};
 pub struct Config {
     pub wallet_path: String,
     pub node_rpc_url: String,
+    /// Network id signatures are bound to (1 = mainnet, 8888 = testnet, 9999 = regtest)
+    #[serde(default = "Config::default_chain_id")]
+    pub chain_id: u64,
 }
 
 impl Config {
@@ -29,6 +32,10 @@ impl Config {
             .into_owned()
     }
 
+    fn default_chain_id() -> u64 {
+        1 // MAINNET_CHAIN_ID
+    }
+
     pub fn default_path() -> PathBuf {
         let home = dirs::home_dir().expect("Cannot find home directory");
         home.join(".Astram/config.json")
@@ -77,6 +84,13 @@ impl Config {
         match key {
             "wallet_path" => self.wallet_path = value.to_string(),
             "node_rpc_url" => self.node_rpc_url = value.to_string(),
+            "chain_id" => match value.parse() {
+                Ok(id) => self.chain_id = id,
+                Err(_) => {
+                    println!("Invalid chain_id: {}", value);
+                    return;
+                }
+            },
             _ => {
                 println!("Unknown configuration key: {}", key);
                 return;
@@ -105,6 +119,7 @@ impl Default for Config {
         Self {
             wallet_path: Self::default_wallet_path(),
             node_rpc_url: "https://rpc.astramchain.com".to_string(),
+            chain_id: Self::default_chain_id(),
         }
     }
 }
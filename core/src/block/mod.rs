@@ -1,7 +1,8 @@
-use crate::transaction::Transaction;
+use crate::transaction::{BINCODE_CONFIG, Transaction};
 use anyhow::Result;
 use bincode::{Decode, Encode};
 use hex;
+use primitive_types::U256;
 use sha2::{Digest, Sha256};
 
 /// block header
@@ -94,6 +95,102 @@ pub fn compute_merkle_root(txids: &[String]) -> String {
     to_hex(&leaves[0])
 }
 
+/// Encoded size of a block, as limited by `config::MAX_BLOCK_SIZE`
+pub fn block_size(block: &Block) -> Result<usize> {
+    Ok(bincode::encode_to_vec(block, *BINCODE_CONFIG)?.len())
+}
+
+/// Fill a block template from mempool candidates `(tx, fee)` by fee rate until
+/// `max_bytes` of encoded transactions. A candidate spending another
+/// candidate's output only becomes eligible once its parent is selected.
+/// Returns the selection in block order.
+pub fn select_transactions(
+    candidates: Vec<(Transaction, U256)>,
+    max_bytes: usize,
+) -> Vec<(Transaction, U256)> {
+    use std::collections::{BinaryHeap, HashMap, HashSet};
+
+    /// Heap entry ordered by fee per byte, then by candidate position
+    struct Ready {
+        fee: U256,
+        size: usize,
+        idx: usize,
+    }
+    impl PartialEq for Ready {
+        fn eq(&self, other: &Self) -> bool {
+            self.cmp(other) == std::cmp::Ordering::Equal
+        }
+    }
+    impl Eq for Ready {}
+    impl PartialOrd for Ready {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+    impl Ord for Ready {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            let lhs = self.fee.saturating_mul(U256::from(other.size));
+            let rhs = other.fee.saturating_mul(U256::from(self.size));
+            lhs.cmp(&rhs).then_with(|| other.idx.cmp(&self.idx))
+        }
+    }
+
+    let sizes: Vec<usize> = candidates
+        .iter()
+        .map(|(tx, _)| {
+            bincode::encode_to_vec(tx, *BINCODE_CONFIG)
+                .map(|b| b.len())
+                .unwrap_or(usize::MAX)
+        })
+        .collect();
+    let position: HashMap<&str, usize> = candidates
+        .iter()
+        .enumerate()
+        .map(|(i, (tx, _))| (tx.txid.as_str(), i))
+        .collect();
+
+    // Unselected in-pool parents per candidate, and the reverse edges
+    let mut waiting = vec![0usize; candidates.len()];
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); candidates.len()];
+    for (i, (tx, _)) in candidates.iter().enumerate() {
+        let parents: HashSet<usize> = tx
+            .inputs
+            .iter()
+            .filter_map(|inp| position.get(inp.txid.as_str()).copied())
+            .filter(|&p| p != i)
+            .collect();
+        waiting[i] = parents.len();
+        for p in parents {
+            children[p].push(i);
+        }
+    }
+
+    let mut heap: BinaryHeap<Ready> = (0..candidates.len())
+        .filter(|&i| waiting[i] == 0)
+        .map(|i| Ready { fee: candidates[i].1, size: sizes[i], idx: i })
+        .collect();
+    let mut selected = Vec::new();
+    let mut remaining = max_bytes;
+
+    // A candidate that does not fit is dropped, and its descendants never become ready
+    while let Some(Ready { idx, size, .. }) = heap.pop() {
+        if size > remaining {
+            continue;
+        }
+        remaining -= size;
+        selected.push(idx);
+        for &c in &children[idx] {
+            waiting[c] -= 1;
+            if waiting[c] == 0 {
+                heap.push(Ready { fee: candidates[c].1, size: sizes[c], idx: c });
+            }
+        }
+    }
+
+    let mut slots: Vec<Option<(Transaction, U256)>> = candidates.into_iter().map(Some).collect();
+    selected.into_iter().filter_map(|i| slots[i].take()).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -122,4 +219,41 @@ mod tests {
         let hash = compute_header_hash(&header).unwrap();
         assert_eq!(hash.len(), 64);
     }
+
+    #[test]
+    fn select_by_fee_rate_within_size() {
+        use crate::transaction::{SEQUENCE_FINAL, TX_VERSION_LEGACY, TransactionInput, TransactionOutput};
+
+        let tx = |txid: &str, parent: &str| Transaction {
+            txid: txid.to_string(),
+            inputs: vec![TransactionInput {
+                txid: parent.to_string(),
+                vout: 0,
+                pubkey: "00".repeat(32),
+                signature: None,
+                sequence: SEQUENCE_FINAL,
+                witness: None,
+            }],
+            outputs: vec![TransactionOutput::new("0xabc".to_string(), U256::from(1))],
+            timestamp: 0,
+            version: TX_VERSION_LEGACY,
+            lock_time: 0,
+        };
+        let size = bincode::encode_to_vec(tx("aa", "p0"), *BINCODE_CONFIG).unwrap().len();
+
+        // "child" pays the most but must follow "aa"; "low" pays the least
+        let candidates = vec![
+            (tx("low", "p1"), U256::from(1)),
+            (tx("child", "aa"), U256::from(100)),
+            (tx("aa", "p0"), U256::from(10)),
+            (tx("bb", "p2"), U256::from(50)),
+        ];
+        let order = |sel: Vec<(Transaction, U256)>| sel.into_iter().map(|(t, _)| t.txid).collect::<Vec<_>>();
+
+        assert_eq!(order(select_transactions(candidates.clone(), 10 * size)), ["bb", "aa", "child", "low"]);
+        // Room for two: "child" waits for "aa", so "bb" and "aa" win
+        assert_eq!(order(select_transactions(candidates.clone(), 2 * size)), ["bb", "aa"]);
+        // Room for one: the parent never fits behind "bb", so its child is dropped too
+        assert_eq!(order(select_transactions(candidates, size)), ["bb"]);
+    }
 }
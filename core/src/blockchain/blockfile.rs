@@ -0,0 +1,192 @@
+//! Block files: main-chain blocks as length-prefixed bincode records, for
+//! seeding or archiving a chain without P2P
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+use bincode::{Decode, Encode};
+
+use super::{BINCODE_CONFIG, Blockchain};
+use crate::block::Block;
+use crate::config::MAX_BLOCK_SIZE;
+
+/// Block file format version
+pub const BLOCK_FILE_VERSION: u32 = 1;
+
+/// First bytes of every block file
+const BLOCK_FILE_MAGIC: &[u8; 8] = b"ASTRBLKS";
+
+/// Blocks loaded per `get_blocks_range` call while exporting
+const EXPORT_CHUNK: u64 = 500;
+
+/// Sanity cap on a record length; valid blocks encode well below it
+const MAX_RECORD_BYTES: usize = 4 * MAX_BLOCK_SIZE;
+
+/// Written after the magic. The file continues with one record per block:
+/// a u32 big-endian length and the bincode-encoded block.
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Eq)]
+struct BlockFileHeader {
+    version: u32,
+    chain_id: u64,
+}
+
+/// Result of `import_blocks`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockImportSummary {
+    pub imported: u64,
+    pub skipped: u64, // already on the local main chain
+}
+
+impl Blockchain {
+    /// Write main-chain blocks `from..=to` to `path`. Fails if a body in the
+    /// range has been pruned.
+    pub fn export_blocks(&self, path: &Path, from: u64, to: u64) -> Result<u64> {
+        let tip_height = self
+            .get_next_index()?
+            .checked_sub(1)
+            .ok_or_else(|| anyhow!("chain is empty"))?;
+        if from > to || to > tip_height {
+            return Err(anyhow!(
+                "invalid range #{}..=#{} (tip is #{})",
+                from,
+                to,
+                tip_height
+            ));
+        }
+        let prune_height = self.prune_height()?;
+        if from < prune_height {
+            return Err(anyhow!("blocks below #{} are pruned on this node", prune_height));
+        }
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(BLOCK_FILE_MAGIC)?;
+        let header = BlockFileHeader {
+            version: BLOCK_FILE_VERSION,
+            chain_id: self.params.chain_id,
+        };
+        bincode::encode_into_std_write(&header, &mut writer, *BINCODE_CONFIG)?;
+
+        let mut height = from;
+        while height <= to {
+            let end = (height + EXPORT_CHUNK - 1).min(to);
+            let blocks = self.get_blocks_range(height, Some(end))?;
+            if blocks.len() as u64 != end - height + 1 {
+                return Err(anyhow!("missing block bodies in #{}..=#{}", height, end));
+            }
+            for block in &blocks {
+                let blob = bincode::encode_to_vec(block, *BINCODE_CONFIG)?;
+                writer.write_all(&(blob.len() as u32).to_be_bytes())?;
+                writer.write_all(&blob)?;
+            }
+            height = end + 1;
+        }
+        writer.flush()?;
+
+        log::info!("Exported blocks #{}..=#{} to {:?}", from, to, path);
+        Ok(to - from + 1)
+    }
+
+    /// Connect the blocks of a block file through `validate_and_insert_block`.
+    /// Blocks already on the local main chain are skipped, so an interrupted
+    /// import resumes by running it again. `progress` is called after each
+    /// imported block.
+    pub fn import_blocks(&mut self, path: &Path, mut progress: impl FnMut(&Block)) -> Result<BlockImportSummary> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 8];
+        reader
+            .read_exact(&mut magic)
+            .map_err(|_| anyhow!("{:?} is not a block file", path))?;
+        if &magic != BLOCK_FILE_MAGIC {
+            return Err(anyhow!("{:?} is not a block file", path));
+        }
+        let header: BlockFileHeader = bincode::decode_from_std_read(&mut reader, *BINCODE_CONFIG)?;
+        if header.version != BLOCK_FILE_VERSION {
+            return Err(anyhow!("unsupported block file version {}", header.version));
+        }
+        if header.chain_id != self.params.chain_id {
+            return Err(anyhow!(
+                "block file is for chain id {}, this node runs {}",
+                header.chain_id,
+                self.params.chain_id
+            ));
+        }
+
+        let mut summary = BlockImportSummary::default();
+        loop {
+            let mut len = [0u8; 4];
+            match reader.read_exact(&mut len) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            }
+            let len = u32::from_be_bytes(len) as usize;
+            if len > MAX_RECORD_BYTES {
+                return Err(anyhow!("block record of {} bytes exceeds the limit", len));
+            }
+            let mut blob = vec![0u8; len];
+            reader
+                .read_exact(&mut blob)
+                .map_err(|e| anyhow!("truncated block record: {}", e))?;
+            let (block, _): (Block, usize) = bincode::decode_from_slice(&blob, *BINCODE_CONFIG)?;
+
+            let height = block.header.index;
+            let next = self.get_next_index()?;
+            if height < next {
+                if self.main_chain_hash(height)? != block.hash {
+                    return Err(anyhow!(
+                        "block #{} in the file ({}) differs from the local main chain",
+                        height,
+                        block.hash
+                    ));
+                }
+                summary.skipped += 1;
+                continue;
+            }
+            if height > next {
+                return Err(anyhow!("block file jumps to #{}, expected #{}", height, next));
+            }
+            self.validate_and_insert_block(&block)
+                .map_err(|e| anyhow!("block #{} ({}) rejected: {}", height, block.hash, e))?;
+            summary.imported += 1;
+            progress(&block);
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::testutil::temp_chain;
+    use crate::network::ChainParams;
+
+    #[test]
+    fn block_file_import_skips_known_blocks() {
+        let (source_dir, mut source) = temp_chain("blockfile-src", ChainParams::mainnet());
+        source.create_genesis("0x00000000000000000000000000000000000000aa").unwrap();
+        let file = source_dir.join("export.blocks");
+        assert_eq!(source.export_blocks(&file, 0, 0).unwrap(), 1);
+        assert!(source.export_blocks(&file.with_extension("bad"), 0, 1).is_err());
+
+        // Re-running over blocks already connected is a no-op (resume)
+        let summary = source.import_blocks(&file, |_| {}).unwrap();
+        assert_eq!(summary, BlockImportSummary { imported: 0, skipped: 1 });
+
+        // A chain with another genesis refuses the file
+        let (_other_dir, mut other) = temp_chain("blockfile-other", ChainParams::mainnet());
+        other.create_genesis("0x00000000000000000000000000000000000000bb").unwrap();
+        assert!(other.import_blocks(&file, |_| {}).is_err());
+
+        // So does a node on another network
+        other.params.chain_id += 1;
+        assert!(
+            other
+                .import_blocks(&file, |_| {})
+                .unwrap_err()
+                .to_string()
+                .contains("chain id")
+        );
+    }
+}
@@ -1,20 +1,70 @@
 use crate::block::{Block, BlockHeader, compute_header_hash, compute_merkle_root};
-use crate::db::{open_db, put_batch};
-use crate::transaction::Transaction;
+use crate::consensus::retarget::{self, RetargetRecord};
+use crate::db::{ChainBatch, ChainDb, open_db, put_batch};
+use crate::transaction::{RelativeLock, TX_VERSION_LEGACY, Transaction};
+use crate::security::{
+    BlockFailureReason, BodyFailure, validate_block_security, validate_transaction_security,
+};
 use crate::utxo::Utxo;
 use anyhow::{Result, anyhow};
-use bincode::config;
+use bincode::{Decode, Encode, config};
 use chrono::Utc;
-use hex;
 use log;
 use once_cell::sync::Lazy;
 use primitive_types::U256;
-use rocksdb::{DB, WriteBatch};
-use crate::config::HALVING_INTERVAL;
-use crate::config::initial_block_reward;
+use crate::config::coinbase_is_mature;
+use crate::checkpoint::{Checkpoint, SignedCheckpoints};
+use crate::network::ChainParams;
+
+mod blockfile;
+mod regtest;
+mod sigcheck;
+mod snapshot;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testutil;
+mod tree;
+mod utxo_stats;
+
+pub use blockfile::{BLOCK_FILE_VERSION, BlockImportSummary};
+pub use sigcheck::{PrecheckedBlock, check_block_contextless};
+pub use snapshot::{SNAPSHOT_VERSION, SnapshotInfo};
+pub use tree::{BlockStatus, ChainTip, ChainTipStatus};
+pub use utxo_stats::UtxoSetStats;
 
 pub static BINCODE_CONFIG: Lazy<config::Configuration> = Lazy::new(|| config::standard());
 
+/// Marker key set once the `a:`/`y:` address indexes cover the whole chain
+const ADDRESS_INDEX_KEY: &[u8] = b"addrindex";
+
+/// Lowest main-chain height whose block body is still stored (u64 big-endian)
+const PRUNE_HEIGHT_KEY: &[u8] = b"prune_height";
+
+/// Chain id of the network that created the database (u64 big-endian)
+const CHAIN_ID_KEY: &[u8] = b"chain_id";
+
+/// Role of an address in a main-chain transaction
+#[derive(Encode, Decode, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryDirection {
+    Send,    // address signed one of the inputs
+    Receive, // address is paid by an output and signed none of the inputs
+}
+
+/// Main-chain location of a transaction (`x:` index)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxLocation {
+    pub block_hash: String,
+    pub height: u64,
+    pub position: u32, // index of the tx within the block
+}
+
+/// Entry of the `y:` address history index
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Eq)]
+pub struct AddressHistoryEntry {
+    pub txid: String,
+    pub height: u64,
+    pub direction: HistoryDirection,
+}
+
 /// Blockchain structure (disk-based RocksDB storage)
 ///
 /// This structure manages the blockchain state including:
@@ -23,90 +73,811 @@ pub static BINCODE_CONFIG: Lazy<config::Configuration> = Lazy::new(|| config::st
 /// - Chain tip tracking
 /// - Balance and transaction queries
 pub struct Blockchain {
-    pub db: DB,
+    pub db: ChainDb,
     pub chain_tip: Option<String>, // tip hash hex
     pub difficulty: u32,
-    pub block_interval: i64,  // Target block generation interval (seconds)
-    pub max_reorg_depth: u64, // Maximum allowed reorganization depth (security)
+    pub params: ChainParams, // Consensus parameters of the network this database belongs to
     pub max_future_block_time: i64, // Maximum seconds a block can be in the future
     pub enable_deep_reorg_alerts: bool, // Alert on deep reorgs (vs hard reject)
+    pub prune_depth: Option<u64>, // Block bodies kept below the tip (None = keep all)
+    pub signed_checkpoints: Option<SignedCheckpoints>, // Checkpoint file in force, with its verified signatures
+    pub assume_valid: Option<Checkpoint>, // Signatures of this block and its ancestors are not checked
 }
 
 impl Blockchain {
-    const POW_LIMIT_BITS: u32 = 0x1f7fffff; // Bitcoin-style compact bits: maximum target (easiest difficulty for testing)
-    const RETARGET_WINDOW: u64 = 24; // DWG3 window: use last 24 blocks
+    /// Checkpoint policy for a block at `height`
+    fn checkpoint_allows(&self, height: u64, hash: &str) -> bool {
+        let mut checkpoints = self.active_checkpoints();
+        // The assume-valid block is held to its hash like a checkpoint, so
+        // blocks connected below it without signature checks are its ancestors
+        checkpoints.extend(self.assume_valid.iter().cloned());
+        crate::checkpoint::validate_against_checkpoints(&checkpoints, height, hash)
+    }
 
-    fn compact_to_target(bits: u32) -> U256 {
-        let exponent = bits >> 24;
-        let mantissa = bits & 0x007f_ffff;
-        if mantissa == 0 {
-            return U256::zero();
+    /// Set the assume-valid block from the `ASSUME_VALID` node setting:
+    /// `<height>:<hash>`, a hash already in the block tree, or `none`. Without
+    /// a setting it is the latest active checkpoint.
+    pub fn set_assume_valid(&mut self, setting: Option<&str>) -> Result<()> {
+        let assume_valid = match setting.map(str::trim) {
+            None => self.active_checkpoints().into_iter().max_by_key(|cp| cp.height),
+            Some(value) if value.is_empty() || value == "0" || value.eq_ignore_ascii_case("none") => None,
+            Some(value) => {
+                let (height, hash) = match value.split_once(':') {
+                    Some((height, hash)) => (
+                        height.trim().parse().map_err(|_| anyhow!("invalid assume-valid height {}", height))?,
+                        hash.trim().to_ascii_lowercase(),
+                    ),
+                    None => {
+                        let hash = value.to_ascii_lowercase();
+                        let header = self
+                            .load_header(&hash)?
+                            .ok_or_else(|| anyhow!("assume-valid block {} is unknown; give <height>:<hash>", hash))?;
+                        (header.index, hash)
+                    }
+                };
+                if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(anyhow!("assume-valid hash must be 64 hex characters"));
+                }
+                if !crate::checkpoint::validate_against_checkpoints(&self.active_checkpoints(), height, &hash) {
+                    return Err(anyhow!("assume-valid block #{} conflicts with a checkpoint", height));
+                }
+                Some(Checkpoint {
+                    height,
+                    hash,
+                    description: "Assume-valid block".to_string(),
+                })
+            }
+        };
+
+        match &assume_valid {
+            Some(av) => log::info!(
+                "Assuming valid signatures up to block #{} ({})",
+                av.height,
+                &av.hash[..16.min(av.hash.len())]
+            ),
+            None => log::info!("Assume-valid disabled: verifying every signature"),
         }
+        self.assume_valid = assume_valid;
+        Ok(())
+    }
 
-        if exponent <= 3 {
-            U256::from(mantissa >> (8 * (3 - exponent)))
-        } else {
-            U256::from(mantissa) << (8 * (exponent - 3))
+    /// Whether block `hash` at `height` is the assume-valid block or one of
+    /// its ancestors: found through the main-chain index once the
+    /// assume-valid block is connected, otherwise by walking the header tree
+    /// back from it (a block at the same height on another branch is not).
+    /// The assume-valid block must also carry at least the main chain's
+    /// work, so a stale branch through it cannot skip signatures.
+    fn assumed_valid(&self, hash: &str, height: u64) -> Result<bool> {
+        let av = match &self.assume_valid {
+            Some(av) if height <= av.height => av,
+            _ => return Ok(false),
+        };
+        if self.on_main_chain(&av.hash, av.height)? {
+            return self.on_main_chain(hash, height);
+        }
+
+        // Not connected yet: its header must be known, with enough work
+        let mut header = match self.load_header(&av.hash)? {
+            Some(header) if header.index == av.height => header,
+            _ => return Ok(false),
+        };
+        let tip_work = match &self.chain_tip {
+            Some(tip) => self.calculate_chain_work(tip)?,
+            None => U256::zero(),
+        };
+        if self.calculate_chain_work(&av.hash)? < tip_work {
+            return Ok(false);
+        }
+
+        let mut ancestor = av.hash.clone();
+        while header.index > height {
+            ancestor = header.previous_hash.clone();
+            header = match self.load_header(&ancestor)? {
+                Some(parent) => parent,
+                None => return Ok(false),
+            };
         }
+        Ok(ancestor == hash)
     }
 
-    fn target_to_compact(target: U256) -> u32 {
+    /// Built-in checkpoints of the network plus those of the signed file in force
+    pub fn active_checkpoints(&self) -> Vec<Checkpoint> {
+        let mut checkpoints = self.params.checkpoints.clone();
+        if let Some(signed) = &self.signed_checkpoints {
+            checkpoints.extend(signed.checkpoints.iter().cloned());
+        }
+        checkpoints
+    }
+
+    /// Put a signed checkpoint file in force. It must carry enough release
+    /// key signatures, be newer than the file already in force and agree
+    /// with the built-in checkpoints. Main-chain blocks that conflict with it
+    /// are only reported here; the startup integrity check truncates them.
+    pub fn apply_signed_checkpoints(&mut self, mut signed: SignedCheckpoints) -> Result<()> {
+        let signers = signed.verify(&self.params)?;
+        if let Some(active) = self.signed_checkpoints.as_ref().filter(|a| a.sequence >= signed.sequence) {
+            return Err(anyhow!(
+                "checkpoint set #{} is not newer than #{} in force",
+                signed.sequence,
+                active.sequence
+            ));
+        }
+        for cp in &signed.checkpoints {
+            if self.params.checkpoints.iter().any(|b| b.height == cp.height && b.hash != cp.hash) {
+                return Err(anyhow!(
+                    "checkpoint set #{} conflicts with the built-in checkpoint at height {}",
+                    signed.sequence,
+                    cp.height
+                ));
+            }
+            let main = self.db.get(format!("i:{}", cp.height).as_bytes())?;
+            if main.is_some_and(|main| main != cp.hash.as_bytes()) {
+                log::warn!(
+                    "⚠️  Main chain block #{} conflicts with signed checkpoint {}",
+                    cp.height,
+                    &cp.hash[..16]
+                );
+            }
+        }
+
+        signed.signatures.retain(|(key, _)| signers.contains(key));
+        log::info!(
+            "✅ Signed checkpoint set #{} in force: {} checkpoints, signed by {} release key(s)",
+            signed.sequence,
+            signed.checkpoints.len(),
+            signers.len()
+        );
+        self.signed_checkpoints = Some(signed);
+        Ok(())
+    }
+
+    /// PoW check against the header's compact bits.
+    /// Uses the DAG hash from `params.dag_pow_height`, the header hash before that.
+    pub fn is_valid_pow(params: &ChainParams, header: &BlockHeader) -> Result<bool> {
+        let target = retarget::compact_to_target(header.difficulty);
         if target.is_zero() {
-            return 0;
+            return Ok(false);
         }
+        let pow_hash = crate::consensus::compute_pow_hash(params, header)?;
+        Ok(U256::from_big_endian(&pow_hash) < target)
+    }
+
+    /// Expected hashes to find a block at `bits`: 2^256 / (target + 1)
+    pub fn block_work(bits: u32) -> U256 {
+        retarget::block_work(bits)
+    }
 
+    fn put_chain_work(batch: &mut ChainBatch, hash: &str, work: U256) {
         let mut bytes = [0u8; 32];
-        target.to_big_endian(&mut bytes);
-        let first_non_zero = bytes.iter().position(|&b| b != 0).unwrap_or(31);
-        let mut size = (32 - first_non_zero) as u32;
+        work.to_big_endian(&mut bytes);
+        batch.put(format!("w:{}", hash).as_bytes(), bytes);
+    }
 
-        let mut mantissa: u32 = if size <= 3 {
-            let mut v: u32 = 0;
-            for i in first_non_zero..32 {
-                v = (v << 8) | bytes[i] as u32;
-            }
-            v << (8 * (3 - size))
-        } else {
-            ((bytes[first_non_zero] as u32) << 16)
-                | ((bytes[first_non_zero + 1] as u32) << 8)
-                | (bytes[first_non_zero + 2] as u32)
-        };
+    fn stored_chain_work(&self, hash: &str) -> Result<Option<U256>> {
+        match self.db.get(format!("w:{}", hash).as_bytes())? {
+            Some(bytes) if bytes.len() == 32 => Ok(Some(U256::from_big_endian(&bytes))),
+            Some(bytes) => Err(anyhow!(
+                "corrupt chain work for {}: expected 32 bytes, got {}",
+                hash,
+                bytes.len()
+            )),
+            None => Ok(None),
+        }
+    }
 
-        if (mantissa & 0x0080_0000) != 0 {
-            mantissa >>= 8;
-            size += 1;
+    /// Queue `c:{txid}` (height of a main-chain coinbase, u64 big-endian) into a batch
+    fn put_coinbase_height(batch: &mut ChainBatch, txid: &str, height: u64) {
+        batch.put(format!("c:{}", txid).as_bytes(), height.to_be_bytes());
+    }
+
+    /// Height at which coinbase `txid` was connected to the main chain
+    /// (None for regular transactions)
+    pub fn coinbase_height(&self, txid: &str) -> Result<Option<u64>> {
+        match self.db.get(format!("c:{}", txid).as_bytes())? {
+            Some(bytes) => {
+                let arr: [u8; 8] = bytes
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| anyhow!("corrupt coinbase height for {}", txid))?;
+                Ok(Some(u64::from_be_bytes(arr)))
+            }
+            None => Ok(None),
         }
+    }
 
-        (size << 24) | (mantissa & 0x007f_ffff)
+    /// Whether `utxo` can be spent in a block at `spend_height`
+    pub fn is_utxo_mature(&self, utxo: &Utxo, spend_height: u64) -> Result<bool> {
+        Ok(match self.coinbase_height(&utxo.txid)? {
+            Some(created) => coinbase_is_mature(created, spend_height, self.params.coinbase_maturity),
+            None => true,
+        })
+    }
+
+    /// Reject a transaction spending coinbase outputs not yet mature at `spend_height`
+    pub fn check_coinbase_maturity(&self, tx: &Transaction, spend_height: u64) -> Result<()> {
+        for inp in &tx.inputs {
+            if let Some(created) = self.coinbase_height(&inp.txid)? {
+                self.ensure_coinbase_mature(&inp.txid, created, spend_height)?;
+            }
+        }
+        Ok(())
     }
 
-    fn hash_to_u256(hash_hex: &str) -> Result<U256> {
-        let normalized = hash_hex.strip_prefix("0x").unwrap_or(hash_hex);
-        let bytes = hex::decode(normalized)?;
-        if bytes.len() != 32 {
+    fn ensure_coinbase_mature(&self, txid: &str, created: u64, spend_height: u64) -> Result<()> {
+        if !coinbase_is_mature(created, spend_height, self.params.coinbase_maturity) {
             return Err(anyhow!(
-                "invalid hash length for PoW comparison: expected 32 bytes, got {}",
-                bytes.len()
+                "premature spend of coinbase {}: created at height {}, spendable from {}",
+                txid,
+                created,
+                created.saturating_add(self.params.coinbase_maturity)
             ));
         }
-        Ok(U256::from_big_endian(&bytes))
+        Ok(())
     }
 
-    fn pow_limit_target() -> U256 {
-        Self::compact_to_target(Self::POW_LIMIT_BITS)
+    /// Write missing `c:{txid}` entries for the coinbases of the last
+    /// `params.coinbase_maturity` main-chain blocks (databases created before they were tracked)
+    fn backfill_coinbase_heights(&self) -> Result<()> {
+        let mut current = match &self.chain_tip {
+            Some(tip) => tip.clone(),
+            None => return Ok(()),
+        };
+        let mut batch = ChainBatch::default();
+        let mut written = 0usize;
+
+        for _ in 0..=self.params.coinbase_maturity {
+            let block = match self.load_block(&current)? {
+                Some(b) => b,
+                None => break,
+            };
+            if let Some(cb) = block.transactions.first()
+                && self.coinbase_height(&cb.txid)?.is_none()
+            {
+                Self::put_coinbase_height(&mut batch, &cb.txid, block.header.index);
+                written += 1;
+            }
+            if block.header.index == 0 {
+                break;
+            }
+            current = block.header.previous_hash;
+        }
+
+        if written > 0 {
+            put_batch(&self.db, batch)?;
+            log::info!("Backfilled {} coinbase height entries", written);
+        }
+        Ok(())
     }
 
-    fn is_valid_pow(hash_hex: &str, bits: u32) -> Result<bool> {
-        let hash = Self::hash_to_u256(hash_hex)?;
-        let target = Self::compact_to_target(bits);
-        if target.is_zero() {
-            return Ok(false);
+    /// Queue `x:{txid}` (height u64 big-endian, position u32 big-endian, block
+    /// hash) into a batch. Entries written before positions were indexed hold
+    /// the height only.
+    fn put_tx_location(batch: &mut ChainBatch, txid: &str, block_hash: &str, height: u64, position: u32) {
+        let mut value = Vec::with_capacity(12 + block_hash.len());
+        value.extend_from_slice(&height.to_be_bytes());
+        value.extend_from_slice(&position.to_be_bytes());
+        value.extend_from_slice(block_hash.as_bytes());
+        batch.put(format!("x:{}", txid).as_bytes(), &value);
+    }
+
+    /// Height of the main-chain block containing `txid`
+    pub fn tx_height(&self, txid: &str) -> Result<Option<u64>> {
+        match self.db.get(format!("x:{}", txid).as_bytes())? {
+            Some(bytes) => {
+                let arr: [u8; 8] = bytes
+                    .get(..8)
+                    .and_then(|b| b.try_into().ok())
+                    .ok_or_else(|| anyhow!("corrupt tx height for {}", txid))?;
+                Ok(Some(u64::from_be_bytes(arr)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Block hash, height and position of a main-chain transaction
+    pub fn tx_location(&self, txid: &str) -> Result<Option<TxLocation>> {
+        let bytes = match self.db.get(format!("x:{}", txid).as_bytes())? {
+            Some(bytes) => bytes,
+            None => return Ok(None),
+        };
+        let height = bytes
+            .get(..8)
+            .and_then(|b| b.try_into().ok())
+            .map(u64::from_be_bytes)
+            .ok_or_else(|| anyhow!("corrupt tx location for {}", txid))?;
+        if bytes.len() > 12 {
+            let position = u32::from_be_bytes(bytes[8..12].try_into()?);
+            let block_hash = String::from_utf8(bytes[12..].to_vec())?;
+            return Ok(Some(TxLocation { block_hash, height, position }));
+        }
+
+        // Height-only entry: resolve the rest from the block at that height
+        let block_hash = match self.db.get(format!("i:{}", height).as_bytes())? {
+            Some(v) => String::from_utf8(v)?,
+            None => return Ok(None),
+        };
+        let position = self
+            .load_block(&block_hash)?
+            .and_then(|b| b.transactions.iter().position(|t| t.txid == txid));
+        Ok(position.map(|p| TxLocation {
+            block_hash,
+            height,
+            position: p as u32,
+        }))
+    }
+
+    /// Index `x:{txid}` for the whole main chain when the tip is not indexed yet
+    /// (databases created before relative locks)
+    fn backfill_tx_heights(&self) -> Result<()> {
+        let tip = match &self.chain_tip {
+            Some(tip) => tip.clone(),
+            None => return Ok(()),
+        };
+        match self.load_block(&tip)? {
+            Some(block) => match block.transactions.first() {
+                Some(cb) if self.tx_height(&cb.txid)?.is_none() => {}
+                _ => return Ok(()),
+            },
+            None => return Ok(()),
+        }
+
+        log::info!("Indexing transaction heights for relative time-locks...");
+        let mut current = tip;
+        let mut batch = ChainBatch::default();
+        let mut written = 0usize;
+        loop {
+            let block = match self.load_block(&current)? {
+                Some(b) => b,
+                None => break,
+            };
+            for (pos, tx) in block.transactions.iter().enumerate() {
+                Self::put_tx_location(&mut batch, &tx.txid, &block.hash, block.header.index, pos as u32);
+                written += 1;
+            }
+            if written >= 10_000 {
+                put_batch(&self.db, std::mem::take(&mut batch))?;
+                written = 0;
+            }
+            if block.header.index == 0 {
+                break;
+            }
+            current = block.header.previous_hash;
+        }
+        put_batch(&self.db, batch)?;
+        log::info!("Transaction height index complete");
+        Ok(())
+    }
+
+    /// Queue the block body `b:{hash}` and its header `h:{hash}`, which is
+    /// kept when the body is pruned
+    fn put_block(batch: &mut ChainBatch, block: &Block) -> Result<()> {
+        let block_blob = bincode::encode_to_vec(block, *BINCODE_CONFIG)?;
+        batch.put(format!("b:{}", block.hash).as_bytes(), &block_blob);
+        let header_blob = bincode::encode_to_vec(&block.header, *BINCODE_CONFIG)?;
+        batch.put(format!("h:{}", block.hash).as_bytes(), &header_blob);
+        Ok(())
+    }
+
+    /// Queue the undo record `r:{block_hash}`: the UTXOs the block removed
+    /// from the committed set, in spend order
+    fn put_undo(batch: &mut ChainBatch, block_hash: &str, spent: &[Utxo]) -> Result<()> {
+        let blob = bincode::encode_to_vec(spent, *BINCODE_CONFIG)?;
+        batch.put(format!("r:{}", block_hash).as_bytes(), &blob);
+        Ok(())
+    }
+
+    /// Undo record of a connected block
+    pub fn load_undo(&self, block_hash: &str) -> Result<Option<Vec<Utxo>>> {
+        match self.db.get(format!("r:{}", block_hash).as_bytes())? {
+            Some(blob) => {
+                let (spent, _): (Vec<Utxo>, usize) =
+                    bincode::decode_from_slice(&blob, *BINCODE_CONFIG)?;
+                Ok(Some(spent))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Outpoints a block spends from outside itself (what its undo record holds)
+    fn external_spends(block: &Block) -> Vec<(String, u32)> {
+        let mut created = std::collections::HashSet::new();
+        let mut spends = Vec::new();
+        for tx in &block.transactions {
+            for inp in &tx.inputs {
+                if !created.remove(&(inp.txid.as_str(), inp.vout)) {
+                    spends.push((inp.txid.clone(), inp.vout));
+                }
+            }
+            for v in 0..tx.outputs.len() {
+                created.insert((tx.txid.as_str(), v as u32));
+            }
+        }
+        spends
+    }
+
+    /// Queue an unspent output under `u:{txid}:{vout}` and `a:{address}:{txid}:{vout}`
+    fn put_utxo(batch: &mut ChainBatch, utxo: &Utxo) -> Result<()> {
+        let ublob = bincode::encode_to_vec(utxo, *BINCODE_CONFIG)?;
+        batch.put(format!("u:{}:{}", utxo.txid, utxo.vout).as_bytes(), &ublob);
+        batch.put(Self::address_utxo_key(&utxo.to, &utxo.txid, utxo.vout).as_bytes(), &ublob);
+        Ok(())
+    }
+
+    /// Queue the removal of an output from the UTXO set and its address index
+    fn delete_utxo(batch: &mut ChainBatch, address: &str, txid: &str, vout: u32) {
+        batch.delete(format!("u:{}:{}", txid, vout).as_bytes());
+        batch.delete(Self::address_utxo_key(address, txid, vout).as_bytes());
+    }
+
+    fn address_utxo_key(address: &str, txid: &str, vout: u32) -> String {
+        format!("a:{}:{}:{}", address.to_lowercase(), txid, vout)
+    }
+
+    /// Height is zero-padded so entries of an address sort by height
+    fn address_history_key(address: &str, height: u64, txid: &str) -> String {
+        format!("y:{}:{:020}:{}", address, height, txid)
+    }
+
+    /// Addresses a transaction touches, lowercased: input signers as `Send`,
+    /// then output recipients that signed no input as `Receive`
+    fn tx_addresses(tx: &Transaction) -> Vec<(String, HistoryDirection)> {
+        let mut touched: Vec<(String, HistoryDirection)> = Vec::new();
+        for inp in &tx.inputs {
+            if let Ok(addr) = inp.spender_address() {
+                let addr = addr.to_lowercase();
+                if !touched.iter().any(|(a, _)| *a == addr) {
+                    touched.push((addr, HistoryDirection::Send));
+                }
+            }
+        }
+        for out in &tx.outputs {
+            let addr = out.to.to_lowercase();
+            if !touched.iter().any(|(a, _)| *a == addr) {
+                touched.push((addr, HistoryDirection::Receive));
+            }
+        }
+        touched
+    }
+
+    /// Queue the `y:{address}:{height}:{txid}` history entries of a main-chain transaction
+    fn put_address_history(batch: &mut ChainBatch, tx: &Transaction, height: u64) -> Result<()> {
+        for (address, direction) in Self::tx_addresses(tx) {
+            let entry = AddressHistoryEntry {
+                txid: tx.txid.clone(),
+                height,
+                direction,
+            };
+            let blob = bincode::encode_to_vec(&entry, *BINCODE_CONFIG)?;
+            batch.put(Self::address_history_key(&address, height, &tx.txid).as_bytes(), &blob);
+        }
+        Ok(())
+    }
+
+    fn delete_address_history(batch: &mut ChainBatch, tx: &Transaction, height: u64) {
+        for (address, _) in Self::tx_addresses(tx) {
+            batch.delete(Self::address_history_key(&address, height, &tx.txid).as_bytes());
+        }
+    }
+
+    /// All entries whose key starts with `prefix`, in key order
+    fn prefix_entries(&self, prefix: &str) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut entries = Vec::new();
+        for item in self.db.prefix_iter(prefix.as_bytes()) {
+            let (key, value) = item?;
+            entries.push((key.to_vec(), value.to_vec()));
+        }
+        Ok(entries)
+    }
+
+    /// Rebuild the `a:` index from the UTXO set and the `y:` index from the
+    /// main chain (databases created before the address indexes)
+    pub fn reindex_addresses(&self) -> Result<()> {
+        log::info!("Indexing UTXOs and history by address...");
+        let mut batch = ChainBatch::default();
+        for prefix in ["a:", "y:"] {
+            for (key, _) in self.prefix_entries(prefix)? {
+                batch.delete(&key);
+            }
+        }
+        put_batch(&self.db, std::mem::take(&mut batch))?;
+
+        let mut written = 0usize;
+        for (_, value) in self.prefix_entries("u:")? {
+            let (utxo, _): (Utxo, usize) = bincode::decode_from_slice(&value, *BINCODE_CONFIG)?;
+            batch.put(Self::address_utxo_key(&utxo.to, &utxo.txid, utxo.vout).as_bytes(), &value);
+            written += 1;
+            if written >= 10_000 {
+                put_batch(&self.db, std::mem::take(&mut batch))?;
+                written = 0;
+            }
+        }
+
+        let prune_height = self.prune_height()?;
+        if prune_height > 0 {
+            log::warn!("Blocks below #{} are pruned; their history is not indexed", prune_height);
+        }
+        let mut height = prune_height;
+        while let Some(hash) = self.db.get(format!("i:{}", height).as_bytes())? {
+            let hash = String::from_utf8(hash)?;
+            let block = self
+                .load_block(&hash)?
+                .ok_or_else(|| anyhow!("missing block {} at height {}", hash, height))?;
+            for tx in &block.transactions {
+                Self::put_address_history(&mut batch, tx, height)?;
+                written += 1;
+            }
+            if written >= 10_000 {
+                put_batch(&self.db, std::mem::take(&mut batch))?;
+                written = 0;
+            }
+            height += 1;
+        }
+        batch.put(ADDRESS_INDEX_KEY, b"1");
+        put_batch(&self.db, batch)?;
+        log::info!("Address index complete ({} blocks)", height);
+        Ok(())
+    }
+
+    /// Queue the removal of a block from the main chain: delete its outputs,
+    /// transactions and main-chain indexes, and restore the UTXOs in its undo
+    /// record. The block itself stays in the tree. `stats` is updated to
+    /// match; the caller writes it.
+    fn disconnect_block(
+        batch: &mut ChainBatch,
+        block: &Block,
+        undo: &[Utxo],
+        stats: &mut UtxoSetStats,
+    ) -> Result<()> {
+        stats.disconnect(block, undo);
+        for tx in block.transactions.iter().rev() {
+            for (v, out) in tx.outputs.iter().enumerate() {
+                Self::delete_utxo(batch, &out.to, &tx.txid, v as u32);
+            }
+            Self::delete_address_history(batch, tx, block.header.index);
+            batch.delete(format!("t:{}", tx.txid).as_bytes());
+            batch.delete(format!("x:{}", tx.txid).as_bytes());
+            if tx.inputs.is_empty() {
+                batch.delete(format!("c:{}", tx.txid).as_bytes());
+            }
+        }
+        for utxo in undo {
+            Self::put_utxo(batch, utxo)?;
+        }
+        batch.delete(format!("r:{}", block.hash).as_bytes());
+        batch.delete(format!("i:{}", block.header.index).as_bytes());
+        Ok(())
+    }
+
+    /// Check that the last `params.max_reorg_depth` main-chain blocks have undo
+    /// records matching their inputs, rebuilding missing or inconsistent ones
+    /// from the parent transactions (databases created before the journal)
+    fn check_undo_journal(&self) -> Result<()> {
+        let mut current = match &self.chain_tip {
+            Some(tip) => tip.clone(),
+            None => return Ok(()),
+        };
+        let mut batch = ChainBatch::default();
+        let mut rebuilt = 0usize;
+
+        for _ in 0..=self.params.max_reorg_depth {
+            let block = match self.load_block(&current)? {
+                Some(b) => b,
+                None => break,
+            };
+            let expected = Self::external_spends(&block);
+            let consistent = match self.load_undo(&block.hash)? {
+                Some(undo) => {
+                    undo.len() == expected.len()
+                        && undo
+                            .iter()
+                            .zip(&expected)
+                            .all(|(u, (txid, vout))| &u.txid == txid && u.vout == *vout)
+                }
+                None => false,
+            };
+
+            if !consistent {
+                match self.rebuild_undo(&block)? {
+                    Some(undo) => {
+                        Self::put_undo(&mut batch, &block.hash, &undo)?;
+                        rebuilt += 1;
+                    }
+                    None => log::warn!(
+                        "Undo record for block #{} ({}) is missing and cannot be rebuilt; it cannot be disconnected",
+                        block.header.index,
+                        &block.hash[..16]
+                    ),
+                }
+            }
+
+            if block.header.index == 0 {
+                break;
+            }
+            current = block.header.previous_hash;
+        }
+
+        if rebuilt > 0 {
+            put_batch(&self.db, batch)?;
+            log::info!("Rebuilt {} block undo records", rebuilt);
+        }
+        Ok(())
+    }
+
+    /// Undo record of a main-chain block rebuilt from the transactions that
+    /// created the outputs it spends. None if one of them is not stored.
+    fn rebuild_undo(&self, block: &Block) -> Result<Option<Vec<Utxo>>> {
+        let expected = Self::external_spends(block);
+        let mut undo = Vec::with_capacity(expected.len());
+        for (txid, vout) in expected {
+            let output = self
+                .load_tx(&txid)?
+                .and_then(|tx| tx.outputs.get(vout as usize).cloned());
+            match output {
+                Some(out) => undo.push(Utxo::new(txid, vout, out.to.to_lowercase(), out.amount())),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(undo))
+    }
+
+    /// Lowest main-chain height whose block body is still stored (0 until the
+    /// node has pruned)
+    pub fn prune_height(&self) -> Result<u64> {
+        match self.db.get(PRUNE_HEIGHT_KEY)? {
+            Some(bytes) => {
+                let arr: [u8; 8] = bytes
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| anyhow!("corrupt prune height record"))?;
+                Ok(u64::from_be_bytes(arr))
+            }
+            None => Ok(0),
+        }
+    }
+
+    /// Enable pruning (`None` keeps every block). The depth never drops below
+    /// the reorg window, whose bodies and undo records a reorg needs.
+    pub fn set_prune_depth(&mut self, depth: Option<u64>) {
+        self.prune_depth = depth.map(|d| d.max(self.params.max_reorg_depth + 1));
+    }
+
+    /// Delete the bodies, undo records and transactions of main-chain blocks
+    /// more than `prune_depth` below the tip. Headers, indexes and the UTXO
+    /// set are kept. Returns the number of blocks pruned.
+    pub fn prune(&self) -> Result<usize> {
+        let depth = match self.prune_depth {
+            Some(d) => d,
+            None => return Ok(0),
+        };
+        let tip_height = match &self.chain_tip {
+            Some(tip) => match self.load_header(tip)? {
+                Some(header) => header.index,
+                None => return Ok(0),
+            },
+            None => return Ok(0),
+        };
+
+        // Keep the `depth` blocks ending at the tip
+        let target = (tip_height + 1).saturating_sub(depth);
+        let mut height = self.prune_height()?;
+        if height >= target {
+            return Ok(0);
+        }
+
+        let mut batch = ChainBatch::default();
+        let mut pruned = 0usize;
+        while height < target {
+            let hash = self
+                .db
+                .get(format!("i:{}", height).as_bytes())?
+                .and_then(|v| String::from_utf8(v).ok())
+                .ok_or_else(|| anyhow!("missing block index {}", height))?;
+            if let Some(block) = self.load_block(&hash)? {
+                for tx in &block.transactions {
+                    batch.delete(format!("t:{}", tx.txid).as_bytes());
+                }
+                batch.delete(format!("b:{}", hash).as_bytes());
+                batch.delete(format!("r:{}", hash).as_bytes());
+                pruned += 1;
+            }
+            height += 1;
+            if batch.len() >= 10_000 {
+                batch.put(PRUNE_HEIGHT_KEY, height.to_be_bytes());
+                put_batch(&self.db, std::mem::take(&mut batch))?;
+            }
+        }
+        batch.put(PRUNE_HEIGHT_KEY, height.to_be_bytes());
+        put_batch(&self.db, batch)?;
+
+        if pruned > 0 {
+            log::info!("Pruned {} blocks (bodies kept from #{})", pruned, height);
+        }
+        Ok(pruned)
+    }
+
+    /// Check absolute `lock_time` and per-input relative locks for inclusion at
+    /// `height`, where `prev_mtp` is the median-time-past of the parent block.
+    /// Inputs whose parent has no `x:` entry (same block or mempool) count as
+    /// created at `height`.
+    pub fn check_tx_locks(&self, tx: &Transaction, height: u64, prev_mtp: i64) -> Result<()> {
+        if tx.version > TX_VERSION_LEGACY && height < self.params.locktime_height {
+            return Err(anyhow!(
+                "tx {} version {} not allowed before height {}",
+                tx.txid,
+                tx.version,
+                self.params.locktime_height
+            ));
+        }
+        if !tx.is_final(height, prev_mtp) {
+            return Err(anyhow!(
+                "tx {} is not final: lock_time {} (height {}, median time {})",
+                tx.txid,
+                tx.lock_time,
+                height,
+                prev_mtp
+            ));
+        }
+
+        for inp in &tx.inputs {
+            let lock = match inp.relative_lock(tx.version) {
+                Some(lock) => lock,
+                None => continue,
+            };
+            let created = self.tx_height(&inp.txid)?.unwrap_or(height);
+            let satisfied = match lock {
+                RelativeLock::Blocks(n) => height >= created.saturating_add(n),
+                RelativeLock::Seconds(secs) => {
+                    // Measured from the MTP of the block before the one that created the output
+                    let base = if created >= height {
+                        prev_mtp
+                    } else {
+                        let base_height = created.saturating_sub(1);
+                        let hash = self
+                            .db
+                            .get(format!("i:{}", base_height).as_bytes())?
+                            .and_then(|v| String::from_utf8(v).ok())
+                            .ok_or_else(|| anyhow!("missing block index {}", base_height))?;
+                        self.median_time_past(&hash)?.unwrap_or(0)
+                    };
+                    prev_mtp >= base.saturating_add(secs)
+                }
+            };
+            if !satisfied {
+                return Err(anyhow!(
+                    "tx {} input {}:{} relative lock {:?} not satisfied (created at height {})",
+                    tx.txid,
+                    inp.txid,
+                    inp.vout,
+                    lock,
+                    created
+                ));
+            }
         }
-        Ok(hash < target)
+        Ok(())
     }
 
-    pub fn new(db_path: &str) -> Result<Self> {
+    /// Open the database at `db_path` for the network described by `params`.
+    /// Refuses a database created for another chain id.
+    pub fn new(db_path: &str, params: ChainParams) -> Result<Self> {
         let db = open_db(db_path)?;
+        match db.get(CHAIN_ID_KEY)? {
+            Some(v) => {
+                let bytes: [u8; 8] = v
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| anyhow!("corrupt chain id record"))?;
+                let stored = u64::from_be_bytes(bytes);
+                if stored != params.chain_id {
+                    return Err(anyhow!(
+                        "database belongs to chain id {}, not {} ({})",
+                        stored,
+                        params.chain_id,
+                        params.network
+                    ));
+                }
+            }
+            None => db.put(CHAIN_ID_KEY, params.chain_id.to_be_bytes())?,
+        }
         // load tip if exists
         let tip = db.get(b"tip")?;
         let chain_tip = tip.map(|v| String::from_utf8(v).unwrap());
@@ -121,54 +892,78 @@ impl Blockchain {
         // Load current difficulty from chain tip
         let difficulty = if let Some(ref tip_hash) = chain_tip {
             // Try to load the tip block header
-            match db.get(format!("b:{}", tip_hash).as_bytes()) {
+            match db.get(format!("h:{}", tip_hash).as_bytes()) {
                 Ok(Some(blob)) => {
-                    match bincode::decode_from_slice::<Block, _>(&blob, *BINCODE_CONFIG) {
-                        Ok((block, _)) => {
+                    match bincode::decode_from_slice::<BlockHeader, _>(&blob, *BINCODE_CONFIG) {
+                        Ok((header, _)) => {
                             log::info!(
                                 "Loaded tip block #{} (hash: {})",
-                                block.header.index,
+                                header.index,
                                 tip_hash
                             );
-                            block.header.difficulty
+                            header.difficulty
                         }
                         Err(e) => {
                             log::error!("Failed to decode tip block: {}", e);
-                            Self::POW_LIMIT_BITS
+                            params.pow_limit_bits
                         }
                     }
                 }
                 Ok(None) => {
                     log::error!("Tip block '{}' not found in database!", tip_hash);
-                    Self::POW_LIMIT_BITS
+                    params.pow_limit_bits
                 }
                 Err(e) => {
                     log::error!("Failed to read tip block from DB: {}", e);
-                    Self::POW_LIMIT_BITS
+                    params.pow_limit_bits
                 }
             }
         } else {
             // No chain exists yet, use default
-            Self::POW_LIMIT_BITS
+            params.pow_limit_bits
         };
 
         log::info!("Blockchain initialized with difficulty: {}", difficulty);
 
-        Ok(Blockchain {
+        // Until `set_assume_valid`: the latest built-in checkpoint
+        let assume_valid = params.checkpoints.iter().max_by_key(|cp| cp.height).cloned();
+        let bc = Blockchain {
             db,
             chain_tip,
             difficulty,
-            block_interval: 60, // Target: 60 seconds per block (auto-adjusts based on network hashrate)
-            max_reorg_depth: 100, // Maximum 100 blocks deep reorganization (security limit)
+            params,
             max_future_block_time: 7200, // Max 2 hours in the future (clock drift tolerance)
             enable_deep_reorg_alerts: true, // Alert on suspicious reorgs
-        })
-    }
+            prune_depth: None,
+            signed_checkpoints: None,
+            assume_valid,
+        };
+
+        // Backfill cumulative chain work for databases created before it was tracked
+        if let Some(ref tip_hash) = bc.chain_tip
+            && let Err(e) = bc.calculate_chain_work(tip_hash)
+        {
+            log::warn!("Failed to load chain work for tip {}: {}", tip_hash, e);
+        }
 
-    /// Helper: Iterate over all blocks efficiently
-    fn get_all_blocks_cached(&self) -> Result<Vec<Block>> {
-        // This could be further optimized with caching in production
-        self.get_all_blocks()
+        // Coinbase heights are only needed while outputs are still immature
+        if let Err(e) = bc.backfill_coinbase_heights() {
+            log::warn!("Failed to backfill coinbase heights: {}", e);
+        }
+        if let Err(e) = bc.backfill_tx_heights() {
+            log::warn!("Failed to backfill transaction heights: {}", e);
+        }
+        if let Err(e) = bc.check_undo_journal() {
+            log::warn!("Failed to check block undo records: {}", e);
+        }
+        if bc.db.get(ADDRESS_INDEX_KEY)?.is_none() {
+            bc.reindex_addresses()
+                .unwrap_or_else(|e| log::warn!("Failed to build address index: {}", e));
+        }
+        // Every connect and disconnect updates the record, so it must exist
+        bc.backfill_utxo_stats()?;
+
+        Ok(bc)
     }
 
     /// Create genesis block (with a single coinbase transaction)
@@ -195,132 +990,108 @@ impl Blockchain {
         };
 
         // commit atomically
-        let mut batch = WriteBatch::default();
+        let mut batch = ChainBatch::default();
         // Store complete block (header + transactions)
-        let block_blob = bincode::encode_to_vec(&block, *BINCODE_CONFIG)?;
-        batch.put(format!("b:{}", hash).as_bytes(), &block_blob);
+        self.put_tree_entry(&mut batch, &block.header, &hash, BlockStatus::DataValid)?;
+        Self::put_block(&mut batch, &block)?;
         // tx
         let tx_blob = bincode::encode_to_vec(&cb, *BINCODE_CONFIG)?;
         batch.put(format!("t:{}", cb.txid).as_bytes(), &tx_blob);
 
         for (i, out) in cb.outputs.iter().enumerate() {
             let utxo = Utxo::new(cb.txid.clone(), i as u32, out.to.clone(), out.amount());
-
-            let utxo_blob = bincode::encode_to_vec(&utxo, *BINCODE_CONFIG)?;
-            batch.put(format!("u:{}:{}", cb.txid, i).as_bytes(), &utxo_blob);
+            Self::put_utxo(&mut batch, &utxo)?;
         }
+        Self::put_address_history(&mut batch, &cb, 0)?;
 
         // index
         batch.put(format!("i:0").as_bytes(), hash.as_bytes());
         batch.put(b"tip", hash.as_bytes());
+        Self::put_coinbase_height(&mut batch, &cb.txid, 0);
+        Self::put_tx_location(&mut batch, &cb.txid, &hash, 0, 0);
+        Self::put_undo(&mut batch, &hash, &[])?;
+        let mut stats = self.utxo_set_stats()?;
+        stats.connect(&block, &[]);
+        Self::put_utxo_stats(&mut batch, &stats)?;
 
         put_batch(&self.db, batch)?;
         self.chain_tip = Some(hash.clone());
         Ok(hash)
     }
 
-    /// Validate and insert a fork block (allows fork without chain_tip check)
-    /// This is used for chain reorganization scenarios
+    /// Validate and store a side-branch block in the block tree (no chain_tip
+    /// check, transactions not connected). `activate_best_chain` connects it
+    /// if its branch gets the most work.
     pub fn validate_fork_block(&mut self, block: &Block) -> Result<()> {
-        // 1) header hash match
-        let computed = compute_header_hash(&block.header)?;
-        if computed != block.hash {
-            return Err(anyhow!(
-                "header hash mismatch: computed {} != block.hash {}",
-                computed,
-                block.hash
-            ));
+        self.validate_fork(block, None)
+    }
+
+    /// `validate_fork_block` for a block whose contextless checks already ran
+    pub fn validate_fork_prechecked(&mut self, pre: &PrecheckedBlock) -> Result<()> {
+        self.validate_fork(pre.block(), Some(pre))
+    }
+
+    fn validate_fork(&mut self, block: &Block, pre: Option<&PrecheckedBlock>) -> Result<()> {
+        let known = self.block_status(&block.hash)?;
+        if known == Some(BlockStatus::Invalid) {
+            return Err(anyhow!("block {} is known to be invalid", block.hash));
+        }
+
+        // 1) Header hash, PoW and merkle root (no chain state needed)
+        if pre.is_none() {
+            check_block_contextless(&self.params, block)?;
         }
 
         // 1.5) Checkpoint policy anchors (official chain protection)
-        if !crate::checkpoint::validate_against_checkpoints(block.header.index, &block.hash) {
-            return Err(anyhow!(
+        if !self.checkpoint_allows(block.header.index, &block.hash) {
+            return Err(BlockFailureReason::CheckpointViolation.record(anyhow!(
                 "checkpoint policy violation at height {} for hash {}",
                 block.header.index,
                 block.hash
-            ));
+            )));
         }
 
-        // 2) Proof-of-Work verification
-        if !Self::is_valid_pow(&block.hash, block.header.difficulty)? {
-            return Err(anyhow!(
-                "invalid PoW at block {}: hash does not satisfy bits 0x{:08x}",
-                block.header.index,
-                block.header.difficulty
-            ));
+        // 2) previous exists (but allow fork - no chain_tip check)
+        // NOTE: We DO NOT check against chain_tip here - that's the point of fork blocks
+        if block.header.index > 0 && self.load_header(&block.header.previous_hash)?.is_none() {
+            return Err(BlockFailureReason::PreviousNotFound.record(anyhow!(
+                "previous header not found: {}",
+                block.header.previous_hash
+            )));
         }
 
-        // 3) Expected difficulty check (validate_fork_block)
+        // 3) Expected difficulty, retargeted over the block's own branch
         if block.header.index > 0 {
-            let expected = self.calculate_adjusted_difficulty(block.header.index)?;
+            let expected = self.next_difficulty_after(&block.header.previous_hash)?;
             if block.header.difficulty != expected {
                 log::error!(
                     "[FORK-DIFF-MISMATCH] Block #{} | block=0x{:08x} | dwg3=0x{:08x} | tip_diff=0x{:08x}",
                     block.header.index, block.header.difficulty, expected, self.difficulty
                 );
-                return Err(anyhow!(
+                return Err(BlockFailureReason::DifficultyOutOfRange.record(anyhow!(
                     "difficulty mismatch at height {}: expected 0x{:08x}, got 0x{:08x}",
                     block.header.index,
                     expected,
                     block.header.difficulty
-                ));
-            }
-        }
-
-        // 4) previous exists (but allow fork - no chain_tip check)
-        if block.header.index > 0 {
-            let prev_key = format!("b:{}", block.header.previous_hash);
-            if self.db.get(prev_key.as_bytes())?.is_none() {
-                return Err(anyhow!(
-                    "previous header not found: {}",
-                    block.header.previous_hash
-                ));
+                )));
             }
-            // NOTE: We DO NOT check against chain_tip here - that's the point of fork blocks
         }
 
         // 5) Future timestamp check
         let now = Utc::now().timestamp();
         if block.header.timestamp > now + self.max_future_block_time {
-            return Err(anyhow!(
+            return Err(BlockFailureReason::TimestampTooFuture.record(anyhow!(
                 "block timestamp too far in future: {} > {}",
                 block.header.timestamp,
                 now + self.max_future_block_time
-            ));
+            )));
         }
 
-        // 6) Difficulty sanity progression check
-        if block.header.index > 0 {
-            let prev_key = format!("b:{}", block.header.previous_hash);
-            if let Ok(Some(prev_bytes)) = self.db.get(prev_key.as_bytes()) {
-                if let Ok((prev_block, _)) =
-                    bincode::decode_from_slice::<Block, _>(&prev_bytes, *BINCODE_CONFIG)
-                {
-                    let prev_target = Self::compact_to_target(prev_block.header.difficulty);
-                    let current_target = Self::compact_to_target(block.header.difficulty);
-
-                    if current_target.is_zero()
-                        || (!prev_target.is_zero()
-                            && ((current_target > prev_target
-                                && (current_target / prev_target) > U256::from(4u8))
-                                || (current_target < prev_target
-                                    && (prev_target / current_target) > U256::from(4u8))))
-                    {
-                        return Err(anyhow!(
-                            "difficulty target changed too aggressively at block {}",
-                            block.header.index
-                        ));
-                    }
-                }
-            }
-        }
+        // 5.5) Block size, genesis-time floor, coinbase placement and per-tx limits
+        self.validate_security(block)?;
 
-        // 7) Merkle check
-        let txids: Vec<String> = block.transactions.iter().map(|t| t.txid.clone()).collect();
-        let merkle = compute_merkle_root(&txids);
-        if merkle != block.header.merkle_root {
-            return Err(anyhow!("merkle mismatch"));
-        }
+        // 6) Difficulty sanity progression check
+        self.check_difficulty_progression(block)?;
 
         // 8) Median-Time-Past
         if block.header.index > 0 {
@@ -328,11 +1099,15 @@ impl Blockchain {
         }
 
         // 9) Store the fork block in DB (without updating chain_tip)
-        let mut batch = WriteBatch::default();
+        let mut batch = ChainBatch::default();
         
-        // Store complete block
-        let block_blob = bincode::encode_to_vec(block, *BINCODE_CONFIG)?;
-        batch.put(format!("b:{}", block.hash).as_bytes(), &block_blob);
+        // Store complete block; a block that was connected before keeps its status
+        let status = match known {
+            Some(BlockStatus::DataValid) => BlockStatus::DataValid,
+            _ => BlockStatus::HeaderValid,
+        };
+        self.put_tree_entry(&mut batch, &block.header, &block.hash, status)?;
+        Self::put_block(&mut batch, block)?;
 
         // Store transactions
         for tx in &block.transactions {
@@ -341,7 +1116,7 @@ impl Blockchain {
         }
         
         // Note: We DO NOT update i:{index} here because that would conflict with main chain
-        // The index will be updated during reorganization if this becomes the main chain
+        // The index is written when activate_best_chain connects the block
         // Note: We do NOT update UTXO set here - that happens during reorganization
         
         put_batch(&self.db, batch)?;
@@ -351,12 +1126,38 @@ impl Blockchain {
         Ok(())
     }
 
+    /// `core::security` block checks, plus per-transaction size, count, dust
+    /// and timestamp limits from `params.tx_security_height`
+    fn validate_security(&self, block: &Block) -> Result<()> {
+        validate_block_security(block, self.params.genesis_timestamp)?;
+        if block.header.index >= self.params.tx_security_height {
+            for tx in &block.transactions {
+                validate_transaction_security(tx, block.header.timestamp)?;
+            }
+        }
+        Ok(())
+    }
+
     /// validate and insert block (core of migration/consensus)
     pub fn validate_and_insert_block(&mut self, block: &Block) -> Result<()> {
+        self.validate_and_insert(block, None)
+    }
+
+    /// `validate_and_insert_block` for a block whose lock-free checks already
+    /// ran; its signatures are not verified again if they were verified
+    /// against the outputs the block spends now
+    pub fn validate_and_insert_prechecked(&mut self, pre: &PrecheckedBlock) -> Result<()> {
+        self.validate_and_insert(pre.block(), Some(pre))
+    }
+
+    fn validate_and_insert(&mut self, block: &Block, pre: Option<&PrecheckedBlock>) -> Result<()> {
         // 0) Duplicate block check: skip only if already on the main chain.
-        // A block may exist in DB as a fork block (b: written but i: missing);
-        // in that case we must continue so the index and tip get updated.
-        let block_key = format!("b:{}", block.hash);
+        // A side-branch block in the tree (h: written but i: missing) is
+        // connected here, so we must continue and update the index and tip.
+        if self.block_status(&block.hash)? == Some(BlockStatus::Invalid) {
+            return Err(anyhow!("block {} is known to be invalid", block.hash));
+        }
+        let block_key = format!("h:{}", block.hash);
         if self.db.get(block_key.as_bytes())?.is_some() {
             let index_key = format!("i:{}", block.header.index);
             let is_on_main_chain = self.db.get(index_key.as_bytes())?
@@ -378,32 +1179,18 @@ impl Blockchain {
             );
         }
 
-        // 1) header hash match
-        let computed = compute_header_hash(&block.header)?;
-        if computed != block.hash {
-            return Err(anyhow!(
-                "header hash mismatch: computed {} != block.hash {}",
-                computed,
-                block.hash
-            ));
+        // 1) Header hash, PoW and merkle root (no chain state needed)
+        if pre.is_none() {
+            check_block_contextless(&self.params, block)?;
         }
 
         // 1.5) Checkpoint policy anchors (official chain protection)
-        if !crate::checkpoint::validate_against_checkpoints(block.header.index, &block.hash) {
-            return Err(anyhow!(
+        if !self.checkpoint_allows(block.header.index, &block.hash) {
+            return Err(BlockFailureReason::CheckpointViolation.record(anyhow!(
                 "checkpoint policy violation at height {} for hash {}",
                 block.header.index,
                 block.hash
-            ));
-        }
-
-        // 2) Proof-of-Work verification
-        if !Self::is_valid_pow(&block.hash, block.header.difficulty)? {
-            return Err(anyhow!(
-                "invalid PoW at block {}: hash does not satisfy bits 0x{:08x}",
-                block.header.index,
-                block.header.difficulty
-            ));
+            )));
         }
 
         // 3) Expected difficulty check (validate_and_insert_block)
@@ -415,7 +1202,7 @@ impl Blockchain {
                     block.header.index, &block.hash[..16],
                     block.header.difficulty, expected, self.difficulty
                 );
-                for i in 1..=Self::RETARGET_WINDOW {
+                for i in 1..=self.params.retarget_window {
                     let h = block.header.index - i as u64;
                     match self.db.get(format!("i:{}", h).as_bytes()).ok().flatten()
                         .and_then(|v| String::from_utf8(v).ok())
@@ -431,23 +1218,22 @@ impl Blockchain {
                         None => log::error!("  [DWG3] i:{} = MISSING", h),
                     }
                 }
-                return Err(anyhow!(
+                return Err(BlockFailureReason::DifficultyOutOfRange.record(anyhow!(
                     "difficulty mismatch at height {}: expected 0x{:08x}, got 0x{:08x}",
                     block.header.index,
                     expected,
                     block.header.difficulty
-                ));
+                )));
             }
         }
 
         // 4) previous exists + longest chain rule
         if block.header.index > 0 {
-            let prev_key = format!("b:{}", block.header.previous_hash);
-            if self.db.get(prev_key.as_bytes())?.is_none() {
-                return Err(anyhow!(
+            if self.load_header(&block.header.previous_hash)?.is_none() {
+                return Err(BlockFailureReason::PreviousNotFound.record(anyhow!(
                     "previous header not found: {}",
                     block.header.previous_hash
-                ));
+                )));
             }
 
             // longest chain rule
@@ -475,62 +1261,36 @@ impl Blockchain {
         // 5) Future timestamp check
         let now = Utc::now().timestamp();
         if block.header.timestamp > now + self.max_future_block_time {
-            return Err(anyhow!(
+            return Err(BlockFailureReason::TimestampTooFuture.record(anyhow!(
                 "block timestamp too far in future: {} > {}",
                 block.header.timestamp,
                 now + self.max_future_block_time
-            ));
+            )));
         }
 
-        // 6) Difficulty sanity progression check
-        if block.header.index > 0 {
-            let prev_key = format!("b:{}", block.header.previous_hash);
-            if let Ok(Some(prev_bytes)) = self.db.get(prev_key.as_bytes()) {
-
-                if let Ok((prev_block, _)) =
-                    bincode::decode_from_slice::<Block, _>(&prev_bytes, *BINCODE_CONFIG)
-                {
-                    let prev_target = Self::compact_to_target(prev_block.header.difficulty);
-                    let current_target = Self::compact_to_target(block.header.difficulty);
-
-                    if current_target.is_zero()
-                        || (!prev_target.is_zero()
-                            && ((current_target > prev_target
-                                && (current_target / prev_target) > U256::from(4u8))
-                                || (current_target < prev_target
-                                    && (prev_target / current_target) > U256::from(4u8))))
-                    {
-                        return Err(anyhow!(
-                            "difficulty target changed too aggressively at block {}",
-                            block.header.index
-                        ));
-                    }
-                }
-            }
-        }
+        // 5.5) Block size, genesis-time floor, coinbase placement and per-tx limits
+        self.validate_security(block)?;
 
-        // 7) Merkle check
-        let txids: Vec<String> = block.transactions.iter().map(|t| t.txid.clone()).collect();
-        let merkle = compute_merkle_root(&txids);
-        if merkle != block.header.merkle_root {
-            return Err(anyhow!("merkle mismatch"));
-        }
+        // 6) Difficulty sanity progression check
+        self.check_difficulty_progression(block)?;
 
         // 8) Median-Time-Past
-        if block.header.index > 0 {
-            self.validate_median_time_past(block)?;
-        }
+        let prev_mtp = if block.header.index > 0 {
+            self.validate_median_time_past(block)?
+        } else {
+            None
+        };
 
         // 9) transaction validation (기존 로직 유지)
-        let mut batch = WriteBatch::default();
+        let mut batch = ChainBatch::default();
 
         if block.transactions.is_empty() {
-            return Err(anyhow!("empty block"));
+            return Err(BlockFailureReason::EmptyBlock.record(anyhow!("empty block")));
         }
 
         let coinbase = &block.transactions[0];
         if !coinbase.inputs.is_empty() {
-            return Err(anyhow!("coinbase must have no inputs"));
+            return Err(BlockFailureReason::InvalidCoinbase.record(anyhow!("coinbase must have no inputs")));
         }
 
         let mut total_fees = U256::zero();
@@ -539,72 +1299,104 @@ impl Blockchain {
         // can be validated before the batch is committed to the DB.
         let mut block_utxos: std::collections::HashMap<String, Utxo> =
             std::collections::HashMap::new();
+        // UTXOs this block removes from the committed set (undo record)
+        let mut undo: Vec<Utxo> = Vec::new();
+        // Outputs spent by any transaction of this block: the batch is not
+        // written yet, so a second spend would still find them in the DB
+        let mut used_utxos = std::collections::HashSet::new();
+        // UTXOs consumed by each non-coinbase transaction, in input order
+        let mut spent_by_tx: Vec<Vec<Utxo>> = Vec::with_capacity(block.transactions.len() - 1);
 
         for (i, tx) in block.transactions.iter().enumerate() {
-            if !tx.verify_signatures()? {
-                return Err(anyhow!("tx signature invalid: {}", tx.txid));
-            }
-
             if i == 0 {
                 // coinbase 저장
                 let tx_blob = bincode::encode_to_vec(tx, *BINCODE_CONFIG)?;
                 batch.put(format!("t:{}", tx.txid).as_bytes(), &tx_blob);
+                Self::put_coinbase_height(&mut batch, &tx.txid, block.header.index);
+                Self::put_tx_location(&mut batch, &tx.txid, &block.hash, block.header.index, 0);
+                Self::put_address_history(&mut batch, tx, block.header.index)?;
                 for (v, out) in tx.outputs.iter().enumerate() {
                     let utxo = Utxo::new(tx.txid.clone(), v as u32, out.to.to_lowercase(), out.amount());
-                    let ublob = bincode::encode_to_vec(&utxo, *BINCODE_CONFIG)?;
-                    batch.put(format!("u:{}:{}", tx.txid, v).as_bytes(), &ublob);
+                    Self::put_utxo(&mut batch, &utxo)?;
                     block_utxos.insert(format!("u:{}:{}", tx.txid, v), utxo);
                 }
                 continue;
             }
 
+            // Absolute and relative time-locks (same-block parents count as this height)
+            self.check_tx_locks(tx, block.header.index, prev_mtp.unwrap_or(0))
+                .map_err(|e| BlockFailureReason::SecurityConstraint.record(e))?;
+
             let mut input_sum = U256::zero();
-            let mut used_utxos = std::collections::HashSet::new();
+            let mut spent = Vec::with_capacity(tx.inputs.len());
 
             for inp in &tx.inputs {
                 let ukey = format!("u:{}:{}", inp.txid, inp.vout);
 
                 if !used_utxos.insert(ukey.clone()) {
-                    return Err(anyhow!("duplicate input in tx {}", tx.txid));
+                    return Err(BlockFailureReason::DuplicateInput
+                        .record(anyhow!("input {} spent twice in block (tx {})", ukey, tx.txid)));
                 }
 
                 // Check UTXOs created by earlier transactions in this block first,
                 // then fall back to committed DB (handles chained mempool transactions).
                 let u = if let Some(pending) = block_utxos.remove(&ukey) {
                     // UTXO was created by a previous tx in this same block
+                    Self::delete_utxo(&mut batch, &pending.to, &pending.txid, pending.vout);
                     pending
                 } else {
                     match self.db.get(ukey.as_bytes())? {
                         Some(blob) => {
                             let (u, _): (Utxo, usize) =
                                 bincode::decode_from_slice(&blob, *BINCODE_CONFIG)?;
-                            batch.delete(ukey.as_bytes());
+                            Self::delete_utxo(&mut batch, &u.to, &u.txid, u.vout);
+                            undo.push(u.clone());
                             u
                         }
                         None => {
-                            return Err(anyhow!("referenced utxo not found"));
+                            return Err(BlockFailureReason::UtxoNotFound
+                                .record(anyhow!("referenced utxo not found")));
                         }
                     }
                 };
 
-                let input_address =
-                    crate::crypto::address_from_pubkey_hex(&inp.pubkey)
-                        .map_err(|e| anyhow!("invalid pubkey address: {}", e))?;
+                // Single-key pubkey or multisig witness lock
+                let input_address = inp
+                    .spender_address()
+                    .map_err(|e| BlockFailureReason::UtxoOwnershipFailure.record_body(e))?;
 
                 if input_address.to_lowercase() != u.to.to_lowercase() {
-                    return Err(anyhow!("UTXO ownership verification failed"));
+                    return Err(BlockFailureReason::UtxoOwnershipFailure
+                        .record_body(anyhow!("UTXO ownership verification failed")));
+                }
+
+                // Coinbase maturity (this block's own coinbase is never mature)
+                if block.header.index >= self.params.coinbase_maturity_height {
+                    let created = if u.txid == coinbase.txid {
+                        Some(block.header.index)
+                    } else {
+                        self.coinbase_height(&u.txid)?
+                    };
+                    if let Some(created) = created {
+                        self.ensure_coinbase_mature(&u.txid, created, block.header.index)
+                            .map_err(|e| BlockFailureReason::SecurityConstraint.record(e))?;
+                    }
                 }
 
                 input_sum = input_sum + u.amount();
+                spent.push(u);
             }
 
+            // Signatures are checked for the whole block after this pass
+            spent_by_tx.push(spent);
+
             let mut output_sum = U256::zero();
             for out in &tx.outputs {
                 output_sum = output_sum + out.amount();
             }
 
             if output_sum > input_sum {
-                return Err(anyhow!("outputs exceed inputs"));
+                return Err(BlockFailureReason::InsufficientFee.record(anyhow!("outputs exceed inputs")));
             }
 
             let fee = input_sum - output_sum;
@@ -613,34 +1405,47 @@ impl Blockchain {
             let tx_blob = bincode::encode_to_vec(tx, *BINCODE_CONFIG)?;
             let min_fee = crate::config::calculate_min_fee(tx_blob.len());
             if fee < min_fee {
-                return Err(anyhow!("transaction fee too low"));
+                return Err(BlockFailureReason::InsufficientFee.record_body(anyhow!("transaction fee too low")));
             }
 
             batch.put(format!("t:{}", tx.txid).as_bytes(), &tx_blob);
+            Self::put_tx_location(&mut batch, &tx.txid, &block.hash, block.header.index, i as u32);
+            Self::put_address_history(&mut batch, tx, block.header.index)?;
             for (v, out) in tx.outputs.iter().enumerate() {
                 let utxo = Utxo::new(tx.txid.clone(), v as u32, out.to.to_lowercase(), out.amount());
-                let ublob = bincode::encode_to_vec(&utxo, *BINCODE_CONFIG)?;
-                batch.put(format!("u:{}:{}", tx.txid, v).as_bytes(), &ublob);
+                Self::put_utxo(&mut batch, &utxo)?;
                 block_utxos.insert(format!("u:{}:{}", tx.txid, v), utxo);
             }
         }
 
+        // Each input signs its own sighash over the UTXO it spends; not
+        // checked for the assume-valid block and its ancestors
+        if !self.assumed_valid(&block.hash, block.header.index)?
+            && pre.and_then(|pre| pre.signed_spends()) != Some(&spent_by_tx)
+        {
+            sigcheck::verify_block_signatures(&self.params, block, &spent_by_tx)?;
+        }
+
         // ⭐ Block reward validation
         let coinbase_output: U256 = coinbase.outputs.iter().map(|o| o.amount()).fold(U256::zero(), |a, b| a + b);
         let expected_reward = self.get_block_reward(block.header.index);
         if coinbase_output > expected_reward + total_fees {
-            return Err(anyhow!(
+            return Err(BlockFailureReason::InvalidCoinbase.record(anyhow!(
                 "invalid coinbase reward: got {}, max {}",
                 coinbase_output,
                 expected_reward + total_fees
-            ));
+            )));
         }
 
         // persist block
-        let block_blob = bincode::encode_to_vec(&block, *BINCODE_CONFIG)?;
-        batch.put(format!("b:{}", block.hash).as_bytes(), &block_blob);
+        self.put_tree_entry(&mut batch, &block.header, &block.hash, BlockStatus::DataValid)?;
+        Self::put_block(&mut batch, block)?;
         batch.put(format!("i:{}", block.header.index).as_bytes(), block.hash.as_bytes());
         batch.put(b"tip", block.hash.as_bytes());
+        Self::put_undo(&mut batch, &block.hash, &undo)?;
+        let mut stats = self.utxo_set_stats()?;
+        stats.connect(block, &undo);
+        Self::put_utxo_stats(&mut batch, &stats)?;
 
         put_batch(&self.db, batch)?;
         self.chain_tip = Some(block.hash.clone());
@@ -648,11 +1453,19 @@ impl Blockchain {
         // and calculate_adjusted_difficulty(next) both see the latest value.
         self.difficulty = block.header.difficulty;
 
+        if let Err(e) = self.prune() {
+            log::warn!("Failed to prune old blocks: {}", e);
+        }
+
         Ok(())
     }
 
-    /// helper: load block header by hash
+    /// helper: load block header by hash (still available for pruned blocks)
     pub fn load_header(&self, hash: &str) -> Result<Option<BlockHeader>> {
+        if let Some(blob) = self.db.get(format!("h:{}", hash).as_bytes())? {
+            let (header, _): (BlockHeader, usize) = bincode::decode_from_slice(&blob, *BINCODE_CONFIG)?;
+            return Ok(Some(header));
+        }
         if let Some(blob) = self.db.get(format!("b:{}", hash).as_bytes())? {
             let (block, _): (Block, usize) = bincode::decode_from_slice(&blob, *BINCODE_CONFIG)?;
             return Ok(Some(block.header));
@@ -669,6 +1482,15 @@ impl Blockchain {
         Ok(None)
     }
 
+    /// load an unspent output from the committed UTXO set
+    pub fn get_utxo(&self, txid: &str, vout: u32) -> Result<Option<Utxo>> {
+        if let Some(blob) = self.db.get(format!("u:{}:{}", txid, vout).as_bytes())? {
+            let (u, _): (Utxo, usize) = bincode::decode_from_slice(&blob, *BINCODE_CONFIG)?;
+            return Ok(Some(u));
+        }
+        Ok(None)
+    }
+
     /// get balance by scanning UTXO set (use get_address_balance_from_db instead)
     #[deprecated(note = "Use get_address_balance_from_db instead")]
     pub fn get_balance(&self, address: &str) -> Result<U256, Box<dyn std::error::Error>> {
@@ -686,30 +1508,75 @@ impl Blockchain {
         Ok(0)
     }
 
+    /// Reject a block whose target is zero or more than 4x away from its
+    /// parent's (no-op while the parent header is unknown)
+    fn check_difficulty_progression(&self, block: &Block) -> Result<()> {
+        if block.header.index > 0
+            && let Ok(Some(prev_header)) = self.load_header(&block.header.previous_hash)
+        {
+            let prev_target = retarget::compact_to_target(prev_header.difficulty);
+            let current_target = retarget::compact_to_target(block.header.difficulty);
+
+            if current_target.is_zero()
+                || (!prev_target.is_zero()
+                    && ((current_target > prev_target
+                        && (current_target / prev_target) > U256::from(4u8))
+                        || (current_target < prev_target
+                            && (prev_target / current_target) > U256::from(4u8))))
+            {
+                return Err(BlockFailureReason::DifficultyOutOfRange.record(anyhow!(
+                    "difficulty target changed too aggressively at block {}",
+                    block.header.index
+                )));
+            }
+        }
+        Ok(())
+    }
+
     /// Validate Median-Time-Past (MTP) - block timestamp must be greater than median of last 11 blocks
-    /// This prevents miners from lying about timestamps to manipulate difficulty
-    fn validate_median_time_past(&self, block: &Block) -> Result<()> {
+    /// This prevents miners from lying about timestamps to manipulate difficulty.
+    /// Returns the parent's MTP (None if no previous blocks are stored).
+    fn validate_median_time_past(&self, block: &Block) -> Result<Option<i64>> {
+        let median = match self.median_time_past(&block.header.previous_hash)? {
+            Some(m) => m,
+            // No previous blocks, skip MTP check
+            None => return Ok(None),
+        };
+
+        // Block timestamp must be strictly greater than MTP
+        if block.header.timestamp <= median {
+            return Err(BlockFailureReason::TimestampTooOld.record(anyhow!(
+                "Block timestamp {} violates Median-Time-Past {} (must be > MTP)",
+                block.header.timestamp,
+                median
+            )));
+        }
+
+        Ok(Some(median))
+    }
+
+    /// Median timestamp of the last 11 blocks ending at `block_hash` (inclusive)
+    pub fn median_time_past(&self, block_hash: &str) -> Result<Option<i64>> {
         const MTP_SPAN: usize = 11; // Bitcoin uses 11 blocks
 
         let mut timestamps = Vec::new();
-        let mut current_hash = block.header.previous_hash.clone();
+        let mut current_hash = block_hash.to_string();
 
         // Collect up to 11 previous block timestamps
         for _ in 0..MTP_SPAN {
-            if let Some(blk) = self.load_block(&current_hash)? {
-                timestamps.push(blk.header.timestamp);
-                if blk.header.index == 0 {
+            if let Some(header) = self.load_header(&current_hash)? {
+                timestamps.push(header.timestamp);
+                if header.index == 0 {
                     break; // Reached genesis
                 }
-                current_hash = blk.header.previous_hash.clone();
+                current_hash = header.previous_hash;
             } else {
                 break;
             }
         }
 
         if timestamps.is_empty() {
-            // No previous blocks, skip MTP check
-            return Ok(());
+            return Ok(None);
         }
 
         // Calculate median
@@ -720,36 +1587,58 @@ impl Blockchain {
             timestamps[timestamps.len() / 2]
         };
 
-        // Block timestamp must be strictly greater than MTP
-        if block.header.timestamp <= median {
-            return Err(anyhow!(
-                "Block timestamp {} violates Median-Time-Past {} (must be > MTP)",
-                block.header.timestamp,
-                median
-            ));
+        Ok(Some(median))
+    }
+
+    /// Expected bits of the child of `parent_hash`, retargeted over the
+    /// parent's own ancestors (`h:` links) instead of the main-chain index,
+    /// so a side-branch block is checked against its branch
+    pub fn next_difficulty_after(&self, parent_hash: &str) -> Result<u32> {
+        if !self.params.retarget {
+            return Ok(self.params.pow_limit_bits);
+        }
+
+        // Window headers, newest first until reversed
+        let window = self.params.retarget_window as usize;
+        let mut history = Vec::with_capacity(window);
+        let mut hash = parent_hash.to_string();
+        while history.len() < window {
+            let header = self.load_header(&hash)?.ok_or_else(|| {
+                anyhow!("DWG3 header missing: {} (ancestor of {})", hash, parent_hash)
+            })?;
+            history.push(RetargetRecord {
+                timestamp: header.timestamp,
+                bits: header.difficulty,
+            });
+            if header.index == 0 {
+                break;
+            }
+            hash = header.previous_hash;
         }
+        history.reverse();
 
-        Ok(())
+        Ok(retarget::next_bits(&self.params, &history))
     }
 
     /// Calculate next difficulty using DWG3 (Dark Gravity Wave v3 style)
     /// - Recalculates every block
-    /// - Uses the last `RETARGET_WINDOW` blocks
+    /// - Uses the last `params.retarget_window` blocks
     /// - Averages historical targets, then scales by measured timespan
     pub fn calculate_adjusted_difficulty(&self, current_index: u64) -> Result<u32> {
+        // Networks without retargeting (regtest) always use the PoW limit
+        if !self.params.retarget {
+            return Ok(self.params.pow_limit_bits);
+        }
+
         // No adjustment until enough history is available.
         // `current_index` is the height to be mined next.
-        if current_index < Self::RETARGET_WINDOW {
+        if current_index < self.params.retarget_window {
             return Ok(self.difficulty);
         }
 
-        let pow_limit = Self::pow_limit_target();
-        let mut past_target_avg = U256::zero();
-        let mut newest_time: Option<i64> = None;
-        let mut oldest_time: Option<i64> = None;
-
-        for i in 1..=Self::RETARGET_WINDOW {
-            let height = current_index - i;
+        // Window headers, oldest first
+        let mut history = Vec::with_capacity(self.params.retarget_window as usize);
+        for height in current_index - self.params.retarget_window..current_index {
             let hash_bytes = match self.db.get(format!("i:{}", height).as_bytes())? {
                 Some(v) => v,
                 None => {
@@ -785,128 +1674,56 @@ impl Blockchain {
                     ));
                 }
             };
-
-            let mut target = Self::compact_to_target(header.difficulty);
-            if target.is_zero() {
-                target = pow_limit;
-            }
-
-            if i == 1 {
-                past_target_avg = target;
-                newest_time = Some(header.timestamp);
-            } else {
-                past_target_avg = (past_target_avg.saturating_mul(U256::from((i - 1) as u64))
-                    .saturating_add(target))
-                    / U256::from(i as u64);
-            }
-
-            oldest_time = Some(header.timestamp);
+            history.push(RetargetRecord {
+                timestamp: header.timestamp,
+                bits: header.difficulty,
+            });
         }
 
-        let newest_time = newest_time.unwrap_or(0);
-        let oldest_time = oldest_time.unwrap_or(newest_time);
-
-        // DWG3-style timespan clamping to reduce oscillation.
-        let raw_actual_timespan = (newest_time - oldest_time).max(1);
-        let target_timespan = (self.block_interval * Self::RETARGET_WINDOW as i64).max(1);
-        let clamped_actual_timespan = raw_actual_timespan.clamp(target_timespan / 3, target_timespan * 3);
-
-        let mut new_target = past_target_avg
-            .saturating_mul(U256::from(clamped_actual_timespan as u64))
-            / U256::from(target_timespan as u64);
-
-        if new_target.is_zero() {
-            new_target = U256::one();
-        }
-        if new_target > pow_limit {
-            new_target = pow_limit;
-        }
-
-        let previous_bits = match self.db.get(format!("i:{}", current_index - 1).as_bytes())? {
-            Some(v) => {
-                let hash = String::from_utf8(v)?;
-                match self.load_header(&hash)? {
-                    Some(h) => h.difficulty,
-                    None => {
-                        return Err(anyhow!(
-                            "DWG3 clamp: header missing for i:{} (block #{})",
-                            current_index - 1, current_index
-                        ));
-                    }
-                }
-            }
-            None => {
-                // i:{current_index-1} must exist at this point because the loop above
-                // already verified all entries from current_index-1 down.
-                // If we reach here something is very wrong.
-                return Err(anyhow!(
-                    "DWG3 clamp: i:{} unexpectedly missing after window scan passed (block #{})",
-                    current_index - 1, current_index
-                ));
-            }
-        };
-
-        // Clamp the result to the same 4× limit that block validation enforces,
-        // so DWG3 can never produce a value that the validator would reject.
-        let prev_target = Self::compact_to_target(previous_bits);
-        if !prev_target.is_zero() {
-            let max_increase = prev_target.saturating_mul(U256::from(4u8));
-            let min_decrease = prev_target / U256::from(4u8);
-            if new_target > max_increase {
-                new_target = max_increase;
-            } else if new_target < min_decrease {
-                new_target = min_decrease;
-            }
-            if new_target.is_zero() {
-                new_target = U256::one();
-            }
-        }
-
-        let next_bits = Self::target_to_compact(new_target);
+        let next_bits = retarget::next_bits(&self.params, &history);
 
+        let (oldest, newest) = (history[0], history[history.len() - 1]);
         log::debug!(
-            "DWG3 retarget @{}: bits 0x{:08x} -> 0x{:08x}, actual={}s, target={}s, avg={:.1}s/block",
+            "DWG3 retarget @{}: bits 0x{:08x} -> 0x{:08x}, actual={}s, avg={:.1}s/block",
             current_index,
-            previous_bits,
+            newest.bits,
             next_bits,
-            raw_actual_timespan,
-            target_timespan,
-            raw_actual_timespan as f64 / Self::RETARGET_WINDOW as f64
+            newest.timestamp - oldest.timestamp,
+            (newest.timestamp - oldest.timestamp) as f64 / self.params.retarget_window as f64
         );
 
         Ok(next_bits)
     }
 
     pub fn get_utxos(&self, address: &str) -> Result<Vec<Utxo>> {
+        let prefix = format!("a:{}:", address.to_lowercase());
         let mut utxos = Vec::new();
-        let iter = self.db.iterator(rocksdb::IteratorMode::Start);
-
-        for item in iter {
-            let (key, value) = item?;
-            let key_str = String::from_utf8_lossy(&key);
-
-            // UTXO key: u:{txid}:{vout}
-            if key_str.starts_with("u:") {
-                let (utxo, _): (Utxo, usize) = bincode::decode_from_slice(&value, *BINCODE_CONFIG)?;
-                if utxo.to == address {
-                    utxos.push(utxo);
-                }
-            }
+        for (_, value) in self.prefix_entries(&prefix)? {
+            let (utxo, _): (Utxo, usize) = bincode::decode_from_slice(&value, *BINCODE_CONFIG)?;
+            utxos.push(utxo);
         }
-
         Ok(utxos)
     }
 
+    /// UTXOs of `address`, each paired with whether it can be spent in the next block
+    /// (false while a coinbase output is immature)
+    pub fn get_utxos_with_maturity(&self, address: &str) -> Result<Vec<(Utxo, bool)>> {
+        let next_height = self.get_next_index()?;
+        self.get_utxos(address)?
+            .into_iter()
+            .map(|u| {
+                let spendable = self.is_utxo_mature(&u, next_height)?;
+                Ok((u, spendable))
+            })
+            .collect()
+    }
+
     /// Count transactions stored in DB (keys starting with `t:`)
     pub fn count_transactions(&self) -> Result<usize> {
         let mut count: usize = 0;
-        let iter = self.db.iterator(rocksdb::IteratorMode::Start);
-        for item in iter {
-            let (k, _v) = item?;
-            let key_str = String::from_utf8_lossy(&k);
-            if key_str.starts_with("t:") {
-                count += 1;
-            }
+        for item in self.db.prefix_iter(b"t:") {
+            item?;
+            count += 1;
         }
         Ok(count)
     }
@@ -976,84 +1793,47 @@ impl Blockchain {
         Ok(blocks)
     }
 
-    pub fn get_transaction(&self, txid: &str) -> anyhow::Result<Option<(Transaction, usize)>> {
-        let blocks = self.get_all_blocks()?;
-
-        for block in blocks {
-            for tx in block.transactions {
-                if tx.txid == txid {
-                    return Ok(Some((tx, block.header.index as usize)));
-                }
-            }
-        }
-
-        Ok(None)
+    /// Main-chain transaction with its location and confirmation count
+    /// (1 when it is in the tip block)
+    pub fn get_transaction(&self, txid: &str) -> Result<Option<(Transaction, TxLocation, u64)>> {
+        let location = match self.tx_location(txid)? {
+            Some(location) => location,
+            None => return Ok(None),
+        };
+        let tx = match self.load_tx(txid)? {
+            Some(tx) => tx,
+            None => return Ok(None),
+        };
+        let confirmations = self.get_next_index()?.saturating_sub(location.height);
+        Ok(Some((tx, location, confirmations)))
     }
 
-    /// Calculate total transaction volume from all outputs in DB (in ram)
+    /// Total amount held in the UTXO set (in ram), from the maintained set statistics
     pub fn calculate_total_volume(&self) -> Result<U256> {
-        let mut total = U256::zero();
-        let iter = self.db.iterator(rocksdb::IteratorMode::Start);
-
-        for item in iter {
-            let (k, v) = item?;
-            let key_str = String::from_utf8_lossy(&k);
-
-            // Iterate through all transaction outputs: u:{txid}:{vout}
-            if key_str.starts_with("u:") {
-                let (utxo, _): (Utxo, usize) = bincode::decode_from_slice(&v, *BINCODE_CONFIG)?;
-                total = total + utxo.amount();
-            }
-        }
-
-        Ok(total)
+        Ok(self.utxo_set_stats()?.total_amount)
     }
 
     /// Get address balance (sum of unspent outputs) from DB
     pub fn get_address_balance_from_db(&self, address: &str) -> Result<U256> {
-        let mut balance = U256::zero();
-        let iter = self.db.iterator(rocksdb::IteratorMode::Start);
-
-        for item in iter {
-            let (key, value) = item?;
-            let key_str = String::from_utf8_lossy(&key);
-
-            // UTXO key: u:{txid}:{vout}
-            if key_str.starts_with("u:") {
-                match bincode::decode_from_slice::<Utxo, _>(&value, *BINCODE_CONFIG) {
-                    Ok((utxo, _)) => {
-                        if utxo.to == address {
-                            let amount = utxo.amount();
-                            balance = balance + amount;
-                        }
-                    }
-                    Err(e) => {
-                        log::warn!("Failed to decode UTXO at {}: {}", key_str, e);
-                    }
-                }
-            }
-        }
+        let balance = self
+            .get_utxos(address)?
+            .iter()
+            .fold(U256::zero(), |acc, u| acc + u.amount());
         Ok(balance)
     }
 
     /// Get all addresses with their UTXO balances (for richlist)
     pub fn get_all_address_balances(&self) -> Result<Vec<(String, U256)>> {
         let mut balances: std::collections::HashMap<String, U256> = std::collections::HashMap::new();
-        let iter = self.db.iterator(rocksdb::IteratorMode::Start);
-
-        for item in iter {
-            let (key, value) = item?;
-            let key_str = String::from_utf8_lossy(&key);
 
-            if key_str.starts_with("u:") {
-                match bincode::decode_from_slice::<Utxo, _>(&value, *BINCODE_CONFIG) {
-                    Ok((utxo, _)) => {
-                        let entry = balances.entry(utxo.to.clone()).or_insert_with(U256::zero);
-                        *entry = *entry + utxo.amount();
-                    }
-                    Err(e) => {
-                        log::warn!("Failed to decode UTXO at {}: {}", key_str, e);
-                    }
+        for (key, value) in self.prefix_entries("a:")? {
+            match bincode::decode_from_slice::<Utxo, _>(&value, *BINCODE_CONFIG) {
+                Ok((utxo, _)) => {
+                    let entry = balances.entry(utxo.to.to_lowercase()).or_insert_with(U256::zero);
+                    *entry += utxo.amount();
+                }
+                Err(e) => {
+                    log::warn!("Failed to decode UTXO at {}: {}", String::from_utf8_lossy(&key), e);
                 }
             }
         }
@@ -1063,17 +1843,40 @@ impl Blockchain {
         Ok(result)
     }
 
+    /// Main-chain transactions touching `address`, newest first
+    pub fn get_address_history(&self, address: &str) -> Result<Vec<AddressHistoryEntry>> {
+        let prefix = format!("y:{}:", address.to_lowercase());
+        let mut history = Vec::new();
+        for (_, value) in self.prefix_entries(&prefix)?.into_iter().rev() {
+            let (entry, _): (AddressHistoryEntry, usize) =
+                bincode::decode_from_slice(&value, *BINCODE_CONFIG)?;
+            history.push(entry);
+        }
+        Ok(history)
+    }
+
+    /// Transaction of a history entry; `None` once its block has been pruned
+    fn history_tx(&self, entry: &AddressHistoryEntry) -> Result<Option<Transaction>> {
+        match self.load_tx(&entry.txid)? {
+            Some(tx) => Ok(Some(tx)),
+            None if entry.height < self.prune_height()? => Ok(None),
+            None => Err(anyhow!("indexed tx {} not found", entry.txid)),
+        }
+    }
+
     /// Get total received amount for address (all outputs to this address)
     pub fn get_address_received_from_db(&self, address: &str) -> Result<U256> {
+        let address = address.to_lowercase();
         let mut total = U256::zero();
-        let blocks = self.get_all_blocks_cached()?;
 
-        for block in blocks {
-            for tx in block.transactions {
-                for output in &tx.outputs {
-                    if output.to == address {
-                        total = total + output.amount();
-                    }
+        for entry in self.get_address_history(&address)? {
+            let tx = match self.history_tx(&entry)? {
+                Some(tx) => tx,
+                None => continue,
+            };
+            for output in &tx.outputs {
+                if output.to.to_lowercase() == address {
+                    total += output.amount();
                 }
             }
         }
@@ -1081,25 +1884,20 @@ impl Blockchain {
         Ok(total)
     }
 
-    /// Get total sent amount for address (all transaction outputs, excluding coinbase inputs)
+    /// Get total sent amount for address (all outputs of transactions it signed)
     pub fn get_address_sent_from_db(&self, address: &str) -> Result<U256> {
         let mut total = U256::zero();
-        let blocks = self.get_all_blocks_cached()?;
 
-        for block in blocks {
-            for tx in block.transactions {
-                // Skip coinbase transactions (first tx in block)
-                if !tx.inputs.is_empty() {
-                    // Check if any input comes from this address
-                    let is_sender = tx.inputs.iter().any(|input| input.pubkey == address);
-
-                    if is_sender {
-                        // Sum all outputs from this transaction
-                        for output in &tx.outputs {
-                            total = total + output.amount();
-                        }
-                    }
-                }
+        for entry in self.get_address_history(address)? {
+            if entry.direction != HistoryDirection::Send {
+                continue;
+            }
+            let tx = match self.history_tx(&entry)? {
+                Some(tx) => tx,
+                None => continue,
+            };
+            for output in &tx.outputs {
+                total += output.amount();
             }
         }
 
@@ -1113,77 +1911,68 @@ impl Blockchain {
         address: &str,
         limit: Option<usize>,
     ) -> Result<Vec<(String, u64, i64, String, U256, String)>> {
-        let blocks = self.get_all_blocks_cached()?;
+        let address = address.to_lowercase();
         let mut results: Vec<(String, u64, i64, String, U256, String)> = Vec::new();
-        let mut seen_txids = std::collections::HashSet::new();
 
-        for block in blocks {
-            let height = block.header.index;
-            for tx in block.transactions {
-                let is_receiver = tx.outputs.iter().any(|o| o.to == address);
-                let is_sender = tx.inputs.iter().any(|i| {
-                    crate::crypto::address_from_pubkey_hex(&i.pubkey)
-                        .map(|a| a.to_lowercase() == address)
-                        .unwrap_or(false)
-                });
-
-                if !is_receiver && !is_sender {
-                    continue;
-                }
-                if !seen_txids.insert(tx.txid.clone()) {
-                    continue;
-                }
+        for entry in self.get_address_history(&address)? {
+            // Rows within a height are re-sorted by timestamp, so finish the height
+            if limit.is_some_and(|n| results.len() >= n)
+                && results.last().map(|r| r.1) != Some(entry.height)
+            {
+                break;
+            }
+            let tx = match self.history_tx(&entry)? {
+                Some(tx) => tx,
+                None => continue,
+            };
+            let height = entry.height;
 
-                if is_sender {
-                    // One entry per unique recipient (excluding change back to self)
-                    for output in &tx.outputs {
-                        if output.to != address {
-                            results.push((
-                                tx.txid.clone(),
-                                height,
-                                tx.timestamp,
-                                "send".to_string(),
-                                output.amount(),
-                                output.to.clone(),
-                            ));
-                        }
-                    }
-                    // If all outputs go back to self (edge case), record as self-send
-                    if tx.outputs.iter().all(|o| o.to == address) {
-                        let total: U256 = tx.outputs.iter().fold(U256::zero(), |acc, o| acc + o.amount());
+            if entry.direction == HistoryDirection::Send {
+                // One entry per unique recipient (excluding change back to self)
+                for output in &tx.outputs {
+                    if output.to.to_lowercase() != address {
                         results.push((
                             tx.txid.clone(),
                             height,
                             tx.timestamp,
                             "send".to_string(),
-                            total,
-                            address.to_string(),
+                            output.amount(),
+                            output.to.clone(),
                         ));
                     }
-                } else {
-                    // Pure receiver
-                    let received: U256 = tx
-                        .outputs
-                        .iter()
-                        .filter(|o| o.to == address)
-                        .fold(U256::zero(), |acc, o| acc + o.amount());
-                    let sender = tx
-                        .inputs
-                        .first()
-                        .map(|i| {
-                            crate::crypto::address_from_pubkey_hex(&i.pubkey)
-                                .unwrap_or_else(|_| i.pubkey.clone())
-                        })
-                        .unwrap_or_else(|| "coinbase".to_string());
+                }
+                // If all outputs go back to self (edge case), record as self-send
+                if tx.outputs.iter().all(|o| o.to.to_lowercase() == address) {
+                    let total: U256 = tx.outputs.iter().fold(U256::zero(), |acc, o| acc + o.amount());
                     results.push((
                         tx.txid.clone(),
                         height,
                         tx.timestamp,
-                        "receive".to_string(),
-                        received,
-                        sender,
+                        "send".to_string(),
+                        total,
+                        address.clone(),
                     ));
                 }
+            } else {
+                // Pure receiver
+                let received: U256 = tx
+                    .outputs
+                    .iter()
+                    .filter(|o| o.to.to_lowercase() == address)
+                    .fold(U256::zero(), |acc, o| acc + o.amount());
+                let sender = tx
+                    .inputs
+                    .first()
+                    .map(|i| i.spender_address().unwrap_or_else(|_| i.pubkey.clone()))
+                    .unwrap_or_else(|| "coinbase".to_string());
+                results.push((
+                    tx.txid.clone(),
+                    height,
+                    tx.timestamp,
+                    "receive".to_string(),
+                    received,
+                    sender,
+                ));
             }
         }
 
@@ -1197,49 +1986,21 @@ impl Blockchain {
 
     /// Get transaction count for address
     pub fn get_address_transaction_count_from_db(&self, address: &str) -> Result<usize> {
-        let blocks = self.get_all_blocks_cached()?;
-        let mut seen_txids = std::collections::HashSet::new();
-
-        for block in blocks {
-            for tx in block.transactions {
-                // Check if address is involved (sender or receiver)
-                let is_receiver = tx.outputs.iter().any(|output| output.to == address);
-                let is_sender = tx.inputs.iter().any(|input| input.pubkey == address);
-
-                // Count each unique transaction only once
-                if (is_receiver || is_sender) && seen_txids.insert(tx.txid.clone()) {
-                    // Counter automatically incremented by HashSet
-                }
-            }
-        }
-
-        Ok(seen_txids.len())
+        let prefix = format!("y:{}:", address.to_lowercase());
+        Ok(self.prefix_entries(&prefix)?.len())
     }
 
     /// Calculate total chain work (cumulative difficulty) from genesis to given block
-    /// Higher difficulty blocks contribute more work
-    pub fn calculate_chain_work(&self, block_hash: &str) -> Result<u64> {
-        // For simplicity and overflow prevention, use height as work metric
-        // In production, could use sum of (2^256 / target) but this is sufficient
-        // for longest chain rule when difficulty is relatively stable
-        
-        let block = self.load_block(block_hash)?;
-        if let Some(block) = block {
-            // Chain work = block height + 1 (genesis = 1, block 1 = 2, etc.)
-            // This ensures longer chain always wins when difficulty is similar
-            Ok(block.header.index + 1)
-        } else {
-            Err(anyhow!("Block not found: {}", block_hash))
-        }
+    /// Sum of `block_work` over the block and its ancestors, recorded in
+    /// `w:{hash}` when the block is stored (`put_tree_entry`).
+    pub fn calculate_chain_work(&self, block_hash: &str) -> Result<U256> {
+        self.stored_chain_work(block_hash)?
+            .ok_or_else(|| anyhow!("Block not found: {}", block_hash))
     }
 
     /// Get block height (index) for a given block hash
     pub fn get_block_height(&self, block_hash: &str) -> Result<Option<u64>> {
-        if let Some(block) = self.load_block(block_hash)? {
-            Ok(Some(block.header.index))
-        } else {
-            Ok(None)
-        }
+        Ok(self.load_header(block_hash)?.map(|header| header.index))
     }
 
     /// Load complete block by hash
@@ -1251,305 +2012,217 @@ impl Blockchain {
         Ok(None)
     }
 
-    /// Find common ancestor between two blocks
-    fn find_common_ancestor(&self, hash_a: &str, hash_b: &str) -> Result<Option<String>> {
-        let mut blocks_a = Vec::new();
-        let mut current = hash_a.to_string();
-
-        // Collect all blocks from hash_a to genesis
-        while let Some(block) = self.load_block(&current)? {
-            blocks_a.push(current.clone());
-            if block.header.index == 0 {
-                break;
-            }
-            current = block.header.previous_hash.clone();
-        }
-
-        // Walk from hash_b to genesis and find first common block
-        let mut current = hash_b.to_string();
-        while let Some(block) = self.load_block(&current)? {
-            if blocks_a.contains(&current) {
-                return Ok(Some(current));
-            }
-            if block.header.index == 0 {
-                break;
-            }
-            current = block.header.previous_hash.clone();
-        }
-
-        Ok(None)
-    }
-
-    /// Reorganize chain to new tip if it has more work
-    /// Returns true if reorg happened, false if current chain is already best
-    pub fn reorganize_if_needed(&mut self, new_block_hash: &str) -> Result<bool> {
-        let mut current_tip = match &self.chain_tip {
+    /// Chain selection over the block tree: switch to the branch with the
+    /// most work if it beats the current tip. Blocks are connected with full
+    /// validation; one that fails is marked invalid (or loses its body, see
+    /// `switch_to_branch`) and selection runs again.
+    /// Returns true if the tip changed.
+    pub fn activate_best_chain(&mut self) -> Result<bool> {
+        let start_tip = match &self.chain_tip {
             Some(tip) => tip.clone(),
-            None => {
-                // No current chain, accept any valid block
-                return Ok(false);
-            }
+            None => return Ok(false), // No chain yet: blocks are connected as they arrive
         };
 
-        let mut current_header = self
-            .load_header(&current_tip)?
-            .ok_or_else(|| anyhow!("Cannot load current tip header"))?;
-
-        // Safety: If tip height is suspiciously low compared to DB block count,
-        // recover tip first to avoid accidental reorg to a shorter fork.
-        {
-            let block_count = self.count_blocks() as u64;
-            if block_count > 100 && current_header.index + 1 < block_count / 2 {
+        loop {
+            let current_tip = self
+                .chain_tip
+                .clone()
+                .ok_or_else(|| anyhow!("chain tip lost during reorganization"))?;
+            let current_height = self
+                .load_header(&current_tip)?
+                .ok_or_else(|| anyhow!("Cannot load current tip header"))?
+                .index;
+            let current_work = self.calculate_chain_work(&current_tip)?;
+
+            let branch = match self.best_branch(current_height, current_work)? {
+                Some(branch) => branch,
+                None => break,
+            };
+            if self.switch_to_branch(&branch)? {
                 log::warn!(
-                    "⚠️  Stale tip detected before reorg (tip height: {}, block_count: {}), recovering tip...",
-                    current_header.index,
-                    block_count
-                );
-
-                self.recover_tip()?;
-
-                current_tip = self
-                    .chain_tip
-                    .clone()
-                    .ok_or_else(|| anyhow!("Tip recovery failed: chain tip is missing"))?;
-                current_header = self
-                    .load_header(&current_tip)?
-                    .ok_or_else(|| anyhow!("Tip recovery failed: recovered tip header missing"))?;
-
-                log::info!(
-                    "✅ Tip recovered before reorg check: now at height {}",
-                    current_header.index
+                    "✅ Reorganization complete: new tip = {}",
+                    &self.chain_tip.as_deref().unwrap_or_default()[..16]
                 );
+                break;
             }
         }
 
-        // Calculate chain work for both tips
-        let current_work = self.calculate_chain_work(&current_tip)?;
-        let new_work = self.calculate_chain_work(new_block_hash)?;
-
-        log::debug!(
-            "Chain work comparison: current={} (hash={}), new={} (hash={})",
-            current_work,
-            &current_tip[..16],
-            new_work,
-            &new_block_hash[..16]
-        );
-
-        // Keep current chain if it has equal or more work
-        if current_work >= new_work {
-            log::debug!("Current chain has more work, keeping it");
-            return Ok(false);
-        }
-
-        log::warn!(
-            "🔄 REORGANIZATION NEEDED: new chain has more work ({} vs {})",
-            new_work,
-            current_work
-        );
+        Ok(self.chain_tip.as_deref() != Some(start_tip.as_str()))
+    }
 
-        // Find common ancestor
-        let ancestor = self.find_common_ancestor(&current_tip, new_block_hash)?;
-        if ancestor.is_none() {
-            return Err(anyhow!("No common ancestor found for reorganization"));
+    /// Branch with the most work above `current_work` that may replace the
+    /// main chain: none of its blocks is invalid or missing its body, and it
+    /// forks no deeper than
+    /// `params.max_reorg_depth` and the checkpoint policy allow
+    fn best_branch(&self, current_height: u64, current_work: U256) -> Result<Option<tree::Branch>> {
+        let mut candidates = Vec::new();
+        for tip in self.branch_tips()? {
+            let work = self.calculate_chain_work(&tip)?;
+            if work > current_work {
+                candidates.push((work, tip));
+            }
         }
+        candidates.sort_by_key(|(work, _)| std::cmp::Reverse(*work));
 
-        let ancestor = ancestor.unwrap();
-        log::info!("Common ancestor: {}", &ancestor[..16]);
-
-        // 🔒 Security: Check reorganization depth to prevent 51% attacks
-        let ancestor_header = self
-            .load_header(&ancestor)?
-            .ok_or_else(|| anyhow!("Cannot load ancestor header"))?;
-
-        let current_height = current_header.index;
-        let fork_point_height = ancestor_header.index;
-        let reorg_depth = current_height - fork_point_height;
+        for (work, tip) in candidates {
+            let branch = match self.branch_from(&tip)? {
+                Some(branch) => branch,
+                None => continue,
+            };
+            if branch.blocks.is_empty() || branch.status == ChainTipStatus::Invalid {
+                continue;
+            }
+            // Blocks whose body was dropped wait for another copy
+            let mut has_bodies = true;
+            for hash in &branch.blocks {
+                if !self.has_block_body(hash)? {
+                    has_bodies = false;
+                    break;
+                }
+            }
+            if !has_bodies {
+                continue;
+            }
 
-        // 🔒 Security: Validate reorganization depth doesn't exceed consensus limit
-        crate::security::validate_reorg_depth(
-            current_height,
-            fork_point_height,
-            self.max_reorg_depth,
-        )?;
+            // 🔒 Security: Validate reorganization depth doesn't exceed consensus limit
+            if let Err(e) = crate::security::validate_reorg_depth(
+                current_height,
+                branch.fork_height,
+                self.params.max_reorg_depth,
+            ) {
+                log::error!("🚨 Ignoring branch {}: {}", &tip[..16], e);
+                continue;
+            }
 
-        // 🔒 Policy: Check if reorg conflicts with checkpoint policy
-        let (checkpoint_allowed, checkpoint_reason) =
-            crate::checkpoint::check_reorg_against_checkpoints(reorg_depth, current_height);
+            // 🔒 Policy: Check if reorg conflicts with checkpoint policy
+            let reorg_depth = current_height.saturating_sub(branch.fork_height);
+            let (checkpoint_allowed, checkpoint_reason) = crate::checkpoint::check_reorg_against_checkpoints(
+                &self.active_checkpoints(),
+                reorg_depth,
+                current_height,
+            );
+            if !checkpoint_allowed {
+                log::error!(
+                    "🚨 Reorganization to {} REJECTED by checkpoint policy: {}",
+                    &tip[..16],
+                    checkpoint_reason.unwrap_or_else(|| "Unknown reason".to_string())
+                );
+                continue;
+            }
 
-        if !checkpoint_allowed {
-            log::error!(
-                "🚨 Reorganization REJECTED by checkpoint policy: {}",
-                checkpoint_reason.unwrap_or_else(|| "Unknown reason".to_string())
+            log::warn!(
+                "🔄 REORGANIZATION NEEDED: branch {} has more work ({} vs {}), forks at #{}",
+                &tip[..16],
+                work,
+                current_work,
+                branch.fork_height
             );
-            return Err(anyhow!(
-                "Reorganization violates checkpoint policy (depth: {}, current height: {})",
-                reorg_depth,
-                current_height
-            ));
+            return Ok(Some(branch));
         }
+        Ok(None)
+    }
 
-        log::info!(
-            "✅ Reorganization passes checkpoint policy check (depth: {}, height: {})",
-            reorg_depth,
-            current_height
-        );
-
-        // Collect blocks to rollback (from current tip to ancestor)
+    /// Roll the main chain back to the branch's fork point and connect its
+    /// blocks. Returns false if one of them fails validation: it is marked
+    /// invalid, or only loses its body on a `BodyFailure`, and the chain is
+    /// left at its parent.
+    fn switch_to_branch(&mut self, branch: &tree::Branch) -> Result<bool> {
+        // Collect blocks to rollback (from current tip to fork point)
         let mut rollback_blocks = Vec::new();
-        let mut current = current_tip.clone();
-        while current != ancestor {
-            let block = self
-                .load_block(&current)?
-                .ok_or_else(|| anyhow!("Block not found during reorg: {}", current))?;
-            rollback_blocks.push(block.clone());
-            current = block.header.previous_hash.clone();
-        }
-
-        // Collect blocks to apply (from ancestor to new tip)
-        let mut apply_blocks = Vec::new();
-        let mut current = new_block_hash.to_string();
-        while current != ancestor {
+        let mut current = self.chain_tip.clone().unwrap_or_default();
+        while current != branch.fork_point {
             let block = self
                 .load_block(&current)?
                 .ok_or_else(|| anyhow!("Block not found during reorg: {}", current))?;
-            apply_blocks.push(block.clone());
             current = block.header.previous_hash.clone();
+            rollback_blocks.push(block);
         }
-        apply_blocks.reverse(); // Apply from ancestor to new tip
 
         log::warn!(
             "Reorganizing: rolling back {} blocks, applying {} blocks",
             rollback_blocks.len(),
-            apply_blocks.len()
+            branch.blocks.len()
         );
-
-        // Rollback: reverse UTXO changes
-        self.rollback_blocks(&rollback_blocks)?;
-
-        // Apply: replay new chain
-        self.replay_blocks(&apply_blocks)?;
-
-        // Update chain tip
-        let mut batch = WriteBatch::default();
-        batch.put(b"tip", new_block_hash.as_bytes());
-        put_batch(&self.db, batch)?;
-        self.chain_tip = Some(new_block_hash.to_string());
-        // Sync bc.difficulty after reorg
-        if let Some(new_tip_block) = apply_blocks.last() {
-            self.difficulty = new_tip_block.header.difficulty;
+        if !rollback_blocks.is_empty() {
+            self.rollback_blocks(&rollback_blocks)?;
         }
 
-        log::warn!(
-            "✅ Reorganization complete: new tip = {}",
-            &new_block_hash[..16]
-        );
-
-        Ok(true)
-    }
-
-    /// Rollback UTXO changes from a list of blocks (reverse order)
-    /// Also deletes the rolled-back blocks from DB
-    fn rollback_blocks(&mut self, blocks: &[Block]) -> Result<()> {
-        let mut batch = WriteBatch::default();
-
-        for block in blocks {
-            log::info!("Rolling back block {} (hash: {})", block.header.index, &block.hash[..16]);
-
-            // Process transactions in reverse order
-            for tx in block.transactions.iter().rev() {
-                // Delete UTXOs created by this transaction
-                for i in 0..tx.outputs.len() {
-                    let ukey = format!("u:{}:{}", tx.txid, i);
-                    batch.delete(ukey.as_bytes());
+        for hash in &branch.blocks {
+            let block = self
+                .load_block(hash)?
+                .ok_or_else(|| anyhow!("Block not found during reorg: {}", hash))?;
+            log::info!("Connecting block {} (hash: {})", block.header.index, &hash[..16]);
+            if let Err(e) = self.validate_and_insert_block(&block) {
+                // Storage errors say nothing about the block
+                if e.downcast_ref::<rocksdb::Error>().is_some() {
+                    return Err(e);
                 }
-
-                // Restore UTXOs spent by this transaction (skip coinbase)
-                if !tx.inputs.is_empty() {
-                    for input in &tx.inputs {
-                        // Restore the UTXO that was spent
-                        let spent_tx = self
-                            .load_tx(&input.txid)?
-                            .ok_or_else(|| anyhow!("Cannot find spent tx: {}", input.txid))?;
-
-                        if let Some(output) = spent_tx.outputs.get(input.vout as usize) {
-                            let utxo = Utxo::new(
-                                input.txid.clone(),
-                                input.vout,
-                                output.to.clone(),
-                                output.amount(),
-                            );
-                            let ublob = bincode::encode_to_vec(&utxo, *BINCODE_CONFIG)?;
-                            batch.put(
-                                format!("u:{}:{}", input.txid, input.vout).as_bytes(),
-                                &ublob,
-                            );
-                        }
-                    }
+                // Signatures and witnesses are not covered by the hash
+                if e.downcast_ref::<BodyFailure>().is_some() {
+                    log::error!(
+                        "❌ Block #{} ({}) failed to connect, dropping its body: {}",
+                        block.header.index,
+                        &hash[..16],
+                        e
+                    );
+                    self.drop_block_body(hash)?;
+                    return Ok(false);
                 }
-                
-                // Delete transaction from DB
-                batch.delete(format!("t:{}", tx.txid).as_bytes());
+                log::error!(
+                    "❌ Block #{} ({}) failed to connect, marking it invalid: {}",
+                    block.header.index,
+                    &hash[..16],
+                    e
+                );
+                self.mark_block_invalid(hash)?;
+                return Ok(false);
             }
-            
-            // Delete block from DB
-            batch.delete(format!("b:{}", block.hash).as_bytes());
-            
-            // Delete block index (will be overwritten by new chain anyway, but clean up)
-            batch.delete(format!("i:{}", block.header.index).as_bytes());
-            
-            log::info!("✅ Block {} deleted from DB during rollback", block.header.index);
         }
-
-        put_batch(&self.db, batch)?;
-        Ok(())
+        Ok(true)
     }
 
-    /// Replay blocks to apply UTXO changes (forward order)
-    fn replay_blocks(&mut self, blocks: &[Block]) -> Result<()> {
+    /// Disconnect main-chain blocks (tip first) using their undo records, in
+    /// one atomic batch that also moves the tip to the parent of the last
+    /// one. The blocks stay in the tree as a side branch. Fails before
+    /// writing anything if a record is missing.
+    fn rollback_blocks(&mut self, blocks: &[Block]) -> Result<()> {
+        let new_tip = match blocks.last() {
+            Some(block) => block.header.previous_hash.clone(),
+            None => return Ok(()),
+        };
+        let mut undos = Vec::with_capacity(blocks.len());
         for block in blocks {
-            log::info!("Replaying block {} (hash: {})", block.header.index, &block.hash[..16]);
-
-            // Update UTXO set and block index
-            let mut batch = WriteBatch::default();
-
-            for tx in &block.transactions {
-                // Create new UTXOs
-                for (i, output) in tx.outputs.iter().enumerate() {
-                    let utxo = Utxo::new(
-                        tx.txid.clone(),
-                        i as u32,
-                        output.to.clone(),
-                        output.amount(),
-                    );
-                    let ublob = bincode::encode_to_vec(&utxo, *BINCODE_CONFIG)?;
-                    batch.put(format!("u:{}:{}", tx.txid, i).as_bytes(), &ublob);
-                }
-
-                // Spend UTXOs (skip coinbase)
-                if !tx.inputs.is_empty() {
-                    for input in &tx.inputs {
-                        batch.delete(format!("u:{}:{}", input.txid, input.vout).as_bytes());
-                    }
-                }
-            }
-            
-            // Update block index for new chain
-            batch.put(format!("i:{}", block.header.index).as_bytes(), block.hash.as_bytes());
-
-            put_batch(&self.db, batch)?;
+            let undo = self.load_undo(&block.hash)?.ok_or_else(|| {
+                anyhow!(
+                    "missing undo record for block #{} ({}), cannot roll back",
+                    block.header.index,
+                    block.hash
+                )
+            })?;
+            undos.push(undo);
+        }
+        let new_tip_header = self
+            .load_header(&new_tip)?
+            .ok_or_else(|| anyhow!("missing header of fork point {}", new_tip))?;
+
+        let mut batch = ChainBatch::default();
+        let mut stats = self.utxo_set_stats()?;
+        for (block, undo) in blocks.iter().zip(&undos) {
+            log::info!("Rolling back block {} (hash: {})", block.header.index, &block.hash[..16]);
+            Self::disconnect_block(&mut batch, block, undo, &mut stats)?;
         }
+        Self::put_utxo_stats(&mut batch, &stats)?;
+        batch.put(b"tip", new_tip.as_bytes());
 
+        put_batch(&self.db, batch)?;
+        self.chain_tip = Some(new_tip);
+        self.difficulty = new_tip_header.difficulty;
+        log::info!("✅ {} blocks rolled back", blocks.len());
         Ok(())
     }
 
     pub fn get_block_reward(&self, height: u64) -> U256 {
-        let halvings = (height / HALVING_INTERVAL) as u32;
-
-        if halvings >= 33 {
-            return U256::zero(); // 공급 상한 도달
-        }
-
-        initial_block_reward() >> halvings
+        self.params.block_reward(height)
     }
 
     /// Walk backwards from chain_tip and write any missing `i:{height}` index entries.
@@ -1562,18 +2235,18 @@ impl Blockchain {
 
         let mut repaired = 0usize;
         let mut current_hash = tip_hash;
-        let mut batch = rocksdb::WriteBatch::default();
+        let mut batch = ChainBatch::default();
 
         loop {
-            let block = match self.load_block(&current_hash)? {
-                Some(b) => b,
+            let header = match self.load_header(&current_hash)? {
+                Some(h) => h,
                 None => {
                     log::warn!("repair_index: block not found for hash {}", &current_hash[..16]);
                     break;
                 }
             };
 
-            let height = block.header.index;
+            let height = header.index;
             let index_key = format!("i:{}", height);
 
             let needs_repair = match self.db.get(index_key.as_bytes())? {
@@ -1590,7 +2263,7 @@ impl Blockchain {
             if height == 0 {
                 break;
             }
-            current_hash = block.header.previous_hash.clone();
+            current_hash = header.previous_hash;
         }
 
         if repaired > 0 {
@@ -1604,6 +2277,8 @@ impl Blockchain {
     /// Truncate the chain to `target_height`, deleting all blocks above it.
     /// Sets chain_tip to the block at `target_height`.
     /// Cleans up b:, i:, t:, u: entries for orphaned blocks above target_height.
+    /// Fails without deleting anything if a block's undo record is missing
+    /// and cannot be rebuilt.
     pub fn truncate_chain_to_height(&mut self, target_height: u64) -> Result<()> {
         let tip_hash = match &self.chain_tip {
             Some(h) => h.clone(),
@@ -1633,31 +2308,34 @@ impl Blockchain {
                 let new_tip_hash = current_hash.clone();
                 let new_tip_block = block;
 
-                // Delete all blocks above target_height
-                let mut batch = WriteBatch::default();
+                // Delete all blocks above target_height, restoring what they spent.
+                // Unlike a reorg they leave the tree too, so chain selection
+                // cannot bring them back.
+                let mut batch = ChainBatch::default();
+                let mut stats = self.utxo_set_stats()?;
                 for del_block in &blocks_to_delete {
-                    let bkey = format!("b:{}", del_block.hash);
-                    let ikey = format!("i:{}", del_block.header.index);
-                    batch.delete(bkey.as_bytes());
-                    batch.delete(ikey.as_bytes());
                     log::info!(
                         "  truncate: deleting block #{} hash={}",
                         del_block.header.index,
                         &del_block.hash[..16]
                     );
-
-                    // Delete transactions for this block
-                    for tx in &del_block.transactions {
-                        let tkey = format!("t:{}", tx.txid);
-                        batch.delete(tkey.as_bytes());
-
-                        // Delete UTXOs for this block's outputs
-                        for (vout, _output) in tx.outputs.iter().enumerate() {
-                            let ukey = format!("u:{}:{}", tx.txid, vout);
-                            batch.delete(ukey.as_bytes());
-                        }
+                    let undo = match self.load_undo(&del_block.hash)? {
+                        Some(undo) => undo,
+                        None => self.rebuild_undo(del_block)?.ok_or_else(|| {
+                            anyhow!(
+                                "missing undo record for block #{} ({}), cannot truncate",
+                                del_block.header.index,
+                                del_block.hash
+                            )
+                        })?,
+                    };
+                    Self::disconnect_block(&mut batch, del_block, &undo, &mut stats)?;
+                    for prefix in ["b", "h", "w", "s", "e"] {
+                        batch.delete(format!("{}:{}", prefix, del_block.hash).as_bytes());
                     }
                 }
+                Self::put_utxo_stats(&mut batch, &stats)?;
+                batch.put(format!("e:{}", new_tip_hash).as_bytes(), b"");
 
                 // Update tip pointer
                 batch.put(b"tip", new_tip_hash.as_bytes());
@@ -1703,32 +2381,31 @@ impl Blockchain {
         log::warn!("🔄 Resetting blockchain database...");
         
         // Delete all blockchain-related keys
-        let mut batch = WriteBatch::default();
+        let mut batch = ChainBatch::default();
         
         // Delete tip
         batch.delete(b"tip");
+        batch.delete(PRUNE_HEIGHT_KEY);
+        batch.delete(snapshot::SNAPSHOT_KEY);
+        batch.delete(snapshot::SNAPSHOT_HEADERS_VALIDATED_KEY);
         
         // Collect all keys to delete
         let mut keys_to_delete = Vec::new();
-        let iter = self.db.iterator(rocksdb::IteratorMode::Start);
-        for item in iter {
-            if let Ok((key, _)) = item {
-                let key_str = String::from_utf8_lossy(&key);
-                // Delete block, transaction, utxo, and index keys
-                if key_str.starts_with("b:") || key_str.starts_with("t:") || 
-                   key_str.starts_with("u:") || key_str.starts_with("i:") {
-                    keys_to_delete.push(key.to_vec());
-                }
+        // Block, header, transaction, utxo, index, chain work, tx/coinbase height, undo, address and tree keys
+        for prefix in ["b:", "h:", "t:", "u:", "i:", "w:", "c:", "x:", "r:", "a:", "y:", "s:", "e:"] {
+            for (key, _) in self.db.prefix_iter(prefix.as_bytes()).flatten() {
+                keys_to_delete.push(key.to_vec());
             }
         }
         
         for key in keys_to_delete {
             batch.delete(&key);
         }
+        Self::put_utxo_stats(&mut batch, &UtxoSetStats::default())?;
         
         put_batch(&self.db, batch)?;
         self.chain_tip = None;
-        self.difficulty = Self::POW_LIMIT_BITS;
+        self.difficulty = self.params.pow_limit_bits;
         
         log::info!("✅ Blockchain reset complete");
         Ok(())
@@ -1739,34 +2416,32 @@ impl Blockchain {
     pub fn recover_tip(&mut self) -> Result<()> {
         log::warn!("🔧 Attempting to recover chain tip from database...");
         
-        let mut highest_block: Option<(u64, String, Block)> = None;
+        let mut highest_block: Option<(u64, String)> = None;
         let mut block_count = 0;
         
-        // Scan all block keys
-        let iter = self.db.iterator(rocksdb::IteratorMode::Start);
-        for item in iter {
-            if let Ok((key, value)) = item {
-                let key_str = String::from_utf8_lossy(&key);
-                
-                // Check if this is a block key (b:{hash})
-                if key_str.starts_with("b:") {
-                    block_count += 1;
-                    if let Ok((block, _)) = bincode::decode_from_slice::<Block, _>(&value, *BINCODE_CONFIG) {
-                        let block_height = block.header.index;
-                        let block_hash = key_str.strip_prefix("b:").unwrap_or("").to_string();
-                        
-                        // Update if this is the highest block so far
-                        if highest_block.is_none() || block_height > highest_block.as_ref().unwrap().0 {
-                            highest_block = Some((block_height, block_hash, block));
-                        }
-                    }
-                }
+        // Scan the main-chain height index: side branches in the block tree
+        // were never connected (or have been disconnected), so they cannot be the tip
+        for (key, value) in self.db.prefix_iter(b"i:").flatten() {
+            let key_str = String::from_utf8_lossy(&key);
+            let block_height = match key_str.strip_prefix("i:").and_then(|h| h.parse::<u64>().ok()) {
+                Some(h) => h,
+                None => continue,
+            };
+            let block_hash = String::from_utf8_lossy(&value).to_string();
+            if self.load_header(&block_hash)?.is_none() {
+                continue;
+            }
+            block_count += 1;
+
+            // Update if this is the highest block so far
+            if highest_block.is_none() || block_height > highest_block.as_ref().unwrap().0 {
+                highest_block = Some((block_height, block_hash));
             }
         }
         
-        log::info!("📊 Found {} blocks in database", block_count);
+        log::info!("📊 Found {} main-chain blocks in database", block_count);
         
-        if let Some((height, hash, _block)) = highest_block {
+        if let Some((height, hash)) = highest_block {
             log::info!("✅ Found highest block: #{} (hash: {})", height, hash);
             
             // Update tip pointer
@@ -1786,8 +2461,8 @@ impl Blockchain {
     /// For each block the following are checked:
     ///   1. Header hash correctness
     ///   2. PoW validity
-    ///   3. DWG3 difficulty match (height ≥ RETARGET_WINDOW)
-    ///   4. Merkle root
+    ///   3. DWG3 difficulty match (height ≥ params.retarget_window)
+    ///   4. Merkle root (skipped for pruned blocks, whose body is gone)
     ///   5. Parent hash linkage
     ///
     /// When the first invalid block at height H is found:
@@ -1811,14 +2486,27 @@ impl Blockchain {
             None => return Err(anyhow!("validate_chain_integrity: tip header missing")),
         };
 
+        // Blocks up to the assume-valid block are not re-checked once the
+        // main chain runs through it
+        let mut prev_hash: Option<String> = None;
+        let mut start_height = 0;
+        if let Some(av) = &self.assume_valid {
+            let main = self.db.get(format!("i:{}", av.height).as_bytes())?;
+            if av.height <= tip_height && main.is_some_and(|main| main == av.hash.as_bytes()) {
+                prev_hash = Some(av.hash.clone());
+                start_height = av.height + 1;
+            }
+        }
+
         log::info!(
-            "🔍 Validating chain integrity: blocks 0..={} ...",
+            "🔍 Validating chain integrity: blocks {}..={} ...",
+            start_height,
             tip_height
         );
 
-        let mut prev_hash: Option<String> = None;
+        let prune_height = self.prune_height()?;
 
-        for height in 0..=tip_height {
+        for height in start_height..=tip_height {
             // Resolve hash for this height via the index.
             let hash = match self.db.get(format!("i:{}", height).as_bytes())? {
                 Some(v) => match String::from_utf8(v) {
@@ -1840,27 +2528,43 @@ impl Blockchain {
                 }
             };
 
-            let block = match self.load_block(&hash)? {
-                Some(b) => b,
+            let header = match self.load_header(&hash)? {
+                Some(h) => h,
                 None => {
                     log::error!(
-                        "validate_chain_integrity: block body missing for i:{} hash={}",
+                        "validate_chain_integrity: header missing for i:{} hash={}",
                         height,
                         &hash[..16.min(hash.len())]
                     );
                     return self.handle_invalid_block(height, tip_height);
                 }
             };
+            // Bodies below the prune height were deleted on purpose
+            let body = if height < prune_height {
+                None
+            } else {
+                match self.load_block(&hash)? {
+                    Some(b) => Some(b),
+                    None => {
+                        log::error!(
+                            "validate_chain_integrity: block body missing for i:{} hash={}",
+                            height,
+                            &hash[..16.min(hash.len())]
+                        );
+                        return self.handle_invalid_block(height, tip_height);
+                    }
+                }
+            };
 
             // 1) Header hash
-            match compute_header_hash(&block.header) {
-                Ok(computed) if computed == block.hash => {}
+            match compute_header_hash(&header) {
+                Ok(computed) if computed == hash => {}
                 Ok(computed) => {
                     log::error!(
                         "validate_chain_integrity: #{} header hash mismatch computed={} stored={}",
                         height,
                         &computed[..16],
-                        &block.hash[..16]
+                        &hash[..16]
                     );
                     return self.handle_invalid_block(height, tip_height);
                 }
@@ -1874,14 +2578,14 @@ impl Blockchain {
             }
 
             // 2) PoW
-            match Self::is_valid_pow(&block.hash, block.header.difficulty) {
+            match Self::is_valid_pow(&self.params, &header) {
                 Ok(true) => {}
                 Ok(false) => {
                     log::error!(
                         "validate_chain_integrity: #{} invalid PoW hash={} bits=0x{:08x}",
                         height,
-                        &block.hash[..16],
-                        block.header.difficulty
+                        &hash[..16],
+                        header.difficulty
                     );
                     return self.handle_invalid_block(height, tip_height);
                 }
@@ -1895,14 +2599,14 @@ impl Blockchain {
             }
 
             // 3) DWG3 difficulty (only once the window is fully available)
-            if height >= Self::RETARGET_WINDOW as u64 {
+            if height >= self.params.retarget_window {
                 match self.calculate_adjusted_difficulty(height) {
-                    Ok(expected) if expected == block.header.difficulty => {}
+                    Ok(expected) if expected == header.difficulty => {}
                     Ok(expected) => {
                         log::error!(
                             "validate_chain_integrity: #{} difficulty mismatch stored=0x{:08x} expected=0x{:08x}",
                             height,
-                            block.header.difficulty,
+                            header.difficulty,
                             expected
                         );
                         return self.handle_invalid_block(height, tip_height);
@@ -1918,26 +2622,28 @@ impl Blockchain {
             }
 
             // 4) Merkle root
-            let txids: Vec<String> = block.transactions.iter().map(|t| t.txid.clone()).collect();
-            let computed_merkle = compute_merkle_root(&txids);
-            if computed_merkle != block.header.merkle_root {
-                log::error!(
-                    "validate_chain_integrity: #{} merkle root mismatch computed={} stored={}",
-                    height,
-                    &computed_merkle[..16.min(computed_merkle.len())],
-                    &block.header.merkle_root[..16.min(block.header.merkle_root.len())]
-                );
-                return self.handle_invalid_block(height, tip_height);
+            if let Some(block) = &body {
+                let txids: Vec<String> = block.transactions.iter().map(|t| t.txid.clone()).collect();
+                let computed_merkle = compute_merkle_root(&txids);
+                if computed_merkle != header.merkle_root {
+                    log::error!(
+                        "validate_chain_integrity: #{} merkle root mismatch computed={} stored={}",
+                        height,
+                        &computed_merkle[..16.min(computed_merkle.len())],
+                        &header.merkle_root[..16.min(header.merkle_root.len())]
+                    );
+                    return self.handle_invalid_block(height, tip_height);
+                }
             }
 
             // 5) Parent hash linkage
             if height > 0 {
                 if let Some(ref expected_prev) = prev_hash {
-                    if &block.header.previous_hash != expected_prev {
+                    if &header.previous_hash != expected_prev {
                         log::error!(
                             "validate_chain_integrity: #{} broken parent link: block.prev={} expected={}",
                             height,
-                            &block.header.previous_hash[..16],
+                            &header.previous_hash[..16],
                             &expected_prev[..16]
                         );
                         return self.handle_invalid_block(height, tip_height);
@@ -1945,14 +2651,24 @@ impl Blockchain {
                 }
             }
 
-            prev_hash = Some(block.hash.clone());
+            // 6) Checkpoints (built-in and signed file in force)
+            if !self.checkpoint_allows(height, &hash) {
+                log::error!(
+                    "validate_chain_integrity: #{} conflicts with checkpoint hash={}",
+                    height,
+                    &hash[..16]
+                );
+                return self.handle_invalid_block(height, tip_height);
+            }
+
+            prev_hash = Some(hash);
 
             if height % 1000 == 0 && height > 0 {
                 log::info!("  ... validated up to block #{}", height);
             }
         }
 
-        log::info!("✅ Chain integrity OK — all {} blocks valid", tip_height + 1);
+        log::info!("✅ Chain integrity OK — all {} blocks valid", tip_height + 1 - start_height);
         Ok((tip_height, None))
     }
 
@@ -1981,18 +2697,409 @@ impl Blockchain {
         Ok((bad_height.saturating_sub(1), Some(bad_height)))
     }
 
-    /// Count blocks in database (diagnostic utility)
+    /// Count blocks in database, pruned ones included (diagnostic utility)
     pub fn count_blocks(&self) -> usize {
-        let mut count = 0;
-        let iter = self.db.iterator(rocksdb::IteratorMode::Start);
-        for item in iter {
-            if let Ok((key, _)) = item {
-                let key_str = String::from_utf8_lossy(&key);
-                if key_str.starts_with("b:") {
-                    count += 1;
-                }
-            }
+        self.db.prefix_iter(b"h:").filter(|item| item.is_ok()).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::testutil::temp_chain;
+
+    #[test]
+    fn block_work_from_compact_bits() {
+        // target = 0x7fffff * 2^224 -> 2^256 / (target + 1) = 512
+        assert_eq!(Blockchain::block_work(ChainParams::mainnet().pow_limit_bits), U256::from(512u64));
+        // target = 2^224 -> floor(2^256 / (2^224 + 1)) = 2^32 - 1
+        assert_eq!(Blockchain::block_work(0x1d010000), U256::from((1u64 << 32) - 1));
+        assert!(Blockchain::block_work(0x1e0fffff) > Blockchain::block_work(0x1f0fffff));
+        assert!(Blockchain::block_work(0x1f000000).is_zero());
+    }
+
+    #[test]
+    fn undo_covers_only_external_spends() {
+        use crate::transaction::{SEQUENCE_FINAL, TransactionInput, TransactionOutput};
+
+        let tx = |txid: &str, spends: &[(&str, u32)]| Transaction {
+            txid: txid.to_string(),
+            inputs: spends
+                .iter()
+                .map(|(t, v)| TransactionInput {
+                    txid: t.to_string(),
+                    vout: *v,
+                    pubkey: String::new(),
+                    signature: None,
+                    sequence: SEQUENCE_FINAL,
+                    witness: None,
+                })
+                .collect(),
+            outputs: vec![TransactionOutput::new("0xabc".to_string(), U256::from(1))],
+            timestamp: 0,
+            version: TX_VERSION_LEGACY,
+            lock_time: 0,
+        };
+        let block = Block {
+            header: BlockHeader {
+                index: 1,
+                previous_hash: "0".repeat(64),
+                merkle_root: "0".repeat(64),
+                timestamp: 0,
+                nonce: 0,
+                difficulty: ChainParams::mainnet().pow_limit_bits,
+            },
+            transactions: vec![
+                tx("cb", &[]),
+                tx("a", &[("x", 0)]),
+                tx("b", &[("a", 0), ("y", 1)]),
+            ],
+            hash: "0".repeat(64),
+        };
+
+        assert_eq!(
+            Blockchain::external_spends(&block),
+            vec![("x".to_string(), 0), ("y".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn history_marks_signers_as_senders() {
+        use crate::crypto::WalletKeypair;
+        use crate::transaction::TransactionBuilder;
+
+        let alice = WalletKeypair::new();
+        let alice_addr = alice.address().to_lowercase();
+        let funding = Utxo::new("f".repeat(64), 0, alice_addr.clone(), U256::from(100));
+        let tx = TransactionBuilder::new()
+            .add_input(funding, &alice)
+            .add_output("0xBOB", U256::from(60))
+            .add_output(&alice_addr, U256::from(30))
+            .build()
+            .unwrap();
+
+        // Change back to the signer does not make it a receiver
+        assert_eq!(
+            Blockchain::tx_addresses(&tx),
+            vec![
+                (alice_addr.clone(), HistoryDirection::Send),
+                ("0xbob".to_string(), HistoryDirection::Receive),
+            ]
+        );
+
+        let cb = Transaction::coinbase("0xMiner", U256::from(50));
+        assert_eq!(
+            Blockchain::tx_addresses(&cb),
+            vec![("0xminer".to_string(), HistoryDirection::Receive)]
+        );
+        assert!(
+            Blockchain::address_history_key("0xa", 9, "t") < Blockchain::address_history_key("0xa", 10, "t")
+        );
+    }
+    #[test]
+    fn dwg3_matches_recorded_bits() {
+        // Bits produced by the retarget before it was split out of Blockchain:
+        // on-schedule blocks, a 7s burst, 400s stalls, then mixed gaps
+        const RECORDED: [u32; 76] = [
+        0x1e00f554, 0x1e00f4e7, 0x1e00f475, 0x1e00f3ff, 0x1e00f385, 0x1e00f305,
+        0x1e00f281, 0x1e00e8ac, 0x1e00de85, 0x1e00d414, 0x1e00c963, 0x1e00be7d,
+        0x1e00b36c, 0x1e00a83b, 0x1e009cf7, 0x1e0091ad, 0x1e00866a, 0x1d7b3abd,
+        0x1d702d35, 0x1d654f5a, 0x1d5aaf10, 0x1d505a2f, 0x1d465e65, 0x1d3ce995,
+        0x1d3a33f3, 0x1d379a9e, 0x1d34f98e, 0x1d3250bb, 0x1d2fa016, 0x1d2ce793,
+        0x1d2a272a, 0x1d275ece, 0x1d2a575f, 0x1d4370c1, 0x1d59d1e0, 0x1d6e916a,
+        0x1e0082b9, 0x1e009757, 0x1e00ad87, 0x1e00c688, 0x1e00e3cb, 0x1e01070e,
+        0x1e012742, 0x1e013e25, 0x1e015940, 0x1e017912, 0x1e019e29, 0x1e01c922,
+        0x1e01faa9, 0x1e0232b8, 0x1e02721c, 0x1e02b9c0, 0x1e030aae, 0x1e03660f,
+        0x1e03cd34, 0x1e044196, 0x1e04c4dd, 0x1e05582e, 0x1e05fac5, 0x1e06aee4,
+        0x1e0776ee, 0x1e085575, 0x1e094d38, 0x1e0a612e, 0x1e0b9483, 0x1e0cea9a,
+        0x1e0e670c, 0x1e100f05, 0x1e11e921, 0x1e13fb1d, 0x1e164b5e, 0x1e18e105,
+        0x1e19c5f1, 0x1e1a5d1e, 0x1e1aa625, 0x1e1a03a1,
+        ];
+
+        let (_dir, bc) = temp_chain("dwg3", ChainParams::mainnet());
+
+        let mut timestamp = 1_800_000_000i64;
+        for height in 0..100u64 {
+            let bits = if height < 24 {
+                0x1e00ffff
+            } else {
+                let bits = bc.calculate_adjusted_difficulty(height).unwrap();
+                assert_eq!(bits, RECORDED[height as usize - 24], "bits at height {}", height);
+                let parent = format!("{:064x}", height - 1);
+                assert_eq!(bc.next_difficulty_after(&parent).unwrap(), bits, "branch bits at height {}", height);
+                bits
+            };
+            timestamp += match height {
+                0..=29 => 60,
+                30..=54 => 7,
+                55..=79 => 400,
+                _ => 45 + (height as i64 % 7) * 10,
+            };
+            let header = BlockHeader {
+                index: height,
+                previous_hash: format!("{:064x}", height.wrapping_sub(1)),
+                merkle_root: String::new(),
+                timestamp,
+                nonce: 0,
+                difficulty: bits,
+            };
+            let hash = format!("{:064x}", height);
+            let mut batch = ChainBatch::default();
+            batch.put(
+                format!("h:{}", hash).as_bytes(),
+                bincode::encode_to_vec(&header, *BINCODE_CONFIG).unwrap(),
+            );
+            batch.put(format!("i:{}", height).as_bytes(), hash.as_bytes());
+            put_batch(&bc.db, batch).unwrap();
+
         }
-        count
+
+        // A side block replacing #99 after a long stall: its child retargets
+        // over the side branch, not over the main-chain index
+        let main_99 = bc.load_header(&format!("{:064x}", 99)).unwrap().unwrap();
+        let side = BlockHeader {
+            timestamp: main_99.timestamp + 3_000,
+            ..main_99.clone()
+        };
+        let side_hash = "ab".repeat(32);
+        bc.db
+            .put(
+                format!("h:{}", side_hash).as_bytes(),
+                bincode::encode_to_vec(&side, *BINCODE_CONFIG).unwrap(),
+            )
+            .unwrap();
+        let mut history: Vec<RetargetRecord> = (76..99u64)
+            .map(|height| {
+                let header = bc.load_header(&format!("{:064x}", height)).unwrap().unwrap();
+                RetargetRecord { timestamp: header.timestamp, bits: header.difficulty }
+            })
+            .collect();
+        history.push(RetargetRecord { timestamp: side.timestamp, bits: side.difficulty });
+        let expected = retarget::next_bits(&bc.params, &history);
+        assert_eq!(bc.next_difficulty_after(&side_hash).unwrap(), expected);
+        assert_ne!(expected, bc.calculate_adjusted_difficulty(100).unwrap());
+    }
+
+    #[test]
+    fn assume_valid_defaults_to_latest_checkpoint() {
+        let mut params = ChainParams::regtest();
+        params.checkpoints = vec![Checkpoint {
+            height: 10,
+            hash: "cd".repeat(32),
+            description: "test checkpoint".to_string(),
+        }];
+        let (_dir, mut bc) = temp_chain("assume-valid", params);
+        let genesis = bc.create_genesis("0x00000000000000000000000000000000000000aa").unwrap();
+
+        assert_eq!(bc.assume_valid.as_ref().map(|av| av.height), Some(10));
+        // Its header is not known yet, so nothing is skipped
+        assert!(!bc.assumed_valid(&genesis, 0).unwrap());
+
+        // A known hash resolves its height; it is then held like a checkpoint
+        bc.set_assume_valid(Some(genesis.as_str())).unwrap();
+        assert_eq!(bc.assume_valid.as_ref().map(|av| av.height), Some(0));
+        assert!(bc.checkpoint_allows(0, &genesis));
+        assert!(!bc.checkpoint_allows(0, &"ef".repeat(32)));
+
+        assert!(bc.assumed_valid(&genesis, 0).unwrap());
+
+        bc.set_assume_valid(Some(format!("20:{}", "ef".repeat(32)).as_str())).unwrap();
+        assert!(!bc.assumed_valid(&"ef".repeat(32), 20).unwrap());
+        assert!(bc.set_assume_valid(Some(format!("10:{}", "ef".repeat(32)).as_str())).is_err());
+        assert!(bc.set_assume_valid(Some("ef".repeat(32).as_str())).is_err()); // unknown, no height
+
+        bc.set_assume_valid(Some("none")).unwrap();
+        assert!(!bc.assumed_valid(&genesis, 0).unwrap());
+        bc.set_assume_valid(None).unwrap();
+        assert_eq!(bc.assume_valid.as_ref().map(|av| av.height), Some(10));
+    }
+
+    #[test]
+    fn assume_valid_skips_only_its_ancestors() {
+        use crate::crypto::WalletKeypair;
+        use crate::transaction::TransactionBuilder;
+
+        let (_dir, mut bc) = temp_chain("assume-valid-ancestors", ChainParams::regtest());
+        let key = WalletKeypair::new();
+        let miner = key.address();
+        let hashes: Vec<String> = (0..11).map(|_| bc.generate_block(&miner, Vec::new()).unwrap().hash).collect();
+
+        // Connected: ancestors are found through the main-chain index
+        bc.set_assume_valid(Some(hashes[5].as_str())).unwrap();
+        assert!(bc.assumed_valid(&hashes[3], 3).unwrap());
+        assert!(bc.assumed_valid(&hashes[5], 5).unwrap());
+        assert!(!bc.assumed_valid(&hashes[6], 6).unwrap());
+        assert!(!bc.assumed_valid(&"ab".repeat(32), 3).unwrap());
+
+        // Not connected: two known headers on top of the tip, the second one assumed valid
+        let mut parent = bc.load_header(&hashes[10]).unwrap().unwrap();
+        let mut side = Vec::new();
+        for (height, hash) in [(11, "ab".repeat(32)), (12, "ac".repeat(32))] {
+            let header = BlockHeader {
+                index: height,
+                previous_hash: side.last().cloned().unwrap_or_else(|| hashes[10].clone()),
+                timestamp: parent.timestamp + 1,
+                ..parent.clone()
+            };
+            let mut batch = ChainBatch::default();
+            batch.put(
+                format!("h:{}", hash).as_bytes(),
+                bincode::encode_to_vec(&header, *BINCODE_CONFIG).unwrap(),
+            );
+            bc.put_tree_entry(&mut batch, &header, &hash, BlockStatus::HeaderValid)
+                .unwrap();
+            bc.db.write(batch).unwrap();
+            side.push(hash);
+            parent = header;
+        }
+        bc.set_assume_valid(Some(side[1].as_str())).unwrap();
+        assert!(bc.assumed_valid(&side[0], 11).unwrap());
+        assert!(bc.assumed_valid(&hashes[4], 4).unwrap());
+
+        // A block at the same height off that path still has its signatures checked
+        let coinbase = bc.load_block(&hashes[0]).unwrap().unwrap().transactions[0].clone();
+        let utxo = bc.get_utxo(&coinbase.txid, 0).unwrap().unwrap();
+        let fee = U256::from(10u64).pow(U256::from(16u8));
+        let mut tx = TransactionBuilder::new()
+            .add_input(utxo.clone(), &key)
+            .add_output(&miner, utxo.amount() - fee)
+            .chain_id(bc.params.chain_id)
+            .build()
+            .unwrap();
+        tx.inputs[0].signature = Some(hex::encode(WalletKeypair::new().sign(b"other")));
+        let err = bc.generate_block(&miner, vec![(tx, fee)]).unwrap_err();
+        assert!(err.to_string().contains("signature"), "{}", err);
+        assert_eq!(bc.chain_tip.as_deref(), Some(hashes[10].as_str()));
+    }
+
+    #[test]
+    fn truncate_never_drops_spent_outputs() {
+        use crate::crypto::WalletKeypair;
+        use crate::transaction::TransactionBuilder;
+
+        let (_dir, mut bc) = temp_chain("truncate", ChainParams::regtest());
+        let key = WalletKeypair::new();
+        let miner = key.address();
+        let funding = bc.generate_block(&miner, Vec::new()).unwrap().transactions[0].clone();
+        for _ in 0..10 {
+            bc.generate_block(&miner, Vec::new()).unwrap();
+        }
+        let tip = bc.chain_tip.clone().unwrap();
+        let utxo = bc.get_utxo(&funding.txid, 0).unwrap().unwrap();
+        let fee = U256::from(10u64).pow(U256::from(16u8));
+        let tx = TransactionBuilder::new()
+            .add_input(utxo.clone(), &key)
+            .add_output(&miner, utxo.amount() - fee)
+            .chain_id(bc.params.chain_id)
+            .build()
+            .unwrap();
+        let spend = bc.generate_block(&miner, vec![(tx, fee)]).unwrap();
+        let stats = bc.utxo_set_stats().unwrap();
+
+        // Neither the undo record nor the funding tx: nothing is deleted
+        let funding_blob = bc.db.get(format!("t:{}", funding.txid).as_bytes()).unwrap().unwrap();
+        bc.db.delete(format!("r:{}", spend.hash).as_bytes()).unwrap();
+        bc.db.delete(format!("t:{}", funding.txid).as_bytes()).unwrap();
+        assert!(bc.truncate_chain_to_height(10).is_err());
+        assert_eq!(bc.chain_tip.as_deref(), Some(spend.hash.as_str()));
+        assert_eq!(bc.utxo_set_stats().unwrap(), stats);
+
+        // The undo record is rebuilt from the funding tx
+        bc.db.put(format!("t:{}", funding.txid).as_bytes(), funding_blob).unwrap();
+        bc.truncate_chain_to_height(10).unwrap();
+        assert_eq!(bc.chain_tip.as_deref(), Some(tip.as_str()));
+        assert_eq!(bc.get_utxo(&funding.txid, 0).unwrap(), Some(utxo));
+        assert_eq!(bc.utxo_set_stats().unwrap().count, 11);
+    }
+
+    #[test]
+    fn block_cannot_spend_a_committed_output_twice() {
+        use crate::crypto::WalletKeypair;
+        use crate::transaction::TransactionBuilder;
+
+        let (_dir, mut bc) = temp_chain("double-spend", ChainParams::regtest());
+        let key = WalletKeypair::new();
+        let miner = key.address();
+        let funding = bc.generate_block(&miner, Vec::new()).unwrap().transactions[0].clone();
+        for _ in 0..10 {
+            bc.generate_block(&miner, Vec::new()).unwrap();
+        }
+        let tip = bc.chain_tip.clone().unwrap();
+        let stats = bc.utxo_set_stats().unwrap();
+
+        // Two transactions spending the same committed output
+        let utxo = bc.get_utxo(&funding.txid, 0).unwrap().unwrap();
+        let fee = U256::from(10u64).pow(U256::from(16u8));
+        let spend_to = |to: &str| {
+            TransactionBuilder::new()
+                .add_input(utxo.clone(), &key)
+                .add_output(to, utxo.amount() - fee)
+                .chain_id(bc.params.chain_id)
+                .build()
+                .unwrap()
+        };
+        let first = spend_to(&miner);
+        let second = spend_to(&WalletKeypair::new().address());
+
+        let block = Block {
+            header: BlockHeader {
+                index: 11,
+                previous_hash: tip.clone(),
+                merkle_root: "0".repeat(64),
+                timestamp: 0,
+                nonce: 0,
+                difficulty: bc.params.pow_limit_bits,
+            },
+            transactions: vec![Transaction::coinbase(&miner, U256::zero()), first.clone(), second.clone()],
+            hash: "ab".repeat(32),
+        };
+        let err = bc.spent_outputs(&block).unwrap_err();
+        assert!(err.to_string().contains("spent twice"), "{}", err);
+
+        let err = bc.generate_block(&miner, vec![(first, fee), (second, fee)]).unwrap_err();
+        assert!(err.to_string().contains("spent twice"), "{}", err);
+        assert_eq!(bc.chain_tip.as_deref(), Some(tip.as_str()));
+        assert_eq!(bc.utxo_set_stats().unwrap(), stats);
+        assert_eq!(bc.get_utxo(&funding.txid, 0).unwrap(), Some(utxo));
+    }
+
+    #[test]
+    fn torsion_keys_stay_valid_below_sighash_height() {
+        use crate::crypto::{WalletKeypair, address_from_pubkey_hex, tests::torsion_key_signature};
+        use crate::transaction::TransactionBuilder;
+
+        let mut params = ChainParams::regtest();
+        params.sighash_height = 100;
+        let (_dir, mut bc) = temp_chain("torsion", params);
+
+        // The key is only known with a torsion component, as blocks signed
+        // before the batch rules may carry it
+        let key = WalletKeypair::new();
+        let (tampered_hex, _) = torsion_key_signature(&key, b"");
+        let owner = address_from_pubkey_hex(&tampered_hex).unwrap();
+        let funding = bc.generate_block(&owner, Vec::new()).unwrap().transactions[0].clone();
+        for _ in 0..10 {
+            bc.generate_block(&key.address(), Vec::new()).unwrap();
+        }
+
+        let utxo = bc.get_utxo(&funding.txid, 0).unwrap().unwrap();
+        let fee = U256::from(10u64).pow(U256::from(16u8));
+        let mut tx = TransactionBuilder::new()
+            .add_input(utxo.clone(), &key)
+            .add_output(&key.address(), utxo.amount() - fee)
+            .chain_id(bc.params.chain_id)
+            .build()
+            .unwrap();
+        let sighash = tx.sighash(0, &utxo, bc.params.chain_id).unwrap();
+        let (tampered_hex, sig) = torsion_key_signature(&key, &sighash);
+        tx.inputs[0].pubkey = tampered_hex;
+        tx.inputs[0].signature = Some(hex::encode(sig));
+
+        let spent = std::slice::from_ref(&utxo);
+        assert!(!tx.verify_signatures_at(spent, bc.params.sighash_height, &bc.params).unwrap());
+        assert!(tx.verify_signatures_at(spent, 11, &bc.params).unwrap());
+        let block = bc.generate_block(&key.address(), vec![(tx, fee)]).unwrap();
+        assert_eq!(block.transactions.len(), 2);
+        assert_eq!(bc.chain_tip.as_deref(), Some(block.hash.as_str()));
     }
 }
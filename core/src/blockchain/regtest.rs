@@ -0,0 +1,104 @@
+//! Block generation on demand for networks with `mine_on_demand` (regtest):
+//! their fixed, trivial target lets `generate_block` mine on the CPU in well
+//! under a millisecond.
+
+use anyhow::{Result, anyhow};
+use chrono::Utc;
+use primitive_types::U256;
+
+use super::Blockchain;
+use crate::block::{Block, BlockHeader, compute_header_hash, compute_merkle_root, select_transactions};
+use crate::config::{BLOCK_TEMPLATE_RESERVED_SIZE, MAX_BLOCK_SIZE};
+use crate::transaction::Transaction;
+
+impl Blockchain {
+    /// Mine a block on the tip (genesis on an empty chain) paying the reward
+    /// and the fees of the selected `candidates` (tx, fee) to `address`, and
+    /// connect it. Only on networks that mine on demand.
+    pub fn generate_block(&mut self, address: &str, candidates: Vec<(Transaction, U256)>) -> Result<Block> {
+        if !self.params.mine_on_demand {
+            return Err(anyhow!("block generation is not available on {}", self.params.network));
+        }
+        let height = self.get_next_index()?;
+        let previous_hash = self.chain_tip.clone().unwrap_or_else(|| "0".repeat(64));
+
+        // Each block is newer than its parent and the median time past, so
+        // blocks generated within one second still get distinct coinbase txids
+        let mut timestamp = Utc::now().timestamp();
+        if let Some(tip) = self.chain_tip.clone() {
+            let parent = self
+                .load_header(&tip)?
+                .ok_or_else(|| anyhow!("missing header for tip {}", tip))?;
+            let median = self.median_time_past(&tip)?.unwrap_or(parent.timestamp);
+            timestamp = timestamp.max(parent.timestamp + 1).max(median + 1);
+        }
+
+        let candidates = candidates.into_iter().filter(|(tx, _)| tx.timestamp <= timestamp).collect();
+        let selected = select_transactions(candidates, MAX_BLOCK_SIZE - BLOCK_TEMPLATE_RESERVED_SIZE);
+        let fees = selected.iter().fold(U256::zero(), |acc, (_, fee)| acc + *fee);
+        let mut coinbase = Transaction::coinbase(address, self.get_block_reward(height) + fees);
+        coinbase.timestamp = timestamp;
+        let mut transactions = vec![coinbase.with_hashes()];
+        transactions.extend(selected.into_iter().map(|(tx, _)| tx));
+
+        let txids: Vec<String> = transactions.iter().map(|t| t.txid.clone()).collect();
+        let mut header = BlockHeader {
+            index: height,
+            previous_hash,
+            merkle_root: compute_merkle_root(&txids),
+            timestamp,
+            nonce: 0,
+            difficulty: self.calculate_adjusted_difficulty(height)?,
+        };
+        while !Self::is_valid_pow(&self.params, &header)? {
+            header.nonce += 1;
+        }
+
+        let block = Block {
+            hash: compute_header_hash(&header)?,
+            header,
+            transactions,
+        };
+        self.validate_and_insert_block(&block)?;
+        Ok(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::testutil::TempDir;
+    use crate::network::ChainParams;
+
+    #[test]
+    fn generated_blocks_extend_the_chain() {
+        let dir = TempDir::new("regtest");
+        let miner = "0x00000000000000000000000000000000000000aa";
+        let mut mainnet = dir.open_chain("mainnet", ChainParams::mainnet());
+        assert!(mainnet.generate_block(miner, Vec::new()).is_err());
+
+        // The database now belongs to mainnet
+        drop(mainnet);
+        assert!(Blockchain::new(dir.join("mainnet").to_str().unwrap(), ChainParams::regtest()).is_err());
+
+        let mut bc = dir.open_chain("regtest", ChainParams::regtest());
+        let mut hashes = Vec::new();
+        for _ in 0..3 {
+            hashes.push(bc.generate_block(miner, Vec::new()).unwrap().hash);
+        }
+        assert_eq!(bc.get_next_index().unwrap(), 3);
+        assert_eq!(bc.chain_tip.as_deref(), Some(hashes[2].as_str()));
+        assert_eq!(bc.main_chain_hash(0).unwrap(), hashes[0]);
+
+        // Every coinbase is a distinct output
+        let stats = bc.utxo_set_stats().unwrap();
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.total_amount, bc.get_block_reward(0) * U256::from(3));
+
+        // Regtest coinbase outputs mature after 10 blocks, not mainnet's 100
+        let genesis_coinbase = bc.load_block(&hashes[0]).unwrap().unwrap().transactions[0].txid.clone();
+        let utxo = bc.get_utxo(&genesis_coinbase, 0).unwrap().unwrap();
+        assert!(!bc.is_utxo_mature(&utxo, 9).unwrap());
+        assert!(bc.is_utxo_mature(&utxo, 10).unwrap());
+    }
+}
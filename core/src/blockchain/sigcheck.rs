@@ -0,0 +1,191 @@
+//! Block checks that need no chain state (header hash, PoW, merkle root) and
+//! the signature pass, run in parallel and in Ed25519 batches once the UTXO
+//! pass has resolved what every input spends. Both may run before the chain
+//! lock is taken (`PrecheckedBlock`).
+
+use anyhow::{Result, anyhow};
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+use super::{BINCODE_CONFIG, Blockchain};
+use crate::block::{Block, compute_header_hash, compute_merkle_root};
+use crate::network::ChainParams;
+use crate::security::BlockFailureReason;
+use crate::transaction::{SignatureCheck, Transaction};
+use crate::utxo::Utxo;
+
+/// Signatures verified together in one `ed25519_dalek` batch
+const SIGNATURE_BATCH_SIZE: usize = 64;
+
+fn invalid_signature(tx: &Transaction) -> anyhow::Error {
+    BlockFailureReason::SignatureFailure.record_body(anyhow!("tx signature invalid: {}", tx.txid))
+}
+
+/// Header hash, proof of work and merkle root: a function of the block
+/// alone, so block handlers run it before taking the chain lock
+pub fn check_block_contextless(params: &ChainParams, block: &Block) -> Result<()> {
+    let computed = compute_header_hash(&block.header)?;
+    if computed != block.hash {
+        return Err(BlockFailureReason::HashMismatch.record(anyhow!(
+            "header hash mismatch: computed {} != block.hash {}",
+            computed,
+            block.hash
+        )));
+    }
+
+    if !Blockchain::is_valid_pow(params, &block.header)? {
+        return Err(BlockFailureReason::InvalidPoW.record(anyhow!(
+            "invalid PoW at block {}: hash does not satisfy bits 0x{:08x}",
+            block.header.index,
+            block.header.difficulty
+        )));
+    }
+
+    let txids: Vec<String> = block.transactions.iter().map(|t| t.txid.clone()).collect();
+    let merkle = compute_merkle_root(&txids);
+    if merkle != block.header.merkle_root {
+        return Err(BlockFailureReason::MerkleRootMismatch.record(anyhow!("merkle mismatch")));
+    }
+    Ok(())
+}
+
+/// Verify the signatures of every non-coinbase transaction of `block`.
+/// `spent[i]` holds the UTXOs consumed by `block.transactions[i + 1]`,
+/// in input order, as resolved by the UTXO pass.
+pub(super) fn verify_block_signatures(params: &ChainParams, block: &Block, spent: &[Vec<Utxo>]) -> Result<()> {
+    let height = block.header.index;
+    let txs = block.transactions.get(1..).unwrap_or_default();
+    if txs.len() != spent.len() {
+        return Err(anyhow!(
+            "expected spent utxos for {} transactions, got {}",
+            txs.len(),
+            spent.len()
+        ));
+    }
+
+    // Legacy whole-tx signatures may stand in for per-input ones, so
+    // before activation each transaction is checked on its own
+    if height < params.sighash_height {
+        return txs.par_iter().zip(spent).try_for_each(|(tx, spent)| {
+            let ok = tx
+                .verify_signatures_at(spent, height, params)
+                .map_err(|e| BlockFailureReason::SignatureFailure.record_body(e))?;
+            if ok { Ok(()) } else { Err(invalid_signature(tx)) }
+        });
+    }
+
+    let per_tx: Vec<Vec<SignatureCheck>> = txs
+        .par_iter()
+        .zip(spent)
+        .map(|(tx, spent)| {
+            match tx
+                .signature_checks(spent, params.chain_id)
+                .map_err(|e| BlockFailureReason::SignatureFailure.record_body(e))?
+            {
+                Some(checks) => Ok(checks),
+                None => Err(invalid_signature(tx)),
+            }
+        })
+        .collect::<Result<_>>()?;
+    let checks: Vec<(&Transaction, &SignatureCheck)> = txs
+        .iter()
+        .zip(&per_tx)
+        .flat_map(|(tx, checks)| checks.iter().map(move |check| (tx, check)))
+        .collect();
+
+    // A failed batch is re-checked one signature at a time, which names
+    // the tx and is authoritative: the block is only rejected for a
+    // signature the strict single check rejects
+    let failed = checks.par_chunks(SIGNATURE_BATCH_SIZE).find_map_any(|chunk| {
+        let entries: Vec<(&str, &[u8], &[u8])> = chunk
+            .iter()
+            .map(|(_, check)| (check.pubkey.as_str(), &check.message[..], check.signature.as_slice()))
+            .collect();
+        if crate::crypto::verify_batch(&entries) {
+            return None;
+        }
+        chunk.iter().find(|(_, check)| !check.is_valid_strict()).map(|(tx, _)| *tx)
+    });
+    match failed {
+        Some(tx) => Err(invalid_signature(tx)),
+        None => Ok(()),
+    }
+}
+
+/// A block whose lock-free checks ran before the chain lock was taken: the
+/// contextless checks, and its signatures if the outputs it spends were
+/// resolved (`Blockchain::spent_outputs`). `validate_and_insert_prechecked`
+/// and `validate_fork_prechecked` skip what it covers.
+pub struct PrecheckedBlock<'a> {
+    block: &'a Block,
+    signed_spends: Option<Vec<Vec<Utxo>>>, // spent outputs the signatures were verified against
+}
+
+impl<'a> PrecheckedBlock<'a> {
+    /// Run the contextless checks
+    pub fn check(params: &ChainParams, block: &'a Block) -> Result<Self> {
+        check_block_contextless(params, block)?;
+        Ok(Self { block, signed_spends: None })
+    }
+
+    /// Verify the signatures against `spent`, as resolved under the lock.
+    /// Connecting re-verifies them unless it resolves the same outputs.
+    pub fn verify_signatures(&mut self, params: &ChainParams, spent: Vec<Vec<Utxo>>) -> Result<()> {
+        verify_block_signatures(params, self.block, &spent)?;
+        self.signed_spends = Some(spent);
+        Ok(())
+    }
+
+    pub fn block(&self) -> &'a Block {
+        self.block
+    }
+
+    pub(super) fn signed_spends(&self) -> Option<&Vec<Vec<Utxo>>> {
+        self.signed_spends.as_ref()
+    }
+}
+
+impl Blockchain {
+    /// Outputs spent by each non-coinbase transaction of `block`, in input
+    /// order, for `PrecheckedBlock::verify_signatures`. None when the block
+    /// does not extend the tip, is assumed valid or spends an output that
+    /// is not found: connecting it resolves (and reports) those itself.
+    /// Fails when two inputs of the block spend the same output.
+    pub fn spent_outputs(&self, block: &Block) -> Result<Option<Vec<Vec<Utxo>>>> {
+        if self.chain_tip.as_deref() != Some(block.header.previous_hash.as_str())
+            || self.assumed_valid(&block.hash, block.header.index)?
+        {
+            return Ok(None);
+        }
+
+        let mut block_utxos: HashMap<String, Utxo> = HashMap::new();
+        let mut used_utxos = HashSet::new();
+        let mut spent_by_tx = Vec::with_capacity(block.transactions.len().saturating_sub(1));
+        for (i, tx) in block.transactions.iter().enumerate() {
+            if i > 0 {
+                let mut spent = Vec::with_capacity(tx.inputs.len());
+                for inp in &tx.inputs {
+                    let ukey = format!("u:{}:{}", inp.txid, inp.vout);
+                    if !used_utxos.insert(ukey.clone()) {
+                        return Err(BlockFailureReason::DuplicateInput
+                            .record(anyhow!("input {} spent twice in block (tx {})", ukey, tx.txid)));
+                    }
+                    let utxo = match block_utxos.remove(&ukey) {
+                        Some(pending) => pending,
+                        None => match self.db.get(ukey.as_bytes())? {
+                            Some(blob) => bincode::decode_from_slice(&blob, *BINCODE_CONFIG)?.0,
+                            None => return Ok(None),
+                        },
+                    };
+                    spent.push(utxo);
+                }
+                spent_by_tx.push(spent);
+            }
+            for (v, out) in tx.outputs.iter().enumerate() {
+                let utxo = Utxo::new(tx.txid.clone(), v as u32, out.to.to_lowercase(), out.amount());
+                block_utxos.insert(format!("u:{}:{}", tx.txid, v), utxo);
+            }
+        }
+        Ok(Some(spent_by_tx))
+    }
+}
@@ -0,0 +1,383 @@
+//! UTXO set snapshots: export the set at a main-chain height and bootstrap a
+//! fresh node from it instead of replaying every block
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+use bincode::{Decode, Encode};
+use primitive_types::U256;
+use sha2::{Digest, Sha256};
+
+use super::{ADDRESS_INDEX_KEY, BINCODE_CONFIG, BlockStatus, Blockchain, PRUNE_HEIGHT_KEY, UtxoSetStats};
+use crate::block::{BlockHeader, compute_header_hash};
+use crate::checkpoint::SnapshotAnchor;
+use crate::db::{ChainBatch, put_batch};
+use crate::utxo::Utxo;
+
+/// Snapshot file format version
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Domain tag hashed into every snapshot content hash
+const SNAPSHOT_DOMAIN: &str = "ASTRAM-UTXO-SNAPSHOT-V1";
+
+/// Default-family record of the snapshot this node was bootstrapped from
+pub(super) const SNAPSHOT_KEY: &[u8] = b"snapshot";
+
+/// Set once the header chain below an imported snapshot has been validated
+/// (its block bodies are never downloaded, so the UTXO set stays trusted)
+pub(super) const SNAPSHOT_HEADERS_VALIDATED_KEY: &[u8] = b"snapshot_headers_validated";
+
+/// Snapshot file header. The file continues with the headers of blocks
+/// `0..=height` and then `utxo_count` coins, each bincode-encoded.
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotInfo {
+    pub version: u32,
+    pub chain_id: u64,
+    pub height: u64,
+    pub block_hash: String,
+    pub utxo_count: u64,
+    pub content_hash: String, // hex SHA256(domain, height, block_hash, coins)
+}
+
+/// Unspent output plus what validation needs to know about its creation
+#[derive(Encode, Decode, Debug, Clone)]
+struct SnapshotCoin {
+    utxo: Utxo,
+    height: u64, // main-chain height of the creating block
+    coinbase: bool,
+}
+
+/// Content hash over the coins in `u:` key order
+struct SnapshotHasher(Sha256);
+
+impl SnapshotHasher {
+    fn new(height: u64, block_hash: &str) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(SNAPSHOT_DOMAIN.as_bytes());
+        hasher.update(height.to_be_bytes());
+        hasher.update(block_hash.as_bytes());
+        SnapshotHasher(hasher)
+    }
+
+    fn add(&mut self, coin: &SnapshotCoin) -> Result<()> {
+        self.0.update(bincode::encode_to_vec(coin, *BINCODE_CONFIG)?);
+        Ok(())
+    }
+
+    fn finish(self) -> String {
+        hex::encode(self.0.finalize())
+    }
+}
+
+/// Record read from a snapshot file
+enum SnapshotItem<'a> {
+    Header(&'a BlockHeader, &'a str), // header and its hash
+    Coin(&'a SnapshotCoin),
+}
+
+/// Stream a snapshot file: checks the header chain links up to the snapshot
+/// block and that the coins hash to the recorded content hash
+fn read_snapshot(path: &Path, mut on_item: impl FnMut(SnapshotItem) -> Result<()>) -> Result<SnapshotInfo> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let info: SnapshotInfo = bincode::decode_from_std_read(&mut reader, *BINCODE_CONFIG)?;
+    if info.version != SNAPSHOT_VERSION {
+        return Err(anyhow!("unsupported snapshot version {}", info.version));
+    }
+
+    let mut prev_hash: Option<String> = None;
+    for height in 0..=info.height {
+        let header: BlockHeader = bincode::decode_from_std_read(&mut reader, *BINCODE_CONFIG)?;
+        let hash = compute_header_hash(&header)?;
+        let linked = prev_hash.as_ref().is_none_or(|prev| prev == &header.previous_hash);
+        if header.index != height || !linked {
+            return Err(anyhow!("snapshot header chain broken at #{}", height));
+        }
+        on_item(SnapshotItem::Header(&header, &hash))?;
+        prev_hash = Some(hash);
+    }
+    if prev_hash.as_deref() != Some(info.block_hash.as_str()) {
+        return Err(anyhow!("snapshot headers do not end at block {}", info.block_hash));
+    }
+
+    let mut hasher = SnapshotHasher::new(info.height, &info.block_hash);
+    for _ in 0..info.utxo_count {
+        let coin: SnapshotCoin = bincode::decode_from_std_read(&mut reader, *BINCODE_CONFIG)?;
+        hasher.add(&coin)?;
+        on_item(SnapshotItem::Coin(&coin))?;
+    }
+    let content_hash = hasher.finish();
+    if content_hash != info.content_hash {
+        return Err(anyhow!(
+            "snapshot content hash {} does not match recorded {}",
+            content_hash,
+            info.content_hash
+        ));
+    }
+    Ok(info)
+}
+
+impl Blockchain {
+    pub(super) fn main_chain_hash(&self, height: u64) -> Result<String> {
+        self.db
+            .get(format!("i:{}", height).as_bytes())?
+            .and_then(|v| String::from_utf8(v).ok())
+            .ok_or_else(|| anyhow!("missing block index {}", height))
+    }
+
+    /// Write the UTXO set as of main-chain block `height` to `path`. Blocks
+    /// above `height` are rolled back in memory from their undo records.
+    pub fn export_utxo_snapshot(&self, height: u64, path: &Path) -> Result<SnapshotInfo> {
+        let tip_height = self
+            .get_next_index()?
+            .checked_sub(1)
+            .ok_or_else(|| anyhow!("chain is empty"))?;
+        if height > tip_height {
+            return Err(anyhow!("snapshot height {} is above the tip #{}", height, tip_height));
+        }
+        let block_hash = self.main_chain_hash(height)?;
+
+        // Keyed like `u:` so the coins come out in database order
+        let mut set: BTreeMap<String, Utxo> = BTreeMap::new();
+        for item in self.db.prefix_iter(b"u:") {
+            let (key, value) = item?;
+            let (utxo, _): (Utxo, usize) = bincode::decode_from_slice(&value, *BINCODE_CONFIG)?;
+            set.insert(String::from_utf8(key.to_vec())?, utxo);
+        }
+        for h in (height + 1..=tip_height).rev() {
+            let hash = self.main_chain_hash(h)?;
+            let block = self
+                .load_block(&hash)?
+                .ok_or_else(|| anyhow!("block #{} is pruned, cannot roll back to #{}", h, height))?;
+            let undo = self
+                .load_undo(&hash)?
+                .ok_or_else(|| anyhow!("no undo record for block #{}", h))?;
+            for tx in &block.transactions {
+                for v in 0..tx.outputs.len() {
+                    set.remove(&format!("u:{}:{}", tx.txid, v));
+                }
+            }
+            for utxo in undo {
+                set.insert(format!("u:{}:{}", utxo.txid, utxo.vout), utxo);
+            }
+        }
+
+        let mut hasher = SnapshotHasher::new(height, &block_hash);
+        let mut coins = Vec::with_capacity(set.len());
+        for utxo in set.into_values() {
+            let created = self
+                .tx_height(&utxo.txid)?
+                .ok_or_else(|| anyhow!("no height indexed for tx {}", utxo.txid))?;
+            let coin = SnapshotCoin {
+                coinbase: self.coinbase_height(&utxo.txid)?.is_some(),
+                height: created,
+                utxo,
+            };
+            hasher.add(&coin)?;
+            coins.push(coin);
+        }
+
+        let info = SnapshotInfo {
+            version: SNAPSHOT_VERSION,
+            chain_id: self.params.chain_id,
+            height,
+            block_hash,
+            utxo_count: coins.len() as u64,
+            content_hash: hasher.finish(),
+        };
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        bincode::encode_into_std_write(&info, &mut writer, *BINCODE_CONFIG)?;
+        for h in 0..=height {
+            let hash = self.main_chain_hash(h)?;
+            let header = self
+                .load_header(&hash)?
+                .ok_or_else(|| anyhow!("missing header for block #{}", h))?;
+            bincode::encode_into_std_write(&header, &mut writer, *BINCODE_CONFIG)?;
+        }
+        for coin in &coins {
+            bincode::encode_into_std_write(coin, &mut writer, *BINCODE_CONFIG)?;
+        }
+        writer.flush()?;
+
+        log::info!(
+            "Exported UTXO snapshot at #{} ({} coins, hash {})",
+            info.height,
+            info.utxo_count,
+            info.content_hash
+        );
+        Ok(info)
+    }
+
+    /// Bootstrap an empty database from the snapshot at `path`. Its height,
+    /// block hash and content hash must match one of `anchors`. Bodies below
+    /// the snapshot are never stored, so the node starts pruned at that height.
+    pub fn import_utxo_snapshot(&mut self, path: &Path, anchors: &[SnapshotAnchor]) -> Result<SnapshotInfo> {
+        if self.chain_tip.is_some() {
+            return Err(anyhow!("snapshot import needs an empty database"));
+        }
+
+        // First pass: check the whole file before writing anything
+        let info = read_snapshot(path, |_| Ok(()))?;
+        if info.chain_id != self.params.chain_id {
+            return Err(anyhow!(
+                "snapshot is for chain id {}, this node runs {}",
+                info.chain_id,
+                self.params.chain_id
+            ));
+        }
+        let anchored = anchors.iter().any(|a| {
+            a.height == info.height && a.block_hash == info.block_hash && a.content_hash == info.content_hash
+        });
+        if !anchored {
+            return Err(anyhow!(
+                "snapshot at #{} (block {}, hash {}) matches no hardcoded snapshot",
+                info.height,
+                info.block_hash,
+                info.content_hash
+            ));
+        }
+
+        log::info!("Importing UTXO snapshot at #{} ({} coins)...", info.height, info.utxo_count);
+        let mut batch = ChainBatch::default();
+        let mut work = U256::zero();
+        let mut difficulty = self.difficulty;
+        let mut stats = UtxoSetStats::default();
+        let db = &self.db;
+        read_snapshot(path, |item| {
+            match item {
+                SnapshotItem::Header(header, hash) => {
+                    let blob = bincode::encode_to_vec(header, *BINCODE_CONFIG)?;
+                    batch.put(format!("h:{}", hash).as_bytes(), &blob);
+                    batch.put(format!("i:{}", header.index).as_bytes(), hash.as_bytes());
+                    work = work.saturating_add(Self::block_work(header.difficulty));
+                    Self::put_chain_work(&mut batch, hash, work);
+                    // The anchored UTXO set stands in for connecting these blocks
+                    Self::put_block_status(&mut batch, hash, BlockStatus::DataValid)?;
+                    difficulty = header.difficulty;
+                }
+                SnapshotItem::Coin(coin) => {
+                    Self::put_utxo(&mut batch, &coin.utxo)?;
+                    stats.add(&coin.utxo);
+                    // Height-only location, enough for relative locks
+                    batch.put(format!("x:{}", coin.utxo.txid).as_bytes(), coin.height.to_be_bytes());
+                    if coin.coinbase {
+                        Self::put_coinbase_height(&mut batch, &coin.utxo.txid, coin.height);
+                    }
+                }
+            }
+            if batch.len() >= 10_000 {
+                put_batch(db, std::mem::take(&mut batch))?;
+            }
+            Ok(())
+        })?;
+
+        batch.put(PRUNE_HEIGHT_KEY, (info.height + 1).to_be_bytes());
+        batch.put(ADDRESS_INDEX_KEY, b"1");
+        Self::put_utxo_stats(&mut batch, &stats)?;
+        batch.put(SNAPSHOT_KEY, bincode::encode_to_vec(&info, *BINCODE_CONFIG)?);
+        batch.put(format!("e:{}", info.block_hash).as_bytes(), b"");
+        batch.put(b"tip", info.block_hash.as_bytes());
+        put_batch(&self.db, batch)?;
+
+        self.chain_tip = Some(info.block_hash.clone());
+        self.difficulty = difficulty;
+        log::info!("Snapshot imported; syncing from #{}", info.height + 1);
+        Ok(info)
+    }
+
+    /// Snapshot this node was bootstrapped from, if any
+    pub fn snapshot_info(&self) -> Result<Option<SnapshotInfo>> {
+        match self.db.get(SNAPSHOT_KEY)? {
+            Some(blob) => {
+                let (info, _): (SnapshotInfo, usize) = bincode::decode_from_slice(&blob, *BINCODE_CONFIG)?;
+                Ok(Some(info))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Whether the header chain below an imported snapshot still awaits validation
+    pub fn snapshot_headers_pending(&self) -> Result<bool> {
+        Ok(self.db.get(SNAPSHOT_KEY)?.is_some() && self.db.get(SNAPSHOT_HEADERS_VALIDATED_KEY)?.is_none())
+    }
+
+    /// Validate main-chain headers `from..to` of an imported snapshot: hash,
+    /// PoW, DWG3 difficulty, parent link and checkpoints. Run in chunks in the
+    /// background while the node syncs from the snapshot tip.
+    pub fn validate_snapshot_headers(&self, from: u64, to: u64) -> Result<()> {
+        for height in from..to {
+            let hash = self.main_chain_hash(height)?;
+            let header = self
+                .load_header(&hash)?
+                .ok_or_else(|| anyhow!("missing header for block #{}", height))?;
+            if compute_header_hash(&header)? != hash {
+                return Err(anyhow!("header hash mismatch at #{}", height));
+            }
+            if !Self::is_valid_pow(&self.params, &header)? {
+                return Err(anyhow!("invalid PoW at #{}", height));
+            }
+            if height >= self.params.retarget_window && self.calculate_adjusted_difficulty(height)? != header.difficulty {
+                return Err(anyhow!("difficulty mismatch at #{}", height));
+            }
+            if height > 0 && header.previous_hash != self.main_chain_hash(height - 1)? {
+                return Err(anyhow!("broken parent link at #{}", height));
+            }
+            if !self.checkpoint_allows(height, &hash) {
+                return Err(anyhow!("checkpoint mismatch at #{}", height));
+            }
+        }
+        Ok(())
+    }
+
+    /// Record that the header chain below the snapshot passed validation
+    pub fn mark_snapshot_headers_validated(&self) -> Result<()> {
+        self.db.put(SNAPSHOT_HEADERS_VALIDATED_KEY, b"1")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::testutil::temp_chain;
+    use crate::network::ChainParams;
+
+    #[test]
+    fn snapshot_round_trip_requires_anchor() {
+        let (source_dir, mut source) = temp_chain("snapshot-src", ChainParams::mainnet());
+        let genesis = source.create_genesis("0x00000000000000000000000000000000000000aa").unwrap();
+        let file = source_dir.join("utxo.snap");
+        let info = source.export_utxo_snapshot(0, &file).unwrap();
+        assert_eq!(info.block_hash, genesis);
+        assert_eq!(info.utxo_count, 1);
+
+        let (target_dir, mut target) = temp_chain("snapshot-dst", ChainParams::mainnet());
+        assert!(target.import_utxo_snapshot(&file, &[]).is_err());
+        assert!(target.chain_tip.is_none());
+
+        let anchor = SnapshotAnchor {
+            height: info.height,
+            block_hash: info.block_hash.clone(),
+            content_hash: info.content_hash.clone(),
+        };
+        target.import_utxo_snapshot(&file, &[anchor]).unwrap();
+        assert_eq!(target.chain_tip.as_deref(), Some(genesis.as_str()));
+        assert_eq!(target.prune_height().unwrap(), 1);
+        assert!(target.load_block(&genesis).unwrap().is_none());
+        assert_eq!(target.load_header(&genesis).unwrap().unwrap().index, 0);
+        let cb = &source.load_block(&genesis).unwrap().unwrap().transactions[0];
+        assert!(target.get_utxo(&cb.txid, 0).unwrap().is_some());
+        assert_eq!(target.coinbase_height(&cb.txid).unwrap(), Some(0));
+        assert!(target.snapshot_headers_pending().unwrap());
+        let stats = target.utxo_set_stats().unwrap();
+        assert_eq!(stats.count, 1);
+        assert_eq!(stats.digest(), source.utxo_set_stats().unwrap().digest());
+
+        // Exporting the imported set reproduces the same snapshot
+        let again = target.export_utxo_snapshot(0, &target_dir.join("utxo.snap")).unwrap();
+        assert_eq!(again, info);
+    }
+}
@@ -0,0 +1,54 @@
+//! Scratch databases for tests
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::Blockchain;
+use crate::network::ChainParams;
+
+static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+
+/// Directory under the system temp dir, removed with everything in it on
+/// drop (also when a failed assert unwinds the test)
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Empty directory named after `name`, unique per process and call
+    pub fn new(name: &str) -> Self {
+        let n = NEXT_DIR.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("astram-{}-{}-{}", name, std::process::id(), n));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).expect("create temp dir");
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path of `name` inside the directory
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+
+    /// Open a chain database in the subdirectory `name`
+    pub fn open_chain(&self, name: &str, params: ChainParams) -> Blockchain {
+        Blockchain::new(self.join(name).to_str().unwrap(), params).expect("open temp chain")
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Fresh chain database in its own temp dir. The guard comes first so that
+/// `let (_dir, bc) = ...` closes the database before removing the directory.
+pub fn temp_chain(name: &str, params: ChainParams) -> (TempDir, Blockchain) {
+    let dir = TempDir::new(name);
+    let bc = dir.open_chain("chain", params);
+    (dir, bc)
+}
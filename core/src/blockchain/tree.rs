@@ -0,0 +1,303 @@
+//! Block tree: every known block keeps its header (`h:`, parent pointer and
+//! height), cumulative work (`w:`) and validation status (`s:`), and the
+//! tips of all branches are marked with `e:`. Side branches survive
+//! reorganizations, so chain selection runs over all of them.
+
+use anyhow::{Result, anyhow};
+use bincode::{Decode, Encode};
+use primitive_types::U256;
+
+use super::{BINCODE_CONFIG, Blockchain};
+use crate::block::{BlockHeader, compute_header_hash};
+use crate::db::ChainBatch;
+
+/// Validation status of a known block (`s:` index)
+#[derive(Encode, Decode, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockStatus {
+    HeaderValid, // header and body checks passed, transactions never connected
+    DataValid,   // connected to the UTXO set at least once
+    Invalid,     // failed to connect; branches through it are never selected
+}
+
+/// How a branch tip relates to the active chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainTipStatus {
+    Active,       // tip of the main chain
+    ValidFork,    // every block off the main chain has been connected before
+    ValidHeaders, // some blocks off the main chain were never connected
+    Invalid,      // the branch contains an invalid block
+}
+
+impl ChainTipStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::ValidFork => "valid-fork",
+            Self::ValidHeaders => "valid-headers",
+            Self::Invalid => "invalid",
+        }
+    }
+}
+
+/// Tip of a branch in the block tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainTip {
+    pub hash: String,
+    pub height: u64,
+    pub chain_work: U256,
+    pub branch_len: u64, // blocks between the tip and the main chain
+    pub status: ChainTipStatus,
+}
+
+/// Blocks of a branch that are not on the main chain, plus its fork point
+pub(super) struct Branch {
+    pub fork_point: String,
+    pub fork_height: u64,
+    pub blocks: Vec<String>, // fork point's child first
+    pub status: ChainTipStatus,
+}
+
+impl Blockchain {
+    pub(super) fn put_block_status(batch: &mut ChainBatch, hash: &str, status: BlockStatus) -> Result<()> {
+        batch.put(format!("s:{}", hash).as_bytes(), bincode::encode_to_vec(status, *BINCODE_CONFIG)?);
+        Ok(())
+    }
+
+    /// Validation status of a known block (None if the block is unknown)
+    pub fn block_status(&self, hash: &str) -> Result<Option<BlockStatus>> {
+        match self.db.get(format!("s:{}", hash).as_bytes())? {
+            Some(blob) => {
+                let (status, _): (BlockStatus, usize) = bincode::decode_from_slice(&blob, *BINCODE_CONFIG)?;
+                Ok(Some(status))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Queue the tree entry of a block about to be stored: its status, its
+    /// cumulative work on top of its (stored) parent's, and the tip marker
+    /// moving from its parent to it (a block already known may have
+    /// children, so its marker is left as is)
+    pub(super) fn put_tree_entry(&self, batch: &mut ChainBatch, header: &BlockHeader, hash: &str, status: BlockStatus) -> Result<()> {
+        if self.db.get(format!("h:{}", hash).as_bytes())?.is_none() {
+            batch.put(format!("e:{}", hash).as_bytes(), b"");
+        }
+        let parent_work = if header.index > 0 {
+            batch.delete(format!("e:{}", header.previous_hash).as_bytes());
+            self.calculate_chain_work(&header.previous_hash)?
+        } else {
+            U256::zero()
+        };
+        Self::put_chain_work(batch, hash, parent_work.saturating_add(Self::block_work(header.difficulty)));
+        Self::put_block_status(batch, hash, status)
+    }
+
+    /// Record that `hash` failed to connect
+    pub(super) fn mark_block_invalid(&self, hash: &str) -> Result<()> {
+        let mut batch = ChainBatch::default();
+        Self::put_block_status(&mut batch, hash, BlockStatus::Invalid)?;
+        self.db.write(batch)?;
+        Ok(())
+    }
+
+    /// Drop the stored body of `hash` after a `security::BodyFailure`: the
+    /// header stays in the tree, and another copy of the block may be
+    /// downloaded and connected
+    pub(super) fn drop_block_body(&self, hash: &str) -> Result<()> {
+        self.db.delete(format!("b:{}", hash).as_bytes())?;
+        Ok(())
+    }
+
+    pub(super) fn has_block_body(&self, hash: &str) -> Result<bool> {
+        Ok(self.db.get(format!("b:{}", hash).as_bytes())?.is_some())
+    }
+
+    /// Whether `hash` needs no download: it is in the tree, not invalid, and
+    /// either on the main chain (where its body may be pruned) or stored
+    /// with its body for chain selection to connect
+    pub fn has_usable_block(&self, hash: &str) -> Result<bool> {
+        if matches!(self.block_status(hash)?, None | Some(BlockStatus::Invalid)) {
+            return Ok(false);
+        }
+        if self.has_block_body(hash)? {
+            return Ok(true);
+        }
+        match self.load_header(hash)? {
+            Some(header) => self.on_main_chain(hash, header.index),
+            None => Ok(false),
+        }
+    }
+
+    /// Chain work at the last of `headers`, a chain received from a peer
+    /// whose PoW was checked: the stored work of the first header's parent
+    /// plus the work of each header. None if the headers do not link up or
+    /// the parent is unknown.
+    pub fn headers_chain_work(&self, headers: &[BlockHeader]) -> Result<Option<U256>> {
+        let Some(first) = headers.first() else {
+            return Ok(None);
+        };
+        let mut work = if first.index == 0 {
+            U256::zero()
+        } else {
+            match self.stored_chain_work(&first.previous_hash)? {
+                Some(work) => work,
+                None => return Ok(None),
+            }
+        };
+        for (i, header) in headers.iter().enumerate() {
+            if i > 0 && header.previous_hash != compute_header_hash(&headers[i - 1])? {
+                return Ok(None);
+            }
+            work = work.saturating_add(Self::block_work(header.difficulty));
+        }
+        Ok(Some(work))
+    }
+
+    /// Hashes of every branch tip (`e:`)
+    pub(super) fn branch_tips(&self) -> Result<Vec<String>> {
+        let mut tips = Vec::new();
+        for item in self.db.prefix_iter(b"e:") {
+            let (key, _) = item?;
+            tips.push(String::from_utf8(key[2..].to_vec())?);
+        }
+        Ok(tips)
+    }
+
+    pub(super) fn on_main_chain(&self, hash: &str, height: u64) -> Result<bool> {
+        Ok(self
+            .db
+            .get(format!("i:{}", height).as_bytes())?
+            .is_some_and(|v| v == hash.as_bytes()))
+    }
+
+    /// Walk from `tip` back to the main chain. None if the branch does not
+    /// join it (another genesis or a missing header).
+    pub(super) fn branch_from(&self, tip: &str) -> Result<Option<Branch>> {
+        let mut blocks = Vec::new();
+        let mut status = ChainTipStatus::ValidFork;
+        let mut hash = tip.to_string();
+        loop {
+            let header = match self.load_header(&hash)? {
+                Some(h) => h,
+                None => return Ok(None),
+            };
+            if self.on_main_chain(&hash, header.index)? {
+                if blocks.is_empty() {
+                    status = ChainTipStatus::Active;
+                }
+                blocks.reverse();
+                return Ok(Some(Branch {
+                    fork_point: hash,
+                    fork_height: header.index,
+                    blocks,
+                    status,
+                }));
+            }
+            match self.block_status(&hash)? {
+                Some(BlockStatus::Invalid) => status = ChainTipStatus::Invalid,
+                Some(BlockStatus::DataValid) => {}
+                Some(BlockStatus::HeaderValid) | None => {
+                    if status != ChainTipStatus::Invalid {
+                        status = ChainTipStatus::ValidHeaders;
+                    }
+                }
+            }
+            if header.index == 0 {
+                return Ok(None);
+            }
+            blocks.push(hash);
+            hash = header.previous_hash;
+        }
+    }
+
+    /// Every branch of the block tree, most work first
+    pub fn chain_tips(&self) -> Result<Vec<ChainTip>> {
+        let mut hashes = self.branch_tips()?;
+        // The active tip has no marker while it has (invalid) children
+        if let Some(tip) = self.chain_tip.as_ref().filter(|tip| !hashes.contains(tip)) {
+            hashes.push(tip.clone());
+        }
+
+        let mut tips = Vec::new();
+        for hash in hashes {
+            let header = self
+                .load_header(&hash)?
+                .ok_or_else(|| anyhow!("missing header for branch tip {}", hash))?;
+            let branch = match self.branch_from(&hash)? {
+                Some(branch) => branch,
+                None => continue,
+            };
+            // A main-chain block is only a tip when it is the active one
+            if branch.status == ChainTipStatus::Active && self.chain_tip.as_deref() != Some(hash.as_str()) {
+                continue;
+            }
+            let (branch_len, status) = (branch.blocks.len() as u64, branch.status);
+            tips.push(ChainTip {
+                chain_work: self.calculate_chain_work(&hash)?,
+                hash,
+                height: header.index,
+                branch_len,
+                status,
+            });
+        }
+        tips.sort_by_key(|tip| std::cmp::Reverse(tip.chain_work));
+        Ok(tips)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::testutil::temp_chain;
+    use crate::network::ChainParams;
+
+    #[test]
+    fn side_branch_is_listed_as_a_tip() {
+        let (_dir, mut bc) = temp_chain("tree", ChainParams::mainnet());
+        let genesis = bc.create_genesis("0x00000000000000000000000000000000000000aa").unwrap();
+        assert_eq!(bc.block_status(&genesis).unwrap(), Some(BlockStatus::DataValid));
+
+        // A header-only child of genesis that never became the main chain
+        let side = BlockHeader {
+            index: 1,
+            previous_hash: genesis.clone(),
+            merkle_root: "0".repeat(64),
+            timestamp: 0,
+            nonce: 0,
+            difficulty: bc.params.pow_limit_bits,
+        };
+        let side_hash = "ab".repeat(32);
+        let mut batch = ChainBatch::default();
+        batch.put(
+            format!("h:{}", side_hash).as_bytes(),
+            bincode::encode_to_vec(&side, *BINCODE_CONFIG).unwrap(),
+        );
+        bc.put_tree_entry(&mut batch, &side, &side_hash, BlockStatus::HeaderValid)
+            .unwrap();
+        bc.db.write(batch).unwrap();
+
+        // The active tip lost its marker to the child but is still listed
+        let tips = bc.chain_tips().unwrap();
+        assert_eq!(tips.len(), 2);
+        let active = tips.iter().find(|t| t.hash == genesis).unwrap();
+        assert_eq!((active.status, active.branch_len), (ChainTipStatus::Active, 0));
+        let fork = tips.iter().find(|t| t.hash == side_hash).unwrap();
+        assert_eq!((fork.status, fork.branch_len, fork.height), (ChainTipStatus::ValidHeaders, 1, 1));
+
+        // Without a body the block still has to be downloaded
+        assert!(!bc.has_usable_block(&side_hash).unwrap());
+        bc.db.put(format!("b:{}", side_hash).as_bytes(), b"body").unwrap();
+        assert!(bc.has_usable_block(&side_hash).unwrap());
+        bc.drop_block_body(&side_hash).unwrap();
+        assert!(!bc.has_usable_block(&side_hash).unwrap());
+        assert_eq!(bc.block_status(&side_hash).unwrap(), Some(BlockStatus::HeaderValid));
+        assert!(bc.has_usable_block(&genesis).unwrap());
+
+        bc.db.put(format!("b:{}", side_hash).as_bytes(), b"body").unwrap();
+        bc.mark_block_invalid(&side_hash).unwrap();
+        assert!(!bc.has_usable_block(&side_hash).unwrap());
+        let fork = bc.branch_from(&side_hash).unwrap().unwrap();
+        assert_eq!(fork.fork_point, genesis);
+        assert_eq!(fork.status, ChainTipStatus::Invalid);
+    }
+}
@@ -0,0 +1,215 @@
+//! UTXO set commitment: a MuHash of every unspent output plus their count and
+//! total amount, updated in the same batch as each block connect and disconnect
+
+use std::collections::HashSet;
+
+use anyhow::{Result, anyhow};
+use bincode::{Decode, Encode};
+use primitive_types::U256;
+
+use super::{BINCODE_CONFIG, Blockchain};
+use crate::block::Block;
+use crate::crypto::muhash::MuHash3072;
+use crate::db::{ChainBatch, put_batch};
+use crate::utxo::Utxo;
+
+/// Default-family record of the current `UtxoSetStats`
+pub(super) const UTXO_STATS_KEY: &[u8] = b"utxostats";
+
+/// Aggregate state of the UTXO set. Two nodes at the same tip agree on
+/// `digest()` exactly when their sets hold the same outputs.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct UtxoSetStats {
+    pub muhash: MuHash3072,
+    pub count: u64,
+    pub total_amount: U256,
+}
+
+/// On-disk form of `UtxoSetStats`
+#[derive(Encode, Decode)]
+struct StoredStats {
+    muhash: Vec<u8>,
+    count: u64,
+    total_amount: [u64; 4],
+}
+
+impl UtxoSetStats {
+    /// Set element of an output: bincode of (txid, vout, lowercased address, amount)
+    fn element(utxo: &Utxo) -> Vec<u8> {
+        bincode::encode_to_vec(
+            (utxo.txid.as_str(), utxo.vout, utxo.to.to_lowercase(), utxo.amount().0),
+            *BINCODE_CONFIG,
+        )
+        .unwrap_or_default()
+    }
+
+    pub fn add(&mut self, utxo: &Utxo) {
+        self.muhash.insert(&Self::element(utxo));
+        self.count += 1;
+        self.total_amount = self.total_amount.saturating_add(utxo.amount());
+    }
+
+    pub fn remove(&mut self, utxo: &Utxo) {
+        self.muhash.remove(&Self::element(utxo));
+        self.count = self.count.saturating_sub(1);
+        self.total_amount = self.total_amount.saturating_sub(utxo.amount());
+    }
+
+    /// Connect a block: drop the outputs in its undo record, add those it leaves unspent
+    pub(super) fn connect(&mut self, block: &Block, undo: &[Utxo]) {
+        for utxo in undo {
+            self.remove(utxo);
+        }
+        for utxo in unspent_outputs(block) {
+            self.add(&utxo);
+        }
+    }
+
+    /// Inverse of `connect`
+    pub(super) fn disconnect(&mut self, block: &Block, undo: &[Utxo]) {
+        for utxo in unspent_outputs(block) {
+            self.remove(&utxo);
+        }
+        for utxo in undo {
+            self.add(utxo);
+        }
+    }
+
+    /// Hex SHA256 of the MuHash value
+    pub fn digest(&self) -> String {
+        hex::encode(self.muhash.finalize())
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        let stored = StoredStats {
+            muhash: self.muhash.to_bytes(),
+            count: self.count,
+            total_amount: self.total_amount.0,
+        };
+        Ok(bincode::encode_to_vec(&stored, *BINCODE_CONFIG)?)
+    }
+
+    fn decode(blob: &[u8]) -> Result<Self> {
+        let (stored, _): (StoredStats, usize) = bincode::decode_from_slice(blob, *BINCODE_CONFIG)?;
+        Ok(UtxoSetStats {
+            muhash: MuHash3072::from_bytes(&stored.muhash)
+                .ok_or_else(|| anyhow!("corrupt UTXO set hash record"))?,
+            count: stored.count,
+            total_amount: U256(stored.total_amount),
+        })
+    }
+}
+
+/// Outputs a block creates and does not spend itself, as stored in `u:`
+fn unspent_outputs(block: &Block) -> Vec<Utxo> {
+    let spent: HashSet<(&str, u32)> = block
+        .transactions
+        .iter()
+        .flat_map(|tx| tx.inputs.iter().map(|inp| (inp.txid.as_str(), inp.vout)))
+        .collect();
+    let mut outputs = Vec::new();
+    for tx in &block.transactions {
+        for (v, out) in tx.outputs.iter().enumerate() {
+            if !spent.contains(&(tx.txid.as_str(), v as u32)) {
+                outputs.push(Utxo::new(tx.txid.clone(), v as u32, out.to.to_lowercase(), out.amount()));
+            }
+        }
+    }
+    outputs
+}
+
+impl Blockchain {
+    /// Current UTXO set statistics, kept up to date with the tip
+    pub fn utxo_set_stats(&self) -> Result<UtxoSetStats> {
+        match self.db.get(UTXO_STATS_KEY)? {
+            Some(blob) => UtxoSetStats::decode(&blob),
+            None => Err(anyhow!("UTXO set statistics have not been computed")),
+        }
+    }
+
+    pub(super) fn put_utxo_stats(batch: &mut ChainBatch, stats: &UtxoSetStats) -> Result<()> {
+        batch.put(UTXO_STATS_KEY, stats.encode()?);
+        Ok(())
+    }
+
+    /// Recompute the statistics from a full scan of `u:` (audits and backfill)
+    pub fn compute_utxo_set_stats(&self) -> Result<UtxoSetStats> {
+        let mut stats = UtxoSetStats::default();
+        for item in self.db.prefix_iter(b"u:") {
+            let (_, value) = item?;
+            let (utxo, _): (Utxo, usize) = bincode::decode_from_slice(&value, *BINCODE_CONFIG)?;
+            stats.add(&utxo);
+        }
+        Ok(stats)
+    }
+
+    /// Compute the statistics for databases created before they were tracked
+    pub(super) fn backfill_utxo_stats(&self) -> Result<()> {
+        if self.db.get(UTXO_STATS_KEY)?.is_some() {
+            return Ok(());
+        }
+        log::info!("Computing UTXO set statistics...");
+        let stats = self.compute_utxo_set_stats()?;
+        let mut batch = ChainBatch::default();
+        Self::put_utxo_stats(&mut batch, &stats)?;
+        put_batch(&self.db, batch)?;
+        log::info!("UTXO set: {} outputs, {} ram, hash {}", stats.count, stats.total_amount, stats.digest());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockHeader;
+    use crate::config::{HALVING_INTERVAL, initial_block_reward};
+    use crate::network::ChainParams;
+    use crate::transaction::Transaction;
+
+    #[test]
+    fn connect_and_disconnect_cancel_out() {
+        let before = Utxo::new("aa".repeat(32), 0, "0xAB".to_string(), U256::from(70));
+        let mut stats = UtxoSetStats::default();
+        stats.add(&before);
+        let start = stats.clone();
+
+        let block = Block {
+            header: BlockHeader {
+                index: 1,
+                previous_hash: "0".repeat(64),
+                merkle_root: String::new(),
+                timestamp: 0,
+                nonce: 0,
+                difficulty: 0,
+            },
+            transactions: vec![Transaction::coinbase("0xcd", U256::from(50))],
+            hash: "h1".to_string(),
+        };
+        stats.connect(&block, std::slice::from_ref(&before));
+        assert_eq!(stats.count, 1);
+        assert_eq!(stats.total_amount, U256::from(50));
+        assert_ne!(stats.digest(), start.digest());
+
+        stats.disconnect(&block, std::slice::from_ref(&before));
+        assert_eq!(stats.count, start.count);
+        assert_eq!(stats.total_amount, start.total_amount);
+        assert_eq!(stats.digest(), start.digest());
+
+        let decoded = UtxoSetStats::decode(&stats.encode().unwrap()).unwrap();
+        assert_eq!(decoded.digest(), stats.digest());
+    }
+
+    #[test]
+    fn scheduled_supply_follows_halvings() {
+        let params = ChainParams::mainnet();
+        let scheduled_supply = |height| params.scheduled_supply(height);
+        let reward = initial_block_reward();
+        assert_eq!(scheduled_supply(0), reward);
+        assert_eq!(scheduled_supply(HALVING_INTERVAL - 1), reward * U256::from(HALVING_INTERVAL));
+        assert_eq!(
+            scheduled_supply(HALVING_INTERVAL),
+            reward * U256::from(HALVING_INTERVAL) + (reward >> 1)
+        );
+        assert!(scheduled_supply(u64::MAX / 2) <= crate::config::max_supply());
+    }
+}
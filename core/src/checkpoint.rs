@@ -1,3 +1,8 @@
+use anyhow::{Result, anyhow};
+
+use crate::crypto::{WalletKeypair, verify_signature};
+use crate::network::ChainParams;
+
 /// Checkpoint Policy System (Not Consensus Rules)
 ///
 /// Checkpoints are POLICY-LEVEL protections, NOT consensus rules.
@@ -23,7 +28,7 @@ pub struct Checkpoint {
     pub description: String, // Human-readable description of checkpoint significance
 }
 
-/// Get hardcoded checkpoints for Astram
+/// Hardcoded mainnet checkpoints (`ChainParams::mainnet`)
 ///
 /// These are POLICY anchors, not consensus rules.
 /// Nodes MAY use these for:
@@ -35,7 +40,7 @@ pub struct Checkpoint {
 /// - Genesis (height 0)
 /// - Major network milestones (every 10,000 blocks)
 /// - After significant events (hard forks, major upgrades)
-pub fn get_checkpoints() -> Vec<Checkpoint> {
+pub fn mainnet_checkpoints() -> Vec<Checkpoint> {
     vec![
         // Genesis block - immutable network origin
         Checkpoint {
@@ -58,6 +63,43 @@ pub fn get_checkpoints() -> Vec<Checkpoint> {
     ]
 }
 
+/// Hardcoded mainnet release keys (`ChainParams::mainnet`): hex Ed25519
+/// public keys whose signatures make a checkpoint file trusted. Empty until
+/// release keys are published; nodes may configure their own with the
+/// `CHECKPOINT_KEYS` node setting.
+pub fn mainnet_checkpoint_keys() -> Vec<String> {
+    vec![
+        // Example:
+        // "3b6a27bcceb6a42d62a3a8d02a6f0d73653215771de243a63ac048a18b59da29".to_string(),
+    ]
+}
+
+/// UTXO snapshot accepted by `--import-snapshot`: the set as of block
+/// `block_hash` at `height` must hash to `content_hash`
+#[derive(Debug, Clone)]
+pub struct SnapshotAnchor {
+    pub height: u64,
+    pub block_hash: String,
+    pub content_hash: String,
+}
+
+/// Hardcoded mainnet UTXO snapshots (`ChainParams::mainnet`)
+///
+/// A snapshot is only added once several independent nodes have exported it
+/// with `--export-snapshot` and agree on its content hash. Importing one
+/// trusts the UTXO set at that height; the header chain below it is still
+/// validated in the background.
+pub fn mainnet_snapshot_anchors() -> Vec<SnapshotAnchor> {
+    vec![
+        // Example:
+        // SnapshotAnchor {
+        //     height: 100000,
+        //     block_hash: "def456...".to_string(),
+        //     content_hash: "0123ab...".to_string(),
+        // },
+    ]
+}
+
 /// Policy check: Validate that a chain doesn't conflict with checkpoints
 ///
 /// This is a POLICY decision, not a consensus rule.
@@ -65,9 +107,7 @@ pub fn get_checkpoints() -> Vec<Checkpoint> {
 /// which means this chain should be rejected by THIS node's policy.
 ///
 /// Other nodes without checkpoints enabled will still see the block as valid.
-pub fn validate_against_checkpoints(height: u64, hash: &str) -> bool {
-    let checkpoints = get_checkpoints();
-
+pub fn validate_against_checkpoints(checkpoints: &[Checkpoint], height: u64, hash: &str) -> bool {
     for cp in checkpoints {
         if cp.height == height {
             if cp.hash.is_empty() {
@@ -99,18 +139,18 @@ pub fn validate_against_checkpoints(height: u64, hash: &str) -> bool {
 
 /// Get the latest checkpoint height
 /// Blocks below this height are considered policy-final by this node
-pub fn get_latest_checkpoint_height() -> u64 {
-    let checkpoints = get_checkpoints();
+pub fn get_latest_checkpoint_height(checkpoints: &[Checkpoint]) -> u64 {
     checkpoints.iter().map(|cp| cp.height).max().unwrap_or(0)
 }
 
 /// Check if reorganization would conflict with checkpoint policy
 /// Returns (allowed, reason)
 pub fn check_reorg_against_checkpoints(
+    checkpoints: &[Checkpoint],
     reorg_depth: u64,
     current_height: u64,
 ) -> (bool, Option<String>) {
-    let latest_checkpoint = get_latest_checkpoint_height();
+    let latest_checkpoint = get_latest_checkpoint_height(checkpoints);
     let reorg_target_height = current_height.saturating_sub(reorg_depth);
 
     // Allow reorg to checkpoint height itself (< instead of <=)
@@ -128,36 +168,214 @@ pub fn check_reorg_against_checkpoints(
     (true, None)
 }
 
+/// Checkpoints published after a release, signed by the network's release
+/// keys (`ChainParams::checkpoint_keys`, Ed25519 like wallet keys). The node
+/// loads them from `checkpoints.txt` in its data dir or from the DNS
+/// server's `/checkpoints`; a higher `SEQUENCE` replaces a lower one.
+///
+/// ```text
+/// NETWORK=Astram-mainnet
+/// SEQUENCE=2
+/// CHECKPOINT=10000:<block hash>
+/// SIGNATURE=<public key hex>:<signature hex>
+/// ```
+#[derive(Debug, Clone)]
+pub struct SignedCheckpoints {
+    pub network_id: String,
+    pub sequence: u64,
+    pub checkpoints: Vec<Checkpoint>,      // ascending height
+    pub signatures: Vec<(String, String)>, // (public key hex, signature hex)
+}
+
+impl SignedCheckpoints {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut network_id = None;
+        let mut sequence = None;
+        let mut checkpoints: Vec<Checkpoint> = Vec::new();
+        let mut signatures = Vec::new();
+        for (line_no, raw) in text.lines().enumerate() {
+            let line = raw.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let bad = |what: &str| anyhow!("line {}: {}", line_no + 1, what);
+            let (key, value) = line.split_once('=').ok_or_else(|| bad("expected KEY=value"))?;
+            let value = value.trim();
+            match key.trim().to_ascii_uppercase().as_str() {
+                "NETWORK" => network_id = Some(value.to_string()),
+                "SEQUENCE" => sequence = Some(value.parse().map_err(|_| bad("invalid SEQUENCE"))?),
+                "CHECKPOINT" => {
+                    let (height, hash) = value.split_once(':').ok_or_else(|| bad("expected <height>:<hash>"))?;
+                    let hash = hash.trim().to_ascii_lowercase();
+                    if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                        return Err(bad("checkpoint hash must be 64 hex characters"));
+                    }
+                    checkpoints.push(Checkpoint {
+                        height: height.trim().parse().map_err(|_| bad("invalid checkpoint height"))?,
+                        hash,
+                        description: String::new(),
+                    });
+                }
+                "SIGNATURE" => {
+                    let (pubkey, signature) = value.split_once(':').ok_or_else(|| bad("expected <key>:<signature>"))?;
+                    signatures.push((pubkey.trim().to_ascii_lowercase(), signature.trim().to_ascii_lowercase()));
+                }
+                other => return Err(bad(&format!("unknown key {}", other))),
+            }
+        }
+
+        let sequence = sequence.ok_or_else(|| anyhow!("missing SEQUENCE"))?;
+        checkpoints.sort_by_key(|cp| cp.height);
+        if checkpoints.windows(2).any(|w| w[0].height == w[1].height) {
+            return Err(anyhow!("duplicate checkpoint height"));
+        }
+        for cp in &mut checkpoints {
+            cp.description = format!("Signed checkpoint set #{}", sequence);
+        }
+        Ok(SignedCheckpoints {
+            network_id: network_id.ok_or_else(|| anyhow!("missing NETWORK"))?,
+            sequence,
+            checkpoints,
+            signatures,
+        })
+    }
+
+    /// Bytes every release key signs: the network, sequence and checkpoints,
+    /// independent of line order, comments and line endings
+    pub fn signing_message(&self) -> Vec<u8> {
+        let mut msg = format!("Astram checkpoints\nnetwork={}\nsequence={}\n", self.network_id, self.sequence);
+        for cp in &self.checkpoints {
+            msg.push_str(&format!("{}:{}\n", cp.height, cp.hash));
+        }
+        msg.into_bytes()
+    }
+
+    /// Add (or replace) the signature of `keypair`
+    pub fn sign(&mut self, keypair: &WalletKeypair) {
+        let pubkey = keypair.public_hex();
+        let signature = hex::encode(keypair.sign(&self.signing_message()));
+        self.signatures.retain(|(key, _)| *key != pubkey);
+        self.signatures.push((pubkey, signature));
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("NETWORK={}\nSEQUENCE={}\n", self.network_id, self.sequence);
+        for cp in &self.checkpoints {
+            text.push_str(&format!("CHECKPOINT={}:{}\n", cp.height, cp.hash));
+        }
+        for (pubkey, signature) in &self.signatures {
+            text.push_str(&format!("SIGNATURE={}:{}\n", pubkey, signature));
+        }
+        text
+    }
+
+    /// Release keys of `params` with a valid signature. Errors unless the set
+    /// is for this network and at least `params.checkpoint_threshold` of
+    /// them signed it.
+    pub fn verify(&self, params: &ChainParams) -> Result<Vec<String>> {
+        if self.network_id != params.network_id {
+            return Err(anyhow!(
+                "checkpoints are for network {}, not {}",
+                self.network_id,
+                params.network_id
+            ));
+        }
+        if params.checkpoint_keys.is_empty() {
+            return Err(anyhow!("no checkpoint release keys are configured"));
+        }
+
+        let required = params.checkpoint_threshold.max(1);
+        let msg = self.signing_message();
+        let mut signers: Vec<String> = Vec::new();
+        for (pubkey, signature) in &self.signatures {
+            let trusted = params.checkpoint_keys.iter().any(|key| key.eq_ignore_ascii_case(pubkey));
+            if !trusted || signers.contains(pubkey) {
+                continue;
+            }
+            if hex::decode(signature).is_ok_and(|sig| verify_signature(pubkey, &msg, &sig)) {
+                signers.push(pubkey.clone());
+            }
+        }
+        if signers.len() < required {
+            return Err(anyhow!(
+                "checkpoint set #{} has {} of {} required release key signatures",
+                self.sequence,
+                signers.len(),
+                required
+            ));
+        }
+        Ok(signers)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_checkpoint_validation() {
+        let checkpoints = mainnet_checkpoints();
+
         // Valid block at non-checkpoint height
-        assert!(validate_against_checkpoints(50, "any_hash"));
+        assert!(validate_against_checkpoints(&checkpoints, 50, "any_hash"));
 
         // Genesis checkpoint must match the official mainnet genesis hash
         let genesis_hash = "0047bb75cef130263090ec45c9e5b464ab0f56c556821cb3a40d59dbf31e7216";
-        assert!(validate_against_checkpoints(0, genesis_hash));
-        assert!(!validate_against_checkpoints(0, "any_hash"));
+        assert!(validate_against_checkpoints(&checkpoints, 0, genesis_hash));
+        assert!(!validate_against_checkpoints(&checkpoints, 0, "any_hash"));
     }
 
     #[test]
     fn test_latest_checkpoint() {
-        let height = get_latest_checkpoint_height();
+        let height = get_latest_checkpoint_height(&mainnet_checkpoints());
         assert_eq!(height, 0); // Currently only genesis
     }
 
     #[test]
     fn test_reorg_checkpoint_policy() {
+        let checkpoints = mainnet_checkpoints();
+
         // Reorg of 10 blocks from height 100 - should be allowed (target: 90)
-        let (allowed, _) = check_reorg_against_checkpoints(10, 100);
+        let (allowed, _) = check_reorg_against_checkpoints(&checkpoints, 10, 100);
         assert!(allowed);
 
         // Reorg to genesis itself is allowed by current policy (<, not <=)
-        let (allowed, reason) = check_reorg_against_checkpoints(150, 100);
+        let (allowed, reason) = check_reorg_against_checkpoints(&checkpoints, 150, 100);
         assert!(allowed);
         assert!(reason.is_none());
     }
+
+    #[test]
+    fn signed_checkpoints_need_release_keys() {
+        let (alice, bob, mallory) = (WalletKeypair::new(), WalletKeypair::new(), WalletKeypair::new());
+        let mut params = ChainParams::regtest();
+        params.checkpoint_keys = vec![alice.public_hex(), bob.public_hex()];
+        params.checkpoint_threshold = 2;
+
+        let text = format!(
+            "# release 1.2\nNETWORK=Astram-regtest\nSEQUENCE=3\nCHECKPOINT=20:{}\nCHECKPOINT=10:{}\n",
+            "bb".repeat(32),
+            "AA".repeat(32)
+        );
+        let mut set = SignedCheckpoints::parse(&text).unwrap();
+        assert_eq!(set.checkpoints[0].height, 10);
+        assert_eq!(set.checkpoints[0].hash, "aa".repeat(32));
+
+        set.sign(&alice);
+        set.sign(&mallory);
+        assert!(set.verify(&params).is_err()); // 1 of 2 release keys
+        set.sign(&bob);
+        set.sign(&bob);
+        assert_eq!(set.signatures.len(), 3);
+
+        // Signatures survive a round trip through the file format
+        let reloaded = SignedCheckpoints::parse(&set.to_text()).unwrap();
+        assert_eq!(reloaded.verify(&params).unwrap(), vec![alice.public_hex(), bob.public_hex()]);
+
+        let mut tampered = reloaded.clone();
+        tampered.checkpoints[1].hash = "cc".repeat(32);
+        assert!(tampered.verify(&params).is_err());
+        assert!(reloaded.verify(&ChainParams::testnet()).is_err()); // other network
+        assert!(SignedCheckpoints::parse("NETWORK=x\nSEQUENCE=1\nCHECKPOINT=1:abc\n").is_err());
+    }
 }
@@ -13,6 +13,21 @@ pub fn initial_block_reward() -> U256 {
 /// Halving occurs every 210,000 blocks (~4 years at 10 min block time)
 pub const HALVING_INTERVAL: u64 = 210_000;
 
+/// Blocks a coinbase output must wait before it can be spent
+/// (spendable from `created_height + COINBASE_MATURITY`)
+pub const COINBASE_MATURITY: u64 = 100;
+
+/// Chain ids committed to by transaction signatures (replay protection)
+pub const MAINNET_CHAIN_ID: u64 = 1;
+pub const TESTNET_CHAIN_ID: u64 = 8888;
+pub const REGTEST_CHAIN_ID: u64 = 9999;
+
+/// Consensus limit on the encoded size of a block (header + transactions), in bytes
+pub const MAX_BLOCK_SIZE: usize = 2_000_000;
+
+/// Room kept for the header and coinbase when filling a block template
+pub const BLOCK_TEMPLATE_RESERVED_SIZE: usize = 1_000;
+
 /// Max supply: 42,000,000 ASRM in ram
 pub fn max_supply() -> U256 {
     RAM_PER_ASRM * U256::from(42_000_000)
@@ -37,6 +52,11 @@ pub const DEFAULT_WALLET_FEE_NAT_PER_BYTE: U256 = U256([300_000_000_000, 0, 0, 0
 
 // ========== Helper Functions ==========
 
+/// Whether a coinbase created at `created_height` may be spent in a block at `spend_height`
+pub fn coinbase_is_mature(created_height: u64, spend_height: u64, maturity: u64) -> bool {
+    spend_height >= created_height.saturating_add(maturity)
+}
+
 /// Calculate block reward for given height based on halving schedule
 pub fn calculate_block_reward(block_height: u64) -> U256 {
     let halvings = (block_height / HALVING_INTERVAL) as u32;
@@ -98,6 +118,15 @@ mod tests {
         assert_eq!(default_fee, expected_default); // 0.00019 ASRM
     }
 
+    #[test]
+    fn test_coinbase_maturity() {
+        assert!(!coinbase_is_mature(10, 10, COINBASE_MATURITY));
+        assert!(!coinbase_is_mature(10, 10 + COINBASE_MATURITY - 1, COINBASE_MATURITY));
+        assert!(coinbase_is_mature(10, 10 + COINBASE_MATURITY, COINBASE_MATURITY));
+        assert!(coinbase_is_mature(10, 11, 0));
+        assert!(!coinbase_is_mature(u64::MAX - 1, u64::MAX - 1, COINBASE_MATURITY));
+    }
+
     #[test]
     fn test_base_fee_prevents_spam() {
         // Even tiny transactions pay base fee (0.0001 ASRM)
@@ -1,6 +1,7 @@
 use crate::block::{
-    Block, BlockHeader, compute_merkle_root, serialize_header,
+    Block, BlockHeader, compute_header_hash, compute_merkle_root, serialize_header,
 };
+use crate::network::ChainParams;
 use crate::transaction::Transaction;
 use anyhow::{Result, anyhow};
 use log;
@@ -38,13 +39,15 @@ const THREADS_PER_BLOCK: u32 = 256; // Stable setting
 const MAX_BLOCKS: u32 = 768; // Increased from 512 (GTX 1050 Ti has 768 cores)
 
 /// Mine a pre-built block header (for pool/stratum mining where coinbase is set by pool).
-/// Returns (nonce, block_hash_hex) on success.
+/// Returns (nonce, pow_hash_hex) on success. The PoW hash is the DAG hash from
+/// `params.dag_pow_height` and the header hash before it.
 ///
 /// `target_override` – if provided, mining stops when `hash < target_override` instead of
 /// the target derived from `header.difficulty`.  Use this for pool share mining so the
 /// miner submits shares at pool difficulty while keeping `header.difficulty` at network
-/// difficulty (so the header hash remains valid).
+/// difficulty (so the block remains valid).
 pub fn mine_header_cuda(
+    params: &ChainParams,
     header: BlockHeader,
     cancel_flag: Arc<AtomicBool>,
     hashrate: Option<Arc<std::sync::Mutex<f64>>>,
@@ -109,6 +112,8 @@ pub fn mine_header_cuda(
     let target = target_override.unwrap_or(network_target);
     let target_dev = DeviceBuffer::from_slice(&target)?;
 
+    let use_dag_pow = params.uses_dag_pow(index) as i32;
+
     let mut start_nonce: u64 = header.nonce;
     let mut last_rate_update = std::time::Instant::now();
     let mut hashes_since_update: u64 = 0;
@@ -135,7 +140,8 @@ pub fn mine_header_cuda(
                 found_nonce.as_device_ptr(),
                 found_hash.as_device_ptr(),
                 dag_dev.as_device_ptr(),
-                dag_arc.len() as u64
+                dag_arc.len() as u64,
+                use_dag_pow
             ))
             .map_err(|e| anyhow!("CUDA kernel launch failed: {}", e))?;
         }
@@ -172,13 +178,13 @@ pub fn mine_header_cuda(
 
         if flag_host[0] != 0 {
             let mut nonce_host = [0u64];
-            let mut gpu_header_hash = [0u8; 32];
+            let mut gpu_pow_hash = [0u8; 32];
             found_nonce.copy_to(&mut nonce_host)?;
-            found_hash.copy_to(&mut gpu_header_hash)?;
+            found_hash.copy_to(&mut gpu_pow_hash)?;
 
             let nonce = nonce_host[0];
 
-            if !hash_meets_target(&gpu_header_hash, &target) {
+            if !hash_meets_target(&gpu_pow_hash, &target) {
                 // GPU sanity check failed (shouldn't happen)
                 start_nonce = nonce.wrapping_add(1);
                 continue;
@@ -194,7 +200,7 @@ pub fn mine_header_cuda(
                 }
             }
 
-            return Ok((nonce, hex::encode(gpu_header_hash)));
+            return Ok((nonce, hex::encode(gpu_pow_hash)));
         }
 
         start_nonce = start_nonce.wrapping_add(batch_size);
@@ -246,6 +252,7 @@ fn build_header_bytes(prefix: &[u8], nonce: u64, suffix: &[u8]) -> Vec<u8> {
 }
 
 pub fn mine_block_with_coinbase_cuda(
+    params: &ChainParams,
     index: u64,
     prev_hash: String,
     difficulty: u32,
@@ -338,6 +345,8 @@ pub fn mine_block_with_coinbase_cuda(
     let target = compact_bits_to_target_bytes(difficulty);
     let target_dev = DeviceBuffer::from_slice(&target)?;
 
+    let use_dag_pow = params.uses_dag_pow(index) as i32;
+
     let mut start_nonce: u64 = 0;
     let mut last_rate_update = std::time::Instant::now();
     let mut hashes_since_update: u64 = 0;
@@ -373,7 +382,8 @@ pub fn mine_block_with_coinbase_cuda(
                 found_nonce.as_device_ptr(),
                 found_hash.as_device_ptr(),
                 dag_dev.as_device_ptr(),
-                dag_arc.len() as u64
+                dag_arc.len() as u64,
+                use_dag_pow
             ))
             .map_err(|e| anyhow!("CUDA kernel launch failed: {}", e))?;
         }
@@ -417,13 +427,13 @@ pub fn mine_block_with_coinbase_cuda(
 
         if flag_host[0] != 0 {
             let mut nonce_host = [0u64];
-            let mut gpu_header_hash = [0u8; 32];
+            let mut gpu_pow_hash = [0u8; 32];
             found_nonce.copy_to(&mut nonce_host)?;
-            found_hash.copy_to(&mut gpu_header_hash)?;
+            found_hash.copy_to(&mut gpu_pow_hash)?;
 
             let nonce = nonce_host[0];
 
-            if !hash_meets_target(&gpu_header_hash, &target) {
+            if !hash_meets_target(&gpu_pow_hash, &target) {
                 return Err(anyhow!(
                     "GPU found nonce did not satisfy compact target bits=0x{:08x}",
                     difficulty
@@ -442,10 +452,11 @@ pub fn mine_block_with_coinbase_cuda(
 
             header.nonce = nonce;
 
+            // Block identity is always the header hash, independent of the PoW hash
             let block = Block {
                 header: header.clone(),
                 transactions: all_txs,
-                hash: hex::encode(gpu_header_hash),
+                hash: compute_header_hash(&header)?,
             };
             return Ok(block);
         }
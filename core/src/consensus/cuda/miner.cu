@@ -200,7 +200,8 @@ extern "C" __global__ void mine_kernel(
     uint64_t *found_nonce,
     uint8_t *found_hash,
     const uint8_t *dag,
-    uint64_t dag_size)
+    uint64_t dag_size,
+    int use_dag_pow)
 {
     uint64_t idx = (uint64_t)blockIdx.x * blockDim.x + threadIdx.x;
     uint64_t stride = (uint64_t)blockDim.x * gridDim.x;
@@ -315,21 +316,23 @@ extern "C" __global__ void mine_kernel(
             }
         }
 
-        // Step 4: Final Blake3 DAG-PoW hash (kept for future node-side validation)
+        // Step 4: Final Blake3 DAG-PoW hash
         uint8_t final_hash[32];
         blake3_hash_simple(mix, 128, final_hash);
 
-        // Check if canonical header hash (Blake3) meets target.
-        // Pool and node both validate using the canonical Blake3 header hash,
-        // so shares/blocks are accepted when header_hash < target.
-        if (meets_target(header_hash, target))
+        // From the DAG PoW activation height the node checks final_hash against the
+        // target (light-cache verification); before it, the plain header hash.
+        // found_hash receives the hash that was checked; the host derives the block
+        // hash from the header itself.
+        const uint8_t *pow_hash = use_dag_pow ? final_hash : header_hash;
+        if (meets_target(pow_hash, target))
         {
             if (atomicCAS(found_flag, 0, 1) == 0)
             {
                 *found_nonce = nonce;
                 for (int j = 0; j < 32; j++)
                 {
-                    found_hash[j] = header_hash[j];
+                    found_hash[j] = pow_hash[j];
                 }
             }
             return;
@@ -3,6 +3,9 @@
 
 use blake3;
 use anyhow::Result;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::sync::Arc;
 
 /// DAG parameters
 pub const DAG_SIZE: usize = 4 * 1024 * 1024 * 1024; // 4GB
@@ -116,6 +119,63 @@ pub fn generate_full_dag(epoch: u64) -> Result<Vec<u8>> {
 /// Hash a header with the DAG (memory-hard mixing)
 /// This is what miners compute repeatedly with different nonces
 pub fn hash_with_dag(header_hash: &[u8; 32], nonce: u64, dag: &[u8]) -> [u8; 32] {
+    mix_with_items(header_hash, nonce, |dag_index| {
+        let dag_offset = dag_index * DAG_ITEM_SIZE;
+        let mut item = [0u8; DAG_ITEM_SIZE];
+        item.copy_from_slice(&dag[dag_offset..dag_offset + DAG_ITEM_SIZE]);
+        item
+    })
+}
+
+/// Light verification cache for one epoch.
+///
+/// Every DAG item depends only on its index and the epoch seed, so a verifier
+/// can regenerate the `MIX_ITERATIONS` items a hash touches instead of holding
+/// the full 4GB dataset. The result is bit-identical to `hash_with_dag`.
+#[derive(Debug, Clone)]
+pub struct LightCache {
+    pub epoch: u64,
+    seed: [u8; 32],
+}
+
+impl LightCache {
+    pub fn new(epoch: u64) -> Self {
+        Self {
+            epoch,
+            seed: get_seed_hash(epoch),
+        }
+    }
+
+    /// Compute the DAG PoW hash, generating the accessed items on demand
+    pub fn hash(&self, header_hash: &[u8; 32], nonce: u64) -> [u8; 32] {
+        mix_with_items(header_hash, nonce, |dag_index| {
+            generate_dag_item(dag_index as u32, &self.seed)
+        })
+    }
+}
+
+/// Most recently used light cache (seed derivation is O(epoch), so keep it around)
+static LIGHT_CACHE: Lazy<Mutex<Option<Arc<LightCache>>>> = Lazy::new(|| Mutex::new(None));
+
+/// Get the light cache for the epoch containing `block_number`
+pub fn light_cache_for_block(block_number: u64) -> Arc<LightCache> {
+    let epoch = get_epoch(block_number);
+    let mut cached = LIGHT_CACHE.lock();
+    if let Some(cache) = cached.as_ref()
+        && cache.epoch == epoch
+    {
+        return cache.clone();
+    }
+    let cache = Arc::new(LightCache::new(epoch));
+    *cached = Some(cache.clone());
+    cache
+}
+
+/// Shared KawPow-style mixing loop; `fetch_item` returns the DAG item at an index
+fn mix_with_items<F>(header_hash: &[u8; 32], nonce: u64, mut fetch_item: F) -> [u8; 32]
+where
+    F: FnMut(usize) -> [u8; DAG_ITEM_SIZE],
+{
     // Step 1: Initial seed from header + nonce
     let mut seed_input = Vec::with_capacity(40);
     seed_input.extend_from_slice(header_hash);
@@ -136,8 +196,6 @@ pub fn hash_with_dag(header_hash: &[u8; 32], nonce: u64, dag: &[u8]) -> [u8; 32]
         mix[start..start + 32].copy_from_slice(expanded.as_bytes());
     }
     
-
-    
     // Perform random DAG accesses
     for iteration in 0..MIX_ITERATIONS {
         // Compute DAG index from current mix state
@@ -147,23 +205,16 @@ pub fn hash_with_dag(header_hash: &[u8; 32], nonce: u64, dag: &[u8]) -> [u8; 32]
         let dag_index = (u32::from_le_bytes(index_bytes) as usize) % DAG_ITEM_COUNT;
         
         // Fetch DAG item
-        let dag_offset = dag_index * DAG_ITEM_SIZE;
-        let dag_item = &dag[dag_offset..dag_offset + DAG_ITEM_SIZE];
-        
-
+        let dag_item = fetch_item(dag_index);
         
         // Mix with XOR + Blake3
         for i in 0..DAG_ITEM_SIZE {
             mix[i] ^= dag_item[i];
         }
         
-
-        
         // Hash the mix for next iteration
         let mixed = blake3::hash(&mix);
         mix[..32].copy_from_slice(mixed.as_bytes());
-        
-
     }
     
     // Step 3: Final Blake3 hash
@@ -201,4 +252,60 @@ mod tests {
         let item2 = generate_dag_item(0, &seed);
         assert_eq!(item1, item2);
     }
+
+    #[test]
+    fn test_light_hash_deterministic() {
+        let cache = LightCache::new(0);
+        let header_hash = *blake3::hash(b"light-cache-test").as_bytes();
+        assert_eq!(cache.hash(&header_hash, 42), LightCache::new(0).hash(&header_hash, 42));
+        assert_ne!(cache.hash(&header_hash, 42), cache.hash(&header_hash, 43));
+        assert_ne!(cache.hash(&header_hash, 42), LightCache::new(1).hash(&header_hash, 42));
+    }
+
+    /// Epoch 0 known answers, computed once with `hash_with_dag` over
+    /// `generate_full_dag(0)`: header hash, then (nonce, expected hash)
+    const KNOWN_HEADER_HASH: &str = "a04989b0d1a736b890d1032779cdc2c043de7c15ed0e7b570e7759f1d59cddad";
+    const KNOWN_ANSWERS: [(u64, &str); 4] = [
+        (0, "de48524bbddb2bbc55f19b85b8788ac9590db4cb628d93065e610b332361e994"),
+        (1, "91ab387f62f861ad3c667da2fe4a0a790ded9cf3f04aaf0df75a918a0800d62e"),
+        (0xdead_beef, "d2ac047794fa90896c84442c10b2c8ce056444c27cab37f7036c1f31a051587e"),
+        (u64::MAX, "0f1575b021d7d692e2e474f17ffcfe8a6ab22f55f6301fe820e4bc045a5e4254"),
+    ];
+
+    fn known_header_hash() -> [u8; 32] {
+        hex::decode(KNOWN_HEADER_HASH).unwrap().try_into().unwrap()
+    }
+
+    #[test]
+    fn test_light_hash_known_answers() {
+        let cache = LightCache::new(0);
+        let header_hash = known_header_hash();
+        for (nonce, expected) in KNOWN_ANSWERS {
+            assert_eq!(hex::encode(cache.hash(&header_hash, nonce)), expected);
+        }
+    }
+
+    #[test]
+    #[ignore = "generates the full 4GB epoch 0 DAG"]
+    fn test_full_dag_known_answers() {
+        let seed = get_seed_hash(0);
+        let dag = generate_full_dag(0).unwrap();
+        for index in [0, 1, DAG_ITEM_COUNT / 2, DAG_ITEM_COUNT - 1] {
+            let item = &dag[index * DAG_ITEM_SIZE..(index + 1) * DAG_ITEM_SIZE];
+            assert_eq!(item, generate_dag_item(index as u32, &seed));
+        }
+        let header_hash = known_header_hash();
+        for (nonce, expected) in KNOWN_ANSWERS {
+            assert_eq!(hex::encode(hash_with_dag(&header_hash, nonce, &dag)), expected);
+        }
+    }
+
+    #[test]
+    fn test_light_cache_epoch_reuse() {
+        let a = light_cache_for_block(10);
+        let b = light_cache_for_block(7499);
+        assert_eq!(a.epoch, 0);
+        assert!(Arc::ptr_eq(&a, &b));
+        assert_eq!(light_cache_for_block(7500).epoch, 1);
+    }
 }
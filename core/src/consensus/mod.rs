@@ -1,6 +1,7 @@
 // core/consensus.rs
 
 pub mod dag;
+pub mod retarget;
 
 #[cfg(feature = "cuda-miner")]
 pub mod cuda;
@@ -11,6 +12,23 @@ pub use cuda::mine_block_with_coinbase_cuda;
 #[cfg(feature = "cuda-miner")]
 pub use cuda::mine_header_cuda;
 
+use crate::block::{BlockHeader, blake3_hash, serialize_header};
+use crate::network::ChainParams;
+use anyhow::Result;
+
+/// Compute the hash that is compared against the compact target for `header`.
+///
+/// Below `params.dag_pow_height` this is the Blake3 header hash (same as the
+/// block hash); from it on it is the DAG mix hash, verified with the light cache.
+pub fn compute_pow_hash(params: &ChainParams, header: &BlockHeader) -> Result<[u8; 32]> {
+    let header_hash = blake3_hash(&serialize_header(header)?);
+    if !params.uses_dag_pow(header.index) {
+        return Ok(header_hash);
+    }
+    let cache = dag::light_cache_for_block(header.index);
+    Ok(cache.hash(&header_hash, header.nonce))
+}
+
 /// Convert compact difficulty format (bits) to required leading zero count.
 /// Used for display / logging purposes.
 pub fn compact_to_leading_zeros(bits: u32) -> u32 {
@@ -31,3 +49,40 @@ pub fn compact_to_leading_zeros(bits: u32) -> u32 {
     let easier_steps = exponent - baseline_exp;
     baseline_zeros.saturating_sub(easier_steps.saturating_mul(2))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::compute_header_hash;
+
+    fn header(index: u64) -> BlockHeader {
+        BlockHeader {
+            index,
+            previous_hash: "0".repeat(64),
+            merkle_root: "0".repeat(64),
+            timestamp: 1_700_000_000,
+            nonce: 7,
+            difficulty: 0x1f7fffff,
+        }
+    }
+
+    #[test]
+    fn pow_hash_is_header_hash_before_activation() {
+        let params = ChainParams::mainnet();
+        let h = header(params.dag_pow_height - 1);
+        let pow = compute_pow_hash(&params, &h).unwrap();
+        assert_eq!(hex::encode(pow), compute_header_hash(&h).unwrap());
+    }
+
+    #[test]
+    fn pow_hash_uses_dag_after_activation() {
+        let params = ChainParams::mainnet();
+        let h = header(params.dag_pow_height);
+        let pow = compute_pow_hash(&params, &h).unwrap();
+        assert_ne!(hex::encode(pow), compute_header_hash(&h).unwrap());
+
+        let header_hash = blake3_hash(&serialize_header(&h).unwrap());
+        let expected = dag::LightCache::new(dag::get_epoch(h.index)).hash(&header_hash, h.nonce);
+        assert_eq!(pow, expected);
+    }
+}
@@ -0,0 +1,173 @@
+//! DWG3 difficulty retarget as a pure function of the headers before a block,
+//! shared by block validation and the difficulty simulator
+
+use primitive_types::U256;
+
+use crate::network::ChainParams;
+
+/// Header fields the retarget reads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetargetRecord {
+    pub timestamp: i64,
+    pub bits: u32,
+}
+
+/// Expand compact bits into a 256-bit target
+pub fn compact_to_target(bits: u32) -> U256 {
+    let exponent = bits >> 24;
+    let mantissa = bits & 0x007f_ffff;
+    if mantissa == 0 {
+        return U256::zero();
+    }
+
+    if exponent <= 3 {
+        U256::from(mantissa >> (8 * (3 - exponent)))
+    } else {
+        U256::from(mantissa) << (8 * (exponent - 3))
+    }
+}
+
+/// Encode a 256-bit target as compact bits (rounding the mantissa down)
+pub fn target_to_compact(target: U256) -> u32 {
+    if target.is_zero() {
+        return 0;
+    }
+
+    let mut bytes = [0u8; 32];
+    target.to_big_endian(&mut bytes);
+    let first_non_zero = bytes.iter().position(|&b| b != 0).unwrap_or(31);
+    let mut size = (32 - first_non_zero) as u32;
+
+    let mut mantissa: u32 = if size <= 3 {
+        let mut v: u32 = 0;
+        for byte in &bytes[first_non_zero..] {
+            v = (v << 8) | *byte as u32;
+        }
+        v << (8 * (3 - size))
+    } else {
+        ((bytes[first_non_zero] as u32) << 16)
+            | ((bytes[first_non_zero + 1] as u32) << 8)
+            | (bytes[first_non_zero + 2] as u32)
+    };
+
+    if (mantissa & 0x0080_0000) != 0 {
+        mantissa >>= 8;
+        size += 1;
+    }
+
+    (size << 24) | (mantissa & 0x007f_ffff)
+}
+
+/// Expected number of hashes to find a block at `bits`: 2^256 / (target + 1)
+pub fn block_work(bits: u32) -> U256 {
+    let target = compact_to_target(bits);
+    if target.is_zero() {
+        return U256::zero();
+    }
+    // 2^256 does not fit in U256; (!target / (target + 1)) + 1 is the same value
+    match target.checked_add(U256::one()) {
+        Some(divisor) => (!target / divisor) + U256::one(),
+        None => U256::one(),
+    }
+}
+
+/// Compact bits of the block after `history` (oldest first, ending with its
+/// parent):
+/// - networks without retargeting always use the PoW limit
+/// - with fewer than `params.retarget_window` records the parent's bits carry
+///   over (the PoW limit on an empty chain)
+/// - otherwise the average target of the last window, scaled by the window's
+///   actual timespan (clamped to 1/3..3x of the target timespan), capped at
+///   the PoW limit and to a 4x move from the parent's target
+pub fn next_bits(params: &ChainParams, history: &[RetargetRecord]) -> u32 {
+    if !params.retarget {
+        return params.pow_limit_bits;
+    }
+    let window = params.retarget_window as usize;
+    let parent = match history.last() {
+        Some(parent) if history.len() >= window => parent,
+        Some(parent) => return parent.bits,
+        None => return params.pow_limit_bits,
+    };
+
+    let pow_limit = compact_to_target(params.pow_limit_bits);
+
+    // Running average, newest first (the rounding depends on the order)
+    let mut past_target_avg = U256::zero();
+    for (i, record) in history.iter().rev().take(window).enumerate() {
+        let mut target = compact_to_target(record.bits);
+        if target.is_zero() {
+            target = pow_limit;
+        }
+        let n = i as u64 + 1;
+        past_target_avg = if n == 1 {
+            target
+        } else {
+            past_target_avg
+                .saturating_mul(U256::from(n - 1))
+                .saturating_add(target)
+                / U256::from(n)
+        };
+    }
+
+    let newest_time = parent.timestamp;
+    let oldest_time = history[history.len() - window].timestamp;
+
+    // DWG3-style timespan clamping to reduce oscillation.
+    let raw_actual_timespan = (newest_time - oldest_time).max(1);
+    let target_timespan = (params.block_interval * params.retarget_window as i64).max(1);
+    let clamped_actual_timespan = raw_actual_timespan.clamp(target_timespan / 3, target_timespan * 3);
+
+    let mut new_target = past_target_avg.saturating_mul(U256::from(clamped_actual_timespan as u64))
+        / U256::from(target_timespan as u64);
+
+    if new_target.is_zero() {
+        new_target = U256::one();
+    }
+    if new_target > pow_limit {
+        new_target = pow_limit;
+    }
+
+    // Clamp the result to the same 4× limit that block validation enforces,
+    // so DWG3 can never produce a value that the validator would reject.
+    let prev_target = compact_to_target(parent.bits);
+    if !prev_target.is_zero() {
+        let max_increase = prev_target.saturating_mul(U256::from(4u8));
+        let min_decrease = prev_target / U256::from(4u8);
+        if new_target > max_increase {
+            new_target = max_increase;
+        } else if new_target < min_decrease {
+            new_target = min_decrease;
+        }
+        if new_target.is_zero() {
+            new_target = U256::one();
+        }
+    }
+
+    target_to_compact(new_target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_and_edge_cases() {
+        let params = ChainParams::mainnet();
+        let history: Vec<RetargetRecord> = (0..30)
+            .map(|i| RetargetRecord {
+                timestamp: 1_800_000_000 + i * params.block_interval,
+                bits: 0x1e00ffff,
+            })
+            .collect();
+        // 24 timestamps span 23 intervals: on-schedule blocks tighten the target by 1/24
+        let next = next_bits(&params, &history);
+        assert_eq!(next, target_to_compact(compact_to_target(0x1e00ffff) * 23 / 24));
+
+        // Short history, regtest and an empty chain
+        assert_eq!(next_bits(&params, &history[..10]), 0x1e00ffff);
+        assert_eq!(next_bits(&ChainParams::regtest(), &history), 0x207fffff);
+        assert_eq!(next_bits(&params, &[]), params.pow_limit_bits);
+        assert_eq!(target_to_compact(compact_to_target(0x1d2a575f)), 0x1d2a575f);
+    }
+}
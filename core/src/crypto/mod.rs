@@ -1,3 +1,6 @@
+pub mod muhash;
+
+use curve25519_dalek::edwards::CompressedEdwardsY;
 use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use rand::rngs::OsRng;
 use sha2::{Digest, Sha256};
@@ -78,33 +81,163 @@ pub fn address_from_pubkey_hex(pubkey_hex: &str) -> Result<String, String> {
 }
 
 pub fn verify_signature(pubkey_hex: &str, msg: &[u8], sig_bytes: &[u8]) -> bool {
-    if sig_bytes.len() != 64 {
-        return false;
+    let (pubkey, signature) = match parse_key_and_signature(pubkey_hex, sig_bytes) {
+        Some(parsed) => parsed,
+        None => return false,
+    };
+
+    let msg_hash = Sha256::digest(msg);
+
+    pubkey.verify(&msg_hash, &signature).is_ok()
+}
+
+/// `verify_signature` that also rejects keys and nonces with a torsion
+/// component and non-canonical nonce encodings. Blocks from
+/// `ChainParams::sighash_height` verify signatures in batches, and this is
+/// the single check that agrees with `verify_batch` on every entry.
+pub fn verify_signature_strict(pubkey_hex: &str, msg: &[u8], sig_bytes: &[u8]) -> bool {
+    match parse_key_and_signature(pubkey_hex, sig_bytes) {
+        Some((pubkey, signature)) if is_strict(&pubkey, &signature) => {
+            pubkey.verify(&Sha256::digest(msg), &signature).is_ok()
+        }
+        _ => false,
     }
+}
 
-    let pubkey_bytes = match hex::decode(pubkey_hex) {
-        Ok(bytes) => bytes,
-        Err(_) => return false,
-    };
+/// Batch form of `verify_signature_strict` over (pubkey hex, msg, signature)
+/// entries: true only if every entry is valid. Faster than checking them one
+/// by one, but does not tell which entry failed.
+pub fn verify_batch(entries: &[(&str, &[u8], &[u8])]) -> bool {
+    if entries.is_empty() {
+        return true;
+    }
+
+    let mut pubkeys = Vec::with_capacity(entries.len());
+    let mut signatures = Vec::with_capacity(entries.len());
+    let mut msg_hashes = Vec::with_capacity(entries.len());
+    for (pubkey_hex, msg, sig_bytes) in entries {
+        let (pubkey, signature) = match parse_key_and_signature(pubkey_hex, sig_bytes) {
+            Some(parsed) if is_strict(&parsed.0, &parsed.1) => parsed,
+            _ => return false,
+        };
+        pubkeys.push(pubkey);
+        signatures.push(signature);
+        msg_hashes.push(Sha256::digest(msg));
+    }
 
+    let messages: Vec<&[u8]> = msg_hashes.iter().map(|h| h.as_slice()).collect();
+    ed25519_dalek::verify_batch(&messages, &signatures, &pubkeys).is_ok()
+}
+
+fn parse_key_and_signature(pubkey_hex: &str, sig_bytes: &[u8]) -> Option<(VerifyingKey, Signature)> {
+    if sig_bytes.len() != 64 {
+        return None;
+    }
+
+    let pubkey_bytes = hex::decode(pubkey_hex).ok()?;
     if pubkey_bytes.len() != 32 {
-        return false;
+        return None;
     }
 
     let mut pubkey_array = [0u8; 32];
     pubkey_array.copy_from_slice(&pubkey_bytes);
-    
-    let pubkey = match VerifyingKey::from_bytes(&pubkey_array) {
-        Ok(pk) => pk,
-        Err(_) => return false,
-    };
+    let pubkey = VerifyingKey::from_bytes(&pubkey_array).ok()?;
 
-    let msg_hash = Sha256::digest(msg);
-    
     let mut sig_array = [0u8; 64];
     sig_array.copy_from_slice(sig_bytes);
-    let signature = Signature::from_bytes(&sig_array);
+    Some((pubkey, Signature::from_bytes(&sig_array)))
+}
 
-    pubkey.verify(&msg_hash, &signature).is_ok()
+/// The batch equation multiplies each entry by a random scalar, which can
+/// cancel a torsion component that the single check compares, and it
+/// decompresses R where the single check compares its encoding. Honest keys
+/// and nonces are torsion-free and canonical.
+fn is_strict(pubkey: &VerifyingKey, signature: &Signature) -> bool {
+    let r_bytes = signature.r_bytes();
+    match CompressedEdwardsY(*r_bytes).decompress() {
+        Some(r) => {
+            pubkey.to_edwards().is_torsion_free() && r.is_torsion_free() && r.compress().as_bytes() == r_bytes
+        }
+        None => false,
+    }
 }
 
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use curve25519_dalek::Scalar;
+    use curve25519_dalek::constants::ED25519_BASEPOINT_POINT;
+    use curve25519_dalek::edwards::EdwardsPoint;
+    use sha2::Sha512;
+
+    /// Order-2 point (0, -1)
+    fn torsion_point() -> EdwardsPoint {
+        let mut bytes = [0xff; 32];
+        bytes[0] = 0xec;
+        bytes[31] = 0x7f;
+        CompressedEdwardsY(bytes).decompress().unwrap()
+    }
+
+    /// Ed25519 signature over `msg` with secret scalar `a` under public
+    /// point `public` and nonce point `r`
+    fn raw_sign(a: Scalar, public: EdwardsPoint, nonce: Scalar, r: EdwardsPoint, msg: &[u8]) -> [u8; 64] {
+        let k = Scalar::from_hash(
+            Sha512::new()
+                .chain_update(r.compress().as_bytes())
+                .chain_update(public.compress().as_bytes())
+                .chain_update(msg),
+        );
+        let mut sig = [0u8; 64];
+        sig[..32].copy_from_slice(r.compress().as_bytes());
+        sig[32..].copy_from_slice((nonce + k * a).as_bytes());
+        sig
+    }
+
+    /// `key`'s public point plus a torsion component (hex), and a signature
+    /// over `msg` under it that `verify_signature` accepts: ground until
+    /// H(R||A||M) is even, so the torsion component cancels
+    pub(crate) fn torsion_key_signature(key: &WalletKeypair, msg: &[u8]) -> (String, [u8; 64]) {
+        let a = key.signing_key.to_scalar();
+        let msg_hash = Sha256::digest(msg);
+        let public = key.verifying_key.to_edwards() + torsion_point();
+        let tampered = VerifyingKey::from(public);
+        let sig = (1u64..)
+            .map(|n| {
+                let nonce = Scalar::from(n);
+                raw_sign(a, public, nonce, ED25519_BASEPOINT_POINT * nonce, &msg_hash)
+            })
+            .find(|sig| tampered.verify(&msg_hash, &Signature::from_bytes(sig)).is_ok())
+            .unwrap();
+        (hex::encode(tampered.to_bytes()), sig)
+    }
+
+    #[test]
+    fn torsion_components_are_rejected_by_the_strict_and_batch_checks() {
+        let key = WalletKeypair::new();
+        let msg = b"torsion";
+
+        // Key with a torsion component: only the plain single check accepts it
+        let (tampered_hex, sig) = torsion_key_signature(&key, msg);
+        assert!(verify_signature(&tampered_hex, msg, &sig));
+        assert!(!verify_signature_strict(&tampered_hex, msg, &sig));
+        assert!(!verify_batch(&[(tampered_hex.as_str(), &msg[..], &sig[..])]));
+
+        // Nonce with a torsion component: the single check rejects it, the
+        // batch equation would accept it whenever its random scalar is even
+        let nonce = Scalar::from(7u64);
+        let a = key.signing_key.to_scalar();
+        let public = key.verifying_key.to_edwards();
+        let r = ED25519_BASEPOINT_POINT * nonce + torsion_point();
+        let sig = raw_sign(a, public, nonce, r, &Sha256::digest(msg));
+        let public_hex = key.public_hex();
+        assert!(!verify_signature(&public_hex, msg, &sig));
+        assert!(!verify_signature_strict(&public_hex, msg, &sig));
+        assert!(!verify_batch(&[(public_hex.as_str(), &msg[..], &sig[..])]));
+
+        // Honest signatures pass all three
+        let sig = key.sign(msg);
+        assert!(verify_signature(&public_hex, msg, &sig));
+        assert!(verify_signature_strict(&public_hex, msg, &sig));
+        assert!(verify_batch(&[(public_hex.as_str(), &msg[..], &sig[..])]));
+    }
+}
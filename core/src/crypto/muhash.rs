@@ -0,0 +1,129 @@
+//! MuHash3072: a hash of a multiset that can be updated one element at a time.
+//! Each element maps to a number modulo the prime 2^3072 - 1103717; the set
+//! hash is their product, so insertion order does not matter and removal is a
+//! multiplication by the inverse (kept in a separate denominator until finalize).
+
+use num_bigint::BigUint;
+use once_cell::sync::Lazy;
+use sha2::{Digest, Sha256};
+
+/// Size of a field element in bytes
+pub const MUHASH_BYTES: usize = 384;
+
+static MODULUS: Lazy<BigUint> =
+    Lazy::new(|| (BigUint::from(1u32) << 3072) - BigUint::from(1_103_717u32));
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MuHash3072 {
+    numerator: BigUint,
+    denominator: BigUint,
+}
+
+impl Default for MuHash3072 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MuHash3072 {
+    /// Hash of the empty set
+    pub fn new() -> Self {
+        MuHash3072 {
+            numerator: BigUint::from(1u32),
+            denominator: BigUint::from(1u32),
+        }
+    }
+
+    /// Expand `data` to a 3072-bit field element: SHA256(SHA256(data) || counter)
+    /// for counters 0..12
+    fn element(data: &[u8]) -> BigUint {
+        let seed = Sha256::digest(data);
+        let mut bytes = Vec::with_capacity(MUHASH_BYTES);
+        for counter in 0..(MUHASH_BYTES / 32) as u32 {
+            let mut hasher = Sha256::new();
+            hasher.update(seed);
+            hasher.update(counter.to_be_bytes());
+            bytes.extend_from_slice(&hasher.finalize());
+        }
+        BigUint::from_bytes_be(&bytes) % &*MODULUS
+    }
+
+    pub fn insert(&mut self, data: &[u8]) {
+        self.numerator = (&self.numerator * Self::element(data)) % &*MODULUS;
+    }
+
+    pub fn remove(&mut self, data: &[u8]) {
+        self.denominator = (&self.denominator * Self::element(data)) % &*MODULUS;
+    }
+
+    /// 32-byte digest of the current set
+    pub fn finalize(&self) -> [u8; 32] {
+        // The modulus is prime and every factor is below it, so the inverse exists
+        let inverse = self
+            .denominator
+            .modinv(&MODULUS)
+            .unwrap_or_else(|| BigUint::from(0u32));
+        let value = (&self.numerator * inverse) % &*MODULUS;
+        Sha256::digest(Self::to_fixed(&value)).into()
+    }
+
+    fn to_fixed(value: &BigUint) -> [u8; MUHASH_BYTES] {
+        let bytes = value.to_bytes_be();
+        let mut out = [0u8; MUHASH_BYTES];
+        out[MUHASH_BYTES - bytes.len()..].copy_from_slice(&bytes);
+        out
+    }
+
+    /// Numerator then denominator, each 384 bytes big-endian
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(2 * MUHASH_BYTES);
+        out.extend_from_slice(&Self::to_fixed(&self.numerator));
+        out.extend_from_slice(&Self::to_fixed(&self.denominator));
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 2 * MUHASH_BYTES {
+            return None;
+        }
+        let (num, den) = bytes.split_at(MUHASH_BYTES);
+        Some(MuHash3072 {
+            numerator: BigUint::from_bytes_be(num) % &*MODULUS,
+            denominator: BigUint::from_bytes_be(den) % &*MODULUS,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_hash_ignores_order_and_cancels_removals() {
+        let mut a = MuHash3072::new();
+        a.insert(b"one");
+        a.insert(b"two");
+        a.insert(b"three");
+        a.remove(b"two");
+
+        let mut b = MuHash3072::new();
+        b.remove(b"two");
+        b.insert(b"three");
+        b.insert(b"two");
+        b.insert(b"one");
+        b.remove(b"two");
+        b.insert(b"two");
+
+        let mut c = MuHash3072::new();
+        c.insert(b"three");
+        c.insert(b"one");
+
+        assert_eq!(a.finalize(), b.finalize());
+        assert_eq!(a.finalize(), c.finalize());
+        assert_ne!(c.finalize(), MuHash3072::new().finalize());
+
+        let restored = MuHash3072::from_bytes(&a.to_bytes()).unwrap();
+        assert_eq!(restored.finalize(), a.finalize());
+        assert!(MuHash3072::from_bytes(&[0u8; 10]).is_none());
+    }
+}
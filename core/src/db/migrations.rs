@@ -0,0 +1,324 @@
+//! On-disk schema upgrades, run by `open_db` before the chain is loaded
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Result, anyhow};
+use primitive_types::U256;
+use rocksdb::{DEFAULT_COLUMN_FAMILY_NAME, IteratorMode, WriteBatch};
+
+use super::{ChainBatch, ChainDb, column_family_for};
+use crate::block::{Block, BlockHeader};
+use crate::blockchain::BlockStatus;
+use crate::consensus::retarget::block_work;
+use crate::transaction::BINCODE_CONFIG;
+
+/// Layout written by this release
+pub const SCHEMA_VERSION: u32 = 5;
+
+const SCHEMA_VERSION_KEY: &[u8] = b"schema_version";
+
+/// Keys moved per write batch. Each batch is atomic, so an interrupted
+/// migration simply resumes on the next start.
+const MIGRATION_BATCH: usize = 10_000;
+
+struct Migration {
+    from: u32, // upgrades `from` -> `from + 1`
+    description: &'static str,
+    run: fn(&ChainDb) -> Result<()>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 1,
+        description: "move prefixed keys into column families",
+        run: split_column_families,
+    },
+    Migration {
+        from: 2,
+        description: "store block headers apart from bodies",
+        run: write_header_records,
+    },
+    Migration {
+        from: 3,
+        description: "build the block tree index",
+        run: build_block_tree,
+    },
+    Migration {
+        from: 4,
+        description: "record the cumulative work of every block",
+        run: record_chain_work,
+    },
+];
+
+fn read_version(db: &ChainDb) -> Result<Option<u32>> {
+    match db.db.get(SCHEMA_VERSION_KEY)? {
+        Some(bytes) => {
+            let arr: [u8; 4] = bytes
+                .as_slice()
+                .try_into()
+                .map_err(|_| anyhow!("corrupt schema version record"))?;
+            Ok(Some(u32::from_be_bytes(arr)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Version of a database with no version record: v1 (everything in the
+/// default family) if it holds any data, otherwise a fresh database
+fn detect_version(db: &ChainDb) -> Result<u32> {
+    match db.db.iterator(IteratorMode::Start).next() {
+        Some(item) => {
+            item?;
+            Ok(1)
+        }
+        None => Ok(SCHEMA_VERSION),
+    }
+}
+
+/// Apply every migration between the stored version and `SCHEMA_VERSION`,
+/// recording the version after each step
+pub(super) fn migrate(db: &ChainDb) -> Result<()> {
+    let stored = read_version(db)?;
+    let mut version = match stored {
+        Some(v) => v,
+        None => detect_version(db)?,
+    };
+    if version > SCHEMA_VERSION {
+        return Err(anyhow!(
+            "database schema v{} is newer than this node supports (v{})",
+            version,
+            SCHEMA_VERSION
+        ));
+    }
+
+    while version < SCHEMA_VERSION {
+        let step = MIGRATIONS
+            .iter()
+            .find(|m| m.from == version)
+            .ok_or_else(|| anyhow!("no migration from schema v{}", version))?;
+        log::info!(
+            "Migrating database schema v{} -> v{}: {}",
+            version,
+            version + 1,
+            step.description
+        );
+        (step.run)(db)?;
+        version += 1;
+        db.db.put(SCHEMA_VERSION_KEY, version.to_be_bytes())?;
+    }
+
+    if stored.is_none() {
+        db.db.put(SCHEMA_VERSION_KEY, version.to_be_bytes())?;
+    }
+    Ok(())
+}
+
+/// v1 -> v2: move every prefixed key out of the default family into its own
+fn split_column_families(db: &ChainDb) -> Result<()> {
+    let mut batch = WriteBatch::default();
+    let mut pending = 0usize;
+    let mut moved = 0usize;
+
+    for item in db.db.iterator(IteratorMode::Start) {
+        let (key, value) = item?;
+        let family = column_family_for(&key);
+        if family == DEFAULT_COLUMN_FAMILY_NAME {
+            continue;
+        }
+        batch.put_cf(db.cf(family), &key, &value);
+        batch.delete(&key);
+        pending += 1;
+        if pending >= MIGRATION_BATCH {
+            db.db.write(std::mem::take(&mut batch))?;
+            moved += pending;
+            pending = 0;
+            log::info!("  moved {} keys...", moved);
+        }
+    }
+    db.db.write(batch)?;
+    moved += pending;
+
+    // Reclaim the space held by the moved keys' tombstones
+    db.db.compact_range(None::<&[u8]>, None::<&[u8]>);
+    log::info!("Moved {} keys into column families", moved);
+    Ok(())
+}
+
+/// v2 -> v3: write `h:{hash}` for every stored block so headers survive pruning
+fn write_header_records(db: &ChainDb) -> Result<()> {
+    let mut batch = ChainBatch::default();
+    let mut written = 0usize;
+
+    for item in db.prefix_iter(b"b:") {
+        let (key, value) = item?;
+        let (block, _): (Block, usize) = bincode::decode_from_slice(&value, *BINCODE_CONFIG)?;
+        let header = bincode::encode_to_vec(&block.header, *BINCODE_CONFIG)?;
+        batch.put([b"h:", &key[2..]].concat(), header);
+        written += 1;
+        if batch.len() >= MIGRATION_BATCH {
+            db.write(std::mem::take(&mut batch))?;
+        }
+    }
+    db.write(batch)?;
+    log::info!("Wrote {} block header records", written);
+    Ok(())
+}
+
+/// v3 -> v4: give every stored block a status (main-chain blocks were
+/// connected, the rest only validated) and mark the branch tips
+fn build_block_tree(db: &ChainDb) -> Result<()> {
+    let mut batch = ChainBatch::default();
+    let mut side: Vec<(String, String)> = Vec::new(); // (hash, parent) off the main chain
+    let connected = bincode::encode_to_vec(BlockStatus::DataValid, *BINCODE_CONFIG)?;
+    let stored = bincode::encode_to_vec(BlockStatus::HeaderValid, *BINCODE_CONFIG)?;
+
+    for item in db.prefix_iter(b"h:") {
+        let (key, value) = item?;
+        let hash = String::from_utf8(key[2..].to_vec())?;
+        let (header, _): (BlockHeader, usize) = bincode::decode_from_slice(&value, *BINCODE_CONFIG)?;
+        let on_main_chain = db
+            .get(format!("i:{}", header.index).as_bytes())?
+            .is_some_and(|v| v == hash.as_bytes());
+        if on_main_chain {
+            batch.put(format!("s:{}", hash).as_bytes(), &connected);
+        } else {
+            batch.put(format!("s:{}", hash).as_bytes(), &stored);
+            side.push((hash, header.previous_hash));
+        }
+        if batch.len() >= MIGRATION_BATCH {
+            db.write(std::mem::take(&mut batch))?;
+        }
+    }
+
+    // Every main-chain block but the tip has a main-chain child, so only the
+    // tip and side blocks can be branch tips
+    let parents: HashSet<&str> = side.iter().map(|(_, parent)| parent.as_str()).collect();
+    let mut tips: Vec<String> = side
+        .iter()
+        .filter(|(hash, _)| !parents.contains(hash.as_str()))
+        .map(|(hash, _)| hash.clone())
+        .collect();
+    if let Some(tip) = db.get(b"tip")? {
+        let tip = String::from_utf8(tip)?;
+        if !parents.contains(tip.as_str()) {
+            tips.push(tip);
+        }
+    }
+    for tip in &tips {
+        batch.put(format!("e:{}", tip).as_bytes(), b"");
+    }
+    db.write(batch)?;
+    log::info!("Indexed {} side-branch blocks, {} branch tips", side.len(), tips.len());
+    Ok(())
+}
+
+/// v4 -> v5: write `w:{hash}` for every header that has none. Older nodes
+/// filled it in lazily, so only some blocks have it; parents are visited
+/// before their children by going up in height.
+fn record_chain_work(db: &ChainDb) -> Result<()> {
+    let mut headers: Vec<(String, BlockHeader)> = Vec::new();
+    for item in db.prefix_iter(b"h:") {
+        let (key, value) = item?;
+        let (header, _): (BlockHeader, usize) = bincode::decode_from_slice(&value, *BINCODE_CONFIG)?;
+        headers.push((String::from_utf8(key[2..].to_vec())?, header));
+    }
+    headers.sort_by_key(|(_, header)| header.index);
+
+    let mut batch = ChainBatch::default();
+    let mut work: HashMap<String, U256> = HashMap::new();
+    let mut written = 0usize;
+    for (hash, header) in headers {
+        let key = format!("w:{}", hash);
+        if let Some(bytes) = db.get(key.as_bytes())? {
+            if bytes.len() != 32 {
+                return Err(anyhow!("corrupt chain work for {}", hash));
+            }
+            work.insert(hash, U256::from_big_endian(&bytes));
+            continue;
+        }
+        let parent_work = if header.index == 0 {
+            U256::zero()
+        } else if let Some(parent_work) = work.get(&header.previous_hash) {
+            *parent_work
+        } else {
+            log::warn!("Header {} has no stored parent, leaving its chain work unset", hash);
+            continue;
+        };
+        let total = parent_work.saturating_add(block_work(header.difficulty));
+        let mut bytes = [0u8; 32];
+        total.to_big_endian(&mut bytes);
+        batch.put(key.as_bytes(), bytes);
+        work.insert(hash, total);
+        written += 1;
+        if batch.len() >= MIGRATION_BATCH {
+            db.write(std::mem::take(&mut batch))?;
+        }
+    }
+    db.write(batch)?;
+    log::info!("Recorded chain work for {} blocks", written);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockHeader;
+    use crate::blockchain::testutil::TempDir;
+    use crate::db::open_db;
+    use rocksdb::{DB, Options};
+
+    #[test]
+    fn upgrades_single_family_layout() {
+        let dir = TempDir::new("schema-test");
+        let path = dir.join("db");
+        let header = BlockHeader {
+            index: 0,
+            previous_hash: "0".repeat(64),
+            merkle_root: String::new(),
+            timestamp: 0,
+            nonce: 0,
+            difficulty: 0,
+        };
+        let block = Block {
+            header: header.clone(),
+            transactions: Vec::new(),
+            hash: "h1".to_string(),
+        };
+        let block_blob = bincode::encode_to_vec(&block, *BINCODE_CONFIG).unwrap();
+        {
+            let mut opts = Options::default();
+            opts.create_if_missing(true);
+            let legacy = DB::open(&opts, &path).unwrap();
+            legacy.put(b"tip", b"h1").unwrap();
+            legacy.put(b"b:h1", &block_blob).unwrap();
+            legacy.put(b"i:0", b"h1").unwrap();
+            legacy.put(b"u:t1:0", b"utxo").unwrap();
+        }
+
+        let db = open_db(path.to_str().unwrap()).unwrap();
+        assert_eq!(read_version(&db).unwrap(), Some(SCHEMA_VERSION));
+        assert_eq!(db.get(b"tip").unwrap(), Some(b"h1".to_vec()));
+        assert_eq!(db.get(b"b:h1").unwrap(), Some(block_blob.clone()));
+        assert_eq!(
+            db.get(b"h:h1").unwrap(),
+            Some(bincode::encode_to_vec(&header, *BINCODE_CONFIG).unwrap())
+        );
+        assert_eq!(db.get(b"i:0").unwrap(), Some(b"h1".to_vec()));
+        assert_eq!(
+            db.get(b"s:h1").unwrap(),
+            Some(bincode::encode_to_vec(BlockStatus::DataValid, *BINCODE_CONFIG).unwrap())
+        );
+        assert!(db.get(b"e:h1").unwrap().is_some());
+        let mut work = [0u8; 32];
+        block_work(header.difficulty).to_big_endian(&mut work);
+        assert_eq!(db.get(b"w:h1").unwrap(), Some(work.to_vec()));
+        assert_eq!(db.prefix_iter(b"u:").count(), 1);
+        // Moved, not copied
+        assert!(db.db.get(b"u:t1:0").unwrap().is_none());
+        drop(db);
+
+        // Reopening an upgraded database changes nothing
+        let db = open_db(path.to_str().unwrap()).unwrap();
+        assert_eq!(db.get(b"b:h1").unwrap(), Some(block_blob));
+    }
+}
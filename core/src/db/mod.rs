@@ -1,26 +1,217 @@
-use rocksdb::{DB, Options, WriteBatch};
+use rocksdb::{
+    BlockBasedOptions, ColumnFamily, ColumnFamilyDescriptor, DBCompressionType, DB,
+    DEFAULT_COLUMN_FAMILY_NAME, Direction, IteratorMode, Options, WriteBatch,
+};
 use anyhow::Result;
 use std::path::Path;
 
+mod migrations;
+
+pub use migrations::SCHEMA_VERSION;
+
 /// key rule (string keys)
 /*
- Keys:
-  h:<block_hash> -> serialized header (bincode)
-  i:<height> -> block_hash (utf8)
-  t:<txid> -> serialized tx (bincode)
-  u:<txid>:<vout> -> serialized UTXO (bincode)
-  tip -> block_hash
+ Keys keep their prefix inside the column family that holds them:
+  blocks:
+   b:<block_hash> -> serialized block (bincode)
+   r:<block_hash> -> undo record: UTXOs the block spent from the set (bincode Vec<Utxo>)
+  txs:
+   t:<txid> -> serialized tx (bincode)
+  utxo:
+   u:<txid>:<vout> -> serialized UTXO (bincode)
+  index:
+   h:<block_hash> -> serialized header (bincode), kept when a pruned node drops the body
+   i:<height> -> block_hash (utf8)
+   w:<block_hash> -> cumulative chain work (U256, 32-byte big-endian)
+   c:<txid> -> main-chain height of a coinbase tx (u64 big-endian)
+   x:<txid> -> main-chain height (u64 big-endian), position in block (u32 big-endian) and block_hash of any tx
+               (older entries hold the height only)
+   s:<block_hash> -> BlockStatus of any known block, main chain or side branch (bincode)
+   e:<block_hash> -> empty; marks a block with no known children (a branch tip)
+  address:
+   a:<address>:<txid>:<vout> -> serialized UTXO, per-address copy of u: (bincode)
+   y:<address>:<height:020>:<txid> -> AddressHistoryEntry (bincode)
+  default:
+   tip -> block_hash
+   addrindex -> set once a: / y: cover the whole chain
+   schema_version -> on-disk layout version (u32 big-endian)
+   prune_height -> lowest main-chain height whose body is still stored (u64 big-endian)
+   utxostats -> MuHash, count and total amount of the UTXO set (bincode)
+   chain_id -> chain id of the network that created the database (u64 big-endian)
 */
 
-pub fn open_db(path: &str) -> Result<DB, anyhow::Error> {
+pub const CF_BLOCKS: &str = "blocks";
+pub const CF_TXS: &str = "txs";
+pub const CF_UTXO: &str = "utxo";
+pub const CF_INDEX: &str = "index";
+pub const CF_ADDRESS: &str = "address";
+
+const COLUMN_FAMILIES: [&str; 5] = [CF_BLOCKS, CF_TXS, CF_UTXO, CF_INDEX, CF_ADDRESS];
+
+type KeyValue = (Box<[u8]>, Box<[u8]>);
+
+/// Column family holding `key`, chosen by its prefix
+pub fn column_family_for(key: &[u8]) -> &'static str {
+    match key.get(..2) {
+        Some(b"b:") | Some(b"r:") => CF_BLOCKS,
+        Some(b"t:") => CF_TXS,
+        Some(b"u:") => CF_UTXO,
+        Some(b"h:") | Some(b"i:") | Some(b"w:") | Some(b"c:") | Some(b"x:") | Some(b"s:") | Some(b"e:") => {
+            CF_INDEX
+        }
+        Some(b"a:") | Some(b"y:") => CF_ADDRESS,
+        _ => DEFAULT_COLUMN_FAMILY_NAME,
+    }
+}
+
+/// Block and tx bodies are large, written once and read whole: bigger blocks
+/// and stronger compression. UTXO and index families serve point lookups
+/// (mostly misses while validating), so they get bloom filters.
+fn cf_options(name: &str) -> Options {
+    let mut opts = Options::default();
+    let mut table = BlockBasedOptions::default();
+    match name {
+        CF_BLOCKS | CF_TXS => {
+            opts.set_compression_type(DBCompressionType::Zstd);
+            table.set_block_size(64 * 1024);
+        }
+        CF_UTXO => {
+            opts.set_compression_type(DBCompressionType::Lz4);
+            opts.set_write_buffer_size(128 * 1024 * 1024);
+            table.set_bloom_filter(10.0, false);
+        }
+        CF_INDEX => {
+            opts.set_compression_type(DBCompressionType::Lz4);
+            table.set_bloom_filter(10.0, false);
+        }
+        _ => opts.set_compression_type(DBCompressionType::Lz4),
+    }
+    opts.set_level_compaction_dynamic_level_bytes(true);
+    opts.set_block_based_table_factory(&table);
+    opts
+}
+
+/// Chain database: every key is routed to its column family by prefix
+pub struct ChainDb {
+    db: DB,
+}
+
+/// Write batch for `ChainDb`; keys are routed to their families on write
+#[derive(Default)]
+pub struct ChainBatch {
+    ops: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+}
+
+impl ChainBatch {
+    pub fn put<K: AsRef<[u8]>, V: AsRef<[u8]>>(&mut self, key: K, value: V) {
+        self.ops.push((key.as_ref().to_vec(), Some(value.as_ref().to_vec())));
+    }
+
+    pub fn delete<K: AsRef<[u8]>>(&mut self, key: K) {
+        self.ops.push((key.as_ref().to_vec(), None));
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
+impl ChainDb {
+    fn cf(&self, name: &str) -> &ColumnFamily {
+        // Every family is opened (or created) in open_db
+        self.db.cf_handle(name).expect("column family not opened")
+    }
+
+    fn cf_for(&self, key: &[u8]) -> &ColumnFamily {
+        self.cf(column_family_for(key))
+    }
+
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Vec<u8>>, rocksdb::Error> {
+        self.db.get_cf(self.cf_for(key.as_ref()), key)
+    }
+
+    pub fn put<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<(), rocksdb::Error> {
+        self.db.put_cf(self.cf_for(key.as_ref()), key, value)
+    }
+
+    pub fn delete<K: AsRef<[u8]>>(&self, key: K) -> Result<(), rocksdb::Error> {
+        self.db.delete_cf(self.cf_for(key.as_ref()), key)
+    }
+
+    pub fn write(&self, batch: ChainBatch) -> Result<(), rocksdb::Error> {
+        let mut wb = WriteBatch::default();
+        for (key, value) in batch.ops {
+            let cf = self.cf_for(&key);
+            match value {
+                Some(value) => wb.put_cf(cf, key, value),
+                None => wb.delete_cf(cf, key),
+            }
+        }
+        self.db.write(wb)
+    }
+
+    /// Entries whose key starts with `prefix`, in key order. The prefix must
+    /// be long enough to pick a family (e.g. `b"u:"`, `b"a:0xab"`).
+    pub fn prefix_iter<'a>(
+        &'a self,
+        prefix: &[u8],
+    ) -> impl Iterator<Item = Result<KeyValue, rocksdb::Error>> + 'a {
+        let prefix = prefix.to_vec();
+        self.db
+            .iterator_cf(self.cf_for(&prefix), IteratorMode::From(&prefix, Direction::Forward))
+            .take_while(move |item| match item {
+                Ok((key, _)) => key.starts_with(&prefix),
+                Err(_) => true,
+            })
+    }
+
+    /// Flush the memtables of every family
+    pub fn flush(&self) -> Result<(), rocksdb::Error> {
+        self.db.flush()?;
+        for name in COLUMN_FAMILIES {
+            self.db.flush_cf(self.cf(name))?;
+        }
+        Ok(())
+    }
+
+    pub fn cancel_all_background_work(&self, wait: bool) {
+        self.db.cancel_all_background_work(wait);
+    }
+}
+
+/// Open (or create) the chain database with its column families and upgrade
+/// the on-disk layout to `SCHEMA_VERSION`
+pub fn open_db(path: &str) -> Result<ChainDb, anyhow::Error> {
     let mut opts = Options::default();
     opts.create_if_missing(true);
+    opts.create_missing_column_families(true);
     let p = Path::new(path);
-    let db = DB::open(&opts, p)?;
+
+    // RocksDB refuses to open without every existing family, so open unknown
+    // ones (e.g. from a newer release) with default options
+    let existing = DB::list_cf(&opts, p).unwrap_or_default();
+    let mut descriptors: Vec<ColumnFamilyDescriptor> = COLUMN_FAMILIES
+        .iter()
+        .map(|name| ColumnFamilyDescriptor::new(*name, cf_options(name)))
+        .collect();
+    for name in existing {
+        if name != DEFAULT_COLUMN_FAMILY_NAME && !COLUMN_FAMILIES.contains(&name.as_str()) {
+            descriptors.push(ColumnFamilyDescriptor::new(name, Options::default()));
+        }
+    }
+
+    let db = ChainDb {
+        db: DB::open_cf_descriptors(&opts, p, descriptors)?,
+    };
+    migrations::migrate(&db)?;
     Ok(db)
 }
 
-pub fn put_batch(db: &DB, batch: WriteBatch) -> Result<(), anyhow::Error> {
+pub fn put_batch(db: &ChainDb, batch: ChainBatch) -> Result<(), anyhow::Error> {
     db.write(batch)?;
     Ok(())
 }
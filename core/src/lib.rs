@@ -17,5 +17,6 @@ pub mod wallet;
 pub use block::{Block, BlockHeader, compute_header_hash, compute_merkle_root};
 pub use blockchain::Blockchain;
 pub use crypto::WalletKeypair;
+pub use network::ChainParams;
 pub use transaction::{Transaction, TransactionInput, TransactionOutput};
 pub use wallet::Wallet;
@@ -0,0 +1,353 @@
+//! Network consensus parameters. Everything that differs between mainnet,
+//! testnet, regtest or a custom network lives in one `ChainParams`, which
+//! `Blockchain::new` and the node, pool and tools take instead of constants.
+
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+use primitive_types::U256;
+
+use crate::checkpoint::{
+    Checkpoint, SnapshotAnchor, mainnet_checkpoint_keys, mainnet_checkpoints, mainnet_snapshot_anchors,
+};
+use crate::config::{
+    COINBASE_MATURITY, HALVING_INTERVAL, MAINNET_CHAIN_ID, REGTEST_CHAIN_ID, TESTNET_CHAIN_ID,
+    initial_block_reward,
+};
+use crate::security::{GENESIS_TIMESTAMP, MAX_REORG_DEPTH};
+
+/// After this many halvings the block reward is zero
+const MAX_HALVINGS: u64 = 33;
+
+/// Mainnet height of the v2 consensus upgrade: DAG PoW, per-input sighash,
+/// versioned transactions, coinbase maturity in blocks, the transaction
+/// security limits and replay protection all activate here, so nodes switch
+/// rule sets once. It was set well above the chain height at the release
+/// that introduced those rules; moving it, or splitting one rule off to its
+/// own height, is a hard fork.
+pub const MAINNET_UPGRADE_HEIGHT: u64 = 150_000;
+
+#[derive(Debug, Clone)]
+pub struct ChainParams {
+    pub network: String,    // preset name, or the name given by a params file
+    pub network_id: String, // P2P handshake network id
+    pub chain_id: u64,      // committed to by transaction signatures
+    pub network_magic: u32, // P2P frame magic
+    pub genesis_timestamp: i64, // blocks older than this are invalid
+    pub pow_limit_bits: u32, // easiest allowed target, compact bits
+    pub retarget: bool,     // false: every block uses pow_limit_bits
+    pub retarget_window: u64, // DWG3 window in blocks
+    pub block_interval: i64, // target seconds per block
+    pub initial_block_reward: U256, // ram
+    pub halving_interval: u64,
+    pub coinbase_maturity: u64, // blocks before a coinbase output can be spent
+    pub max_reorg_depth: u64,
+    pub dag_pow_height: u64,           // first block whose PoW is the KawPow-Blake3 DAG hash
+    pub replay_protection_height: u64, // legacy signatures must commit to chain_id; bare ones below
+    pub sighash_height: u64,           // per-input sighash only; legacy whole-tx signatures below
+    pub locktime_height: u64,          // blocks may contain transactions with version >= 2
+    pub coinbase_maturity_height: u64, // block validation enforces coinbase_maturity (the mempool always does)
+    pub tx_security_height: u64,       // blocks must pass validate_transaction_security
+    pub checkpoints: Vec<Checkpoint>,
+    pub checkpoint_keys: Vec<String>, // release keys (hex Ed25519) trusted to sign checkpoint files
+    pub checkpoint_threshold: usize,  // signatures a checkpoint file needs
+    pub snapshot_anchors: Vec<SnapshotAnchor>,
+    pub dns_seed: bool,       // register with and discover peers through the DNS server
+    pub mine_on_demand: bool, // POST /regtest/generate is served
+}
+
+impl ChainParams {
+    pub fn mainnet() -> Self {
+        ChainParams {
+            network: "mainnet".to_string(),
+            network_id: "Astram-mainnet".to_string(),
+            chain_id: MAINNET_CHAIN_ID,
+            network_magic: 0xA57A_0001,
+            genesis_timestamp: GENESIS_TIMESTAMP,
+            pow_limit_bits: 0x1f7fffff,
+            retarget: true,
+            retarget_window: 24,
+            block_interval: 60,
+            initial_block_reward: initial_block_reward(),
+            halving_interval: HALVING_INTERVAL,
+            coinbase_maturity: COINBASE_MATURITY,
+            max_reorg_depth: MAX_REORG_DEPTH,
+            dag_pow_height: MAINNET_UPGRADE_HEIGHT,
+            replay_protection_height: MAINNET_UPGRADE_HEIGHT,
+            sighash_height: MAINNET_UPGRADE_HEIGHT,
+            locktime_height: MAINNET_UPGRADE_HEIGHT,
+            coinbase_maturity_height: MAINNET_UPGRADE_HEIGHT,
+            tx_security_height: MAINNET_UPGRADE_HEIGHT,
+            checkpoints: mainnet_checkpoints(),
+            checkpoint_keys: mainnet_checkpoint_keys(),
+            checkpoint_threshold: 1,
+            snapshot_anchors: mainnet_snapshot_anchors(),
+            dns_seed: true,
+            mine_on_demand: false,
+        }
+    }
+
+    /// Mainnet rules under the testnet ids
+    pub fn testnet() -> Self {
+        ChainParams {
+            network: "testnet".to_string(),
+            network_id: "Astram-testnet".to_string(),
+            chain_id: TESTNET_CHAIN_ID,
+            network_magic: 0xA57A_22B8,
+            ..Self::mainnet()
+        }
+    }
+
+    /// Local test network: trivial fixed target, no checkpoints, no DNS,
+    /// blocks mined on demand, coinbase spendable after 10 blocks. The v2
+    /// rules apply from genesis except DAG PoW, which would make on-demand
+    /// mining build a light cache.
+    pub fn regtest() -> Self {
+        ChainParams {
+            network: "regtest".to_string(),
+            network_id: "Astram-regtest".to_string(),
+            chain_id: REGTEST_CHAIN_ID,
+            network_magic: 0xA57A_7E57,
+            pow_limit_bits: 0x207fffff,
+            retarget: false,
+            coinbase_maturity: 10,
+            replay_protection_height: 0,
+            sighash_height: 0,
+            locktime_height: 0,
+            coinbase_maturity_height: 0,
+            tx_security_height: 0,
+            checkpoints: Vec::new(),
+            checkpoint_keys: Vec::new(),
+            snapshot_anchors: Vec::new(),
+            dns_seed: false,
+            mine_on_demand: true,
+            ..Self::mainnet()
+        }
+    }
+
+    /// Preset by name: mainnet, testnet or regtest
+    pub fn for_network(name: &str) -> Result<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "mainnet" => Ok(Self::mainnet()),
+            "testnet" => Ok(Self::testnet()),
+            "regtest" => Ok(Self::regtest()),
+            other => Err(anyhow!("unknown network '{}'", other)),
+        }
+    }
+
+    /// Custom network from a `KEY=value` file (see `parse`)
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("cannot read chain params {:?}: {}", path, e))?;
+        Self::parse(&text).map_err(|e| anyhow!("{:?}: {}", path, e))
+    }
+
+    /// Custom network: `BASE` names the preset to start from (default
+    /// testnet), the other keys override it. A custom network starts without
+    /// checkpoints, release keys or snapshot anchors; each
+    /// `CHECKPOINT=<height>:<hash>` or `CHECKPOINT_KEY=<hex>` line adds one.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut entries = Vec::new();
+        for (line_no, raw) in text.lines().enumerate() {
+            let line = raw.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| anyhow!("line {}: expected KEY=value", line_no + 1))?;
+            entries.push((line_no + 1, key.trim().to_ascii_uppercase(), value.trim().to_string()));
+        }
+
+        let base = entries
+            .iter()
+            .find(|(_, key, _)| key == "BASE")
+            .map(|(_, _, value)| value.as_str())
+            .unwrap_or("testnet");
+        let mut params = ChainParams {
+            network: "custom".to_string(),
+            checkpoints: Vec::new(),
+            checkpoint_keys: Vec::new(),
+            snapshot_anchors: Vec::new(),
+            ..Self::for_network(base)?
+        };
+
+        for (line_no, key, value) in entries {
+            let bad = |e: String| anyhow!("line {}: invalid {}: {}", line_no, key, e);
+            match key.as_str() {
+                "BASE" => {}
+                "NETWORK" => params.network = value,
+                "NETWORK_ID" => params.network_id = value,
+                "CHAIN_ID" => params.chain_id = value.parse().map_err(|e| bad(format!("{}", e)))?,
+                "NETWORK_MAGIC" => params.network_magic = parse_u32(&value).map_err(bad)?,
+                "GENESIS_TIMESTAMP" => {
+                    params.genesis_timestamp = value.parse().map_err(|e| bad(format!("{}", e)))?
+                }
+                "POW_LIMIT_BITS" => params.pow_limit_bits = parse_u32(&value).map_err(bad)?,
+                "RETARGET" => params.retarget = parse_bool(&value).map_err(bad)?,
+                "RETARGET_WINDOW" => {
+                    params.retarget_window = value.parse().map_err(|e| bad(format!("{}", e)))?
+                }
+                "BLOCK_INTERVAL" => {
+                    params.block_interval = value.parse().map_err(|e| bad(format!("{}", e)))?
+                }
+                "INITIAL_BLOCK_REWARD" => params.initial_block_reward = parse_u256(&value).map_err(bad)?,
+                "HALVING_INTERVAL" => {
+                    params.halving_interval = value.parse().map_err(|e| bad(format!("{}", e)))?
+                }
+                "COINBASE_MATURITY" => {
+                    params.coinbase_maturity = value.parse().map_err(|e| bad(format!("{}", e)))?
+                }
+                "MAX_REORG_DEPTH" => {
+                    params.max_reorg_depth = value.parse().map_err(|e| bad(format!("{}", e)))?
+                }
+                "DAG_POW_HEIGHT" => {
+                    params.dag_pow_height = value.parse().map_err(|e| bad(format!("{}", e)))?
+                }
+                "REPLAY_PROTECTION_HEIGHT" => {
+                    params.replay_protection_height = value.parse().map_err(|e| bad(format!("{}", e)))?
+                }
+                "SIGHASH_HEIGHT" => {
+                    params.sighash_height = value.parse().map_err(|e| bad(format!("{}", e)))?
+                }
+                "LOCKTIME_HEIGHT" => {
+                    params.locktime_height = value.parse().map_err(|e| bad(format!("{}", e)))?
+                }
+                "COINBASE_MATURITY_HEIGHT" => {
+                    params.coinbase_maturity_height = value.parse().map_err(|e| bad(format!("{}", e)))?
+                }
+                "TX_SECURITY_HEIGHT" => {
+                    params.tx_security_height = value.parse().map_err(|e| bad(format!("{}", e)))?
+                }
+                "DNS_SEED" => params.dns_seed = parse_bool(&value).map_err(bad)?,
+                "MINE_ON_DEMAND" => params.mine_on_demand = parse_bool(&value).map_err(bad)?,
+                "CHECKPOINT" => {
+                    let (height, hash) = value
+                        .split_once(':')
+                        .ok_or_else(|| bad("expected <height>:<hash>".to_string()))?;
+                    params.checkpoints.push(Checkpoint {
+                        height: height.trim().parse().map_err(|e| bad(format!("{}", e)))?,
+                        hash: hash.trim().to_string(),
+                        description: format!("{} checkpoint", params.network),
+                    });
+                }
+                "CHECKPOINT_KEY" => {
+                    crate::crypto::address_from_pubkey_hex(&value).map_err(bad)?;
+                    params.checkpoint_keys.push(value.to_ascii_lowercase());
+                }
+                "CHECKPOINT_THRESHOLD" => {
+                    params.checkpoint_threshold = value.parse().map_err(|e| bad(format!("{}", e)))?
+                }
+                _ => return Err(anyhow!("line {}: unknown key {}", line_no, key)),
+            }
+        }
+
+        if params.retarget_window == 0 || params.block_interval <= 0 || params.halving_interval == 0 {
+            return Err(anyhow!("RETARGET_WINDOW, BLOCK_INTERVAL and HALVING_INTERVAL must be positive"));
+        }
+        Ok(params)
+    }
+
+    /// Whether the block at `height` must satisfy the DAG PoW hash
+    pub fn uses_dag_pow(&self, height: u64) -> bool {
+        height >= self.dag_pow_height
+    }
+
+    /// Coinbase reward allowed at `height`: halves every `halving_interval`
+    pub fn block_reward(&self, height: u64) -> U256 {
+        let halvings = height / self.halving_interval;
+        if halvings >= MAX_HALVINGS {
+            return U256::zero();
+        }
+        self.initial_block_reward >> halvings as usize
+    }
+
+    /// Coins the reward schedule allows to be issued by blocks `0..=height`.
+    /// The UTXO total never exceeds it: miners may claim less, never more.
+    pub fn scheduled_supply(&self, height: u64) -> U256 {
+        let mut supply = U256::zero();
+        let mut start = 0u64;
+        while start <= height {
+            let reward = self.block_reward(start);
+            if reward.is_zero() {
+                break;
+            }
+            let end = height.min(start.saturating_add(self.halving_interval - 1));
+            supply += reward * U256::from(end - start + 1);
+            start = match start.checked_add(self.halving_interval) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+        supply
+    }
+}
+
+fn parse_u32(value: &str) -> std::result::Result<u32, String> {
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+    .map_err(|e| e.to_string())
+}
+
+fn parse_u256(value: &str) -> std::result::Result<U256, String> {
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => U256::from_str_radix(hex, 16).map_err(|e| format!("{:?}", e)),
+        None => U256::from_dec_str(value).map_err(|e| format!("{:?}", e)),
+    }
+}
+
+fn parse_bool(value: &str) -> std::result::Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" => Ok(true),
+        "false" | "0" | "no" => Ok(false),
+        _ => Err(format!("expected true or false, got '{}'", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::calculate_block_reward;
+
+    #[test]
+    fn mainnet_reward_matches_config() {
+        let params = ChainParams::mainnet();
+        for height in [0, HALVING_INTERVAL - 1, HALVING_INTERVAL, 5 * HALVING_INTERVAL, 40 * HALVING_INTERVAL] {
+            assert_eq!(params.block_reward(height), calculate_block_reward(height));
+        }
+    }
+
+    #[test]
+    fn custom_params_override_a_preset() {
+        let params = ChainParams::parse(
+            "# devnet\n\
+             BASE=regtest\n\
+             NETWORK=devnet\n\
+             CHAIN_ID=4242\n\
+             NETWORK_MAGIC=0xA57A4242\n\
+             BLOCK_INTERVAL=30\n\
+             INITIAL_BLOCK_REWARD=1000\n\
+             DAG_POW_HEIGHT=500\n\
+             CHECKPOINT=0:00aa\n",
+        )
+        .unwrap();
+        assert_eq!(params.network, "devnet");
+        assert_eq!(params.chain_id, 4242);
+        assert_eq!(params.network_magic, 0xA57A_4242);
+        assert_eq!(params.block_interval, 30);
+        assert_eq!(params.block_reward(0), U256::from(1000));
+        assert!(!params.uses_dag_pow(499) && params.uses_dag_pow(500));
+        assert_eq!(params.sighash_height, 0); // regtest base
+        assert!(params.mine_on_demand);
+        assert_eq!(params.checkpoints.len(), 1);
+        assert_eq!(params.checkpoints[0].hash, "00aa");
+
+        // Custom networks never inherit mainnet checkpoints
+        assert!(ChainParams::parse("BASE=mainnet\n").unwrap().checkpoints.is_empty());
+        assert!(ChainParams::parse("BLOCK_INTERVAL=0\n").is_err());
+        assert!(ChainParams::parse("UNKNOWN=1\n").is_err());
+        assert!(ChainParams::for_network("Regtest").unwrap().mine_on_demand);
+    }
+}
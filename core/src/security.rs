@@ -1,4 +1,5 @@
 use crate::block::Block;
+use crate::config::MAX_BLOCK_SIZE;
 use crate::transaction::Transaction;
 /// Security validation utilities for blockchain operations
 use anyhow::{Result, anyhow};
@@ -12,9 +13,13 @@ pub const MAX_TX_INPUTS: usize = 1000; // Prevent huge transactions
 pub const MAX_TX_OUTPUTS: usize = 1000;
 pub const MAX_FUTURE_TIMESTAMP: i64 = 7200; // 2 hours tolerance
 pub const MAX_REORG_DEPTH: u64 = 100; // Maximum blocks to reorganize (51% attack protection)
-pub const GENESIS_TIMESTAMP: i64 = 1738800000; // ~Feb 6, 2026 - blocks before this are invalid
+pub const GENESIS_TIMESTAMP: i64 = 1738800000; // ~Feb 6, 2026 - mainnet blocks before this are invalid
 pub const REORG_WARNING_THRESHOLD: u64 = 50;
 
+/// Mempool spam limits per spending address
+pub const MAX_TX_PER_ADDRESS: u32 = 100;
+pub const ADDRESS_RATE_WINDOW: i64 = 60;
+
 /// Block validation failure reasons (for statistics and debugging)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BlockFailureReason {
@@ -59,6 +64,38 @@ impl BlockFailureReason {
             Self::Other => "other",
         }
     }
+
+    /// Count this failure in `VALIDATION_STATS` and pass the error through
+    pub fn record(self, err: anyhow::Error) -> anyhow::Error {
+        VALIDATION_STATS.increment(self);
+        err
+    }
+
+    /// `record` for a failure the block hash does not commit to, wrapped
+    /// in `BodyFailure`
+    pub fn record_body(self, err: anyhow::Error) -> anyhow::Error {
+        VALIDATION_STATS.increment(self);
+        anyhow::Error::new(BodyFailure(err))
+    }
+}
+
+/// Block failure in data outside the block hash: signatures, pubkeys and
+/// witnesses are not part of the txid, and neither are the encoded sizes
+/// they make up. Another copy of the same block may be valid, so the hash
+/// must not be marked invalid for it.
+#[derive(Debug)]
+pub struct BodyFailure(pub anyhow::Error);
+
+impl std::fmt::Display for BodyFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl std::error::Error for BodyFailure {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
 }
 
 /// Global statistics for block validation failures
@@ -210,60 +247,60 @@ pub fn validate_transaction_security(tx: &Transaction, block_timestamp: i64) ->
         .map_err(|e| anyhow!("failed to serialize tx: {}", e))?;
 
     if tx_bytes.len() > MAX_TX_SIZE {
-        return Err(anyhow!(
+        return Err(BlockFailureReason::SecurityConstraint.record_body(anyhow!(
             "transaction too large: {} bytes (max {})",
             tx_bytes.len(),
             MAX_TX_SIZE
-        ));
+        )));
     }
 
     // 2. Check input/output count (prevent resource exhaustion)
     if tx.inputs.len() > MAX_TX_INPUTS {
-        return Err(anyhow!(
+        return Err(BlockFailureReason::SecurityConstraint.record(anyhow!(
             "too many inputs: {} (max {})",
             tx.inputs.len(),
             MAX_TX_INPUTS
-        ));
+        )));
     }
 
     if tx.outputs.len() > MAX_TX_OUTPUTS {
-        return Err(anyhow!(
+        return Err(BlockFailureReason::SecurityConstraint.record(anyhow!(
             "too many outputs: {} (max {})",
             tx.outputs.len(),
             MAX_TX_OUTPUTS
-        ));
+        )));
     }
 
     // 3. Timestamp validation (prevent future/old transactions)
     let current_time = chrono::Utc::now().timestamp();
 
     if tx.timestamp > current_time + MAX_FUTURE_TIMESTAMP {
-        return Err(anyhow!(
+        return Err(BlockFailureReason::TimestampTooFuture.record(anyhow!(
             "transaction timestamp too far in future: {} > {}",
             tx.timestamp,
             current_time + MAX_FUTURE_TIMESTAMP
-        ));
+        )));
     }
 
     // Transaction shouldn't be newer than containing block
     if tx.timestamp > block_timestamp {
-        return Err(anyhow!(
+        return Err(BlockFailureReason::TimestampTooFuture.record(anyhow!(
             "transaction timestamp ({}) exceeds block timestamp ({})",
             tx.timestamp,
             block_timestamp
-        ));
+        )));
     }
 
     // 4. Validate outputs are not dust (except coinbase)
     if !tx.inputs.is_empty() {
         for (idx, out) in tx.outputs.iter().enumerate() {
             if out.amount() < U256::from(MIN_OUTPUT_VALUE) {
-                return Err(anyhow!(
+                return Err(BlockFailureReason::SecurityConstraint.record(anyhow!(
                     "output {} is dust: {} (minimum {})",
                     idx,
                     out.amount(),
                     MIN_OUTPUT_VALUE
-                ));
+                )));
             }
         }
     }
@@ -271,49 +308,61 @@ pub fn validate_transaction_security(tx: &Transaction, block_timestamp: i64) ->
     // 5. Validate no empty addresses
     for (idx, out) in tx.outputs.iter().enumerate() {
         if out.to.is_empty() {
-            return Err(anyhow!("output {} has empty address", idx));
+            return Err(BlockFailureReason::SecurityConstraint
+                .record(anyhow!("output {} has empty address", idx)));
         }
     }
 
     Ok(())
 }
 
-/// Validate block security constraints
-pub fn validate_block_security(block: &Block) -> Result<()> {
+/// Validate block security constraints (`genesis_timestamp` from the chain params)
+pub fn validate_block_security(block: &Block, genesis_timestamp: i64) -> Result<()> {
     // 1. Block must have at least coinbase transaction
     if block.transactions.is_empty() {
-        return Err(anyhow!("block has no transactions"));
+        return Err(BlockFailureReason::EmptyBlock.record(anyhow!("block has no transactions")));
+    }
+
+    // Consensus block size limit
+    let size = crate::block::block_size(block)?;
+    if size > MAX_BLOCK_SIZE {
+        return Err(BlockFailureReason::SecurityConstraint.record_body(anyhow!(
+            "block too large: {} bytes (max {})",
+            size,
+            MAX_BLOCK_SIZE
+        )));
     }
 
     // 2. Validate block timestamp
     let current_time = chrono::Utc::now().timestamp();
 
     if block.header.timestamp > current_time + MAX_FUTURE_TIMESTAMP {
-        return Err(anyhow!(
+        return Err(BlockFailureReason::TimestampTooFuture.record(anyhow!(
             "block timestamp too far in future: {} > {}",
             block.header.timestamp,
             current_time + MAX_FUTURE_TIMESTAMP
-        ));
+        )));
     }
 
     // Prevent pre-genesis blocks
-    if block.header.timestamp < GENESIS_TIMESTAMP {
-        return Err(anyhow!(
+    if block.header.timestamp < genesis_timestamp {
+        return Err(BlockFailureReason::TimestampTooOld.record(anyhow!(
             "block timestamp predates genesis: {} < {}",
             block.header.timestamp,
-            GENESIS_TIMESTAMP
-        ));
+            genesis_timestamp
+        )));
     }
 
     // 3. Coinbase must be first and only coinbase
     let coinbase = &block.transactions[0];
     if !coinbase.inputs.is_empty() {
-        return Err(anyhow!("first transaction is not coinbase"));
+        return Err(BlockFailureReason::InvalidCoinbase.record(anyhow!("first transaction is not coinbase")));
     }
 
     for (idx, tx) in block.transactions.iter().enumerate().skip(1) {
         if tx.inputs.is_empty() {
-            return Err(anyhow!("non-first transaction {} is coinbase-like", idx));
+            return Err(BlockFailureReason::InvalidCoinbase
+                .record(anyhow!("non-first transaction {} is coinbase-like", idx)));
         }
     }
 
@@ -431,6 +480,8 @@ mod tests {
                 vout: 0,
                 pubkey: "0".repeat(130),
                 signature: Some("0".repeat(128)),
+                sequence: crate::transaction::SEQUENCE_FINAL,
+                witness: None,
             });
         }
 
@@ -439,6 +490,8 @@ mod tests {
             inputs,
             outputs: vec![TransactionOutput::new("addr".to_string(), U256::from(100))],
             timestamp: 0,
+            version: crate::transaction::TX_VERSION_LEGACY,
+            lock_time: 0,
         };
 
         let result = validate_transaction_security(&tx, 100);
@@ -446,6 +499,39 @@ mod tests {
         assert!(result.unwrap_err().to_string().contains("too large"));
     }
 
+    #[test]
+    fn test_rejections_are_counted() {
+        use crate::transaction::{Transaction, TransactionInput, TransactionOutput};
+
+        let now = chrono::Utc::now().timestamp();
+        let mut tx = Transaction {
+            txid: "dust".to_string(),
+            inputs: vec![TransactionInput {
+                txid: "0".repeat(64),
+                vout: 0,
+                pubkey: "0".repeat(64),
+                signature: None,
+                sequence: crate::transaction::SEQUENCE_FINAL,
+                witness: None,
+            }],
+            outputs: vec![TransactionOutput::new("0xabc".to_string(), U256::from(1))],
+            timestamp: now,
+            version: crate::transaction::TX_VERSION_LEGACY,
+            lock_time: 0,
+        };
+
+        let before = VALIDATION_STATS.security_constraint.load(Ordering::Relaxed);
+        assert!(validate_transaction_security(&tx, now).is_err());
+        assert!(VALIDATION_STATS.security_constraint.load(Ordering::Relaxed) > before);
+
+        tx.outputs[0] = TransactionOutput::new("0xabc".to_string(), U256::from(MIN_OUTPUT_VALUE));
+        assert!(validate_transaction_security(&tx, now).is_ok());
+
+        let before = VALIDATION_STATS.timestamp_too_future.load(Ordering::Relaxed);
+        assert!(validate_transaction_security(&tx, now - 1).is_err());
+        assert!(VALIDATION_STATS.timestamp_too_future.load(Ordering::Relaxed) > before);
+    }
+
     #[test]
     fn test_reorg_depth_validation() {
         // Safe reorganization
@@ -479,10 +565,10 @@ mod tests {
             hash: "0".repeat(64),
         };
 
-        assert!(validate_block_security(&block).is_err());
+        assert!(validate_block_security(&block, GENESIS_TIMESTAMP).is_err());
 
         // After genesis should be OK
         block.header.timestamp = GENESIS_TIMESTAMP + 1000;
-        assert!(validate_block_security(&block).is_ok());
+        assert!(validate_block_security(&block, GENESIS_TIMESTAMP).is_ok());
     }
 }
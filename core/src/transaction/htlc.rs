@@ -0,0 +1,171 @@
+use anyhow::{Result, anyhow};
+use bincode::{Decode, Encode};
+use primitive_types::U256;
+use sha2::{Digest, Sha256};
+
+use super::{BINCODE_CONFIG, LOCKTIME_THRESHOLD, Transaction, TransactionBuilder};
+use crate::crypto::WalletKeypair;
+use crate::utxo::Utxo;
+
+/// Prefix of HTLC addresses
+pub const HTLC_ADDRESS_PREFIX: &str = "hl";
+
+/// Domain tag hashed into every HTLC address
+const HTLC_DOMAIN: &str = "ASTRAM-HTLC-V1";
+
+/// Hash function committing to the swap secret
+#[derive(Encode, Decode, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha256,
+    Blake3,
+}
+
+impl HashAlgorithm {
+    pub fn digest(&self, data: &[u8]) -> [u8; 32] {
+        match self {
+            HashAlgorithm::Sha256 => Sha256::digest(data).into(),
+            HashAlgorithm::Blake3 => *blake3::hash(data).as_bytes(),
+        }
+    }
+}
+
+/// Hash-time-locked output. Spendable by `recipient_pubkey` with a preimage of
+/// `hash`, or by `refund_pubkey` in a transaction whose `lock_time` is at least
+/// `refund_height` (so it is only final above that height).
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Eq)]
+pub struct HtlcLock {
+    pub algorithm: HashAlgorithm,
+    pub hash: String,             // hex, 32 bytes
+    pub recipient_pubkey: String, // hex Ed25519
+    pub refund_pubkey: String,    // hex Ed25519
+    pub refund_height: u64,
+}
+
+impl HtlcLock {
+    pub fn new(
+        algorithm: HashAlgorithm,
+        hash: [u8; 32],
+        recipient_pubkey: &str,
+        refund_pubkey: &str,
+        refund_height: u64,
+    ) -> Result<Self> {
+        let lock = HtlcLock {
+            algorithm,
+            hash: hex::encode(hash),
+            recipient_pubkey: recipient_pubkey.to_lowercase(),
+            refund_pubkey: refund_pubkey.to_lowercase(),
+            refund_height,
+        };
+        lock.validate()?;
+        Ok(lock)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let hash = hex::decode(&self.hash).map_err(|e| anyhow!("invalid htlc hash: {}", e))?;
+        if hash.len() != 32 {
+            return Err(anyhow!("htlc hash must be 32 bytes, got {}", hash.len()));
+        }
+        crate::crypto::address_from_pubkey_hex(&self.recipient_pubkey)
+            .map_err(|e| anyhow!("invalid htlc recipient key: {}", e))?;
+        crate::crypto::address_from_pubkey_hex(&self.refund_pubkey)
+            .map_err(|e| anyhow!("invalid htlc refund key: {}", e))?;
+        if self.refund_height == 0 || self.refund_height >= LOCKTIME_THRESHOLD {
+            return Err(anyhow!("htlc refund height {} out of range", self.refund_height));
+        }
+        Ok(())
+    }
+
+    /// `hl` + first 20 bytes of SHA256(domain, lock)
+    pub fn address(&self) -> String {
+        let bytes = bincode::encode_to_vec((HTLC_DOMAIN, self), *BINCODE_CONFIG).unwrap_or_default();
+        let hash = Sha256::digest(&bytes);
+        format!("{}{}", HTLC_ADDRESS_PREFIX, hex::encode(&hash[..20]))
+    }
+
+    /// Whether `preimage` hashes to the committed value
+    pub fn preimage_matches(&self, preimage: &[u8]) -> bool {
+        hex::encode(self.algorithm.digest(preimage)) == self.hash.to_lowercase()
+    }
+}
+
+pub fn is_htlc_address(address: &str) -> bool {
+    address.starts_with(HTLC_ADDRESS_PREFIX)
+}
+
+/// Claim an HTLC output with the swap secret, paying `to` everything but `fee`
+pub fn build_claim_tx(
+    spent: &Utxo,
+    lock: &HtlcLock,
+    preimage: &[u8],
+    recipient: &WalletKeypair,
+    to: &str,
+    fee: U256,
+    chain_id: u64,
+) -> Result<Transaction> {
+    let amount = spent
+        .amount()
+        .checked_sub(fee)
+        .ok_or_else(|| anyhow!("fee exceeds htlc amount"))?;
+    TransactionBuilder::new()
+        .chain_id(chain_id)
+        .add_htlc_claim_input(spent.clone(), lock.clone(), preimage.to_vec(), recipient)
+        .add_output(to, amount)
+        .build()
+}
+
+/// Refund an expired HTLC output to `to`; final only above `lock.refund_height`
+pub fn build_refund_tx(
+    spent: &Utxo,
+    lock: &HtlcLock,
+    refund: &WalletKeypair,
+    to: &str,
+    fee: U256,
+    chain_id: u64,
+) -> Result<Transaction> {
+    let amount = spent
+        .amount()
+        .checked_sub(fee)
+        .ok_or_else(|| anyhow!("fee exceeds htlc amount"))?;
+    TransactionBuilder::new()
+        .chain_id(chain_id)
+        .add_htlc_refund_input(spent.clone(), lock.clone(), refund)
+        .add_output(to, amount)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn htlc_lock_rules() {
+        let alice = WalletKeypair::new();
+        let bob = WalletKeypair::new();
+        let secret = b"swap secret";
+
+        for algorithm in [HashAlgorithm::Sha256, HashAlgorithm::Blake3] {
+            let lock = HtlcLock::new(
+                algorithm,
+                algorithm.digest(secret),
+                &bob.public_hex(),
+                &alice.public_hex(),
+                1_000,
+            )
+            .unwrap();
+            assert!(is_htlc_address(&lock.address()));
+            assert!(lock.preimage_matches(secret));
+            assert!(!lock.preimage_matches(b"wrong"));
+        }
+
+        let hash = HashAlgorithm::Sha256.digest(secret);
+        assert!(HtlcLock::new(HashAlgorithm::Sha256, hash, &bob.public_hex(), &alice.public_hex(), 0).is_err());
+        assert!(HtlcLock::new(
+            HashAlgorithm::Sha256,
+            hash,
+            &bob.public_hex(),
+            &alice.public_hex(),
+            LOCKTIME_THRESHOLD
+        )
+        .is_err());
+    }
+}
@@ -1,20 +1,121 @@
 use anyhow::Result;
-use bincode::error::EncodeError;
+use bincode::de::Decoder;
+use bincode::de::read::Reader;
+use bincode::enc::Encoder;
+use bincode::error::{DecodeError, EncodeError};
 use bincode::{Decode, Encode, config};
 use hex;
 use once_cell::sync::Lazy;
 use primitive_types::U256;
 use sha2::{Digest, Sha256};
 
+use crate::config::MAINNET_CHAIN_ID;
+use crate::network::ChainParams;
+use crate::utxo::Utxo;
+
+pub mod htlc;
+pub mod multisig;
+
+pub use htlc::{HashAlgorithm, HtlcLock};
+pub use multisig::MultisigLock;
+
 pub static BINCODE_CONFIG: Lazy<config::Configuration> = Lazy::new(|| config::standard());
 
+/// Domain tag prepended to every sighash preimage
+const SIGHASH_DOMAIN: &str = "ASTRAM-SIGHASH-V1";
+
+/// Original encoding: no version, lock_time or sequences on the wire
+pub const TX_VERSION_LEGACY: u32 = 1;
+/// Adds an absolute `lock_time` and per-input relative `sequence` locks
+pub const TX_VERSION_LOCKTIME: u32 = 2;
+/// Adds an optional per-input `witness` for script-hash outputs (multisig, HTLC)
+pub const TX_VERSION_WITNESS: u32 = 3;
+/// Highest transaction version this node understands
+pub const TX_VERSION_CURRENT: u32 = TX_VERSION_WITNESS;
+
+/// Versioned encodings start with this value where legacy ones start with the
+/// txid string length (which can never be u64::MAX)
+const VERSIONED_TX_MARKER: u64 = u64::MAX;
+
+/// `lock_time` below this is a block height, otherwise a unix timestamp
+pub const LOCKTIME_THRESHOLD: u64 = 500_000_000;
+
+/// Sequence of an input with no relative lock
+pub const SEQUENCE_FINAL: u32 = 0xffff_ffff;
+/// Set: the input has no relative lock
+pub const SEQUENCE_LOCKTIME_DISABLE_FLAG: u32 = 1 << 31;
+/// Set: the relative lock is in units of 512 seconds, otherwise in blocks
+pub const SEQUENCE_LOCKTIME_TYPE_FLAG: u32 = 1 << 22;
+/// Bits holding the relative lock value
+pub const SEQUENCE_LOCKTIME_MASK: u32 = 0x0000_ffff;
+/// Time-based relative locks count 2^9 = 512 second units
+pub const SEQUENCE_LOCKTIME_GRANULARITY: u32 = 9;
+
 /// Input: previous txid and vout index
-#[derive(Encode, Decode, Debug, Clone)]
+///
+/// Encoded as part of `Transaction`; `sequence` is only on the wire from version 2
+/// and `witness` from version 3.
+#[derive(Debug, Clone)]
 pub struct TransactionInput {
     pub txid: String, // hex
     pub vout: u32,
     pub pubkey: String,            // hex of public key (secp256k1, uncompressed)
     pub signature: Option<String>, // hex of signature (64 bytes compact)
+    pub sequence: u32,             // relative lock (BIP68 layout), SEQUENCE_FINAL if unused
+    pub witness: Option<Witness>,  // unlock data for script-hash outputs (pubkey/signature unused)
+}
+
+/// Unlock data for outputs not owned by a single key.
+/// Excluded from the txid and sighash, like `signature`.
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Eq)]
+pub enum Witness {
+    /// Spend of a `MultisigLock` address: one signature slot per lock key
+    Multisig {
+        lock: MultisigLock,
+        signatures: Vec<Option<String>>,
+    },
+    /// HTLC claim: hex preimage of the lock hash, signed by the recipient
+    /// through the input's `pubkey`/`signature`
+    HtlcClaim { lock: HtlcLock, preimage: String },
+    /// HTLC refund: signed by the refund key; needs `lock_time >= refund_height`
+    HtlcRefund { lock: HtlcLock },
+}
+
+/// Relative time-lock carried by an input's `sequence`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelativeLock {
+    /// Spendable once the spent output has this many confirmations beyond its own block
+    Blocks(u64),
+    /// Spendable once median-time-past has advanced this many seconds
+    Seconds(i64),
+}
+
+impl TransactionInput {
+    /// Address this input proves ownership of: the witness lock address,
+    /// otherwise the address of `pubkey`
+    pub fn spender_address(&self) -> Result<String, anyhow::Error> {
+        match &self.witness {
+            Some(Witness::Multisig { lock, .. }) => Ok(lock.address()),
+            Some(Witness::HtlcClaim { lock, .. }) | Some(Witness::HtlcRefund { lock }) => {
+                Ok(lock.address())
+            }
+            None => crate::crypto::address_from_pubkey_hex(&self.pubkey)
+                .map_err(|e| anyhow::anyhow!("invalid pubkey address: {}", e)),
+        }
+    }
+
+    /// Relative lock of this input in a transaction of `tx_version` (None if disabled)
+    pub fn relative_lock(&self, tx_version: u32) -> Option<RelativeLock> {
+        if tx_version < TX_VERSION_LOCKTIME || self.sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG != 0 {
+            return None;
+        }
+        let value = self.sequence & SEQUENCE_LOCKTIME_MASK;
+        if self.sequence & SEQUENCE_LOCKTIME_TYPE_FLAG != 0 {
+            Some(RelativeLock::Seconds((value as i64) << SEQUENCE_LOCKTIME_GRANULARITY))
+        } else {
+            Some(RelativeLock::Blocks(value as u64))
+        }
+    }
 }
 
 /// Output: recipient address (assumed to be a simple pubkey hash) + amount
@@ -43,12 +144,112 @@ impl TransactionOutput {
 }
 
 /// Transaction: inputs / outputs / timestamp / txid
-#[derive(Encode, Decode, Debug, Clone)]
+///
+/// Wire format (bincode standard):
+/// - version 1: `txid, inputs[txid, vout, pubkey, signature], outputs, timestamp`
+/// - version 2: `VERSIONED_TX_MARKER, version, txid,
+///   inputs[txid, vout, pubkey, signature, sequence], outputs, timestamp, lock_time`
+/// - version 3: as version 2 with `witness` after each input's `sequence`
+#[derive(Debug, Clone)]
 pub struct Transaction {
     pub txid: String,     // UTXO transaction tracking (SHA256 double hash)
     pub inputs: Vec<TransactionInput>,
     pub outputs: Vec<TransactionOutput>,
     pub timestamp: i64,
+    pub version: u32,
+    pub lock_time: u64, // height (< LOCKTIME_THRESHOLD) or unix time; 0 = none
+}
+
+impl Encode for Transaction {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), EncodeError> {
+        let versioned = self.version >= TX_VERSION_LOCKTIME;
+        let with_witness = self.version >= TX_VERSION_WITNESS;
+        if !with_witness && self.inputs.iter().any(|i| i.witness.is_some()) {
+            return Err(EncodeError::Other("input witness requires transaction version 3"));
+        }
+        if versioned {
+            VERSIONED_TX_MARKER.encode(encoder)?;
+            self.version.encode(encoder)?;
+        }
+        self.txid.encode(encoder)?;
+        (self.inputs.len() as u64).encode(encoder)?;
+        for inp in &self.inputs {
+            inp.txid.encode(encoder)?;
+            inp.vout.encode(encoder)?;
+            inp.pubkey.encode(encoder)?;
+            inp.signature.encode(encoder)?;
+            if versioned {
+                inp.sequence.encode(encoder)?;
+            }
+            if with_witness {
+                inp.witness.encode(encoder)?;
+            }
+        }
+        self.outputs.encode(encoder)?;
+        self.timestamp.encode(encoder)?;
+        if versioned {
+            self.lock_time.encode(encoder)?;
+        }
+        Ok(())
+    }
+}
+
+impl<Context> Decode<Context> for Transaction {
+    fn decode<D: Decoder<Context = Context>>(decoder: &mut D) -> Result<Self, DecodeError> {
+        // Legacy transactions start with the txid length; versioned ones with the marker
+        let first = u64::decode(decoder)?;
+        let (version, txid) = if first == VERSIONED_TX_MARKER {
+            let version = u32::decode(decoder)?;
+            if !(TX_VERSION_LOCKTIME..=TX_VERSION_CURRENT).contains(&version) {
+                return Err(DecodeError::Other("unsupported transaction version"));
+            }
+            (version, String::decode(decoder)?)
+        } else {
+            (TX_VERSION_LEGACY, decode_string_body(decoder, first)?)
+        };
+        let versioned = version >= TX_VERSION_LOCKTIME;
+        let with_witness = version >= TX_VERSION_WITNESS;
+
+        let input_count = u64::decode(decoder)?;
+        let input_count = usize::try_from(input_count)
+            .map_err(|_| DecodeError::OutsideUsizeRange(input_count))?;
+        decoder.claim_container_read::<TransactionInput>(input_count)?;
+        let mut inputs = Vec::with_capacity(input_count);
+        for _ in 0..input_count {
+            decoder.unclaim_bytes_read(core::mem::size_of::<TransactionInput>());
+            inputs.push(TransactionInput {
+                txid: String::decode(decoder)?,
+                vout: u32::decode(decoder)?,
+                pubkey: String::decode(decoder)?,
+                signature: Option::<String>::decode(decoder)?,
+                sequence: if versioned { u32::decode(decoder)? } else { SEQUENCE_FINAL },
+                witness: if with_witness { Option::<Witness>::decode(decoder)? } else { None },
+            });
+        }
+
+        let outputs = Vec::<TransactionOutput>::decode(decoder)?;
+        let timestamp = i64::decode(decoder)?;
+        let lock_time = if versioned { u64::decode(decoder)? } else { 0 };
+
+        Ok(Transaction {
+            txid,
+            inputs,
+            outputs,
+            timestamp,
+            version,
+            lock_time,
+        })
+    }
+}
+bincode::impl_borrow_decode!(Transaction);
+
+/// Rest of a bincode `String` whose length prefix has already been read
+fn decode_string_body<D: Decoder>(decoder: &mut D, len: u64) -> Result<String, DecodeError> {
+    let len = usize::try_from(len).map_err(|_| DecodeError::OutsideUsizeRange(len))?;
+    decoder.claim_container_read::<u8>(len)?;
+    let mut bytes = vec![0u8; len];
+    decoder.reader().read(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|e| DecodeError::Utf8 { inner: e.utf8_error() })
 }
 
 impl Transaction {
@@ -59,11 +260,45 @@ impl Transaction {
             inputs: vec![],
             outputs: vec![output],
             timestamp: chrono::Utc::now().timestamp(),
+            version: TX_VERSION_LEGACY,
+            lock_time: 0,
         };
         tx.with_hashes()
     }
 
+    /// Whether the absolute `lock_time` allows inclusion in a block at `height`
+    /// whose parent has median-time-past `median_time_past` (BIP113)
+    pub fn is_final(&self, height: u64, median_time_past: i64) -> bool {
+        if self.lock_time == 0 {
+            return true;
+        }
+        if self.lock_time < LOCKTIME_THRESHOLD {
+            self.lock_time < height
+        } else {
+            // Compared as u64: lock times above i64::MAX must not wrap negative
+            median_time_past >= 0 && self.lock_time < median_time_past as u64
+        }
+    }
+
     pub fn serialize_for_hash(&self) -> Result<Vec<u8>, EncodeError> {
+        if self.version >= TX_VERSION_LOCKTIME {
+            let inputs_for_hash: Vec<_> = self
+                .inputs
+                .iter()
+                .map(|i| (i.txid.clone(), i.vout, i.sequence))
+                .collect();
+            return bincode::encode_to_vec(
+                (
+                    self.version,
+                    &inputs_for_hash,
+                    &self.outputs,
+                    &self.timestamp,
+                    self.lock_time,
+                ),
+                *BINCODE_CONFIG,
+            );
+        }
+
         let inputs_for_hash: Vec<_> = self
             .inputs
             .iter()
@@ -98,9 +333,24 @@ impl Transaction {
         self.with_hashes()
     }
 
+    /// Legacy signing preimage bound to `chain_id`
+    fn legacy_signing_bytes(&self, chain_id: u64) -> Result<Vec<u8>, EncodeError> {
+        let mut bytes = bincode::encode_to_vec((SIGHASH_DOMAIN, chain_id), *BINCODE_CONFIG)?;
+        bytes.extend(self.serialize_for_hash()?);
+        Ok(bytes)
+    }
+
     /// sign inputs using secp256k1
-    pub fn sign(&mut self, secret_key: &crate::crypto::WalletKeypair) -> Result<(), anyhow::Error> {
-        let tx_bytes = self.serialize_for_hash()?;
+    ///
+    /// Legacy scheme: one signature over the whole tx and `chain_id`, copied into
+    /// every input. Only accepted below `ChainParams::sighash_height`; new code
+    /// should use `sign_input` or `TransactionBuilder`.
+    pub fn sign(
+        &mut self,
+        secret_key: &crate::crypto::WalletKeypair,
+        chain_id: u64,
+    ) -> Result<(), anyhow::Error> {
+        let tx_bytes = self.legacy_signing_bytes(chain_id)?;
         let sig_bytes = secret_key.sign(&tx_bytes);
 
         let sig_hex = hex::encode(sig_bytes);
@@ -113,14 +363,234 @@ impl Transaction {
         Ok(())
     }
 
-    /// verify signatures using secp256k1
-    pub fn verify_signatures(&self) -> Result<bool, anyhow::Error> {
+    /// Per-input signature hash (SHA256 double hash).
+    ///
+    /// Commits to every input outpoint, the index being signed, the outpoint,
+    /// amount and owner of the UTXO it spends, and all outputs. Pubkeys and
+    /// signatures are excluded so inputs can be signed in any order.
+    /// `chain_id` keeps signatures from being replayed on another network.
+    /// From version 2 it also commits to the version, sequences and lock_time.
+    pub fn sighash(
+        &self,
+        input_index: usize,
+        spent: &Utxo,
+        chain_id: u64,
+    ) -> Result<[u8; 32], anyhow::Error> {
+        let inp = self
+            .inputs
+            .get(input_index)
+            .ok_or_else(|| anyhow::anyhow!("input index {} out of range", input_index))?;
+        if inp.txid != spent.txid || inp.vout != spent.vout {
+            return Err(anyhow::anyhow!(
+                "spent utxo {}:{} does not match input {}",
+                spent.txid,
+                spent.vout,
+                input_index
+            ));
+        }
+
+        let spent_for_hash = (&spent.txid, spent.vout, spent.amount().0, spent.to.to_lowercase());
+
+        let bytes = if self.version >= TX_VERSION_LOCKTIME {
+            let inputs_for_hash: Vec<_> = self
+                .inputs
+                .iter()
+                .map(|i| (i.txid.clone(), i.vout, i.sequence))
+                .collect();
+            bincode::encode_to_vec(
+                (
+                    SIGHASH_DOMAIN,
+                    chain_id,
+                    self.version,
+                    &inputs_for_hash,
+                    input_index as u32,
+                    &spent_for_hash,
+                    &self.outputs,
+                    &self.timestamp,
+                    self.lock_time,
+                ),
+                *BINCODE_CONFIG,
+            )?
+        } else {
+            let inputs_for_hash: Vec<_> = self
+                .inputs
+                .iter()
+                .map(|i| (i.txid.clone(), i.vout))
+                .collect();
+            bincode::encode_to_vec(
+                (
+                    SIGHASH_DOMAIN,
+                    chain_id,
+                    &inputs_for_hash,
+                    input_index as u32,
+                    &spent_for_hash,
+                    &self.outputs,
+                    &self.timestamp,
+                ),
+                *BINCODE_CONFIG,
+            )?
+        };
+        let h1 = Sha256::digest(&bytes);
+        let h2 = Sha256::digest(h1);
+        Ok(h2.into())
+    }
+
+    /// Sign a single input with its own sighash.
+    pub fn sign_input(
+        &mut self,
+        input_index: usize,
+        spent: &Utxo,
+        keypair: &crate::crypto::WalletKeypair,
+        chain_id: u64,
+    ) -> Result<(), anyhow::Error> {
+        let hash = self.sighash(input_index, spent, chain_id)?;
+        let sig_bytes = keypair.sign(&hash);
+
+        let inp = &mut self.inputs[input_index];
+        inp.pubkey = keypair.public_hex();
+        inp.signature = Some(hex::encode(sig_bytes));
+        Ok(())
+    }
+
+    /// Add `keypair`'s signature to a multisig input (cosigners may sign
+    /// separately, in any order). The input must already carry its witness.
+    pub fn sign_multisig_input(
+        &mut self,
+        input_index: usize,
+        spent: &Utxo,
+        keypair: &crate::crypto::WalletKeypair,
+        chain_id: u64,
+    ) -> Result<(), anyhow::Error> {
+        let hash = self.sighash(input_index, spent, chain_id)?;
+        let sig_hex = hex::encode(keypair.sign(&hash));
+
+        match &mut self.inputs[input_index].witness {
+            Some(Witness::Multisig { lock, signatures }) => {
+                let slot = lock.key_index(&keypair.public_hex()).ok_or_else(|| {
+                    anyhow::anyhow!("key {} is not part of the multisig lock", keypair.public_hex())
+                })?;
+                signatures.resize(lock.pubkeys.len(), None);
+                signatures[slot] = Some(sig_hex);
+                Ok(())
+            }
+            _ => Err(anyhow::anyhow!("input {} has no multisig witness", input_index)),
+        }
+    }
+
+    /// Verify per-input signatures.
+    ///
+    /// `spent` holds the UTXO consumed by each input, in input order. Each input
+    /// must also own its UTXO: the address of `pubkey`, or of the witness lock.
+    pub fn verify_signatures(&self, spent: &[Utxo], chain_id: u64) -> Result<bool, anyhow::Error> {
+        match self.signature_checks(spent, chain_id)? {
+            Some(checks) => Ok(checks.iter().all(SignatureCheck::is_valid)),
+            None => Ok(false),
+        }
+    }
+
+    /// Signatures the per-input scheme relies on, once every other spend rule
+    /// holds (ownership, witness shape, HTLC preimage and refund height);
+    /// None if one of those fails. Lets block validation verify the
+    /// signatures of many transactions in one batch.
+    pub fn signature_checks(
+        &self,
+        spent: &[Utxo],
+        chain_id: u64,
+    ) -> Result<Option<Vec<SignatureCheck>>, anyhow::Error> {
         if self.inputs.is_empty() {
-            return Ok(true);
+            return Ok(Some(Vec::new()));
         }
+        if spent.len() != self.inputs.len() {
+            return Err(anyhow::anyhow!(
+                "expected {} spent utxos, got {}",
+                self.inputs.len(),
+                spent.len()
+            ));
+        }
+
+        let mut checks = Vec::with_capacity(self.inputs.len());
+        for (i, (inp, utxo)) in self.inputs.iter().zip(spent).enumerate() {
+            let hash = self.sighash(i, utxo, chain_id)?;
+            match inp.spender_address() {
+                Ok(owner) if owner.to_lowercase() == utxo.to.to_lowercase() => {}
+                _ => return Ok(None),
+            }
+
+            match &inp.witness {
+                Some(Witness::Multisig { lock, signatures }) => {
+                    if lock.validate().is_err() || signatures.len() != lock.pubkeys.len() {
+                        return Ok(None);
+                    }
+                    let signed: Vec<(&String, &String)> = lock
+                        .pubkeys
+                        .iter()
+                        .zip(signatures)
+                        .filter_map(|(pk, sig)| sig.as_ref().map(|sig| (pk, sig)))
+                        .collect();
+                    if signed.len() < lock.threshold as usize {
+                        return Ok(None);
+                    }
+                    for (pk, sig) in signed {
+                        checks.push(SignatureCheck {
+                            pubkey: pk.clone(),
+                            message: hash,
+                            signature: hex::decode(sig)?,
+                        });
+                    }
+                }
+                Some(Witness::HtlcClaim { lock, preimage }) => {
+                    let preimage = hex::decode(preimage).unwrap_or_default();
+                    if lock.validate().is_err()
+                        || !lock.preimage_matches(&preimage)
+                        || inp.pubkey.to_lowercase() != lock.recipient_pubkey.to_lowercase()
+                    {
+                        return Ok(None);
+                    }
+                    checks.push(Self::input_signature_check(inp, hash)?);
+                }
+                Some(Witness::HtlcRefund { lock }) => {
+                    // lock_time makes the tx non-final until after refund_height
+                    if lock.validate().is_err()
+                        || self.lock_time < lock.refund_height
+                        || self.lock_time >= LOCKTIME_THRESHOLD
+                        || inp.pubkey.to_lowercase() != lock.refund_pubkey.to_lowercase()
+                    {
+                        return Ok(None);
+                    }
+                    checks.push(Self::input_signature_check(inp, hash)?);
+                }
+                None => checks.push(Self::input_signature_check(inp, hash)?),
+            }
+        }
+        Ok(Some(checks))
+    }
 
-        let tx_bytes = self.serialize_for_hash()?;
+    /// Single-key signature of `inp` over `hash`
+    fn input_signature_check(inp: &TransactionInput, hash: [u8; 32]) -> Result<SignatureCheck, anyhow::Error> {
+        let sig_hex = inp
+            .signature
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Missing signature"))?;
+        Ok(SignatureCheck {
+            pubkey: inp.pubkey.clone(),
+            message: hash,
+            signature: hex::decode(sig_hex)?,
+        })
+    }
 
+    /// verify legacy whole-transaction signatures bound to `chain_id` (see `sign`)
+    pub fn verify_legacy_signatures(&self, chain_id: u64) -> Result<bool, anyhow::Error> {
+        self.verify_whole_tx_signatures(&self.legacy_signing_bytes(chain_id)?)
+    }
+
+    /// verify legacy signatures made before chain ids, over the bare tx hash
+    /// preimage. They replay across networks, so they are only valid below
+    /// `ChainParams::replay_protection_height`.
+    pub fn verify_unbound_legacy_signatures(&self) -> Result<bool, anyhow::Error> {
+        self.verify_whole_tx_signatures(&self.serialize_for_hash()?)
+    }
+
+    fn verify_whole_tx_signatures(&self, signed_bytes: &[u8]) -> Result<bool, anyhow::Error> {
         for inp in &self.inputs {
             let sig_hex = inp
                 .signature
@@ -130,12 +600,255 @@ impl Transaction {
             // Standard Astram signature verification
             let sig_bytes = hex::decode(sig_hex)?;
 
-            if !crate::crypto::verify_signature(&inp.pubkey, &tx_bytes, &sig_bytes) {
+            if !crate::crypto::verify_signature(&inp.pubkey, signed_bytes, &sig_bytes) {
                 return Ok(false);
             }
         }
         Ok(true)
     }
+
+    /// Verify signatures under the rules in force at block `height`:
+    /// per-input sighash always, and strict signatures (see
+    /// `SignatureCheck::is_valid_strict`) from `params.sighash_height`;
+    /// legacy signatures only below it, and those without a chain id only
+    /// below `params.replay_protection_height`.
+    pub fn verify_signatures_at(
+        &self,
+        spent: &[Utxo],
+        height: u64,
+        params: &ChainParams,
+    ) -> Result<bool, anyhow::Error> {
+        if height >= params.sighash_height {
+            return match self.signature_checks(spent, params.chain_id)? {
+                Some(checks) => Ok(checks.iter().all(SignatureCheck::is_valid_strict)),
+                None => Ok(false),
+            };
+        }
+        if self.verify_signatures(spent, params.chain_id)? {
+            return Ok(true);
+        }
+        if self.verify_legacy_signatures(params.chain_id)? {
+            return Ok(true);
+        }
+        if height < params.replay_protection_height {
+            return self.verify_unbound_legacy_signatures();
+        }
+        Ok(false)
+    }
+}
+
+/// One Ed25519 signature an input relies on (see `Transaction::signature_checks`)
+#[derive(Debug, Clone)]
+pub struct SignatureCheck {
+    pub pubkey: String,     // hex
+    pub message: [u8; 32],  // sighash of the input
+    pub signature: Vec<u8>, // 64 bytes
+}
+
+impl SignatureCheck {
+    pub fn is_valid(&self) -> bool {
+        crate::crypto::verify_signature(&self.pubkey, &self.message, &self.signature)
+    }
+
+    /// `is_valid` under the key and nonce rules of batch verification,
+    /// which blocks use from `ChainParams::sighash_height`
+    pub fn is_valid_strict(&self) -> bool {
+        crate::crypto::verify_signature_strict(&self.pubkey, &self.message, &self.signature)
+    }
+}
+
+/// Keys that sign one builder input
+enum InputSigner<'a> {
+    Single(&'a crate::crypto::WalletKeypair),
+    Multisig(MultisigLock, Vec<&'a crate::crypto::WalletKeypair>),
+    /// Single key plus witness (HTLC claim / refund)
+    Witnessed(Witness, &'a crate::crypto::WalletKeypair),
+}
+
+/// Builds a transaction and signs each input with the keypair owning it.
+///
+/// Inputs may come from different wallets; each one gets its own sighash.
+pub struct TransactionBuilder<'a> {
+    inputs: Vec<(Utxo, InputSigner<'a>, u32)>,
+    outputs: Vec<TransactionOutput>,
+    timestamp: Option<i64>,
+    lock_time: u64,
+    chain_id: u64,
+}
+
+impl<'a> TransactionBuilder<'a> {
+    pub fn new() -> Self {
+        TransactionBuilder {
+            inputs: vec![],
+            outputs: vec![],
+            timestamp: None,
+            lock_time: 0,
+            chain_id: MAINNET_CHAIN_ID,
+        }
+    }
+
+    /// Spend `utxo`, signing with `keypair`
+    pub fn add_input(self, utxo: Utxo, keypair: &'a crate::crypto::WalletKeypair) -> Self {
+        self.add_input_with_sequence(utxo, keypair, SEQUENCE_FINAL)
+    }
+
+    /// Spend `utxo` with a relative lock encoded in `sequence`
+    pub fn add_input_with_sequence(
+        mut self,
+        utxo: Utxo,
+        keypair: &'a crate::crypto::WalletKeypair,
+        sequence: u32,
+    ) -> Self {
+        self.inputs.push((utxo, InputSigner::Single(keypair), sequence));
+        self
+    }
+
+    /// Spend a multisig `utxo`, signing with the cosigner keys available here.
+    /// Fewer than `threshold` keys yields a partially signed input that other
+    /// cosigners complete with `Transaction::sign_multisig_input`.
+    pub fn add_multisig_input(
+        mut self,
+        utxo: Utxo,
+        lock: MultisigLock,
+        keypairs: Vec<&'a crate::crypto::WalletKeypair>,
+    ) -> Self {
+        self.inputs.push((utxo, InputSigner::Multisig(lock, keypairs), SEQUENCE_FINAL));
+        self
+    }
+
+    /// Claim an HTLC `utxo` with `preimage`, signing as the recipient
+    pub fn add_htlc_claim_input(
+        mut self,
+        utxo: Utxo,
+        lock: HtlcLock,
+        preimage: Vec<u8>,
+        recipient: &'a crate::crypto::WalletKeypair,
+    ) -> Self {
+        let witness = Witness::HtlcClaim {
+            lock,
+            preimage: hex::encode(preimage),
+        };
+        self.inputs.push((utxo, InputSigner::Witnessed(witness, recipient), SEQUENCE_FINAL));
+        self
+    }
+
+    /// Refund an HTLC `utxo`; raises `lock_time` to the lock's refund height
+    pub fn add_htlc_refund_input(
+        mut self,
+        utxo: Utxo,
+        lock: HtlcLock,
+        refund: &'a crate::crypto::WalletKeypair,
+    ) -> Self {
+        self.lock_time = self.lock_time.max(lock.refund_height);
+        let witness = Witness::HtlcRefund { lock };
+        self.inputs.push((utxo, InputSigner::Witnessed(witness, refund), SEQUENCE_FINAL));
+        self
+    }
+
+    pub fn add_output(mut self, to: &str, amount: U256) -> Self {
+        self.outputs.push(TransactionOutput::new(to.to_string(), amount));
+        self
+    }
+
+    /// Override the timestamp (defaults to now)
+    pub fn timestamp(mut self, timestamp: i64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Network the signatures are bound to (defaults to mainnet)
+    pub fn chain_id(mut self, chain_id: u64) -> Self {
+        self.chain_id = chain_id;
+        self
+    }
+
+    /// Absolute lock: block height (< LOCKTIME_THRESHOLD) or unix time
+    pub fn lock_time(mut self, lock_time: u64) -> Self {
+        self.lock_time = lock_time;
+        self
+    }
+
+    /// Assemble, sign every input and set the txid.
+    /// Uses the oldest encoding that can carry the requested locks and witnesses.
+    pub fn build(self) -> Result<Transaction, anyhow::Error> {
+        for (_, signer, _) in &self.inputs {
+            if let InputSigner::Witnessed(Witness::HtlcRefund { lock }, _) = signer
+                && (self.lock_time < lock.refund_height || self.lock_time >= LOCKTIME_THRESHOLD)
+            {
+                return Err(anyhow::anyhow!(
+                    "htlc refund needs a height lock_time >= {}",
+                    lock.refund_height
+                ));
+            }
+        }
+
+        let needs_witness = self
+            .inputs
+            .iter()
+            .any(|(_, signer, _)| !matches!(signer, InputSigner::Single(_)));
+        let needs_locks =
+            self.lock_time != 0 || self.inputs.iter().any(|(_, _, seq)| *seq != SEQUENCE_FINAL);
+        let version = if needs_witness {
+            TX_VERSION_WITNESS
+        } else if needs_locks {
+            TX_VERSION_LOCKTIME
+        } else {
+            TX_VERSION_LEGACY
+        };
+
+        let mut tx = Transaction {
+            txid: "".to_string(),
+            inputs: self
+                .inputs
+                .iter()
+                .map(|(u, signer, seq)| {
+                    let (pubkey, witness) = match signer {
+                        InputSigner::Single(kp) => (kp.public_hex(), None),
+                        InputSigner::Multisig(lock, _) => (
+                            "".to_string(),
+                            Some(Witness::Multisig {
+                                lock: lock.clone(),
+                                signatures: vec![None; lock.pubkeys.len()],
+                            }),
+                        ),
+                        InputSigner::Witnessed(witness, kp) => (kp.public_hex(), Some(witness.clone())),
+                    };
+                    TransactionInput {
+                        txid: u.txid.clone(),
+                        vout: u.vout,
+                        pubkey,
+                        signature: None,
+                        sequence: *seq,
+                        witness,
+                    }
+                })
+                .collect(),
+            outputs: self.outputs,
+            timestamp: self.timestamp.unwrap_or_else(|| chrono::Utc::now().timestamp()),
+            version,
+            lock_time: self.lock_time,
+        };
+
+        for (i, (utxo, signer, _)) in self.inputs.iter().enumerate() {
+            match signer {
+                InputSigner::Single(keypair) | InputSigner::Witnessed(_, keypair) => {
+                    tx.sign_input(i, utxo, keypair, self.chain_id)?
+                }
+                InputSigner::Multisig(_, keypairs) => {
+                    for keypair in keypairs {
+                        tx.sign_multisig_input(i, utxo, keypair, self.chain_id)?;
+                    }
+                }
+            }
+        }
+        Ok(tx.with_hashes())
+    }
+}
+
+impl Default for TransactionBuilder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[test]
@@ -145,13 +858,16 @@ fn sign_and_verify() {
     let keypair = WalletKeypair::new();
 
     let tx = Transaction::coinbase("addr", U256::from(50));
-    assert!(tx.verify_signatures().unwrap());
+    assert!(tx.verify_signatures(&[], MAINNET_CHAIN_ID).unwrap());
 
+    let spent = Utxo::new("00".repeat(32), 0, keypair.address(), U256::from(20));
     let inp = TransactionInput {
-        txid: "00".repeat(32),
-        vout: 0,
+        txid: spent.txid.clone(),
+        vout: spent.vout,
         pubkey: "".to_string(),
         signature: None,
+        sequence: SEQUENCE_FINAL,
+        witness: None,
     };
     let out = TransactionOutput::new("alice".to_string(), U256::from(10));
     let mut tx2 = Transaction {
@@ -159,7 +875,371 @@ fn sign_and_verify() {
         inputs: vec![inp],
         outputs: vec![out],
         timestamp: chrono::Utc::now().timestamp(),
+        version: TX_VERSION_LEGACY,
+        lock_time: 0,
+    };
+    tx2.sign_input(0, &spent, &keypair, MAINNET_CHAIN_ID).unwrap();
+    assert!(tx2.verify_signatures(std::slice::from_ref(&spent), MAINNET_CHAIN_ID).unwrap());
+
+    // A different spent amount changes the sighash
+    let mut forged = spent.clone();
+    forged.set_amount(U256::from(21));
+    assert!(!tx2.verify_signatures(&[forged], MAINNET_CHAIN_ID).unwrap());
+
+    // Legacy signatures only pass before activation
+    let params = ChainParams::mainnet();
+    let mut legacy = tx2.clone();
+    legacy.sign(&keypair, MAINNET_CHAIN_ID).unwrap();
+    assert!(legacy.verify_legacy_signatures(MAINNET_CHAIN_ID).unwrap());
+    assert!(legacy
+        .verify_signatures_at(std::slice::from_ref(&spent), 0, &params)
+        .unwrap());
+    assert!(!legacy
+        .verify_signatures_at(std::slice::from_ref(&spent), params.sighash_height, &params)
+        .unwrap());
+}
+
+#[test]
+fn signatures_are_bound_to_chain_id() {
+    use crate::config::TESTNET_CHAIN_ID;
+    use crate::crypto::WalletKeypair;
+
+    let keypair = WalletKeypair::new();
+    let u = Utxo::new("66".repeat(32), 0, keypair.address(), U256::from(20));
+    let testnet_tx = TransactionBuilder::new()
+        .chain_id(TESTNET_CHAIN_ID)
+        .add_input(u.clone(), &keypair)
+        .add_output("frank", U256::from(10))
+        .build()
+        .unwrap();
+
+    assert!(testnet_tx
+        .verify_signatures(std::slice::from_ref(&u), TESTNET_CHAIN_ID)
+        .unwrap());
+    assert!(!testnet_tx
+        .verify_signatures(std::slice::from_ref(&u), MAINNET_CHAIN_ID)
+        .unwrap());
+
+    let mut legacy = testnet_tx.clone();
+    legacy.sign(&keypair, TESTNET_CHAIN_ID).unwrap();
+    assert!(legacy.verify_legacy_signatures(TESTNET_CHAIN_ID).unwrap());
+    assert!(!legacy.verify_legacy_signatures(MAINNET_CHAIN_ID).unwrap());
+
+    // Below sighash activation a testnet legacy signature still fails on mainnet
+    let mainnet = ChainParams::mainnet();
+    for height in [0, mainnet.replay_protection_height, mainnet.sighash_height - 1] {
+        assert!(!legacy
+            .verify_signatures_at(std::slice::from_ref(&u), height, &mainnet)
+            .unwrap());
+    }
+
+    // Signatures from before chain ids replay anywhere, so they are only
+    // accepted below the replay protection height
+    let mut unbound = testnet_tx.clone();
+    let sig = hex::encode(keypair.sign(&unbound.serialize_for_hash().unwrap()));
+    unbound.inputs[0].signature = Some(sig);
+    assert!(unbound.verify_unbound_legacy_signatures().unwrap());
+    assert!(unbound
+        .verify_signatures_at(std::slice::from_ref(&u), mainnet.replay_protection_height - 1, &mainnet)
+        .unwrap());
+    assert!(!unbound
+        .verify_signatures_at(std::slice::from_ref(&u), mainnet.replay_protection_height, &mainnet)
+        .unwrap());
+}
+
+#[test]
+fn builder_signs_inputs_with_different_keys() {
+    use crate::crypto::WalletKeypair;
+
+    let alice = WalletKeypair::new();
+    let bob = WalletKeypair::new();
+    let u0 = Utxo::new("11".repeat(32), 0, alice.address(), U256::from(30));
+    let u1 = Utxo::new("22".repeat(32), 1, bob.address(), U256::from(40));
+
+    let tx = TransactionBuilder::new()
+        .add_input(u0.clone(), &alice)
+        .add_input(u1.clone(), &bob)
+        .add_output("carol", U256::from(60))
+        .timestamp(1_700_000_000)
+        .build()
+        .unwrap();
+
+    assert_eq!(tx.txid, tx.compute_txid().unwrap());
+    assert_eq!(tx.inputs[0].pubkey, alice.public_hex());
+    assert_eq!(tx.inputs[1].pubkey, bob.public_hex());
+    assert_ne!(tx.inputs[0].signature, tx.inputs[1].signature);
+    assert!(tx.verify_signatures(&[u0.clone(), u1.clone()], MAINNET_CHAIN_ID).unwrap());
+
+    // Swapping the spent UTXOs must not verify
+    assert!(tx.verify_signatures(&[u1.clone(), u0.clone()], MAINNET_CHAIN_ID).is_err());
+
+    // Signatures cannot be moved between inputs
+    let mut swapped = tx.clone();
+    let sig0 = swapped.inputs[0].signature.take();
+    swapped.inputs[0].signature = swapped.inputs[1].signature.clone();
+    swapped.inputs[1].signature = sig0;
+    assert!(!swapped.verify_signatures(&[u0, u1], MAINNET_CHAIN_ID).unwrap());
+}
+
+#[test]
+fn legacy_encoding_is_unchanged() {
+    // Encoding produced by the derived impl before versioning
+    #[derive(Encode)]
+    struct OldInput {
+        txid: String,
+        vout: u32,
+        pubkey: String,
+        signature: Option<String>,
+    }
+    #[derive(Encode)]
+    struct OldTx {
+        txid: String,
+        inputs: Vec<OldInput>,
+        outputs: Vec<TransactionOutput>,
+        timestamp: i64,
+    }
+
+    let old = OldTx {
+        txid: "ab".repeat(32),
+        inputs: vec![OldInput {
+            txid: "cd".repeat(32),
+            vout: 3,
+            pubkey: "ef".repeat(32),
+            signature: Some("01".repeat(64)),
+        }],
+        outputs: vec![TransactionOutput::new("bob".to_string(), U256::from(7))],
+        timestamp: 1_700_000_000,
+    };
+    let bytes = bincode::encode_to_vec(&old, *BINCODE_CONFIG).unwrap();
+
+    let (tx, read): (Transaction, usize) =
+        bincode::decode_from_slice(&bytes, *BINCODE_CONFIG).unwrap();
+    assert_eq!(read, bytes.len());
+    assert_eq!(tx.version, TX_VERSION_LEGACY);
+    assert_eq!(tx.lock_time, 0);
+    assert_eq!(tx.inputs[0].sequence, SEQUENCE_FINAL);
+    assert_eq!(tx.inputs[0].vout, 3);
+    assert_eq!(bincode::encode_to_vec(&tx, *BINCODE_CONFIG).unwrap(), bytes);
+}
+
+#[test]
+fn versioned_encoding_round_trips() {
+    use crate::crypto::WalletKeypair;
+
+    let kp = WalletKeypair::new();
+    let u = Utxo::new("33".repeat(32), 0, kp.address(), U256::from(5));
+
+    let plain = TransactionBuilder::new()
+        .add_input(u.clone(), &kp)
+        .add_output("dave", U256::from(4))
+        .build()
+        .unwrap();
+    assert_eq!(plain.version, TX_VERSION_LEGACY);
+
+    let tx = TransactionBuilder::new()
+        .add_input_with_sequence(u.clone(), &kp, 10)
+        .add_output("dave", U256::from(4))
+        .lock_time(1234)
+        .timestamp(plain.timestamp)
+        .build()
+        .unwrap();
+    assert_eq!(tx.version, TX_VERSION_LOCKTIME);
+    assert_ne!(tx.txid, plain.txid);
+    assert!(tx.verify_signatures(std::slice::from_ref(&u), MAINNET_CHAIN_ID).unwrap());
+
+    let bytes = bincode::encode_to_vec(&tx, *BINCODE_CONFIG).unwrap();
+    let (decoded, _): (Transaction, usize) =
+        bincode::decode_from_slice(&bytes, *BINCODE_CONFIG).unwrap();
+    assert_eq!(decoded.version, TX_VERSION_LOCKTIME);
+    assert_eq!(decoded.lock_time, 1234);
+    assert_eq!(decoded.inputs[0].sequence, 10);
+    assert_eq!(decoded.txid, decoded.compute_txid().unwrap());
+
+    // Changing a sequence breaks the signature
+    let mut tampered = decoded.clone();
+    tampered.inputs[0].sequence = 11;
+    assert!(!tampered.verify_signatures(&[u], MAINNET_CHAIN_ID).unwrap());
+}
+
+#[test]
+fn lock_time_and_relative_locks() {
+    let mut tx = Transaction::coinbase("addr", U256::from(1));
+    assert!(tx.is_final(0, 0));
+
+    tx.lock_time = 100;
+    assert!(!tx.is_final(100, i64::MAX));
+    assert!(tx.is_final(101, 0));
+
+    tx.lock_time = 1_700_000_000;
+    assert!(!tx.is_final(u64::MAX, 1_700_000_000));
+    assert!(tx.is_final(0, 1_700_000_001));
+
+    // A time lock no median time can reach stays locked
+    tx.lock_time = u64::MAX;
+    assert!(!tx.is_final(u64::MAX, i64::MAX));
+    assert!(!tx.is_final(u64::MAX, -1));
+
+    let mut inp = TransactionInput {
+        txid: "00".repeat(32),
+        vout: 0,
+        pubkey: "".to_string(),
+        signature: None,
+        sequence: 20,
+        witness: None,
     };
-    tx2.sign(&keypair).unwrap();
-    assert!(tx2.verify_signatures().unwrap());
+    assert_eq!(inp.relative_lock(TX_VERSION_LEGACY), None);
+    assert_eq!(inp.relative_lock(TX_VERSION_LOCKTIME), Some(RelativeLock::Blocks(20)));
+
+    inp.sequence = SEQUENCE_LOCKTIME_TYPE_FLAG | 3;
+    assert_eq!(inp.relative_lock(TX_VERSION_LOCKTIME), Some(RelativeLock::Seconds(3 * 512)));
+
+    inp.sequence = SEQUENCE_FINAL;
+    assert_eq!(inp.relative_lock(TX_VERSION_LOCKTIME), None);
+}
+
+#[test]
+fn multisig_input_threshold() {
+    use crate::crypto::WalletKeypair;
+
+    let a = WalletKeypair::new();
+    let b = WalletKeypair::new();
+    let c = WalletKeypair::new();
+    let lock =
+        MultisigLock::new(2, vec![a.public_hex(), b.public_hex(), c.public_hex()]).unwrap();
+    let u = Utxo::new("44".repeat(32), 0, lock.address(), U256::from(100));
+
+    // One cosigner signs, the second completes it later
+    let mut tx = TransactionBuilder::new()
+        .add_multisig_input(u.clone(), lock.clone(), vec![&a])
+        .add_output("erin", U256::from(90))
+        .build()
+        .unwrap();
+    assert_eq!(tx.version, TX_VERSION_WITNESS);
+    assert!(!tx.verify_signatures(std::slice::from_ref(&u), MAINNET_CHAIN_ID).unwrap());
+
+    tx.sign_multisig_input(0, &u, &c, MAINNET_CHAIN_ID).unwrap();
+    assert!(tx.verify_signatures(std::slice::from_ref(&u), MAINNET_CHAIN_ID).unwrap());
+
+    // Witness survives encoding and does not change the txid
+    let bytes = bincode::encode_to_vec(&tx, *BINCODE_CONFIG).unwrap();
+    let (decoded, _): (Transaction, usize) =
+        bincode::decode_from_slice(&bytes, *BINCODE_CONFIG).unwrap();
+    assert_eq!(decoded.inputs[0].witness, tx.inputs[0].witness);
+    assert_eq!(decoded.compute_txid().unwrap(), tx.txid);
+    assert!(decoded.verify_signatures(std::slice::from_ref(&u), MAINNET_CHAIN_ID).unwrap());
+
+    // Outsiders cannot sign, and the lock must match the spent address
+    let outsider = WalletKeypair::new();
+    assert!(tx.sign_multisig_input(0, &u, &outsider, MAINNET_CHAIN_ID).is_err());
+    let other_lock = MultisigLock::new(1, vec![a.public_hex()]).unwrap();
+    let mut wrong = tx.clone();
+    wrong.inputs[0].witness = Some(Witness::Multisig {
+        lock: other_lock,
+        signatures: vec![None],
+    });
+    wrong.sign_multisig_input(0, &u, &a, MAINNET_CHAIN_ID).unwrap();
+    assert!(!wrong.verify_signatures(&[u], MAINNET_CHAIN_ID).unwrap());
+
+    // Witnesses need the version 3 encoding
+    let mut old = tx.clone();
+    old.version = TX_VERSION_LOCKTIME;
+    assert!(bincode::encode_to_vec(&old, *BINCODE_CONFIG).is_err());
+}
+
+#[test]
+fn htlc_claim_and_refund() {
+    use crate::crypto::WalletKeypair;
+
+    let alice = WalletKeypair::new(); // funds the swap, can refund
+    let bob = WalletKeypair::new(); // claims with the secret
+    let secret = b"atomic swap secret";
+    let lock = HtlcLock::new(
+        HashAlgorithm::Blake3,
+        HashAlgorithm::Blake3.digest(secret),
+        &bob.public_hex(),
+        &alice.public_hex(),
+        200_000,
+    )
+    .unwrap();
+    let u = Utxo::new("55".repeat(32), 0, lock.address(), U256::from(1_000));
+
+    let chain = MAINNET_CHAIN_ID;
+    let claim =
+        htlc::build_claim_tx(&u, &lock, secret, &bob, &bob.address(), U256::from(10), chain).unwrap();
+    assert_eq!(claim.version, TX_VERSION_WITNESS);
+    assert_eq!(claim.lock_time, 0);
+    assert!(claim.verify_signatures(std::slice::from_ref(&u), MAINNET_CHAIN_ID).unwrap());
+
+    // Wrong secret or wrong signer
+    assert!(!htlc::build_claim_tx(&u, &lock, b"guess", &bob, &bob.address(), U256::zero(), chain)
+        .unwrap()
+        .verify_signatures(std::slice::from_ref(&u), MAINNET_CHAIN_ID)
+        .unwrap());
+    assert!(!htlc::build_claim_tx(&u, &lock, secret, &alice, &alice.address(), U256::zero(), chain)
+        .unwrap()
+        .verify_signatures(std::slice::from_ref(&u), MAINNET_CHAIN_ID)
+        .unwrap());
+
+    let refund =
+        htlc::build_refund_tx(&u, &lock, &alice, &alice.address(), U256::from(10), chain).unwrap();
+    assert_eq!(refund.lock_time, 200_000);
+    assert!(refund.verify_signatures(std::slice::from_ref(&u), MAINNET_CHAIN_ID).unwrap());
+    assert!(!refund.is_final(200_000, i64::MAX));
+    assert!(refund.is_final(200_001, 0));
+
+    // Lowering lock_time below the refund height invalidates the refund
+    assert!(TransactionBuilder::new()
+        .add_htlc_refund_input(u.clone(), lock.clone(), &alice)
+        .lock_time(10)
+        .add_output(&alice.address(), U256::from(990))
+        .build()
+        .is_err());
+    let mut early = refund.clone();
+    early.lock_time = 10;
+    early.sign_input(0, &u, &alice, MAINNET_CHAIN_ID).unwrap();
+    assert!(!early.verify_signatures(std::slice::from_ref(&u), MAINNET_CHAIN_ID).unwrap());
+
+    // The recipient cannot use the refund path
+    let mut stolen = refund.clone();
+    stolen.sign_input(0, &u, &bob, MAINNET_CHAIN_ID).unwrap();
+    assert!(!stolen.verify_signatures(&[u], MAINNET_CHAIN_ID).unwrap());
+}
+
+#[test]
+fn signature_checks_verify_in_a_batch() {
+    use crate::crypto::{WalletKeypair, verify_batch};
+
+    let alice = WalletKeypair::new();
+    let bob = WalletKeypair::new();
+    let u0 = Utxo::new("77".repeat(32), 0, alice.address(), U256::from(30));
+    let u1 = Utxo::new("88".repeat(32), 1, bob.address(), U256::from(40));
+    let tx = TransactionBuilder::new()
+        .add_input(u0.clone(), &alice)
+        .add_input(u1.clone(), &bob)
+        .add_output("carol", U256::from(60))
+        .build()
+        .unwrap();
+
+    let spent = [u0.clone(), u1.clone()];
+    let checks = tx.signature_checks(&spent, MAINNET_CHAIN_ID).unwrap().unwrap();
+    assert_eq!(checks.len(), 2);
+    let entries: Vec<(&str, &[u8], &[u8])> = checks
+        .iter()
+        .map(|c| (c.pubkey.as_str(), &c.message[..], c.signature.as_slice()))
+        .collect();
+    assert!(verify_batch(&entries));
+
+    // One bad signature fails the whole batch
+    let mut forged = checks.clone();
+    forged[1].signature = forged[0].signature.clone();
+    assert!(!forged[1].is_valid());
+    let entries: Vec<(&str, &[u8], &[u8])> = forged
+        .iter()
+        .map(|c| (c.pubkey.as_str(), &c.message[..], c.signature.as_slice()))
+        .collect();
+    assert!(!verify_batch(&entries));
+
+    // Spending someone else's UTXO fails before any signature is checked
+    let stolen = Utxo::new("88".repeat(32), 1, alice.address(), U256::from(40));
+    assert!(tx.signature_checks(&[u0, stolen], MAINNET_CHAIN_ID).unwrap().is_none());
 }
@@ -0,0 +1,130 @@
+use anyhow::{Result, anyhow};
+use bincode::{Decode, Encode};
+use sha2::{Digest, Sha256};
+
+use super::BINCODE_CONFIG;
+
+/// Largest n in an m-of-n lock
+pub const MAX_MULTISIG_KEYS: usize = 15;
+
+/// Prefix of multisig addresses (single-key addresses use `0x`)
+pub const MULTISIG_ADDRESS_PREFIX: &str = "ms";
+
+/// Domain tag hashed into every multisig address
+const MULTISIG_DOMAIN: &str = "ASTRAM-MULTISIG-V1";
+
+/// m-of-n Ed25519 lock. Outputs pay to `address()`; the spending input
+/// reveals the lock and carries one signature slot per key.
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Eq)]
+pub struct MultisigLock {
+    pub threshold: u8,
+    pub pubkeys: Vec<String>, // hex, 32-byte Ed25519 keys (order matters)
+}
+
+impl MultisigLock {
+    pub fn new(threshold: u8, pubkeys: Vec<String>) -> Result<Self> {
+        let lock = MultisigLock {
+            threshold,
+            pubkeys: pubkeys.into_iter().map(|k| k.to_lowercase()).collect(),
+        };
+        lock.validate()?;
+        Ok(lock)
+    }
+
+    /// Check threshold bounds and that every key is a distinct valid Ed25519 key
+    pub fn validate(&self) -> Result<()> {
+        let n = self.pubkeys.len();
+        if n == 0 || n > MAX_MULTISIG_KEYS {
+            return Err(anyhow!("multisig needs 1..={} keys, got {}", MAX_MULTISIG_KEYS, n));
+        }
+        if self.threshold == 0 || self.threshold as usize > n {
+            return Err(anyhow!("invalid multisig threshold {}-of-{}", self.threshold, n));
+        }
+        let mut seen = std::collections::HashSet::new();
+        for pk in &self.pubkeys {
+            crate::crypto::address_from_pubkey_hex(pk)
+                .map_err(|e| anyhow!("invalid multisig key {}: {}", pk, e))?;
+            if !seen.insert(pk.to_lowercase()) {
+                return Err(anyhow!("duplicate multisig key {}", pk));
+            }
+        }
+        Ok(())
+    }
+
+    /// `ms` + first 20 bytes of SHA256(domain, threshold, pubkeys)
+    pub fn address(&self) -> String {
+        let keys: Vec<String> = self.pubkeys.iter().map(|k| k.to_lowercase()).collect();
+        let bytes = bincode::encode_to_vec((MULTISIG_DOMAIN, self.threshold, &keys), *BINCODE_CONFIG)
+            .unwrap_or_default();
+        let hash = Sha256::digest(&bytes);
+        format!("{}{}", MULTISIG_ADDRESS_PREFIX, hex::encode(&hash[..20]))
+    }
+
+    /// Position of `pubkey_hex` in the lock
+    pub fn key_index(&self, pubkey_hex: &str) -> Option<usize> {
+        let pk = pubkey_hex.to_lowercase();
+        self.pubkeys.iter().position(|k| k.to_lowercase() == pk)
+    }
+
+    /// Whether `signatures` (one slot per key) holds at least `threshold`
+    /// valid signatures over `msg` and no invalid ones
+    pub fn verify(&self, msg: &[u8], signatures: &[Option<String>]) -> bool {
+        if self.validate().is_err() || signatures.len() != self.pubkeys.len() {
+            return false;
+        }
+        let mut valid = 0usize;
+        for (pk, sig) in self.pubkeys.iter().zip(signatures) {
+            let sig_hex = match sig {
+                Some(s) => s,
+                None => continue,
+            };
+            let sig_bytes = match hex::decode(sig_hex) {
+                Ok(b) => b,
+                Err(_) => return false,
+            };
+            if !crate::crypto::verify_signature(pk, msg, &sig_bytes) {
+                return false;
+            }
+            valid += 1;
+        }
+        valid >= self.threshold as usize
+    }
+}
+
+pub fn is_multisig_address(address: &str) -> bool {
+    address.starts_with(MULTISIG_ADDRESS_PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::WalletKeypair;
+
+    #[test]
+    fn multisig_lock_rules() {
+        let a = WalletKeypair::new();
+        let b = WalletKeypair::new();
+        let lock = MultisigLock::new(2, vec![a.public_hex(), b.public_hex()]).unwrap();
+
+        assert!(is_multisig_address(&lock.address()));
+        assert_eq!(lock.address().len(), 42);
+        // Key order and threshold are part of the address
+        let swapped = MultisigLock::new(2, vec![b.public_hex(), a.public_hex()]).unwrap();
+        assert_ne!(lock.address(), swapped.address());
+        let one_of_two = MultisigLock::new(1, vec![a.public_hex(), b.public_hex()]).unwrap();
+        assert_ne!(lock.address(), one_of_two.address());
+
+        assert!(MultisigLock::new(3, vec![a.public_hex(), b.public_hex()]).is_err());
+        assert!(MultisigLock::new(0, vec![a.public_hex()]).is_err());
+        assert!(MultisigLock::new(1, vec![a.public_hex(), a.public_hex()]).is_err());
+
+        let msg = b"payload";
+        let sig_a = Some(hex::encode(a.sign(msg)));
+        let sig_b = Some(hex::encode(b.sign(msg)));
+        assert!(lock.verify(msg, &[sig_a.clone(), sig_b.clone()]));
+        assert!(!lock.verify(msg, &[sig_a.clone(), None]));
+        assert!(one_of_two.verify(msg, &[None, sig_b.clone()]));
+        // A signature in the wrong slot is invalid
+        assert!(!one_of_two.verify(msg, &[sig_b, None]));
+    }
+}
@@ -3,7 +3,7 @@ use primitive_types::U256;
 use serde::{Deserialize, Serialize};
 
 /// UTXO with amount stored as [u64; 4] for bincode compatibility
-#[derive(Encode, Decode, Debug, Clone, Serialize, Deserialize)]
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Utxo {
     pub txid: String,
     pub vout: u32,
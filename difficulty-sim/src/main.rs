@@ -0,0 +1,184 @@
+mod sim;
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, anyhow};
+use clap::Parser;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+use Astram_core::ChainParams;
+use sim::{Profile, SimBlock};
+
+/// Replays a hashrate profile through the difficulty retarget and reports
+/// block-time variance and recovery after hashrate drops
+#[derive(Parser, Debug)]
+#[command(name = "Astram-difficulty-sim")]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// Network preset (mainnet, testnet, regtest) or chain params file
+    #[arg(long, default_value = "mainnet")]
+    params: String,
+
+    /// Synthetic profile: comma-separated <blocks>:<hashrate multiplier> segments
+    #[arg(long, default_value = "300:1,300:0.25,300:1,300:4")]
+    profile: String,
+
+    /// Read the synthetic profile from a file (one or more segments per line)
+    #[arg(long, conflicts_with = "chain")]
+    profile_file: Option<PathBuf>,
+
+    /// Replay the hashrate of a recorded chain (CSV of timestamp,bits per block)
+    #[arg(long)]
+    chain: Option<PathBuf>,
+
+    /// Bits of the first synthetic block; multiplier 1 mines it in one block interval
+    #[arg(long, default_value = "0x1e00ffff", value_parser = sim::parse_bits)]
+    start_bits: u32,
+
+    /// Timestamp of the first synthetic block
+    #[arg(long, default_value = "1800000000")]
+    start_time: i64,
+
+    /// Seed for the solve-time sampler
+    #[arg(long, default_value = "1")]
+    seed: u64,
+
+    /// Solve every block in exactly its expected time
+    #[arg(long)]
+    deterministic: bool,
+
+    /// Recovered once the expected block time is within this fraction of the target
+    #[arg(long, default_value = "0.2")]
+    tolerance: f64,
+
+    /// Write the simulated chain to a CSV file
+    #[arg(long)]
+    csv: Option<PathBuf>,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let params = load_chain_params(&args.params)?;
+    if !params.retarget {
+        println!("note: {} does not retarget, every block uses 0x{:08x}", params.network, params.pow_limit_bits);
+    }
+
+    let (profile, start_bits, start_time) = match &args.chain {
+        Some(path) => {
+            let records = sim::load_chain(path)?;
+            let profile = Profile::recorded(&records, 4 * params.retarget_window as usize)?;
+            let checked = records.len().saturating_sub(params.retarget_window as usize);
+            let mismatches = sim::bits_mismatches(&params, &records);
+            println!("recorded chain: {} blocks from {:?}", records.len(), path);
+            let timestamps: Vec<i64> = records.iter().map(|r| r.timestamp).collect();
+            print_stats("  recorded block time", &timestamps);
+            match mismatches.first() {
+                Some(first) => println!(
+                    "  bits check: {} of {} blocks differ from the retarget (first at row {})",
+                    mismatches.len(),
+                    checked,
+                    first
+                ),
+                None => println!("  bits check: all {} blocks with a full window match the retarget", checked),
+            }
+            (profile, records[0].bits, records[0].timestamp)
+        }
+        None => {
+            let spec = match &args.profile_file {
+                Some(path) => std::fs::read_to_string(path).map_err(|e| anyhow!("cannot read {:?}: {}", path, e))?,
+                None => args.profile.clone(),
+            };
+            let base = sim::work_f64(args.start_bits) / params.block_interval as f64;
+            (Profile::synthetic(&spec, base)?, args.start_bits, args.start_time)
+        }
+    };
+
+    let mut rng = StdRng::seed_from_u64(args.seed);
+    let blocks = sim::simulate(
+        &params,
+        &profile,
+        start_bits,
+        start_time,
+        if args.deterministic { None } else { Some(&mut rng) },
+    );
+
+    println!(
+        "simulated {} blocks on {} (target {}s, window {})",
+        blocks.len(),
+        params.network,
+        params.block_interval,
+        params.retarget_window
+    );
+    let timestamps: Vec<i64> = blocks.iter().map(|b| b.timestamp).collect();
+    print_stats("  block time", &timestamps);
+
+    let recoveries = sim::recoveries(&params, &blocks, &profile.drops, args.tolerance);
+    if recoveries.is_empty() {
+        println!("  no hashrate drops in the profile");
+    }
+    for recovery in recoveries {
+        let (drop, ratio) = (recovery.drop.height, recovery.drop.ratio);
+        match (recovery.blocks, recovery.seconds) {
+            (Some(blocks), Some(seconds)) => println!(
+                "  drop at block {} (hashrate x{:.2}): within {:.0}% of target after {} blocks, {}s",
+                drop,
+                ratio,
+                args.tolerance * 100.0,
+                blocks,
+                seconds
+            ),
+            _ => println!(
+                "  drop at block {} (hashrate x{:.2}): not within {:.0}% of target before the profile moved on",
+                drop,
+                ratio,
+                args.tolerance * 100.0
+            ),
+        }
+    }
+
+    if let Some(path) = &args.csv {
+        write_csv(path, &blocks)?;
+        println!("wrote {:?}", path);
+    }
+    Ok(())
+}
+
+/// A preset name or the path of a custom chain params file
+fn load_chain_params(value: &str) -> Result<ChainParams> {
+    let path = Path::new(value);
+    if path.is_file() {
+        ChainParams::from_file(path)
+    } else {
+        ChainParams::for_network(value)
+    }
+}
+
+fn print_stats(label: &str, timestamps: &[i64]) {
+    match sim::block_time_stats(timestamps) {
+        Some(stats) => println!(
+            "{}: mean {:.1}s, variance {:.1}s², std dev {:.1}s, min {}s, max {}s",
+            label, stats.mean, stats.variance, stats.std_dev, stats.min, stats.max
+        ),
+        None => println!("{}: not enough blocks", label),
+    }
+}
+
+fn write_csv(path: &Path, blocks: &[SimBlock]) -> Result<()> {
+    let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+    writeln!(out, "height,timestamp,bits,hashrate,expected_time")?;
+    for (height, block) in blocks.iter().enumerate() {
+        writeln!(
+            out,
+            "{},{},0x{:08x},{:.3},{:.1}",
+            height,
+            block.timestamp,
+            block.bits,
+            block.hashrate,
+            block.expected_time()
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
@@ -0,0 +1,358 @@
+//! Block-by-block replay of a hashrate profile. Bits come from
+//! `retarget::next_bits`, the function block validation uses, so every
+//! simulated chain is one the node would accept.
+
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+use rand::Rng;
+use rand::rngs::StdRng;
+
+use Astram_core::ChainParams;
+use Astram_core::consensus::retarget::{self, RetargetRecord};
+
+/// Hashrate (hashes per second) mining each block, and where it drops
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub hashrate: Vec<f64>,
+    pub drops: Vec<HashrateDrop>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HashrateDrop {
+    pub height: usize, // first block mined at the lower hashrate
+    pub ratio: f64,    // new hashrate / old hashrate
+}
+
+impl Profile {
+    /// Segments of `<blocks>:<multiplier>` separated by commas or newlines
+    /// (`#` starts a comment), relative to `base_hashrate`
+    pub fn synthetic(spec: &str, base_hashrate: f64) -> Result<Self> {
+        let mut hashrate: Vec<f64> = Vec::new();
+        let mut drops = Vec::new();
+        let mut previous = None;
+        let segments = spec
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or(""))
+            .flat_map(|line| line.split(','))
+            .map(str::trim)
+            .filter(|segment| !segment.is_empty());
+        for segment in segments {
+            let (blocks, multiplier) = segment
+                .split_once(':')
+                .ok_or_else(|| anyhow!("profile segment '{}': expected <blocks>:<multiplier>", segment))?;
+            let blocks: usize = blocks
+                .trim()
+                .parse()
+                .map_err(|e| anyhow!("profile segment '{}': {}", segment, e))?;
+            let multiplier: f64 = multiplier
+                .trim()
+                .parse()
+                .map_err(|e| anyhow!("profile segment '{}': {}", segment, e))?;
+            if multiplier <= 0.0 || !multiplier.is_finite() {
+                return Err(anyhow!("profile segment '{}': multiplier must be positive", segment));
+            }
+            if let Some(previous) = previous.filter(|&p| multiplier < p && !hashrate.is_empty()) {
+                drops.push(HashrateDrop {
+                    height: hashrate.len(),
+                    ratio: multiplier / previous,
+                });
+            }
+            hashrate.extend(std::iter::repeat_n(base_hashrate * multiplier, blocks));
+            previous = Some(multiplier);
+        }
+        if hashrate.is_empty() {
+            return Err(anyhow!("profile has no blocks"));
+        }
+        Ok(Profile { hashrate, drops })
+    }
+
+    /// Piecewise-constant hashrate fitted to a recorded chain. The work of
+    /// the last `span` blocks over the time they took estimates the hashrate
+    /// at each block; a step is where that estimate halves or doubles from
+    /// one span earlier (shorter spans mistake the spread of solve times for
+    /// steps), placed where the ratio peaks. Each segment between steps
+    /// mines at its average rate.
+    pub fn recorded(records: &[RetargetRecord], span: usize) -> Result<Self> {
+        if records.len() < 2 {
+            return Err(anyhow!("a recorded chain needs at least two blocks"));
+        }
+        let span = span.max(1);
+        // Work per second of blocks `from..to` (at least one block after `from - 1`)
+        let rate = |from: usize, to: usize| {
+            let from = from.max(1);
+            let work: f64 = records[from..to].iter().map(|r| work_f64(r.bits)).sum();
+            work / (records[to - 1].timestamp - records[from - 1].timestamp).max(1) as f64
+        };
+        let estimate: Vec<f64> = (0..records.len())
+            .map(|i| rate(i.saturating_sub(span) + 1, i.max(1) + 1))
+            .collect();
+
+        let ratio_at = |i: usize| estimate[i] / estimate[i - span];
+        let distance = |i: usize| ratio_at(i).ln().abs();
+        let mut steps = vec![0];
+        let mut i = 2 * span;
+        while i < records.len() {
+            if distance(i) < 2f64.ln() {
+                i += 1;
+                continue;
+            }
+            // Estimates one span apart straddle the step best when the
+            // later one starts right after it
+            let end = (i + span).min(records.len());
+            let peak = (i..end).max_by(|&a, &b| distance(a).total_cmp(&distance(b))).unwrap_or(i);
+            steps.push(peak - span + 1);
+            i = peak + span;
+        }
+        steps.push(records.len());
+
+        let mut hashrate = Vec::with_capacity(records.len());
+        let mut drops = Vec::new();
+        for segment in steps.windows(2) {
+            let segment_rate = rate(segment[0], segment[1]);
+            if let Some(&previous) = hashrate.last().filter(|&&p| segment_rate < p) {
+                drops.push(HashrateDrop {
+                    height: segment[0],
+                    ratio: segment_rate / previous,
+                });
+            }
+            hashrate.extend(std::iter::repeat_n(segment_rate, segment[1] - segment[0]));
+        }
+        Ok(Profile { hashrate, drops })
+    }
+}
+
+/// A block of a simulated chain
+#[derive(Debug, Clone, Copy)]
+pub struct SimBlock {
+    pub timestamp: i64,
+    pub bits: u32,
+    pub hashrate: f64,
+}
+
+impl SimBlock {
+    /// Mean seconds to find this block at its hashrate
+    pub fn expected_time(&self) -> f64 {
+        work_f64(self.bits) / self.hashrate
+    }
+
+    pub fn record(&self) -> RetargetRecord {
+        RetargetRecord {
+            timestamp: self.timestamp,
+            bits: self.bits,
+        }
+    }
+}
+
+/// Mine one block per profile entry. Solve times are exponential around the
+/// expected time, or exactly the expected time without `rng`.
+pub fn simulate(
+    params: &ChainParams,
+    profile: &Profile,
+    start_bits: u32,
+    start_time: i64,
+    mut rng: Option<&mut StdRng>,
+) -> Vec<SimBlock> {
+    let mut blocks: Vec<SimBlock> = Vec::with_capacity(profile.hashrate.len());
+    let mut history: Vec<RetargetRecord> = Vec::with_capacity(profile.hashrate.len());
+    let mut clock = start_time as f64;
+    for (height, &hashrate) in profile.hashrate.iter().enumerate() {
+        let bits = if height == 0 {
+            start_bits
+        } else {
+            retarget::next_bits(params, &history)
+        };
+        if height > 0 {
+            let expected = work_f64(bits) / hashrate;
+            clock += match rng.as_deref_mut() {
+                Some(rng) => -rng.gen_range(f64::EPSILON..1.0).ln() * expected,
+                None => expected,
+            };
+        }
+        let block = SimBlock {
+            timestamp: clock.round() as i64,
+            bits,
+            hashrate,
+        };
+        history.push(block.record());
+        blocks.push(block);
+    }
+    blocks
+}
+
+/// Heights (with a full retarget window in `records`) whose bits differ from
+/// what the retarget computes
+pub fn bits_mismatches(params: &ChainParams, records: &[RetargetRecord]) -> Vec<usize> {
+    let window = params.retarget_window as usize;
+    (window..records.len())
+        .filter(|&i| records[i].bits != retarget::next_bits(params, &records[..i]))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BlockTimeStats {
+    pub mean: f64,
+    pub variance: f64,
+    pub std_dev: f64,
+    pub min: i64,
+    pub max: i64,
+}
+
+/// Statistics of the gaps between consecutive timestamps
+pub fn block_time_stats(timestamps: &[i64]) -> Option<BlockTimeStats> {
+    let gaps: Vec<i64> = timestamps.windows(2).map(|w| w[1] - w[0]).collect();
+    if gaps.is_empty() {
+        return None;
+    }
+    let n = gaps.len() as f64;
+    let mean = gaps.iter().sum::<i64>() as f64 / n;
+    let variance = gaps.iter().map(|&g| (g as f64 - mean).powi(2)).sum::<f64>() / n;
+    Some(BlockTimeStats {
+        mean,
+        variance,
+        std_dev: variance.sqrt(),
+        min: *gaps.iter().min()?,
+        max: *gaps.iter().max()?,
+    })
+}
+
+/// How long the retarget took to bring the expected block time back within
+/// `tolerance` of the target after a drop
+#[derive(Debug, Clone, Copy)]
+pub struct Recovery {
+    pub drop: HashrateDrop,
+    pub blocks: Option<usize>, // None: not recovered before the next drop or the end
+    pub seconds: Option<i64>,
+}
+
+pub fn recoveries(params: &ChainParams, blocks: &[SimBlock], drops: &[HashrateDrop], tolerance: f64) -> Vec<Recovery> {
+    let target = params.block_interval as f64;
+    drops
+        .iter()
+        .enumerate()
+        .filter(|&(_, drop)| drop.height < blocks.len())
+        .map(|(i, &drop)| {
+            let end = drops.get(i + 1).map_or(blocks.len(), |next| next.height.min(blocks.len()));
+            let recovered =
+                (drop.height..end).find(|&h| (blocks[h].expected_time() - target).abs() <= tolerance * target);
+            let before = blocks[drop.height.saturating_sub(1)].timestamp;
+            Recovery {
+                drop,
+                blocks: recovered.map(|h| h - drop.height),
+                seconds: recovered.map(|h| blocks[h].timestamp - before),
+            }
+        })
+        .collect()
+}
+
+/// Recorded chain: one `timestamp,bits` line per block, oldest first. Bits
+/// may be hex (`0x...`) or decimal; a header line and `#` comments are
+/// skipped.
+pub fn load_chain(path: &Path) -> Result<Vec<RetargetRecord>> {
+    let text = std::fs::read_to_string(path).map_err(|e| anyhow!("cannot read {:?}: {}", path, e))?;
+    let mut records = Vec::new();
+    for (line_no, raw) in text.lines().enumerate() {
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (timestamp, bits) = line
+            .split_once(',')
+            .ok_or_else(|| anyhow!("{:?} line {}: expected timestamp,bits", path, line_no + 1))?;
+        let timestamp = match timestamp.trim().parse::<i64>() {
+            Ok(t) => t,
+            Err(_) if records.is_empty() => continue, // header
+            Err(e) => return Err(anyhow!("{:?} line {}: {}", path, line_no + 1, e)),
+        };
+        let bits = parse_bits(bits).map_err(|e| anyhow!("{:?} line {}: {}", path, line_no + 1, e))?;
+        records.push(RetargetRecord { timestamp, bits });
+    }
+    Ok(records)
+}
+
+pub fn parse_bits(value: &str) -> std::result::Result<u32, String> {
+    let value = value.trim();
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+    .map_err(|e| format!("invalid bits '{}': {}", value, e))
+}
+
+/// `retarget::block_work` as a float
+pub fn work_f64(bits: u32) -> f64 {
+    let work = retarget::block_work(bits);
+    work.0.iter().rev().fold(0.0, |acc, &limb| acc * 2f64.powi(64) + limb as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use Astram_core::blockchain::BINCODE_CONFIG;
+    use Astram_core::blockchain::testutil::temp_chain;
+    use Astram_core::db::{ChainBatch, put_batch};
+    use Astram_core::BlockHeader;
+
+    #[test]
+    fn node_computes_the_simulated_bits() {
+        let params = ChainParams::mainnet();
+        let base = work_f64(0x1e00ffff) / params.block_interval as f64;
+        let profile = Profile::synthetic("60:1, 60:0.2\n60:3 # surge\n40:0.5", base).unwrap();
+        let drops: Vec<usize> = profile.drops.iter().map(|d| d.height).collect();
+        assert_eq!(drops, vec![60, 180]);
+        let mut rng = StdRng::seed_from_u64(7);
+        let blocks = simulate(&params, &profile, 0x1e00ffff, 1_800_000_000, Some(&mut rng));
+        let records: Vec<RetargetRecord> = blocks.iter().map(SimBlock::record).collect();
+        assert!(bits_mismatches(&params, &records).is_empty());
+
+        let (_dir, bc) = temp_chain("difficulty-sim", params.clone());
+        for (height, block) in blocks.iter().enumerate() {
+            if height >= params.retarget_window as usize {
+                assert_eq!(
+                    bc.calculate_adjusted_difficulty(height as u64).unwrap(),
+                    block.bits,
+                    "bits at height {}",
+                    height
+                );
+            }
+            let header = BlockHeader {
+                index: height as u64,
+                previous_hash: String::new(),
+                merkle_root: String::new(),
+                timestamp: block.timestamp,
+                nonce: 0,
+                difficulty: block.bits,
+            };
+            let hash = format!("{:064x}", height);
+            let mut batch = ChainBatch::default();
+            batch.put(
+                format!("h:{}", hash).as_bytes(),
+                bincode::encode_to_vec(&header, *BINCODE_CONFIG).unwrap(),
+            );
+            batch.put(format!("i:{}", height).as_bytes(), hash.as_bytes());
+            put_batch(&bc.db, batch).unwrap();
+        }
+    }
+
+    #[test]
+    fn deterministic_drop_recovers() {
+        let params = ChainParams::mainnet();
+        let base = work_f64(0x1e00ffff) / params.block_interval as f64;
+        let profile = Profile::synthetic("100:1,200:0.25", base).unwrap();
+        let blocks = simulate(&params, &profile, 0x1e00ffff, 1_800_000_000, None);
+
+        let recovery = recoveries(&params, &blocks, &profile.drops, 0.1)[0];
+        assert_eq!(recovery.drop.height, 100);
+        let recovered = recovery.blocks.unwrap();
+        assert!(recovered > 0 && recovered < 100, "recovered after {} blocks", recovered);
+        assert!(recovery.seconds.unwrap() > recovered as i64 * params.block_interval);
+
+        // The recorded replay sees the same drop, and nothing else
+        let records: Vec<RetargetRecord> = blocks.iter().map(SimBlock::record).collect();
+        let replay = Profile::recorded(&records, 96).unwrap();
+        assert_eq!(replay.drops.len(), 1, "{:?}", replay.drops);
+        assert!((99..=102).contains(&replay.drops[0].height), "{:?}", replay.drops);
+        assert!((replay.drops[0].ratio - 0.25).abs() < 0.02, "{:?}", replay.drops);
+    }
+}
@@ -442,20 +442,13 @@ impl NodeRpcClient {
                     }
                 } else {
                     // Standard tx: compute input/output sums and fee
-                    let from_pubkey = tx
+                    // Sender address (pubkey or multisig lock, for change exclusion)
+                    let from_address = tx
                         .inputs
                         .first()
-                        .map(|i| i.pubkey.clone())
+                        .map(|i| i.spender_address().unwrap_or_else(|_| i.pubkey.clone()))
                         .unwrap_or_else(|| "Unknown".to_string());
 
-                    // Convert pubkey to address (for change exclusion)
-                    let from_address = if from_pubkey != "Unknown" {
-                        Astram_core::crypto::address_from_pubkey_hex(&from_pubkey)
-                            .unwrap_or_else(|_| from_pubkey.clone())
-                    } else {
-                        from_pubkey.clone()
-                    };
-
                     // Sum inputs (lookup from UTXO map)
                     let mut input_sum = U256::zero();
                     let mut missing_inputs = 0;
@@ -20,16 +20,17 @@ use anyhow::{Result, anyhow};
 use flexi_logger::{Age, Cleanup, Criterion, Duplicate, FileSpec, Logger, Naming, WriteMode};
 use astram_config::config::Config;
 use base64::{Engine as _, engine::general_purpose};
-use Astram_core::block::{Block, BlockHeader};
-use Astram_core::config::calculate_block_reward;
+use Astram_core::block::{Block, BlockHeader, select_transactions};
+use Astram_core::config::{BLOCK_TEMPLATE_RESERVED_SIZE, MAX_BLOCK_SIZE, calculate_block_reward};
 use Astram_core::consensus;
+use Astram_core::network::ChainParams;
 use Astram_core::transaction::{BINCODE_CONFIG, Transaction};
 use futures::{SinkExt, StreamExt};
 use primitive_types::U256;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
@@ -200,6 +201,12 @@ impl MinerSettings {
     }
 }
 
+/// Consensus parameters of the network mined (the miner only mines mainnet)
+fn chain_params() -> &'static ChainParams {
+    static PARAMS: OnceLock<ChainParams> = OnceLock::new();
+    PARAMS.get_or_init(ChainParams::mainnet)
+}
+
 /// Convert a pool difficulty (leading hex-zero count) to a 32-byte mining target.
 fn pool_leading_zeros_to_target(n: u32) -> [u8; 32] {
     let zeros = n.min(63) as usize;
@@ -245,7 +252,8 @@ struct ChainStatus {
 #[derive(Deserialize)]
 struct MempoolResponse {
     transactions_b64: String,
-    total_fees: String,
+    #[serde(default)]
+    fees: Vec<String>,
 }
 
 fn parse_u256(s: &str) -> Option<U256> {
@@ -283,9 +291,18 @@ async fn fetch_mempool(client: &reqwest::Client, base_url: &str) -> Result<(Vec<
     let bytes = general_purpose::STANDARD.decode(resp.transactions_b64.as_bytes())?;
     let (txs, _) = bincode::decode_from_slice::<Vec<Transaction>, _>(&bytes, *BINCODE_CONFIG)
         .map_err(|e| anyhow!("mempool decode: {}", e))?;
-    let total_fees = parse_u256(&resp.total_fees).unwrap_or_else(U256::zero);
 
-    Ok((txs, total_fees))
+    // Fill the block by fee rate up to the size limit; the coinbase may only
+    // claim the fees of what is included. Missing per-tx fees count as zero.
+    let fees = resp.fees.iter().map(|f| parse_u256(f).unwrap_or_else(U256::zero));
+    let candidates: Vec<(Transaction, U256)> = txs
+        .into_iter()
+        .zip(fees.chain(std::iter::repeat(U256::zero())))
+        .collect();
+    let selected = select_transactions(candidates, MAX_BLOCK_SIZE - BLOCK_TEMPLATE_RESERVED_SIZE);
+    let total_fees = selected.iter().fold(U256::zero(), |acc, (_, fee)| acc + *fee);
+
+    Ok((selected.into_iter().map(|(tx, _)| tx).collect(), total_fees))
 }
 
 async fn submit_block(client: &reqwest::Client, base_url: &str, block: &Block) -> Result<()> {
@@ -377,6 +394,7 @@ async fn run_solo(settings: MinerSettings, status: Arc<Mutex<MinerStatus>>) {
 
         let mine_result: Result<Block> = tokio::task::spawn_blocking(move || {
             consensus::mine_block_with_coinbase_cuda(
+                chain_params(),
                 next_height,
                 prev_hash,
                 diff,
@@ -587,7 +605,7 @@ async fn run_stratum_session(
                         let hr = hashrate.clone();
                         let pt = pool_target;
                         mining_handle = Some(tokio::task::spawn_blocking(move || {
-                            consensus::mine_header_cuda(header, cf, Some(hr), Some(pt))
+                            consensus::mine_header_cuda(chain_params(), header, cf, Some(hr), Some(pt))
                         }));
                     }
 
@@ -662,7 +680,7 @@ async fn run_stratum_session(
                             let hr = hashrate.clone();
                             let pt = pool_target;
                             mining_handle = Some(tokio::task::spawn_blocking(move || {
-                                consensus::mine_header_cuda(new_header, cf, Some(hr), Some(pt))
+                                consensus::mine_header_cuda(chain_params(), new_header, cf, Some(hr), Some(pt))
                             }));
                             continue;
                         }
@@ -699,7 +717,7 @@ async fn run_stratum_session(
                         let hr = hashrate.clone();
                         let pt = pool_target;
                         mining_handle = Some(tokio::task::spawn_blocking(move || {
-                            consensus::mine_header_cuda(new_header, cf, Some(hr), Some(pt))
+                            consensus::mine_header_cuda(chain_params(), new_header, cf, Some(hr), Some(pt))
                         }));
                     }
                     Ok(Err(e)) => {
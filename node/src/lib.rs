@@ -6,7 +6,13 @@ pub use server::*;
 
 use Astram_core::Blockchain;
 use Astram_core::block::Block;
+use Astram_core::blockchain::PrecheckedBlock;
+use Astram_core::security::{
+    ADDRESS_RATE_WINDOW, AddressRateLimiter, BlockFailureReason, MAX_TX_PER_ADDRESS,
+    VALIDATION_STATS, validate_transaction_security,
+};
 use Astram_core::transaction::Transaction;
+use Astram_core::utxo::Utxo;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
@@ -18,6 +24,102 @@ pub struct NodeHandles {
 
 // Lock order (when nested): bc -> chain -> mempool -> mining -> meta.
 
+impl NodeHandles {
+    /// Verify a mempool candidate's per-input signatures under the rules of
+    /// the next block. Spent UTXOs come from the committed set, falling back
+    /// to outputs of pending mempool transactions (chained spends).
+    pub fn verify_tx_signatures(&self, tx: &Transaction) -> anyhow::Result<bool> {
+        let bc = self.bc.lock().unwrap();
+        let height = bc.get_next_index()?;
+        let mempool = self.mempool.lock().unwrap();
+
+        let mut spent = Vec::with_capacity(tx.inputs.len());
+        for inp in &tx.inputs {
+            let utxo = match bc.get_utxo(&inp.txid, inp.vout)? {
+                Some(u) => u,
+                None => mempool.pending_output(&inp.txid, inp.vout).ok_or_else(|| {
+                    BlockFailureReason::UtxoNotFound.record(anyhow::anyhow!(
+                        "referenced utxo {}:{} not found",
+                        inp.txid,
+                        inp.vout
+                    ))
+                })?,
+            };
+            spent.push(utxo);
+        }
+        drop(mempool);
+        drop(bc);
+
+        let result = tx.verify_signatures_at(&spent, height, crate::p2p::manager::chain_params());
+        if !matches!(result, Ok(true)) {
+            VALIDATION_STATS.increment(BlockFailureReason::SignatureFailure);
+        }
+        result
+    }
+
+    /// Block checks that need no chain lock: header hash, PoW and merkle
+    /// root, then (for a block extending the tip) the signatures against the
+    /// outputs it spends, looked up under a brief lock. The lock is then
+    /// taken again only to connect it (`validate_and_insert_prechecked`).
+    pub fn precheck_block<'a>(&self, block: &'a Block) -> anyhow::Result<PrecheckedBlock<'a>> {
+        let params = crate::p2p::manager::chain_params();
+        let mut pre = PrecheckedBlock::check(params, block)?;
+        let spent = self.bc.lock().unwrap().spent_outputs(block)?;
+        if let Some(spent) = spent {
+            pre.verify_signatures(params, spent)?;
+        }
+        Ok(pre)
+    }
+
+    /// Size, input/output count, dust and timestamp limits for a mempool
+    /// candidate, plus the per-address submission rate limit. Transactions
+    /// already seen (relayed back by peers) do not count against the limit.
+    pub fn check_tx_security(&self, tx: &Transaction) -> anyhow::Result<()> {
+        validate_transaction_security(tx, chrono::Utc::now().timestamp())?;
+
+        let mut mempool = self.mempool.lock().unwrap();
+        if mempool.seen_tx.contains_key(&tx.txid) {
+            return Ok(());
+        }
+        let mut senders = std::collections::HashSet::new();
+        for inp in &tx.inputs {
+            let sender = inp
+                .spender_address()
+                .map_err(|e| BlockFailureReason::UtxoOwnershipFailure.record(e))?;
+            if senders.insert(sender.to_lowercase()) {
+                mempool
+                    .rate_limiter
+                    .check_and_update(&sender)
+                    .map_err(|e| BlockFailureReason::SecurityConstraint.record(e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reject a mempool candidate that spends coinbase outputs still immature
+    /// at the next block.
+    pub fn check_coinbase_maturity(&self, tx: &Transaction) -> anyhow::Result<()> {
+        let bc = self.bc.lock().unwrap();
+        let height = bc.get_next_index()?;
+        bc.check_coinbase_maturity(tx, height)
+            .map_err(|e| BlockFailureReason::SecurityConstraint.record(e))
+    }
+
+    /// Reject a mempool candidate whose lock_time or relative locks do not
+    /// allow it into the next block. Unconfirmed parents count as created
+    /// at the next height, so only zero relative locks can chain on them.
+    pub fn check_tx_locks(&self, tx: &Transaction) -> anyhow::Result<()> {
+        let bc = self.bc.lock().unwrap();
+        let height = bc.get_next_index()?;
+        let mtp = match &bc.chain_tip {
+            Some(tip) => bc.median_time_past(tip)?.unwrap_or(0),
+            None => 0,
+        };
+        bc.check_tx_locks(tx, height, mtp)
+            .map_err(|e| BlockFailureReason::SecurityConstraint.record(e))
+    }
+}
+
 pub struct ChainState {
     pub blockchain: Vec<Block>,
     /// Orphan blocks pool: blocks waiting for their parent
@@ -74,6 +176,8 @@ pub struct MempoolState {
     /// Seen transactions with timestamp (to prevent relay loops and track when seen)
     /// Key: txid, Value: timestamp when first seen
     pub seen_tx: HashMap<String, i64>,
+    /// Per-address submission limit (spam protection)
+    pub rate_limiter: AddressRateLimiter,
 }
 
 impl Default for MempoolState {
@@ -81,6 +185,7 @@ impl Default for MempoolState {
         Self {
             pending: Vec::new(),
             seen_tx: HashMap::new(),
+            rate_limiter: AddressRateLimiter::new(MAX_TX_PER_ADDRESS, ADDRESS_RATE_WINDOW),
         }
     }
 }
@@ -123,6 +228,13 @@ impl ChainState {
 }
 
 impl MempoolState {
+    /// Output `vout` of a pending transaction, as a UTXO
+    pub fn pending_output(&self, txid: &str, vout: u32) -> Option<Utxo> {
+        let tx = self.pending.iter().find(|t| t.txid == txid)?;
+        let out = tx.outputs.get(vout as usize)?;
+        Some(Utxo::new(tx.txid.clone(), vout, out.to.to_lowercase(), out.amount()))
+    }
+
     /// Security: Enforce mempool limits to prevent DoS attacks
     /// Evicts low-fee or old transactions when limits are exceeded
     pub fn enforce_mempool_limit(&mut self) {
@@ -134,6 +246,7 @@ impl MempoolState {
         // Collect expired txids first, then retain, to update seen_tx correctly.
         let before = self.pending.len();
         self.pending.retain(|tx| now - tx.timestamp <= MEMPOOL_EXPIRY_TIME);
+        self.rate_limiter.cleanup();
         let expired_count = before - self.pending.len();
         if expired_count > 0 {
             log::info!(
@@ -1,6 +1,7 @@
 // Use library exports instead of declaring local modules to avoid duplicate crate types
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use Astram_core::Blockchain;
+use Astram_core::checkpoint::SignedCheckpoints;
 use astram_config::config::Config;
 use astram_node::ChainState;
 use astram_node::MempoolState;
@@ -60,6 +61,13 @@ struct NodeSettings {
     eth_rpc_port: u16,
     dns_server_url: String,
     bootstrap_peers: Vec<String>,
+    /// Block bodies kept below the tip; 0 keeps every block (archive node).
+    prune_depth: u64,
+    /// Release keys (hex Ed25519) trusted to sign checkpoint files; empty keeps the network's own.
+    checkpoint_keys: Vec<String>,
+    checkpoint_threshold: Option<usize>,
+    /// Block whose signatures, and its ancestors', are not verified; None = latest checkpoint.
+    assume_valid: Option<String>,
 }
 
 impl Default for NodeSettings {
@@ -75,6 +83,10 @@ impl Default for NodeSettings {
             eth_rpc_port: 8545,
             dns_server_url: "http://161.33.19.183:8053".to_string(),
             bootstrap_peers: Vec::new(),
+            prune_depth: 0,
+            checkpoint_keys: Vec::new(),
+            checkpoint_threshold: None,
+            assume_valid: None,
         }
     }
 }
@@ -161,7 +173,18 @@ fn load_node_settings_from(config_path: Option<PathBuf>) -> NodeSettings {
                         settings.eth_rpc_port = value.parse().unwrap_or(settings.eth_rpc_port)
                     }
                     "DNS_SERVER_URL" => settings.dns_server_url = value.to_string(),
-                    "ASTRAM_NETWORK" | "ASTRAM_NETWORK_ID" | "ASTRAM_CHAIN_ID" | "ASTRAM_NETWORK_MAGIC" => {
+                    "PRUNE_DEPTH" => settings.prune_depth = value.parse().unwrap_or(settings.prune_depth),
+                    "CHECKPOINT_KEYS" => {
+                        settings.checkpoint_keys = value
+                            .split(',')
+                            .map(|entry| entry.trim().to_ascii_lowercase())
+                            .filter(|entry| !entry.is_empty())
+                            .collect();
+                    }
+                    "CHECKPOINT_THRESHOLD" => settings.checkpoint_threshold = value.parse().ok(),
+                    "ASSUME_VALID" => settings.assume_valid = Some(value.to_string()),
+                    "ASTRAM_NETWORK" | "ASTRAM_CHAIN_PARAMS" | "ASTRAM_NETWORK_ID" | "ASTRAM_CHAIN_ID"
+                    | "ASTRAM_NETWORK_MAGIC" => {
                         #[cfg(debug_assertions)]
                         {
                             // Debug builds: allow environment variable overrides for testing
@@ -205,6 +228,132 @@ struct Cli {
     /// Path to node settings file (default: config/nodeSettings.conf)
     #[arg(long, value_name = "FILE")]
     config: Option<PathBuf>,
+    /// Write a UTXO snapshot to FILE and exit
+    #[arg(long, value_name = "FILE")]
+    export_snapshot: Option<PathBuf>,
+    /// Height of the exported snapshot (default: chain tip)
+    #[arg(long, value_name = "HEIGHT", requires = "export_snapshot")]
+    snapshot_height: Option<u64>,
+    /// Bootstrap an empty data directory from a hardcoded UTXO snapshot in FILE
+    #[arg(long, value_name = "FILE")]
+    import_snapshot: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Write main-chain blocks to a block file and exit
+    ExportBlocks {
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+        /// First height to export
+        #[arg(long, value_name = "HEIGHT", default_value_t = 0)]
+        from: u64,
+        /// Last height to export (default: chain tip)
+        #[arg(long, value_name = "HEIGHT")]
+        to: Option<u64>,
+    },
+    /// Validate and connect the blocks of a block file, then exit. Blocks
+    /// already on the chain are skipped, so rerunning resumes an interrupted import.
+    ImportBlocks {
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+    },
+}
+
+/// Run an `export-blocks` / `import-blocks` subcommand and exit
+fn run_block_file_command(bc: &mut Blockchain, command: &Command) -> ! {
+    match command {
+        Command::ExportBlocks { file, from, to } => {
+            let to = to.unwrap_or_else(|| bc.get_next_index().unwrap_or(0).saturating_sub(1));
+            match bc.export_blocks(file, *from, to) {
+                Ok(count) => {
+                    println!("[INFO] Exported {} blocks (#{}..=#{}) to {:?}", count, from, to, file);
+                    std::process::exit(0);
+                }
+                Err(e) => {
+                    eprintln!("Failed to export blocks: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::ImportBlocks { file } => {
+            info!(
+                "📥 Importing blocks from {:?} (local chain has {} blocks)",
+                file,
+                bc.get_next_index().unwrap_or(0)
+            );
+            let started = std::time::Instant::now();
+            let mut last_report = started;
+            let mut imported = 0u64;
+            let result = bc.import_blocks(file, |block| {
+                imported += 1;
+                if last_report.elapsed() >= Duration::from_secs(10) {
+                    info!(
+                        "  imported #{} ({} blocks, {:.1} blocks/s)",
+                        block.header.index,
+                        imported,
+                        imported as f64 / started.elapsed().as_secs_f64()
+                    );
+                    last_report = std::time::Instant::now();
+                }
+            });
+            match result {
+                Ok(summary) => {
+                    println!(
+                        "[INFO] Imported {} blocks ({} already on the chain) in {:.0?}; chain now has {} blocks",
+                        summary.imported,
+                        summary.skipped,
+                        started.elapsed(),
+                        bc.get_next_index().unwrap_or(0)
+                    );
+                    std::process::exit(0);
+                }
+                Err(e) => {
+                    eprintln!("Block import stopped: {}", e);
+                    eprintln!("Blocks imported so far are kept; run the import again to resume.");
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+}
+
+/// Validate the header chain below an imported UTXO snapshot in chunks, so
+/// block processing is held up for at most one chunk at a time. Block bodies
+/// below the snapshot are not fetched, so its UTXO set stays trusted. An
+/// invalid header chain stops the node.
+fn spawn_snapshot_header_validation(node: NodeHandle, shutdown_flag: Arc<AtomicBool>) {
+    const CHUNK: u64 = 1_000;
+    tokio::task::spawn_blocking(move || {
+        let top = match node.bc.lock().unwrap().snapshot_info() {
+            Ok(Some(info)) => info.height,
+            _ => return,
+        };
+        log::info!("🔍 Validating snapshot headers 0..={} in the background...", top);
+        let mut from = 0;
+        while from <= top {
+            if shutdown_flag.load(OtherOrdering::SeqCst) {
+                return;
+            }
+            let to = (from + CHUNK).min(top + 1);
+            if let Err(e) = node.bc.lock().unwrap().validate_snapshot_headers(from, to) {
+                log::error!(
+                    "❌ Snapshot header chain is invalid: {}. Shutting down; delete the data directory and resync without --import-snapshot.",
+                    e
+                );
+                shutdown_flag.store(true, OtherOrdering::SeqCst);
+                node.mining.cancel_flag.store(true, OtherOrdering::SeqCst);
+                return;
+            }
+            from = to;
+        }
+        match node.bc.lock().unwrap().mark_snapshot_headers_validated() {
+            Ok(()) => log::info!("✅ Snapshot headers validated (blocks 0..={})", top),
+            Err(e) => log::warn!("⚠️  Failed to record snapshot validation: {}", e),
+        }
+    });
 }
 
 #[tokio::main]
@@ -283,7 +432,16 @@ async fn main() {
     }
 
     // Initialize core Blockchain (RocksDB-backed)
-    let bc = match Blockchain::new(db_path.as_str()) {
+    // Transaction signatures are bound to the active network's chain id
+    let chain_params = astram_node::p2p::manager::chain_params();
+    let mut params = chain_params.clone();
+    if !node_settings.checkpoint_keys.is_empty() {
+        params.checkpoint_keys = node_settings.checkpoint_keys.clone();
+    }
+    if let Some(threshold) = node_settings.checkpoint_threshold {
+        params.checkpoint_threshold = threshold;
+    }
+    let mut bc = match Blockchain::new(db_path.as_str(), params) {
         Ok(b) => b,
         Err(e) => {
             eprintln!("Failed to open blockchain DB: {}", e);
@@ -291,6 +449,51 @@ async fn main() {
             std::process::exit(1);
         }
     };
+    if chain_params.mine_on_demand {
+        info!("{}: POST /regtest/generate enabled", chain_params.network);
+    }
+    if node_settings.prune_depth > 0 {
+        bc.set_prune_depth(Some(node_settings.prune_depth));
+    }
+    load_signed_checkpoints(&mut bc, &node_settings).await;
+    if let Err(e) = bc.set_assume_valid(node_settings.assume_valid.as_deref()) {
+        eprintln!("Invalid ASSUME_VALID setting: {}", e);
+        std::process::exit(1);
+    }
+
+    if let Some(command) = &cli.command {
+        run_block_file_command(&mut bc, command);
+    }
+
+    if let Some(path) = &cli.export_snapshot {
+        let height = cli
+            .snapshot_height
+            .unwrap_or_else(|| bc.get_next_index().unwrap_or(0).saturating_sub(1));
+        match bc.export_utxo_snapshot(height, path) {
+            Ok(info) => {
+                println!(
+                    "[INFO] Snapshot written to {:?}: height={} block={} utxos={} hash={}",
+                    path, info.height, info.block_hash, info.utxo_count, info.content_hash
+                );
+                std::process::exit(0);
+            }
+            Err(e) => {
+                eprintln!("Failed to export UTXO snapshot: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(path) = &cli.import_snapshot {
+        if bc.chain_tip.is_some() {
+            warn!("Ignoring --import-snapshot: the database already holds a chain");
+        } else if let Err(e) = bc.import_utxo_snapshot(path, &chain_params.snapshot_anchors) {
+            eprintln!("Failed to import UTXO snapshot: {}", e);
+            std::process::exit(1);
+        }
+    }
+    let snapshot_headers_pending = bc.snapshot_headers_pending().unwrap_or(false);
+    let prune_depth = bc.prune_depth;
     let bc = Arc::new(Mutex::new(bc));
 
     // Check and recover tip if needed
@@ -325,25 +528,35 @@ async fn main() {
             // Validate all stored blocks (hash, PoW, DWG3 difficulty, merkle root, parent link).
             // If an invalid block is found the chain is truncated to just before it so the node
             // can re-sync cleanly from peers instead of being stuck on a corrupt chain.
-            log::info!("🔍 Validating stored blocks...");
-            match bc_guard.validate_chain_integrity() {
-                Ok((checked, None)) => {
-                    log::info!("✅ All {} blocks passed integrity check", checked + 1);
-                }
-                Ok((valid_tip, Some(bad_height))) => {
-                    log::warn!(
-                        "⚠️  Chain truncated to height {} (invalid block detected at height {}). \
-                        Node will re-sync missing blocks from peers.",
-                        valid_tip, bad_height
-                    );
-                }
-                Err(e) => {
-                    log::error!("❌ Chain integrity check failed unexpectedly: {}", e);
+            if snapshot_headers_pending {
+                log::info!("⏭️  Imported snapshot: its header chain is validated in the background");
+            } else {
+                log::info!("🔍 Validating stored blocks...");
+                match bc_guard.validate_chain_integrity() {
+                    Ok((checked, None)) => {
+                        log::info!("✅ All {} blocks passed integrity check", checked + 1);
+                    }
+                    Ok((valid_tip, Some(bad_height))) => {
+                        log::warn!(
+                            "⚠️  Chain truncated to height {} (invalid block detected at height {}). \
+                            Node will re-sync missing blocks from peers.",
+                            valid_tip, bad_height
+                        );
+                    }
+                    Err(e) => {
+                        log::error!("❌ Chain integrity check failed unexpectedly: {}", e);
+                    }
                 }
             }
         }
-    }
 
+        if let Some(depth) = prune_depth {
+            log::info!("✂️  Pruned mode: keeping the last {} blocks", depth);
+            if let Err(e) = bc_guard.prune() {
+                log::warn!("⚠️  Pruning failed: {}", e);
+            }
+        }
+    }
     // Read the chain height once, immediately after validation (which may have truncated the chain).
     // This single value is used for both P2P and DNS so both always agree on the same height.
     let my_height = {
@@ -404,6 +617,7 @@ async fn main() {
     // Set listening port in P2P manager (for self-connection detection)
     p2p_handle.set_my_listening_port(node_settings.p2p_port);
     p2p_handle.set_my_bind_addr(node_settings.p2p_bind_addr.clone());
+    p2p_handle.set_my_prune_depth(prune_depth);
 
     p2p_service
         .start(bind_addr, node_handle.clone(), chain_state.clone())
@@ -439,6 +653,10 @@ async fn main() {
         }
     });
 
+    if snapshot_headers_pending {
+        spawn_snapshot_header_validation(node_handle.clone(), shutdown_flag.clone());
+    }
+
     let (task_handles, server_handle) = start_services(
         node_handle.clone(),
         p2p_handle.clone(),
@@ -590,6 +808,67 @@ fn get_localhost_address() -> String {
     "127.0.0.1".to_string()
 }
 
+/// Signed checkpoint file kept in the data dir
+const CHECKPOINT_FILE: &str = "checkpoints.txt";
+
+/// Put the signed checkpoint file from the data dir in force, then a newer
+/// one from the DNS server (saved to the data dir once accepted)
+async fn load_signed_checkpoints(bc: &mut Blockchain, settings: &NodeSettings) {
+    let path = std::path::Path::new(&settings.data_dir).join(CHECKPOINT_FILE);
+    match fs::read_to_string(&path) {
+        Ok(text) => {
+            if let Err(e) = SignedCheckpoints::parse(&text).and_then(|set| bc.apply_signed_checkpoints(set)) {
+                warn!("Ignoring checkpoint file {:?}: {}", path, e);
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => warn!("Cannot read checkpoint file {:?}: {}", path, e),
+    }
+
+    if !bc.params.dns_seed {
+        return;
+    }
+    let url = format!("{}/checkpoints", settings.dns_server_url);
+    let text = match fetch_checkpoints_from_dns(&url).await {
+        Ok(Some(text)) => text,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Could not fetch checkpoints from {}: {}", url, e);
+            return;
+        }
+    };
+    let set = match SignedCheckpoints::parse(&text) {
+        Ok(set) => set,
+        Err(e) => {
+            warn!("Ignoring checkpoints from {}: {}", url, e);
+            return;
+        }
+    };
+    if bc.signed_checkpoints.as_ref().is_some_and(|active| active.sequence >= set.sequence) {
+        return;
+    }
+    match bc.apply_signed_checkpoints(set) {
+        Ok(()) => {
+            if let Err(e) = fs::write(&path, &text) {
+                warn!("Could not save checkpoint file {:?}: {}", path, e);
+            }
+        }
+        Err(e) => warn!("Ignoring checkpoints from {}: {}", url, e),
+    }
+}
+
+/// Checkpoint file served by the DNS server (None if it serves none)
+async fn fetch_checkpoints_from_dns(url: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()?;
+    let response = client.get(url).send().await?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    Ok(Some(response.error_for_status()?.text().await?))
+}
+
 /// Fetch best nodes from DNS server, excluding self
 async fn fetch_best_nodes_from_dns(
     node_meta: Arc<NodeMeta>,
@@ -597,6 +876,11 @@ async fn fetch_best_nodes_from_dns(
     my_port: u16,
     limit: usize,
 ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    // Networks without a DNS seed (regtest) only connect to BOOTSTRAP_PEERS
+    if !astram_node::p2p::manager::chain_params().dns_seed {
+        return Ok(Vec::new());
+    }
+
     // Get my public address from state
     let my_address = { node_meta.my_public_address.lock().unwrap().clone() };
 
@@ -1017,18 +1301,22 @@ async fn start_services(
     let mut task_handles = Vec::new();
 
     let my_node_port = settings.p2p_port;
+    // Networks without a DNS seed (regtest) stay off the public DNS server
+    let dns_enabled = astram_node::p2p::manager::chain_params().dns_seed;
 
     // Register with DNS server (fail fast if registration fails)
     // Note: This is outside the main mining loop, so it happens only once at startup
     // Periodic re-registration is done without trying to acquire any locks
-    match register_with_dns(node_handle.clone(), &settings, initial_height).await {
-        Ok(registered_address) => {
-            p2p_handle.set_my_public_ip(Some(registered_address.clone()));
-            *node_meta.my_public_address.lock().unwrap() = Some(registered_address);
-        }
-        Err(e) => {
-            log::error!("DNS registration failed; shutting down node: {}", e);
-            std::process::exit(1);
+    if dns_enabled {
+        match register_with_dns(node_handle.clone(), &settings, initial_height).await {
+            Ok(registered_address) => {
+                p2p_handle.set_my_public_ip(Some(registered_address.clone()));
+                *node_meta.my_public_address.lock().unwrap() = Some(registered_address);
+            }
+            Err(e) => {
+                log::error!("DNS registration failed; shutting down node: {}", e);
+                std::process::exit(1);
+            }
         }
     }
 
@@ -1038,6 +1326,10 @@ async fn start_services(
     let shutdown_flag_dns = shutdown_flag.clone();
     let settings_dns = settings.clone();
     let dns_task = tokio::spawn(async move {
+        if !dns_enabled {
+            return;
+        }
+
         // Re-register every 5 minutes to keep the node alive in DNS
         let mut interval = tokio::time::interval(Duration::from_secs(300));
         interval.tick().await; // Skip first immediate tick
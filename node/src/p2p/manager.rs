@@ -1,5 +1,6 @@
 use crate::p2p::messages::{HandshakeInfo, InventoryType, P2pMessage};
-use crate::p2p::peer::{Peer, PeerId};
+use crate::p2p::peer::{Peer, PeerId, frame_codec};
+use Astram_core::ChainParams;
 use Astram_core::block;
 use Astram_core::transaction::Transaction;
 use bincode::{Decode, Encode};
@@ -10,6 +11,7 @@ use futures::future;
 use hex;
 use log::{debug, info, warn};
 use parking_lot::Mutex;
+use primitive_types::U256;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
@@ -18,7 +20,7 @@ use std::sync::Arc;
 use std::sync::OnceLock;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
-use tokio_util::codec::{FramedRead, FramedWrite, LengthDelimitedCodec};
+use tokio_util::codec::{FramedRead, FramedWrite};
 
 #[derive(Encode, Decode, Debug, serde::Serialize, serde::Deserialize)]
 pub struct SavedPeer {
@@ -29,16 +31,8 @@ pub struct SavedPeer {
 pub const MAX_OUTBOUND: usize = 8;
 pub const PEERS_FILE: &str = "peers.json";
 pub const PROTOCOL_VERSION: u32 = 1;
-pub const MAINNET_NETWORK_ID: &str = "Astram-mainnet";
-pub const TESTNET_NETWORK_ID: &str = "Astram-testnet";
-pub const MAINNET_CHAIN_ID: u64 = 1;
-pub const TESTNET_CHAIN_ID: u64 = 8888;
-pub const MAINNET_NETWORK_MAGIC: u32 = 0xA57A_0001;
-pub const TESTNET_NETWORK_MAGIC: u32 = 0xA57A_22B8;
 
-static NETWORK_ID: OnceLock<String> = OnceLock::new();
-static CHAIN_ID: OnceLock<u64> = OnceLock::new();
-static NETWORK_MAGIC: OnceLock<u32> = OnceLock::new();
+static CHAIN_PARAMS: OnceLock<ChainParams> = OnceLock::new();
 
 // Security: Network identity hardcoding for production releases
 //
@@ -46,58 +40,75 @@ static NETWORK_MAGIC: OnceLock<u32> = OnceLock::new();
 // accidental or malicious network misconfiguration that could cause consensus failures,
 // chain forks, or eclipse attacks.
 //
-// Debug builds allow environment variable overrides (ASTRAM_NETWORK, ASTRAM_NETWORK_ID,
-// ASTRAM_CHAIN_ID, ASTRAM_NETWORK_MAGIC) for development, testing, and testnet operation.
+// Debug builds select the network with environment variables for development, testing,
+// testnet and regtest operation: ASTRAM_CHAIN_PARAMS (a custom chain params file) or
+// ASTRAM_NETWORK (mainnet, testnet, regtest), then the ASTRAM_NETWORK_ID, ASTRAM_CHAIN_ID
+// and ASTRAM_NETWORK_MAGIC overrides.
 //
 // This compile-time enforcement ensures production nodes always connect to the official
 // Astram mainnet with verified genesis hash and chain parameters, protecting the network
 // from operational errors and malicious configuration tampering.
 
-fn resolve_network_id() -> &'static str {
-    NETWORK_ID
-        .get_or_init(|| {
-            #[cfg(debug_assertions)]
-            {
-                // Debug builds: allow environment variable overrides for testing
-                if let Ok(value) = std::env::var("ASTRAM_NETWORK_ID") {
-                    let trimmed = value.trim();
-                    if !trimmed.is_empty() {
-                        return trimmed.to_string();
-                    }
-                }
-
-                let network = std::env::var("ASTRAM_NETWORK").unwrap_or_else(|_| "mainnet".to_string());
-                if network.eq_ignore_ascii_case("testnet") {
-                    return TESTNET_NETWORK_ID.to_string();
-                }
-            }
-
-            // Release builds: hardcoded mainnet only
-            MAINNET_NETWORK_ID.to_string()
+/// Consensus parameters of the network this node runs. Exits the process if
+/// a custom chain params file cannot be loaded.
+pub fn chain_params() -> &'static ChainParams {
+    CHAIN_PARAMS.get_or_init(|| {
+        load_chain_params().unwrap_or_else(|e| {
+            log::error!("Invalid chain parameters: {}", e);
+            std::process::exit(1);
         })
-        .as_str()
+    })
 }
 
-fn resolve_chain_id() -> u64 {
-    *CHAIN_ID.get_or_init(|| {
-        #[cfg(debug_assertions)]
-        {
-            // Debug builds: allow environment variable overrides for testing
-            if let Ok(value) = std::env::var("ASTRAM_CHAIN_ID") {
-                if let Ok(parsed) = value.trim().parse::<u64>() {
-                    return parsed;
-                }
-            }
+#[cfg(not(debug_assertions))]
+fn load_chain_params() -> anyhow::Result<ChainParams> {
+    // Release builds: hardcoded mainnet only
+    Ok(ChainParams::mainnet())
+}
 
+#[cfg(debug_assertions)]
+fn load_chain_params() -> anyhow::Result<ChainParams> {
+    // Debug builds: allow environment variable overrides for testing
+    let mut params = match std::env::var("ASTRAM_CHAIN_PARAMS") {
+        Ok(path) if !path.trim().is_empty() => ChainParams::from_file(std::path::Path::new(path.trim()))?,
+        _ => {
             let network = std::env::var("ASTRAM_NETWORK").unwrap_or_else(|_| "mainnet".to_string());
-            if network.eq_ignore_ascii_case("testnet") {
-                return TESTNET_CHAIN_ID;
-            }
+            ChainParams::for_network(&network).unwrap_or_else(|_| {
+                warn!("Unknown ASTRAM_NETWORK='{}', using mainnet", network);
+                ChainParams::mainnet()
+            })
         }
+    };
 
-        // Release builds: hardcoded mainnet only
-        MAINNET_CHAIN_ID
-    })
+    if let Ok(value) = std::env::var("ASTRAM_NETWORK_ID") {
+        let trimmed = value.trim();
+        if !trimmed.is_empty() {
+            params.network_id = trimmed.to_string();
+        }
+    }
+    if let Ok(value) = std::env::var("ASTRAM_CHAIN_ID")
+        && let Ok(parsed) = value.trim().parse::<u64>()
+    {
+        params.chain_id = parsed;
+    }
+    if let Ok(value) = std::env::var("ASTRAM_NETWORK_MAGIC") {
+        match parse_network_magic(&value) {
+            Some(parsed) => params.network_magic = parsed,
+            None => warn!(
+                "Invalid ASTRAM_NETWORK_MAGIC='{}', using network defaults",
+                value
+            ),
+        }
+    }
+    Ok(params)
+}
+
+fn resolve_network_id() -> &'static str {
+    chain_params().network_id.as_str()
+}
+
+pub fn resolve_chain_id() -> u64 {
+    chain_params().chain_id
 }
 
 #[cfg(debug_assertions)]
@@ -124,30 +135,57 @@ fn parse_network_magic(value: &str) -> Option<u32> {
 }
 
 fn resolve_network_magic() -> u32 {
-    *NETWORK_MAGIC.get_or_init(|| {
-        #[cfg(debug_assertions)]
-        {
-            // Debug builds: allow environment variable overrides for testing
-            if let Ok(value) = std::env::var("ASTRAM_NETWORK_MAGIC") {
-                if let Some(parsed) = parse_network_magic(&value) {
-                    return parsed;
-                }
+    chain_params().network_magic
+}
 
-                warn!(
-                    "Invalid ASTRAM_NETWORK_MAGIC='{}', using network defaults",
-                    value
-                );
-            }
+/// Handshake feature of pruned nodes: `pruned:<depth>`, the number of recent
+/// blocks whose bodies the node still serves
+const PRUNED_FEATURE_PREFIX: &str = "pruned:";
+
+/// Handshake feature carrying the cumulative chain work of the sender's tip:
+/// `chainwork:<hex>`. A feature rather than a field, so nodes that predate it
+/// still decode the handshake; their work counts as zero.
+const CHAIN_WORK_FEATURE_PREFIX: &str = "chainwork:";
+
+/// Chain work advertised in a peer's handshake features (zero if absent)
+pub fn parse_chain_work(features: &[String]) -> U256 {
+    features
+        .iter()
+        .find_map(|f| f.strip_prefix(CHAIN_WORK_FEATURE_PREFIX))
+        .and_then(|work| U256::from_str_radix(work, 16).ok())
+        .unwrap_or_default()
+}
 
-            let network = std::env::var("ASTRAM_NETWORK").unwrap_or_else(|_| "mainnet".to_string());
-            if network.eq_ignore_ascii_case("testnet") {
-                return TESTNET_NETWORK_MAGIC;
-            }
-        }
+/// Prune depth advertised in a peer's handshake features (None = archive node)
+pub fn parse_prune_depth(features: &[String]) -> Option<u64> {
+    features
+        .iter()
+        .find_map(|f| f.strip_prefix(PRUNED_FEATURE_PREFIX))
+        .and_then(|depth| depth.parse().ok())
+}
 
-        // Release builds: hardcoded mainnet only
-        MAINNET_NETWORK_MAGIC
-    })
+/// Whether a peer at `peer_height` keeping `prune_depth` blocks can serve block `height`
+fn peer_serves_block(peer_height: u64, prune_depth: Option<u64>, height: u64) -> bool {
+    match prune_depth {
+        Some(depth) => height.saturating_add(depth) > peer_height,
+        None => true,
+    }
+}
+
+/// Chain work of a peer, ranked by `rank` for sync-peer selection. `proven`
+/// comes from headers and blocks the peer sent that passed validation; the
+/// handshake figure is only a hint, capped at the work we had proven when it
+/// arrived, so advertising more than that wins nothing.
+#[derive(Debug, Clone, Copy, Default)]
+struct PeerWork {
+    hint: U256,
+    proven: U256,
+}
+
+impl PeerWork {
+    fn rank(&self) -> U256 {
+        self.hint.max(self.proven)
+    }
 }
 
 // Security: Network-level protection constants
@@ -162,15 +200,21 @@ pub const MAX_PEERS_PER_SUBNET_16: usize = 4; // Max peers from same /16 subnet
 pub const MIN_OUTBOUND_SUBNET_DIVERSITY: usize = 3; // Require connections to at least 3 different /16 subnets
 
 type Shared<T> = Arc<Mutex<T>>;
+type BlockCallback = Arc<dyn Fn(PeerId, block::Block) + Send + Sync>;
+type ChainWorkCallback = Arc<dyn Fn() -> U256 + Send + Sync>;
 pub struct PeerManager {
     peers: Shared<HashMap<PeerId, UnboundedSender<P2pMessage>>>,
     peer_heights: Shared<HashMap<PeerId, u64>>,
+    /// Chain work each peer advertised and proved (see `PeerWork`)
+    peer_chain_work: Shared<HashMap<PeerId, PeerWork>>,
     peer_handshakes: Shared<HashMap<PeerId, HandshakeInfo>>,
     peer_ips: Shared<HashMap<String, Vec<PeerId>>>, // IP -> list of peer IDs
     my_height: Arc<Mutex<u64>>,
     my_listening_port: Arc<Mutex<u16>>,
     my_bind_addr: Arc<Mutex<String>>,
     my_public_ip: Arc<Mutex<Option<String>>>,
+    /// Block bodies kept by this node (None = archive node), advertised to peers
+    my_prune_depth: Arc<Mutex<Option<u64>>>,
     is_syncing: Arc<Mutex<bool>>, // 블록 동기화 중 플래그 (Tx Inv 필터링용)
     pending_header_requests: Shared<HashMap<PeerId, std::time::Instant>>, // 요청 시각 추적
     /// Sync blacklist: peer_id → time when the ban was imposed.
@@ -180,7 +224,7 @@ pub struct PeerManager {
     /// Used to distinguish "slow processing" from "true stall" in sync.
     last_block_received_at: Arc<std::sync::atomic::AtomicU64>,
     /// callback when a new block is received
-    on_block: Arc<Mutex<Option<Arc<dyn Fn(block::Block) + Send + Sync>>>>,
+    on_block: Shared<Option<BlockCallback>>,
     /// callback when a new transaction is received
     on_tx: Arc<Mutex<Option<Arc<dyn Fn(Transaction) + Send + Sync>>>>,
     on_getheaders: Arc<
@@ -192,6 +236,8 @@ pub struct PeerManager {
     >,
     on_getdata: Arc<Mutex<Option<Arc<dyn Fn(PeerId, InventoryType, Vec<Vec<u8>>) + Send + Sync>>>>,
     on_get_chain_locator: Arc<Mutex<Option<Arc<dyn Fn() -> Vec<Vec<u8>> + Send + Sync>>>>,
+    /// Callback returning the cumulative chain work of our current tip
+    on_get_chain_work: Shared<Option<ChainWorkCallback>>,
     on_headers: Arc<
         Mutex<Option<Arc<dyn Fn(PeerId, Vec<block::BlockHeader>) -> bool + Send + Sync>>>,
     >,
@@ -205,18 +251,21 @@ impl PeerManager {
         Self {
             peers: Arc::new(Mutex::new(HashMap::new())),
             peer_heights: Arc::new(Mutex::new(HashMap::new())),
+            peer_chain_work: Arc::new(Mutex::new(HashMap::new())),
             peer_handshakes: Arc::new(Mutex::new(HashMap::new())),
             peer_ips: Arc::new(Mutex::new(HashMap::new())),
             my_height: Arc::new(Mutex::new(0)),
             my_listening_port: Arc::new(Mutex::new(18335)), // Default port
             my_bind_addr: Arc::new(Mutex::new("0.0.0.0".to_string())),
             my_public_ip: Arc::new(Mutex::new(None)),
+            my_prune_depth: Arc::new(Mutex::new(None)),
             is_syncing: Arc::new(Mutex::new(false)),
             on_block: Arc::new(Mutex::new(None)),
             on_tx: Arc::new(Mutex::new(None)),
             on_getheaders: Arc::new(Mutex::new(None)),
             on_getdata: Arc::new(Mutex::new(None)),
             on_get_chain_locator: Arc::new(Mutex::new(None)),
+            on_get_chain_work: Arc::new(Mutex::new(None)),
             on_headers: Arc::new(Mutex::new(None)),
             pending_header_requests: Arc::new(Mutex::new(HashMap::new())),
             sync_blacklist: Arc::new(Mutex::new(HashMap::new())),
@@ -227,7 +276,7 @@ impl PeerManager {
 
     pub fn set_on_block<F>(&self, cb: F)
     where
-        F: Fn(block::Block) + Send + Sync + 'static,
+        F: Fn(PeerId, block::Block) + Send + Sync + 'static,
     {
         *self.on_block.lock() = Some(Arc::new(cb));
     }
@@ -253,6 +302,21 @@ impl PeerManager {
         *self.on_get_chain_locator.lock() = Some(Arc::new(cb));
     }
 
+    pub fn set_on_get_chain_work<F>(&self, cb: F)
+    where
+        F: Fn() -> U256 + Send + Sync + 'static,
+    {
+        *self.on_get_chain_work.lock() = Some(Arc::new(cb));
+    }
+
+    /// Our tip's cumulative chain work
+    fn my_chain_work(&self) -> U256 {
+        match &*self.on_get_chain_work.lock() {
+            Some(cb) => (cb)(),
+            None => U256::zero(),
+        }
+    }
+
     pub fn set_on_headers<F>(&self, cb: F)
     where
         F: Fn(PeerId, Vec<block::BlockHeader>) -> bool + Send + Sync + 'static,
@@ -310,6 +374,32 @@ impl PeerManager {
         *self.my_public_ip.lock() = public_ip;
     }
 
+    pub fn set_my_prune_depth(&self, depth: Option<u64>) {
+        *self.my_prune_depth.lock() = depth;
+    }
+
+    /// Features sent in our handshake
+    fn my_features(&self) -> Vec<String> {
+        let mut features = vec![
+            "blocks".to_string(),
+            "transactions".to_string(),
+            "headers".to_string(),
+        ];
+        if let Some(depth) = *self.my_prune_depth.lock() {
+            features.push(format!("{}{}", PRUNED_FEATURE_PREFIX, depth));
+        }
+        features.push(format!("{}{:x}", CHAIN_WORK_FEATURE_PREFIX, self.my_chain_work()));
+        features
+    }
+
+    /// Prune depth a peer advertised in its handshake (None = archive node)
+    fn peer_prune_depth(&self, peer_id: &PeerId) -> Option<u64> {
+        self.peer_handshakes
+            .lock()
+            .get(peer_id)
+            .and_then(|info| parse_prune_depth(&info.supported_features))
+    }
+
     fn is_self_connection(&self, peer_id: &str, peer_listening_port: u16) -> bool {
         let my_port = self.get_my_listening_port();
         if peer_listening_port != my_port {
@@ -344,6 +434,7 @@ impl PeerManager {
         warn!("[P2P] Disconnecting {}: {}", peer_id, reason);
         self.peers.lock().remove(peer_id);
         self.peer_heights.lock().remove(peer_id);
+        self.peer_chain_work.lock().remove(peer_id);
         self.peer_handshakes.lock().remove(peer_id);
     }
 
@@ -672,8 +763,8 @@ impl PeerManager {
 
         let (r, w) = tokio::io::split(stream);
 
-        let reader = FramedRead::new(r, LengthDelimitedCodec::new());
-        let writer = FramedWrite::new(w, LengthDelimitedCodec::new());
+        let reader = FramedRead::new(r, frame_codec());
+        let writer = FramedWrite::new(w, frame_codec());
 
         let peer = Peer {
             id: peer_id.clone(),
@@ -728,11 +819,7 @@ impl PeerManager {
             let handshake_info = HandshakeInfo {
                 protocol_version: PROTOCOL_VERSION,
                 software_version: env!("CARGO_PKG_VERSION").to_string(),
-                supported_features: vec![
-                    "blocks".to_string(),
-                    "transactions".to_string(),
-                    "headers".to_string(),
-                ],
+                supported_features: self.my_features(),
                 network_id: resolve_network_id().to_string(),
                 chain_id: resolve_chain_id(),
                 network_magic: resolve_network_magic(),
@@ -832,6 +919,16 @@ impl PeerManager {
                         match bincode::decode_from_slice::<P2pMessage, _>(payload, config_read) {
                             Ok((msg, _remaining)) => {
                                 debug!("[P2P] Message decoded from {}", peer_id_clone);
+                                if let P2pMessage::Block { block } = &msg {
+                                    let size = block::block_size(block).unwrap_or(usize::MAX);
+                                    if size > Astram_core::config::MAX_BLOCK_SIZE {
+                                        warn!(
+                                            "[P2P] Peer {} sent oversized block {} ({} bytes)",
+                                            peer_id_clone, block.header.index, size
+                                        );
+                                        break;
+                                    }
+                                }
                                 // delegate to manager
                                 manager_clone
                                     .handle_message(peer_id_clone.clone(), msg)
@@ -871,6 +968,7 @@ impl PeerManager {
                 }
                 self.peers.lock().remove(&peer_id_clone2);
                 self.peer_heights.lock().remove(&peer_id_clone2);
+                self.peer_chain_work.lock().remove(&peer_id_clone2);
                 self.peer_handshakes.lock().remove(&peer_id_clone2);
 
                 // Security: Remove from IP tracking (OPTIMIZED: single lock)
@@ -907,6 +1005,7 @@ impl PeerManager {
                 }
                 self.peers.lock().remove(&peer_id_clone2);
                 self.peer_heights.lock().remove(&peer_id_clone2);
+                self.peer_chain_work.lock().remove(&peer_id_clone2);
                 self.peer_handshakes.lock().remove(&peer_id_clone2);
 
                 // Security: Remove from IP tracking (OPTIMIZED: single lock)
@@ -1021,6 +1120,7 @@ impl PeerManager {
                 self.peer_heights
                     .lock()
                     .insert(peer_id.clone(), info.height);
+                self.record_advertised_work(&peer_id, parse_chain_work(&info.supported_features));
                 self.peer_handshakes
                     .lock()
                     .insert(peer_id.clone(), info.clone());
@@ -1032,11 +1132,7 @@ impl PeerManager {
                     let my_info = HandshakeInfo {
                         protocol_version: PROTOCOL_VERSION,
                         software_version: env!("CARGO_PKG_VERSION").to_string(),
-                        supported_features: vec![
-                            "blocks".to_string(),
-                            "transactions".to_string(),
-                            "headers".to_string(),
-                        ],
+                        supported_features: self.my_features(),
                         network_id: resolve_network_id().to_string(),
                         chain_id: resolve_chain_id(),
                         network_magic: resolve_network_magic(),
@@ -1143,6 +1239,7 @@ impl PeerManager {
                 self.peer_heights
                     .lock()
                     .insert(peer_id.clone(), info.height);
+                self.record_advertised_work(&peer_id, parse_chain_work(&info.supported_features));
                 let heights_duration = lock_start.elapsed();
 
                 let lock_start = std::time::Instant::now();
@@ -1234,6 +1331,7 @@ impl PeerManager {
                         warn!("[P2P] Disconnecting {} due to incompatible chain policy", peer_id);
                         self.peers.lock().remove(&peer_id);
                         self.peer_heights.lock().remove(&peer_id);
+                        self.peer_chain_work.lock().remove(&peer_id);
                         self.peer_handshakes.lock().remove(&peer_id);
                         return;
                     }
@@ -1250,9 +1348,16 @@ impl PeerManager {
                     }
 
                     // request full blocks for these headers, skipping ones we already have
+                    // and, from a pruned peer, ones it no longer stores
                     let exists_cb = self.check_block_exists.lock().clone();
+                    let peer_prune_depth = self.peer_prune_depth(&peer_id);
+                    let peer_height = self.peer_heights.lock().get(&peer_id).copied().unwrap_or(0);
                     let mut hashes: Vec<Vec<u8>> = Vec::new();
                     for hdr in headers.iter() {
+                        if !peer_serves_block(peer_height, peer_prune_depth, hdr.index) {
+                            debug!("[P2P] Skipping block #{}: pruned on peer {}", hdr.index, peer_id);
+                            continue;
+                        }
                         if let Ok(hash_hex) = block::compute_header_hash(hdr) {
                             // Skip blocks already in our DB to avoid duplicate downloads
                             if let Some(ref cb) = exists_cb {
@@ -1353,7 +1458,7 @@ impl PeerManager {
                             lock_duration
                         );
                     }
-                    (cb)(block.clone());
+                    (cb)(peer_id.clone(), block.clone());
                     info!(
                         "[P2P] ✅ Block callback completed in {:?}",
                         callback_start.elapsed()
@@ -1544,6 +1649,17 @@ impl PeerManager {
         let peers = self.peers.lock().clone();
         debug!("[P2P] request_headers_from_peers: {} peers", peers.len());
 
+        // Pruned peers that no longer store our next block cannot serve the sync
+        let next_height = self.get_my_height() + 1;
+        let peer_heights = self.peer_heights.lock().clone();
+        let peers: Vec<_> = peers
+            .into_iter()
+            .filter(|(id, _)| {
+                let peer_height = peer_heights.get(id).copied().unwrap_or(0);
+                peer_serves_block(peer_height, self.peer_prune_depth(id), next_height)
+            })
+            .collect();
+
         let now = std::time::Instant::now();
         let mut pending = self.pending_header_requests.lock();
         for (id, tx) in peers {
@@ -1616,23 +1732,50 @@ impl PeerManager {
         bl.retain(|_, t| t.elapsed() < ban_duration);
     }
 
-    /// Return the connected, non-banned peer with the highest reported block height.
-    /// Returns None if no eligible peer exists.
+    /// Record the handshake chain work of `peer_id` as a hint
+    fn record_advertised_work(&self, peer_id: &PeerId, work: U256) {
+        let hint = work.min(self.my_chain_work());
+        self.peer_chain_work.lock().entry(peer_id.clone()).or_default().hint = hint;
+    }
+
+    /// Record chain work that headers or blocks received from `peer_id`
+    /// proved: the `calculate_chain_work` of the best of them once they
+    /// passed validation
+    pub fn record_proven_work(&self, peer_id: &PeerId, work: U256) {
+        let mut peer_chain_work = self.peer_chain_work.lock();
+        let entry = peer_chain_work.entry(peer_id.clone()).or_default();
+        entry.proven = entry.proven.max(work);
+    }
+
+    /// Return the connected, non-banned peer with the most chain work (see
+    /// `PeerWork::rank`; reported block height breaks ties). Pruned peers that
+    /// no longer store our next block are skipped. Returns (peer, height), or
+    /// None if no eligible peer exists.
     pub fn get_best_sync_peer(
         &self,
         ban_duration: std::time::Duration,
     ) -> Option<(PeerId, u64)> {
+        let next_height = self.get_my_height() + 1;
         let peers = self.peers.lock();
         let peer_heights = self.peer_heights.lock();
+        let peer_chain_work = self.peer_chain_work.lock();
+        let peer_handshakes = self.peer_handshakes.lock();
         let bl = self.sync_blacklist.lock();
 
         peer_heights
             .iter()
-            .filter(|(peer_id, _)| {
+            .filter(|(peer_id, h)| {
+                let prune_depth = peer_handshakes
+                    .get(*peer_id)
+                    .and_then(|info| parse_prune_depth(&info.supported_features));
                 peers.contains_key(*peer_id)
                     && bl.get(*peer_id).map_or(true, |t| t.elapsed() >= ban_duration)
+                    && peer_serves_block(**h, prune_depth, next_height)
+            })
+            .max_by_key(|(peer_id, h)| {
+                let work = peer_chain_work.get(*peer_id).copied().unwrap_or_default();
+                (work.rank(), **h)
             })
-            .max_by_key(|(_, h)| *h)
             .map(|(id, h)| (id.clone(), *h))
     }
 
@@ -1898,7 +2041,10 @@ struct DnsNodesResponse {
 
 #[cfg(test)]
 mod tests {
-    use super::parse_network_magic;
+    use super::{
+        HandshakeInfo, P2pMessage, PROTOCOL_VERSION, PeerManager, U256, parse_chain_work, parse_network_magic,
+        parse_prune_depth, peer_serves_block, resolve_chain_id, resolve_network_id, resolve_network_magic,
+    };
 
     #[test]
     fn parse_network_magic_formats() {
@@ -1908,4 +2054,87 @@ mod tests {
         assert_eq!(parse_network_magic(""), None);
         assert_eq!(parse_network_magic("not-a-number"), None);
     }
+
+    #[test]
+    fn pruned_peer_features() {
+        let archive = vec!["blocks".to_string(), "headers".to_string()];
+        let pruned = vec!["blocks".to_string(), "pruned:288".to_string()];
+        assert_eq!(parse_prune_depth(&archive), None);
+        assert_eq!(parse_prune_depth(&pruned), Some(288));
+        assert_eq!(parse_prune_depth(&["pruned:x".to_string()]), None);
+
+        // A peer at height 1000 keeping 288 blocks stores #713..=#1000
+        assert!(peer_serves_block(1000, None, 0));
+        assert!(peer_serves_block(1000, Some(288), 713));
+        assert!(!peer_serves_block(1000, Some(288), 712));
+    }
+
+    #[tokio::test]
+    async fn handshake_with_nodes_before_and_after_chain_work() {
+        let pm = PeerManager::new();
+        pm.set_on_get_chain_work(|| U256::from(100));
+        let peer = "203.0.113.7:8335".to_string();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        pm.peers.lock().insert(peer.clone(), tx);
+        let handshake = |features: &[&str]| P2pMessage::Handshake {
+            info: HandshakeInfo {
+                protocol_version: 1,
+                software_version: "1.0.11".to_string(),
+                supported_features: features.iter().map(|f| f.to_string()).collect(),
+                network_id: resolve_network_id().to_string(),
+                chain_id: resolve_chain_id(),
+                network_magic: resolve_network_magic(),
+                height: 5,
+                listening_port: 1,
+            },
+        };
+
+        // A node that predates the feature is accepted with no work hint
+        pm.handle_message(peer.clone(), handshake(&["blocks", "transactions", "headers"]))
+            .await;
+        assert!(pm.peer_handshakes.lock().contains_key(&peer));
+        assert_eq!(pm.peer_chain_work.lock()[&peer].hint, U256::zero());
+        match rx.try_recv() {
+            Ok(P2pMessage::HandshakeAck { info }) => {
+                assert_eq!(info.protocol_version, PROTOCOL_VERSION);
+                assert_eq!(parse_chain_work(&info.supported_features), U256::from(100));
+            }
+            other => panic!("expected a handshake ack, got {:?}", other),
+        }
+
+        // An upgraded node advertises its work
+        pm.handle_message(peer.clone(), handshake(&["blocks", "chainwork:5a"]))
+            .await;
+        assert_eq!(pm.peer_chain_work.lock()[&peer].hint, U256::from(0x5a));
+        assert_eq!(parse_chain_work(&["chainwork:zz".to_string()]), U256::zero());
+    }
+
+    #[test]
+    fn sync_peer_ranked_by_proven_work() {
+        let pm = PeerManager::new();
+        pm.set_on_get_chain_work(|| U256::from(100));
+        let mut receivers = Vec::new();
+        for (id, height) in [("honest", 10), ("liar", 50)] {
+            let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+            receivers.push(rx);
+            pm.peers.lock().insert(id.to_string(), tx);
+            pm.peer_heights.lock().insert(id.to_string(), height);
+        }
+        let ban = std::time::Duration::from_secs(60);
+        let best = || pm.get_best_sync_peer(ban).map(|(id, _)| id);
+
+        // Handshake claims count for no more than our own work
+        pm.record_advertised_work(&"liar".to_string(), U256::MAX);
+        pm.record_advertised_work(&"honest".to_string(), U256::from(90));
+        assert_eq!(best().as_deref(), Some("liar"));
+        pm.record_advertised_work(&"honest".to_string(), U256::from(100));
+        assert_eq!(best().as_deref(), Some("liar")); // same work, more height
+
+        // Work proven by validated headers or blocks outranks any claim
+        pm.record_proven_work(&"honest".to_string(), U256::from(150));
+        assert_eq!(best().as_deref(), Some("honest"));
+        pm.record_proven_work(&"honest".to_string(), U256::from(120)); // an older block
+        pm.record_proven_work(&"liar".to_string(), U256::from(140));
+        assert_eq!(best().as_deref(), Some("honest"));
+    }
 }
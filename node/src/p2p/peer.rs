@@ -7,6 +7,17 @@ use tokio_util::codec::{FramedRead, FramedWrite, LengthDelimitedCodec};
 
 pub type PeerId = String;
 
+/// Largest frame accepted from a peer: one maximum-size block plus the
+/// magic prefix and message envelope
+pub const MAX_FRAME_SIZE: usize = Astram_core::config::MAX_BLOCK_SIZE + 64 * 1024;
+
+/// Length-delimited codec bounded by `MAX_FRAME_SIZE`
+pub fn frame_codec() -> LengthDelimitedCodec {
+    LengthDelimitedCodec::builder()
+        .max_frame_length(MAX_FRAME_SIZE)
+        .new_codec()
+}
+
 pub struct Peer {
     pub id: PeerId,
     pub reader: FramedRead<ReadHalf<TcpStream>, LengthDelimitedCodec>,
@@ -17,8 +28,8 @@ pub struct Peer {
 impl Peer {
     pub fn new(id: PeerId, stream: TcpStream) -> Self {
         let (read_half, write_half) = tokio::io::split(stream);
-        let reader = FramedRead::new(read_half, LengthDelimitedCodec::new());
-        let writer = FramedWrite::new(write_half, LengthDelimitedCodec::new());
+        let reader = FramedRead::new(read_half, frame_codec());
+        let writer = FramedWrite::new(write_half, frame_codec());
         Self {
             id,
             reader,
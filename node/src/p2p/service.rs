@@ -2,9 +2,11 @@
 use crate::ChainState;
 use crate::NodeHandle;
 use crate::p2p::manager::{MAX_OUTBOUND, PeerManager};
+use crate::p2p::peer::PeerId;
 use hex;
 use log::{debug, info, warn};
 use Astram_core::block;
+use Astram_core::security::{BlockFailureReason, VALIDATION_STATS};
 use std::collections::HashSet;
 use std::sync::Arc;
 use tokio::time::{Duration, sleep};
@@ -88,23 +90,17 @@ impl P2PService {
         let p2p = self.manager.clone();
 
         // Create unbounded channel for sequential block processing (prevents deadlock)
-        let (block_tx, mut block_rx) = tokio::sync::mpsc::unbounded_channel::<block::Block>();
+        let (block_tx, mut block_rx) = tokio::sync::mpsc::unbounded_channel::<(PeerId, block::Block)>();
 
         // Check if a block hash already exists in our DB (used by Headers handler to skip duplicates)
         let nh_exists = node_handle.clone();
         p2p.set_check_block_exists(move |hash_hex: &str| {
             if let Ok(bc) = nh_exists.bc.try_lock() {
-                // Block must exist AND be indexed on the main chain (i:{height} → hash).
-                // A fork block has b:{hash} but no i:{height}; we must NOT skip it so
-                // it gets re-requested and promoted to the main chain on re-insertion.
-                if let Ok(Some(header)) = bc.load_header(hash_hex) {
-                    let index_key = format!("i:{}", header.index);
-                    bc.db.get(index_key.as_bytes()).ok().flatten()
-                        .map(|v| v.as_slice() == hash_hex.as_bytes())
-                        .unwrap_or(false)
-                } else {
-                    false
-                }
+                // Main-chain blocks and side-branch blocks with a stored body count:
+                // side branches are connected from local storage by chain selection.
+                // Invalid blocks and blocks whose body was dropped after a signature
+                // or witness failure are downloaded again.
+                bc.has_usable_block(hash_hex).unwrap_or(false)
             } else {
                 false // If lock contended, assume block doesn't exist (safe to re-request)
             }
@@ -137,13 +133,39 @@ impl P2PService {
             locator
         });
 
+        // chain work callback for handshakes (peers pick sync sources by work)
+        let nh_work = node_handle.clone();
+        p2p.set_on_get_chain_work(move || {
+            let bc = nh_work.bc.lock().unwrap();
+            bc.chain_tip
+                .as_ref()
+                .and_then(|tip| bc.calculate_chain_work(tip).ok())
+                .unwrap_or_default()
+        });
+
         // headers handler - detect chain reorg from genesis
         let nh_headers = node_handle.clone();
+        let p2p_headers = p2p.clone();
         p2p.set_on_headers(move |peer_id, headers| {
             if headers.is_empty() {
                 return false;
             }
 
+            // Reject header batches that do not carry valid PoW before downloading blocks.
+            // The check is stateless (light DAG cache), so no chain lock is needed.
+            for header in &headers {
+                match Astram_core::Blockchain::is_valid_pow(crate::p2p::manager::chain_params(), header) {
+                    Ok(true) => {}
+                    Ok(false) | Err(_) => {
+                        log::warn!(
+                            "❌ Rejecting peer {}: header #{} fails PoW (bits=0x{:08x})",
+                            peer_id, header.index, header.difficulty
+                        );
+                        return false;
+                    }
+                }
+            }
+
             // Check if this is a genesis-starting chain that differs from ours
             let first_header = &headers[0];
             if first_header.index == 0 {
@@ -179,6 +201,11 @@ impl P2PService {
                 }
             }
 
+            // Work the batch proves on top of a block we know ranks the peer for sync
+            if let Ok(Some(work)) = nh_headers.bc.lock().unwrap().headers_chain_work(&headers) {
+                p2p_headers.record_proven_work(&peer_id, work);
+            }
+
             true
         });
 
@@ -199,7 +226,7 @@ impl P2PService {
                 let mut found = 0u64;
                 for loc_bytes in &locator_hashes {
                     let hash_hex = hex::encode(loc_bytes);
-                    // load_header reads "b:{hash}" - one DB read, no chain walk
+                    // load_header reads "h:{hash}" - one DB read, no chain walk
                     if let Ok(Some(header)) = bc.load_header(&hash_hex) {
                         found = header.index + 1;
                         break;
@@ -241,7 +268,7 @@ impl P2PService {
         let p2p_processor = p2p.clone();
         tokio::spawn(async move {
             info!("[P2P] 🔄 Sequential block processor task started");
-            while let Some(block) = block_rx.recv().await {
+            while let Some((peer_id, block)) = block_rx.recv().await {
                 debug!("[P2P] 📦 Processing block #{} {} from queue", block.header.index, &block.hash[..16]);
                 let handler_start = std::time::Instant::now();
                 
@@ -294,31 +321,51 @@ impl P2PService {
                     }
                 }
 
-                // Try to insert the block
-                let lock_start = std::time::Instant::now();
-                debug!("[LOCK-DEBUG] 🔒 Block #{} attempting bc.lock()...", block.header.index);
-                let mut bc = state.bc.lock().unwrap();
-                debug!("[LOCK-DEBUG] ✅ Block #{} acquired bc.lock() after {:?}", block.header.index, lock_start.elapsed());
-
                 // Early duplicate check: skip only if the block is in the DB AND has a valid
                 // index entry on the main chain. A block may exist as a fork block (b: written
                 // but i: missing); in that case we must retry insertion so the index gets set.
-                let block_key = format!("b:{}", block.hash);
-                let index_key = format!("i:{}", block.header.index);
-                let already_indexed = bc.db.get(block_key.as_bytes()).ok().flatten().is_some()
-                    && bc.db.get(index_key.as_bytes()).ok().flatten()
-                        .map(|v| v.as_slice() == block.hash.as_bytes())
-                        .unwrap_or(false);
+                let already_indexed = {
+                    let bc = state.bc.lock().unwrap();
+                    let block_key = format!("b:{}", block.hash);
+                    let index_key = format!("i:{}", block.header.index);
+                    bc.db.get(block_key.as_bytes()).ok().flatten().is_some()
+                        && bc.db.get(index_key.as_bytes()).ok().flatten()
+                            .map(|v| v.as_slice() == block.hash.as_bytes())
+                            .unwrap_or(false)
+                };
                 if already_indexed {
                     debug!("[P2P] Block #{} ({}) already in DB with index, skipping", block.header.index, &block.hash[..16]);
-                    drop(bc);
                     continue;
                 }
 
+                // Header hash, PoW, merkle root and (if the block extends the tip)
+                // signatures are checked before the chain lock is held for connecting
                 let validation_start = std::time::Instant::now();
+                let pre = match state.precheck_block(&block) {
+                    Ok(pre) => pre,
+                    Err(e) => {
+                        warn!(
+                            "[BLOCK-REJECT] Block #{} ({}) failed lock-free checks: {:?}",
+                            block.header.index,
+                            &block.hash[..16],
+                            e
+                        );
+                        continue;
+                    }
+                };
+
+                // Try to insert the block
+                let lock_start = std::time::Instant::now();
+                debug!("[LOCK-DEBUG] 🔒 Block #{} attempting bc.lock()...", block.header.index);
+                let mut bc = state.bc.lock().unwrap();
+                debug!("[LOCK-DEBUG] ✅ Block #{} acquired bc.lock() after {:?}", block.header.index, lock_start.elapsed());
+
                 let syncing = p2p_block.get_syncing();
-                match bc.validate_and_insert_block(&block) {
+                match bc.validate_and_insert_prechecked(&pre) {
                     Ok(_) => {
+                        if let Ok(work) = bc.calculate_chain_work(&block.hash) {
+                            p2p_block.record_proven_work(&peer_id, work);
+                        }
                         if syncing {
                             debug!(
                                 "[OK] Block #{} added (validation: {:?})",
@@ -372,8 +419,8 @@ impl P2PService {
                         let mut bc = state.bc.lock().unwrap();
                         debug!("[LOCK-DEBUG] ✅ Block #{} acquired bc.lock() for reorg after {:?}", block.header.index, lock_reacq_time.elapsed());
                         
-                        // Check if this block triggers a chain reorganization
-                        match bc.reorganize_if_needed(&block.hash) {
+                        // Check if a side branch now has more work
+                        match bc.activate_best_chain() {
                             Ok(true) => {
                                 info!("[OK] Chain reorganization completed");
                             }
@@ -430,12 +477,15 @@ impl P2PService {
                                 debug!("[P2P] 🔀 Fork block detected at height {}, attempting chain reorganization...", block.header.index);
                                 
                                 // Validate the fork block without chain tip check
-                                match bc.validate_fork_block(&block) {
+                                match bc.validate_fork_prechecked(&pre) {
                                     Ok(_) => {
                                         debug!("[P2P] ✅ Fork block validated, checking if reorg needed...");
+                                        if let Ok(work) = bc.calculate_chain_work(&block.hash) {
+                                            p2p_block.record_proven_work(&peer_id, work);
+                                        }
                                         
                                         // Try to reorganize to this fork
-                                        match bc.reorganize_if_needed(&block.hash) {
+                                        match bc.activate_best_chain() {
                                             Ok(true) => {
                                                 info!("[P2P] ✅ Chain reorganized to fork block #{}", block.header.index);
                                                 
@@ -612,8 +662,8 @@ impl P2PService {
         });
 
         // Block handler - just enqueue blocks for sequential processing
-        p2p.set_on_block(move |block: block::Block| {
-            if let Err(e) = block_tx.send(block) {
+        p2p.set_on_block(move |peer_id: PeerId, block: block::Block| {
+            if let Err(e) = block_tx.send((peer_id, block)) {
                 warn!("[P2P] Failed to enqueue block for processing: {:?}", e);
             }
         });
@@ -648,10 +698,19 @@ impl P2PService {
                     // Validate transaction signatures
                     info!("[P2P] 🔐 TX handler: validating signatures...");
                     let validation_start = std::time::Instant::now();
-                    match tx.verify_signatures() {
+                    match state.verify_tx_signatures(&tx) {
                         Ok(true) => {
                             info!("[P2P] ✅ TX handler: signatures validated (took {:?})", validation_start.elapsed());
                             info!("[OK] Transaction {} received and validated from p2p", tx.txid);
+
+                            if let Err(e) = state
+                                .check_coinbase_maturity(&tx)
+                                .and_then(|_| state.check_tx_locks(&tx))
+                                .and_then(|_| state.check_tx_security(&tx))
+                            {
+                                warn!("[WARN] Transaction {} rejected: {}", tx.txid, e);
+                                return;
+                            }
                             
                             // Security: Check for double-spending in mempool
                             let mut tx_utxos = std::collections::HashSet::new();
@@ -691,6 +750,7 @@ impl P2PService {
                             }
 
                             if has_conflict {
+                                VALIDATION_STATS.increment(BlockFailureReason::DuplicateInput);
                                 false
                             } else {
                                 // Mark transaction as seen with timestamp
@@ -831,7 +891,7 @@ impl P2PService {
                             }
 
                             // Check for reorganization
-                            let _ = bc.reorganize_if_needed(&hash);
+                            let _ = bc.activate_best_chain();
                         }
                         Err(e) => {
                             warn!(
@@ -4,6 +4,8 @@ use crate::NodeMeta;
 use crate::PeerManager;
 use base64::{Engine as _, engine::general_purpose};
 use Astram_core::block::Block;
+use Astram_core::config::{BLOCK_TEMPLATE_RESERVED_SIZE, MAX_BLOCK_SIZE};
+use Astram_core::security::{BlockFailureReason, VALIDATION_STATS};
 use Astram_core::transaction::{BINCODE_CONFIG, Transaction};
 use Astram_core::utxo::Utxo;
 use primitive_types::U256;
@@ -12,6 +14,269 @@ use warp::Filter;
 use warp::{http::StatusCode, reply::with_status}; // bincode v2
 use std::collections::HashMap;
 use std::net::SocketAddr;
+
+/// UTXO list for `/address/{addr}/utxos`: each entry gets a `spendable` flag
+/// (false for coinbase outputs that have not reached maturity)
+fn utxos_to_json(list: Vec<(Utxo, bool)>) -> Vec<serde_json::Value> {
+    list.into_iter()
+        .map(|(utxo, spendable)| {
+            let mut v = serde_json::to_value(&utxo).unwrap_or_default();
+            v["spendable"] = serde_json::Value::Bool(spendable);
+            v
+        })
+        .collect()
+}
+
+/// Body and status for `/tx/{txid}`: the main chain first, then the mempool
+/// (reported with no block and zero confirmations)
+fn tx_lookup_json(node: &NodeHandle, txid: &str) -> (serde_json::Value, StatusCode) {
+    let encode = |tx: &Transaction| {
+        let bincode_bytes = bincode::encode_to_vec(tx, *BINCODE_CONFIG).unwrap();
+        general_purpose::STANDARD.encode(&bincode_bytes)
+    };
+
+    let confirmed = node.bc.lock().unwrap().get_transaction(txid);
+    match confirmed {
+        Ok(Some((tx, location, confirmations))) => (
+            serde_json::json!({
+                "txid": txid,
+                "block_height": location.height,
+                "block_hash": location.block_hash,
+                "position": location.position,
+                "confirmations": confirmations,
+                "transaction": encode(&tx),
+                "encoding": "bincode+base64"
+            }),
+            StatusCode::OK,
+        ),
+        Ok(None) => {
+            let pending = node
+                .mempool
+                .lock()
+                .unwrap()
+                .pending
+                .iter()
+                .find(|t| t.txid == txid)
+                .cloned();
+            match pending {
+                Some(tx) => (
+                    serde_json::json!({
+                        "txid": txid,
+                        "block_height": null,
+                        "block_hash": null,
+                        "confirmations": 0,
+                        "transaction": encode(&tx),
+                        "encoding": "bincode+base64"
+                    }),
+                    StatusCode::OK,
+                ),
+                None => (serde_json::json!({"error": "tx not found"}), StatusCode::NOT_FOUND),
+            }
+        }
+        Err(e) => (
+            serde_json::json!({"error": format!("db error: {}", e)}),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ),
+    }
+}
+
+/// Body and status for `/utxoset/info`: the UTXO set statistics maintained
+/// at the tip, and the issuance the reward schedule allows up to that height
+fn utxo_set_info_json(node: &NodeHandle) -> (serde_json::Value, StatusCode) {
+    let bc = node.bc.lock().unwrap();
+    let tip = bc
+        .chain_tip
+        .as_ref()
+        .and_then(|hash| bc.load_header(hash).ok().flatten().map(|h| (h.index, hash.clone())));
+    match bc.utxo_set_stats() {
+        Ok(stats) => {
+            let scheduled = tip
+                .as_ref()
+                .map(|(height, _)| bc.params.scheduled_supply(*height))
+                .unwrap_or_default();
+            (
+                serde_json::json!({
+                    "height": tip.as_ref().map(|(height, _)| *height),
+                    "block_hash": tip.as_ref().map(|(_, hash)| hash.clone()),
+                    "utxo_count": stats.count,
+                    "total_amount": format!("0x{:x}", stats.total_amount),
+                    "muhash": stats.digest(),
+                    "scheduled_supply": format!("0x{:x}", scheduled),
+                    "within_schedule": stats.total_amount <= scheduled,
+                }),
+                StatusCode::OK,
+            )
+        }
+        Err(e) => (
+            serde_json::json!({"error": format!("db error: {}", e)}),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ),
+    }
+}
+
+/// Body and status for `/chaintips`: every branch of the block tree, most work first
+fn chain_tips_json(node: &NodeHandle) -> (serde_json::Value, StatusCode) {
+    let bc = node.bc.lock().unwrap();
+    match bc.chain_tips() {
+        Ok(tips) => {
+            let tips: Vec<serde_json::Value> = tips
+                .iter()
+                .map(|tip| {
+                    serde_json::json!({
+                        "hash": tip.hash,
+                        "height": tip.height,
+                        "chain_work": format!("0x{:x}", tip.chain_work),
+                        "branch_len": tip.branch_len,
+                        "status": tip.status.as_str(),
+                    })
+                })
+                .collect();
+            (serde_json::json!(tips), StatusCode::OK)
+        }
+        Err(e) => (
+            serde_json::json!({"error": format!("db error: {}", e)}),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ),
+    }
+}
+
+/// `checkpoints` section of `/status`: the built-in set alone, or with the
+/// signed checkpoint file in force on top
+fn checkpoints_json(bc: &Astram_core::Blockchain) -> serde_json::Value {
+    let active = bc.active_checkpoints();
+    let signed = bc.signed_checkpoints.as_ref();
+    serde_json::json!({
+        "source": if signed.is_some() { "signed" } else { "built-in" },
+        "sequence": signed.map(|set| set.sequence),
+        "count": active.len(),
+        "latest_height": Astram_core::checkpoint::get_latest_checkpoint_height(&active),
+        "signers": signed
+            .map(|set| set.signatures.iter().map(|(key, _)| key.clone()).collect::<Vec<_>>())
+            .unwrap_or_default(),
+    })
+}
+
+/// Mempool transactions with their fees. Inputs resolve against the UTXO
+/// set, then pending parents.
+fn mempool_candidates(node: &NodeHandle) -> (usize, Vec<(Transaction, U256)>) {
+    let bc = node.bc.lock().unwrap();
+    let mempool = node.mempool.lock().unwrap();
+
+    let mut candidates = Vec::with_capacity(mempool.pending.len());
+    for tx in &mempool.pending {
+        let mut input_sum = U256::zero();
+        for inp in &tx.inputs {
+            let amount = match bc.get_utxo(&inp.txid, inp.vout) {
+                Ok(Some(utxo)) => Some(utxo.amount()),
+                _ => mempool.pending_output(&inp.txid, inp.vout).map(|u| u.amount()),
+            };
+            if let Some(amount) = amount {
+                input_sum += amount;
+            }
+        }
+        let output_sum = tx
+            .outputs
+            .iter()
+            .fold(U256::zero(), |acc, out| acc + out.amount());
+        let fee = input_sum.saturating_sub(output_sum);
+        candidates.push((tx.clone(), fee));
+    }
+    (mempool.pending.len(), candidates)
+}
+
+/// After a locally mined block is connected: record it in memory, drop its
+/// transactions from the mempool, update the advertised height and relay it
+fn announce_local_block(
+    node: &NodeHandle,
+    p2p: &std::sync::Arc<PeerManager>,
+    chain_state: &std::sync::Arc<std::sync::Mutex<ChainState>>,
+    block: &Block,
+) {
+    let now = chrono::Utc::now().timestamp();
+    // Single chain_state lock: push block + update recently_mined_blocks.
+    {
+        let mut chain = chain_state.lock().unwrap();
+        chain.blockchain.push(block.clone());
+        chain.enforce_memory_limit();
+        chain.recently_mined_blocks.insert(block.hash.clone(), now);
+        chain
+            .recently_mined_blocks
+            .retain(|_, &mut timestamp| now - timestamp < 300);
+    }
+    // Remove confirmed transactions from the mempool so they are
+    // not included again in future block templates.
+    {
+        let block_txids: std::collections::HashSet<String> =
+            block.transactions.iter().map(|tx| tx.txid.clone()).collect();
+        let mut mempool = node.mempool.lock().unwrap();
+        let before = mempool.pending.len();
+        mempool.pending.retain(|tx| !block_txids.contains(&tx.txid));
+        let removed = before - mempool.pending.len();
+        if removed > 0 {
+            log::info!("[MEMPOOL] Removed {} confirmed TXs after block {}", removed, block.header.index);
+        }
+    }
+    p2p.set_my_height(block.header.index);
+
+    let p2p = p2p.clone();
+    let block_to_broadcast = block.clone();
+    tokio::spawn(async move {
+        p2p.broadcast_block(&block_to_broadcast).await;
+    });
+}
+
+/// Most blocks one `/regtest/generate` call may mine
+const MAX_REGTEST_GENERATE: u64 = 1_000;
+
+/// Body and status for `/regtest/generate`: mine `n` blocks paying `address`,
+/// each taking transactions from the mempool
+fn regtest_generate_json(
+    node: &NodeHandle,
+    p2p: &std::sync::Arc<PeerManager>,
+    chain_state: &std::sync::Arc<std::sync::Mutex<ChainState>>,
+    n: u64,
+    address: &str,
+) -> (serde_json::Value, StatusCode) {
+    if !crate::p2p::manager::chain_params().mine_on_demand {
+        return (
+            serde_json::json!({"status": "error", "message": "block generation is not available on this network"}),
+            StatusCode::FORBIDDEN,
+        );
+    }
+    if n == 0 || n > MAX_REGTEST_GENERATE || address.trim().is_empty() {
+        return (
+            serde_json::json!({
+                "status": "error",
+                "message": format!("expected 1..={} blocks and a payout address", MAX_REGTEST_GENERATE)
+            }),
+            StatusCode::BAD_REQUEST,
+        );
+    }
+
+    let mut hashes = Vec::new();
+    for _ in 0..n {
+        let (_, candidates) = mempool_candidates(node);
+        let result = node.bc.lock().unwrap().generate_block(address.trim(), candidates);
+        match result {
+            Ok(block) => {
+                announce_local_block(node, p2p, chain_state, &block);
+                hashes.push(block.hash);
+            }
+            Err(e) => {
+                return (
+                    serde_json::json!({
+                        "status": "error",
+                        "message": format!("block generation failed: {}", e),
+                        "blocks": hashes
+                    }),
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                );
+            }
+        }
+    }
+    (serde_json::json!({"status": "ok", "blocks": hashes}), StatusCode::OK)
+}
+
 /// run_server expects NodeHandle (Arc<NodeHandles>)
 pub async fn run_server(
     node: NodeHandle,
@@ -266,6 +531,8 @@ pub async fn run_server(
                 chain_difficulty,
                 next_difficulty,
                 dwg3_ok,
+                chain_work,
+                checkpoints,
                 is_mining,
                 _current_difficulty,
                 hashrate,
@@ -276,7 +543,7 @@ pub async fn run_server(
                 let state = node.clone();
 
                 let _bc_lock_start = std::time::Instant::now();
-                let (chain_tip, genesis_hash, chain_height, chain_difficulty, next_difficulty, dwg3_ok) = {
+                let (chain_tip, genesis_hash, chain_height, chain_difficulty, next_difficulty, dwg3_ok, chain_work, checkpoints) = {
                     let bc = state.bc.lock().unwrap();
                     let tip = bc.chain_tip
                         .as_ref()
@@ -312,7 +579,11 @@ pub async fn run_server(
                             (diff, false)  // fallback value (unusable — dwg3_ok=false)
                         }
                     };
-                    (tip, genesis, height, diff, next_diff, dwg3_ok)
+                    let work = bc.chain_tip
+                        .as_ref()
+                        .and_then(|tip_hash| bc.calculate_chain_work(tip_hash).ok())
+                        .unwrap_or(U256::zero());
+                    (tip, genesis, height, diff, next_diff, dwg3_ok, work, checkpoints_json(&bc))
                 };
                 let _chain_lock_start = std::time::Instant::now();
                 let memory_count = {
@@ -345,6 +616,8 @@ pub async fn run_server(
                     chain_difficulty,
                     next_difficulty,
                     dwg3_ok,
+                    chain_work,
+                    checkpoints,
                     state.mining.active.load(std::sync::atomic::Ordering::Relaxed),
                     diff,
                     hash,
@@ -395,6 +668,8 @@ pub async fn run_server(
                     "difficulty": chain_difficulty,
                     "next_difficulty": next_difficulty,
                     "dwg3_ok": dwg3_ok,
+                    "chain_work": format!("0x{:x}", chain_work),
+                    "checkpoints": checkpoints,
                 },
                 "mempool": {
                     "pending_transactions": pending_tx,
@@ -479,9 +754,24 @@ pub async fn run_server(
             let state = node.clone();
 
             // Signature check
-            match tx.verify_signatures() {
+            match state.verify_tx_signatures(&tx) {
                 Ok(true) => {
                     log::info!("TX {} signature OK", tx.txid);
+
+                    if let Err(e) = state
+                        .check_coinbase_maturity(&tx)
+                        .and_then(|_| state.check_tx_locks(&tx))
+                        .and_then(|_| state.check_tx_security(&tx))
+                    {
+                        log::warn!("TX {} rejected: {}", tx.txid, e);
+                        return Ok::<_, warp::Rejection>(with_status(
+                            warp::reply::json(&serde_json::json!({
+                                "status": "error",
+                                "message": e.to_string()
+                            })),
+                            StatusCode::BAD_REQUEST,
+                        ));
+                    }
                     
                     // Security: Validate fee before accepting to mempool
                     // Calculate input/output sums to verify fee
@@ -518,6 +808,7 @@ pub async fn run_server(
                     let min_fee = Astram_core::config::calculate_min_fee(tx_blob.len());
                     
                     if fee < min_fee {
+                        VALIDATION_STATS.increment(BlockFailureReason::InsufficientFee);
                         log::warn!("TX {} fee too low: got {}, need {}", tx.txid, fee, min_fee);
                         return Ok::<_, warp::Rejection>(with_status(
                             warp::reply::json(&serde_json::json!({
@@ -553,6 +844,7 @@ pub async fn run_server(
                         for pending_inp in &pending_tx.inputs {
                             let pending_utxo = format!("{}:{}", pending_inp.txid, pending_inp.vout);
                             if tx_utxos.contains(&pending_utxo) {
+                                VALIDATION_STATS.increment(BlockFailureReason::DuplicateInput);
                                 log::warn!(
                                     "Double-spend attempt: TX {} tries to use UTXO {} already used by pending TX {}",
                                     tx.txid, pending_utxo, pending_tx.txid
@@ -624,13 +916,37 @@ pub async fn run_server(
             let state = node.clone();
 
             // Verify signature + fee
-            if !tx.verify_signatures().unwrap_or(false) {
+            if !state.verify_tx_signatures(&tx).unwrap_or(false) {
                 log::warn!("relay invalid signature");
                 return Ok::<_, warp::Rejection>(with_status(
                     warp::reply::json(&serde_json::json!({"status":"invalid_signature"})),
                     StatusCode::OK,
                 ));
             }
+
+            if let Err(e) = state.check_coinbase_maturity(&tx) {
+                log::warn!("relay tx {} rejected: {}", tx.txid, e);
+                return Ok::<_, warp::Rejection>(with_status(
+                    warp::reply::json(&serde_json::json!({"status":"immature_coinbase"})),
+                    StatusCode::OK,
+                ));
+            }
+
+            if let Err(e) = state.check_tx_locks(&tx) {
+                log::warn!("relay tx {} rejected: {}", tx.txid, e);
+                return Ok::<_, warp::Rejection>(with_status(
+                    warp::reply::json(&serde_json::json!({"status":"non_final"})),
+                    StatusCode::OK,
+                ));
+            }
+
+            if let Err(e) = state.check_tx_security(&tx) {
+                log::warn!("relay tx {} rejected: {}", tx.txid, e);
+                return Ok::<_, warp::Rejection>(with_status(
+                    warp::reply::json(&serde_json::json!({"status":"rejected"})),
+                    StatusCode::OK,
+                ));
+            }
             
             // Security: Validate fee for relayed transactions
             let mut input_sum = U256::zero();
@@ -676,6 +992,7 @@ pub async fn run_server(
                 log::info!("relay accepted tx {} (fee: {} >= {})", tx.txid, fee, min_fee);
                 mempool.pending.push(tx);
             } else {
+                VALIDATION_STATS.increment(BlockFailureReason::InsufficientFee);
                 log::warn!("relay rejected tx {}: fee too low ({} < {})", tx.txid, fee, min_fee);
             }
 
@@ -686,67 +1003,31 @@ pub async fn run_server(
         });
 
     // -------------------------------
-    // GET /mempool - Pending transactions + fee summary
+    // GET /mempool - Block template transactions (by fee rate, within
+    // the block size limit) + fee summary
     // -------------------------------
     let get_mempool = warp::path("mempool")
         .and(warp::get())
         .and(node_filter.clone())
         .and_then(|node: NodeHandle| async move {
-            let state = node.clone();
-            let txs = {
-                let mempool = state.mempool.lock().unwrap();
-                mempool.pending.clone()
-            };
-
-            let mut input_keys = std::collections::HashSet::new();
-            for tx in &txs {
-                for inp in &tx.inputs {
-                    input_keys.insert(format!("u:{}:{}", inp.txid, inp.vout));
-                }
-            }
-
-            let utxo_amounts = {
-                let bc = state.bc.lock().unwrap();
-                let mut map = std::collections::HashMap::new();
-                for ukey in input_keys {
-                    if let Ok(Some(blob)) = bc.db.get(ukey.as_bytes()) {
-                        if let Ok((utxo, _)) =
-                            bincode::decode_from_slice::<Utxo, _>(&blob, *BINCODE_CONFIG)
-                        {
-                            map.insert(ukey, utxo.amount());
-                        }
-                    }
-                }
-                map
-            };
-
-            let mut total_fees = U256::zero();
-            for tx in &txs {
-                let mut input_sum = U256::zero();
-                let mut output_sum = U256::zero();
-
-                for inp in &tx.inputs {
-                    let ukey = format!("u:{}:{}", inp.txid, inp.vout);
-                    if let Some(amount) = utxo_amounts.get(&ukey) {
-                        input_sum = input_sum + *amount;
-                    }
-                }
-
-                for out in &tx.outputs {
-                    output_sum = output_sum + out.amount();
-                }
+            let (pending, candidates) = mempool_candidates(&node);
 
-                if input_sum >= output_sum {
-                    total_fees = total_fees + (input_sum - output_sum);
-                }
-            }
+            let selected = Astram_core::block::select_transactions(
+                candidates,
+                MAX_BLOCK_SIZE - BLOCK_TEMPLATE_RESERVED_SIZE,
+            );
+            let total_fees = selected.iter().fold(U256::zero(), |acc, (_, fee)| acc + *fee);
+            let fees: Vec<String> = selected.iter().map(|(_, fee)| format!("0x{:x}", fee)).collect();
+            let txs: Vec<Transaction> = selected.into_iter().map(|(tx, _)| tx).collect();
 
             let bincode_bytes = bincode::encode_to_vec(&txs, *BINCODE_CONFIG).unwrap();
             let encoded = general_purpose::STANDARD.encode(&bincode_bytes);
 
             Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({
                 "count": txs.len(),
+                "pending": pending,
                 "transactions_b64": encoded,
+                "fees": fees,
                 "total_fees": format!("0x{:x}", total_fees)
             })))
         });
@@ -793,41 +1074,14 @@ pub async fn run_server(
                 }
             };
 
-            let state = node.clone();
-            // Acquire bc lock, validate, then release before taking chain_state lock.
-            let validate_result = state.bc.lock().unwrap().validate_and_insert_block(&block);
+            // Check the block without the bc lock, take it only to connect, then
+            // release it before taking the chain_state lock.
+            let validate_result = node
+                .precheck_block(&block)
+                .and_then(|pre| node.bc.lock().unwrap().validate_and_insert_prechecked(&pre));
             match validate_result {
                 Ok(_) => {
-                    let now = chrono::Utc::now().timestamp();
-                    // Single chain_state lock: push block + update recently_mined_blocks.
-                    {
-                        let mut chain = chain_state.lock().unwrap();
-                        chain.blockchain.push(block.clone());
-                        chain.enforce_memory_limit();
-                        chain.recently_mined_blocks.insert(block.hash.clone(), now);
-                        chain
-                            .recently_mined_blocks
-                            .retain(|_, &mut timestamp| now - timestamp < 300);
-                    }
-                    // Remove confirmed transactions from the mempool so they are
-                    // not included again in future block templates.
-                    {
-                        let block_txids: std::collections::HashSet<String> =
-                            block.transactions.iter().map(|tx| tx.txid.clone()).collect();
-                        let mut mempool = state.mempool.lock().unwrap();
-                        let before = mempool.pending.len();
-                        mempool.pending.retain(|tx| !block_txids.contains(&tx.txid));
-                        let removed = before - mempool.pending.len();
-                        if removed > 0 {
-                            log::info!("[MEMPOOL] Removed {} confirmed TXs after block {}", removed, block.header.index);
-                        }
-                    }
-                    p2p.set_my_height(block.header.index);
-
-                    let block_to_broadcast = block.clone();
-                    tokio::spawn(async move {
-                        p2p.broadcast_block(&block_to_broadcast).await;
-                    });
+                    announce_local_block(&node, &p2p, &chain_state, &block);
 
                     Ok::<_, warp::Rejection>(with_status(
                         warp::reply::json(&serde_json::json!({
@@ -905,13 +1159,13 @@ pub async fn run_server(
             let bc_arc = node.bc.clone();
             let addr = address.clone();
             let result = tokio::task::spawn_blocking(move || {
-                bc_arc.lock().unwrap().get_utxos(&addr)
+                bc_arc.lock().unwrap().get_utxos_with_maturity(&addr)
             })
             .await
             .expect("spawn_blocking panicked");
 
             match result {
-                Ok(list) => Ok::<_, warp::Rejection>(warp::reply::json(&list)),
+                Ok(list) => Ok::<_, warp::Rejection>(warp::reply::json(&utxos_to_json(list))),
                 Err(e) => {
                     log::warn!("UTXO lookup failed {}: {:?}", address, e);
                     Ok::<_, warp::Rejection>(warp::reply::json(&Vec::<Utxo>::new()))
@@ -1008,36 +1262,30 @@ pub async fn run_server(
         .and(warp::get())
         .and(node_filter.clone())
         .and_then(|txid: String, node: NodeHandle| async move {
-            match node.bc.lock().unwrap().get_transaction(&txid) {
-                Ok(Some((tx, height))) => {
-                    let bincode_bytes = bincode::encode_to_vec(&tx, *BINCODE_CONFIG).unwrap();
-                    let encoded = general_purpose::STANDARD.encode(&bincode_bytes);
-
-                    Ok::<_, warp::Rejection>(with_status(
-                        warp::reply::json(&serde_json::json!({
-                            "txid": txid,
-                            "block_height": height,
-                            "transaction": encoded,
-                            "encoding": "bincode+base64"
-                        })),
-                        StatusCode::OK,
-                    ))
-                }
+            let (body, status) = tx_lookup_json(&node, &txid);
+            Ok::<_, warp::Rejection>(with_status(warp::reply::json(&body), status))
+        });
 
-                Ok(None) => Ok::<_, warp::Rejection>(with_status(
-                    warp::reply::json(&serde_json::json!({
-                        "error": "tx not found"
-                    })),
-                    StatusCode::NOT_FOUND,
-                )),
+    // GET /utxoset/info - UTXO set hash, count and total amount at the tip
+    let get_utxoset_info = warp::path!("utxoset" / "info")
+        .and(warp::get())
+        .and(node_filter.clone())
+        .and_then(|node: NodeHandle| async move {
+            let (body, status) = tokio::task::spawn_blocking(move || utxo_set_info_json(&node))
+                .await
+                .expect("spawn_blocking panicked");
+            Ok::<_, warp::Rejection>(with_status(warp::reply::json(&body), status))
+        });
 
-                Err(e) => Ok::<_, warp::Rejection>(with_status(
-                    warp::reply::json(&serde_json::json!({
-                        "error": format!("db error: {}", e)
-                    })),
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                )),
-            }
+    // GET /chaintips - known branches of the block tree
+    let get_chaintips = warp::path!("chaintips")
+        .and(warp::get())
+        .and(node_filter.clone())
+        .and_then(|node: NodeHandle| async move {
+            let (body, status) = tokio::task::spawn_blocking(move || chain_tips_json(&node))
+                .await
+                .expect("spawn_blocking panicked");
+            Ok::<_, warp::Rejection>(with_status(warp::reply::json(&body), status))
         });
 
 
@@ -1084,6 +1332,29 @@ pub async fn run_server(
             }
         });
 
+    // -------------------------------
+    // POST /regtest/generate { "n": N, "address": "0x..." } - Mine N blocks instantly (regtest only)
+    #[derive(Deserialize)]
+    struct GenerateRequest {
+        n: u64,
+        address: String,
+    }
+
+    let regtest_generate = warp::path!("regtest" / "generate")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(node_filter.clone())
+        .and(p2p_filter.clone())
+        .and(chain_filter.clone())
+        .and_then(|req: GenerateRequest, node: NodeHandle, p2p: std::sync::Arc<PeerManager>, chain_state: std::sync::Arc<std::sync::Mutex<ChainState>>| async move {
+            let (body, status) = tokio::task::spawn_blocking(move || {
+                regtest_generate_json(&node, &p2p, &chain_state, req.n, &req.address)
+            })
+            .await
+            .expect("spawn_blocking panicked");
+            Ok::<_, warp::Rejection>(with_status(warp::reply::json(&body), status))
+        });
+
     // -------------------------------
     // GET / - Node Status Dashboard HTML
     let dashboard = warp::path::end()
@@ -1109,12 +1380,15 @@ pub async fn run_server(
         .or(get_mempool)
         .or(submit_block)
         .or(truncate_chain)
+        .or(regtest_generate)
         .or(status)
         .or(get_balance)
         .or(get_address_info)
         .or(get_address_transactions)
         .or(get_utxos)
         .or(get_tx)
+        .or(get_utxoset_info)
+        .or(get_chaintips)
         .with(warp::log("Astram::http"))
         .boxed();
 
@@ -1188,7 +1462,7 @@ pub async fn run_public_server(
                     None => (HashMap::new(), 0, 0, 0),
                 };
 
-            let (chain_tip, chain_height, chain_difficulty) = {
+            let (chain_tip, chain_height, chain_difficulty, chain_work, checkpoints) = {
                 let bc = node.bc.lock().unwrap();
                 let tip = bc.chain_tip.as_ref().map(|h| h.clone()).unwrap_or_else(|| "none".to_string());
                 let height = bc.chain_tip
@@ -1198,7 +1472,11 @@ pub async fn run_public_server(
                     .unwrap_or(0);
                 // Real compact-bits difficulty from chain tip
                 let diff = bc.difficulty;
-                (tip, height, diff)
+                let work = bc.chain_tip
+                    .as_ref()
+                    .and_then(|tip_hash| bc.calculate_chain_work(tip_hash).ok())
+                    .unwrap_or(U256::zero());
+                (tip, height, diff, work, checkpoints_json(&bc))
             };
             let memory_blocks = chain_state.lock().unwrap().blockchain.len();
             let (pending_tx, seen_tx) = {
@@ -1226,6 +1504,8 @@ pub async fn run_public_server(
                     "memory_blocks": memory_blocks,
                     "chain_tip": chain_tip,
                     "difficulty": chain_difficulty,
+                    "chain_work": format!("0x{:x}", chain_work),
+                    "checkpoints": checkpoints,
                 },
                 "mempool": {
                     "pending_transactions": pending_tx,
@@ -1293,29 +1573,30 @@ pub async fn run_public_server(
         .and(warp::get())
         .and(node_filter.clone())
         .and_then(|txid: String, node: NodeHandle| async move {
-            match node.bc.lock().unwrap().get_transaction(&txid) {
-                Ok(Some((tx, height))) => {
-                    let bincode_bytes = bincode::encode_to_vec(&tx, *BINCODE_CONFIG).unwrap();
-                    let encoded = general_purpose::STANDARD.encode(&bincode_bytes);
-                    Ok::<_, warp::Rejection>(with_status(
-                        warp::reply::json(&serde_json::json!({
-                            "txid": txid,
-                            "block_height": height,
-                            "transaction": encoded,
-                            "encoding": "bincode+base64"
-                        })),
-                        StatusCode::OK,
-                    ))
-                }
-                Ok(None) => Ok::<_, warp::Rejection>(with_status(
-                    warp::reply::json(&serde_json::json!({"error": "tx not found"})),
-                    StatusCode::NOT_FOUND,
-                )),
-                Err(e) => Ok::<_, warp::Rejection>(with_status(
-                    warp::reply::json(&serde_json::json!({"error": format!("db error: {}", e)})),
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                )),
-            }
+            let (body, status) = tx_lookup_json(&node, &txid);
+            Ok::<_, warp::Rejection>(with_status(warp::reply::json(&body), status))
+        });
+
+    // GET /utxoset/info - UTXO set hash, count and total amount at the tip
+    let get_utxoset_info = warp::path!("utxoset" / "info")
+        .and(warp::get())
+        .and(node_filter.clone())
+        .and_then(|node: NodeHandle| async move {
+            let (body, status) = tokio::task::spawn_blocking(move || utxo_set_info_json(&node))
+                .await
+                .expect("spawn_blocking panicked");
+            Ok::<_, warp::Rejection>(with_status(warp::reply::json(&body), status))
+        });
+
+    // GET /chaintips - known branches of the block tree
+    let get_chaintips = warp::path!("chaintips")
+        .and(warp::get())
+        .and(node_filter.clone())
+        .and_then(|node: NodeHandle| async move {
+            let (body, status) = tokio::task::spawn_blocking(move || chain_tips_json(&node))
+                .await
+                .expect("spawn_blocking panicked");
+            Ok::<_, warp::Rejection>(with_status(warp::reply::json(&body), status))
         });
 
     // GET /address/{address}/balance
@@ -1348,12 +1629,12 @@ pub async fn run_public_server(
             let bc_arc = node.bc.clone();
             let addr = address.clone();
             let result = tokio::task::spawn_blocking(move || {
-                bc_arc.lock().unwrap().get_utxos(&addr)
+                bc_arc.lock().unwrap().get_utxos_with_maturity(&addr)
             })
             .await
             .expect("spawn_blocking panicked");
             match result {
-                Ok(list) => Ok::<_, warp::Rejection>(warp::reply::json(&list)),
+                Ok(list) => Ok::<_, warp::Rejection>(warp::reply::json(&utxos_to_json(list))),
                 Err(_) => Ok::<_, warp::Rejection>(warp::reply::json(&Vec::<Utxo>::new())),
             }
         });
@@ -1481,13 +1762,24 @@ pub async fn run_public_server(
                 }
             };
 
-            if !tx.verify_signatures().unwrap_or(false) {
+            if !node.verify_tx_signatures(&tx).unwrap_or(false) {
                 return Ok::<_, warp::Rejection>(with_status(
                     warp::reply::json(&serde_json::json!({"status":"error","message":"invalid signature"})),
                     StatusCode::BAD_REQUEST,
                 ));
             }
 
+            if let Err(e) = node
+                .check_coinbase_maturity(&tx)
+                .and_then(|_| node.check_tx_locks(&tx))
+                .and_then(|_| node.check_tx_security(&tx))
+            {
+                return Ok::<_, warp::Rejection>(with_status(
+                    warp::reply::json(&serde_json::json!({"status":"error","message": e.to_string()})),
+                    StatusCode::BAD_REQUEST,
+                ));
+            }
+
             // Fee check
             let mut input_sum = U256::zero();
             {
@@ -1506,6 +1798,7 @@ pub async fn run_public_server(
             let tx_blob = bincode::encode_to_vec(&tx, *BINCODE_CONFIG).unwrap();
             let min_fee = Astram_core::config::calculate_min_fee(tx_blob.len());
             if fee < min_fee {
+                VALIDATION_STATS.increment(BlockFailureReason::InsufficientFee);
                 return Ok::<_, warp::Rejection>(with_status(
                     warp::reply::json(&serde_json::json!({
                         "status":"error",
@@ -1529,6 +1822,7 @@ pub async fn run_public_server(
             for pending in &mempool.pending {
                 for inp in &pending.inputs {
                     if tx_utxos.contains(&format!("{}:{}", inp.txid, inp.vout)) {
+                        VALIDATION_STATS.increment(BlockFailureReason::DuplicateInput);
                         return Ok::<_, warp::Rejection>(with_status(
                             warp::reply::json(&serde_json::json!({"status":"error","message":"double-spend detected"})),
                             StatusCode::BAD_REQUEST,
@@ -1558,6 +1852,8 @@ pub async fn run_public_server(
         .or(get_address_transactions_pub)
         .or(get_utxos)
         .or(get_tx)
+        .or(get_utxoset_info)
+        .or(get_chaintips)
         .or(post_tx)
         .with(warp::log("Astram::public_rpc"))
         .boxed();
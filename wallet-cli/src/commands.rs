@@ -1,5 +1,8 @@
 use crate::wallet::Wallet;
-use Astram_core::transaction::{BINCODE_CONFIG, Transaction, TransactionInput, TransactionOutput};
+use Astram_core::transaction::{BINCODE_CONFIG, MultisigLock, Transaction, TransactionBuilder};
+use Astram_core::checkpoint::SignedCheckpoints;
+use Astram_core::crypto::WalletKeypair;
+use Astram_core::utxo::Utxo;
 use astram_config::config::Config;
 use primitive_types::U256;
 use reqwest::blocking::Client;
@@ -7,7 +10,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::fs;
 use std::io::Read;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 // ASRM unit constants (18 decimal places)
 const RAM_PER_ASRM: u128 = 1_000_000_000_000_000_000; // 1 ASRM = 10^18 ram
 
@@ -52,6 +55,20 @@ pub enum Commands {
         amount: f64,
     },
 
+    /// Derive the address of an m-of-n multisig lock (key order matters)
+    MultisigAddress {
+        #[arg(help = "Required number of signatures (m)")]
+        threshold: u8,
+        #[arg(help = "Hex Ed25519 public keys of all cosigners (n)")]
+        pubkeys: Vec<String>,
+    },
+
+    /// Sign a checkpoint file with this wallet's key (release keys only)
+    SignCheckpoints {
+        #[arg(help = "Checkpoint file (NETWORK, SEQUENCE and CHECKPOINT lines); signed in place")]
+        file: PathBuf,
+    },
+
     /// Manage CLI configuration
     Config {
         #[command(subcommand)]
@@ -198,6 +215,53 @@ pub fn get_balance(address: &str) {
     }
 }
 
+pub fn multisig_address(threshold: u8, pubkeys: Vec<String>) {
+    match MultisigLock::new(threshold, pubkeys) {
+        Ok(lock) => {
+            println!("Multisig address ({}-of-{}): {}", lock.threshold, lock.pubkeys.len(), lock.address());
+        }
+        Err(e) => println!("[ERROR] Invalid multisig lock: {}", e),
+    }
+}
+
+pub fn sign_checkpoints(file: &Path) {
+    let text = match fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(e) => {
+            println!("[ERROR] Cannot read {}: {}", file.display(), e);
+            return;
+        }
+    };
+    let mut set = match SignedCheckpoints::parse(&text) {
+        Ok(set) => set,
+        Err(e) => {
+            println!("[ERROR] Invalid checkpoint file: {}", e);
+            return;
+        }
+    };
+    let wallet = load_wallet();
+    let keypair = match WalletKeypair::from_secret_hex(&wallet.secret_hex()) {
+        Ok(keypair) => keypair,
+        Err(e) => {
+            println!("[ERROR] Invalid wallet key: {}", e);
+            return;
+        }
+    };
+    set.sign(&keypair);
+    if let Err(e) = fs::write(file, set.to_text()) {
+        println!("[ERROR] Cannot write {}: {}", file.display(), e);
+        return;
+    }
+    println!(
+        "[OK] Signed checkpoint set #{} for {} ({} checkpoints, {} signatures)",
+        set.sequence,
+        set.network_id,
+        set.checkpoints.len(),
+        set.signatures.len()
+    );
+    println!("Public Key: {}", keypair.public_hex());
+}
+
 pub fn send_transaction(to: &str, amount_ram: U256) {
     let cfg = Config::load();
     let wallet = load_wallet();
@@ -224,9 +288,13 @@ pub fn send_transaction(to: &str, amount_ram: U256) {
         return;
     }
 
-    let mut input_pool: Vec<(TransactionInput, U256)> = Vec::new();
+    let mut input_pool: Vec<Utxo> = Vec::new();
 
     for (_i, u) in utxos.iter().enumerate() {
+        // Immature coinbase outputs are listed but cannot be spent yet
+        if u["spendable"].as_bool() == Some(false) {
+            continue;
+        }
         let txid = u["txid"].as_str().unwrap().to_string();
         let vout = u["vout"].as_u64().unwrap() as u32;
         
@@ -264,22 +332,17 @@ pub fn send_transaction(to: &str, amount_ram: U256) {
                 .unwrap_or_else(U256::zero)
         };
 
-        input_pool.push((TransactionInput {
-            txid,
-            vout,
-            pubkey: wallet.address.clone(),
-            signature: None,
-        }, amt));
+        input_pool.push(Utxo::new(txid, vout, wallet.address.clone(), amt));
     }
 
-    let mut selected_inputs: Vec<TransactionInput> = vec![];
+    let mut selected_utxos: Vec<Utxo> = vec![];
     let mut input_sum = U256::zero();
     let mut cursor = 0usize;
 
     while cursor < input_pool.len() && input_sum < amount_ram {
-        let (inp, amt) = input_pool[cursor].clone();
-        selected_inputs.push(inp);
-        input_sum = input_sum + amt;
+        let utxo = input_pool[cursor].clone();
+        input_sum += utxo.amount();
+        selected_utxos.push(utxo);
         cursor += 1;
     }
 
@@ -314,31 +377,33 @@ pub fn send_transaction(to: &str, amount_ram: U256) {
                 );
                 return;
             }
-            let (inp, amt) = input_pool[cursor].clone();
-            selected_inputs.push(inp);
-            input_sum = input_sum + amt;
+            let utxo = input_pool[cursor].clone();
+            input_sum += utxo.amount();
+            selected_utxos.push(utxo);
             cursor += 1;
         }
 
         let change = input_sum - amount_ram - fee;
-        let mut outputs = vec![TransactionOutput::new(to.to_string(), amount_ram)];
+
+        // Each input is signed over its own sighash (index, spent UTXO, outputs, chain id)
+        let mut builder = TransactionBuilder::new().chain_id(cfg.chain_id);
+        for utxo in &selected_utxos {
+            builder = builder.add_input(utxo.clone(), &keypair);
+        }
+        builder = builder.add_output(to, amount_ram);
         if change > U256::zero() {
-            outputs.push(TransactionOutput::new(wallet.address.clone(), change));
+            builder = builder.add_output(&wallet.address, change);
         }
 
-        let mut candidate_tx = Transaction {
-            txid: "".to_string(),
-            inputs: selected_inputs.clone(),
-            outputs,
-            timestamp: chrono::Utc::now().timestamp(),
+        let candidate_tx = match builder.build() {
+            Ok(tx) => tx,
+            Err(e) => {
+                println!("[ERROR] Failed to sign transaction: {}", e);
+                return;
+            }
         };
 
-        if let Err(e) = candidate_tx.sign(&keypair) {
-            println!("[ERROR] Failed to sign transaction: {}", e);
-            return;
-        }
-
-        match candidate_tx.verify_signatures() {
+        match candidate_tx.verify_signatures(&selected_utxos, cfg.chain_id) {
             Ok(true) => {}
             Ok(false) => {
                 println!("[ERROR] Signature verification failed after signing");
@@ -350,8 +415,6 @@ pub fn send_transaction(to: &str, amount_ram: U256) {
             }
         }
 
-        candidate_tx = candidate_tx.with_hashes();
-
         let candidate_body = match bincode::encode_to_vec(&candidate_tx, *BINCODE_CONFIG) {
             Ok(b) => b,
             Err(e) => {
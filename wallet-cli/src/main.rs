@@ -36,6 +36,8 @@ fn main() {
             println!("Sending {} ASRM to {}", amount, to);
             send_transaction(&to, amount_ram)
         }
+        Commands::MultisigAddress { threshold, pubkeys } => multisig_address(threshold, pubkeys),
+        Commands::SignCheckpoints { file } => sign_checkpoints(&file),
         Commands::Config { subcommand } => match subcommand {
             ConfigCommands::View => {
                 let cfg = Config::load();